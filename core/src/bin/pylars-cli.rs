@@ -0,0 +1,216 @@
+//! Command-line interface running the pylars tests on CSV or parquet files and printing JSON.
+//!
+//! Build it with `cargo build --release -p pylars-core --features cli` (add `parquet` to read parquet
+//! files).
+//!
+//! ```text
+//! pylars-cli <bootstrap|permutation> <file_a> <file_b> --column <name> [options]
+//! ```
+
+use pylars_core::{bootstrap_columns, permutation_columns, Resampler};
+use std::process::exit;
+
+const USAGE: &str = "usage: pylars-cli <bootstrap|permutation> <file_a> <file_b> --column <name> [options]
+
+Files ending in .parquet are read as parquet, all other files as CSV with a header row.
+
+options:
+    --column <name>            the numeric column to analyse (required)
+    --filter <expr>            parquet only: keep rows matching \"column op value\", e.g. \"country == 'US'\"
+    --confidence-level <f>     the confidence level of the interval (default 0.95)
+    --n-resamples <n>          the number of resamples (default 10000)
+    --seed <n>                 the seed mixed into every resample (default 0)
+    --paired                   bootstrap only: resample the two files as paired observations
+    --bias-corrected           bootstrap only: report the bias-corrected uplift
+    --one-sided                report a one-sided p-value
+    --deterministic            use jumped substreams with fixed ordering";
+
+struct Options {
+    test: String,
+    path_a: String,
+    path_b: String,
+    column: String,
+    filter: Option<String>,
+    confidence_level: f64,
+    n_resamples: u64,
+    seed: u64,
+    paired: bool,
+    bias_corrected: bool,
+    one_sided: bool,
+    deterministic: bool,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("pylars-cli: {message}\n\n{USAGE}");
+    exit(2)
+}
+
+/// Reports a failure of the analysis itself, without the usage text.
+fn error(message: &str) -> ! {
+    eprintln!("pylars-cli: {message}");
+    exit(1)
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| fail(&format!("{flag} needs a value")));
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("invalid value {value} for {flag}")))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut positional = Vec::new();
+    let mut options = Options {
+        test: String::new(),
+        path_a: String::new(),
+        path_b: String::new(),
+        column: String::new(),
+        filter: None,
+        confidence_level: 0.95,
+        n_resamples: 10_000,
+        seed: 0,
+        paired: false,
+        bias_corrected: false,
+        one_sided: false,
+        deterministic: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0)
+            }
+            "--column" => options.column = parse_value(&arg, args.next()),
+            "--filter" => options.filter = Some(parse_value(&arg, args.next())),
+            "--confidence-level" => options.confidence_level = parse_value(&arg, args.next()),
+            "--n-resamples" => options.n_resamples = parse_value(&arg, args.next()),
+            "--seed" => options.seed = parse_value(&arg, args.next()),
+            "--paired" => options.paired = true,
+            "--bias-corrected" => options.bias_corrected = true,
+            "--one-sided" => options.one_sided = true,
+            "--deterministic" => options.deterministic = true,
+            flag if flag.starts_with("--") => fail(&format!("unknown option {flag}")),
+            _ => positional.push(arg),
+        }
+    }
+    let [test, path_a, path_b]: [String; 3] = positional
+        .try_into()
+        .unwrap_or_else(|_| fail("expected a test and two files"));
+    if options.column.is_empty() {
+        fail("--column is required")
+    }
+    Options {
+        test,
+        path_a,
+        path_b,
+        ..options
+    }
+}
+
+/// Non-empty values of `column` in a CSV file with a header row.
+fn read_csv_column(path: &str, column: &str) -> Vec<f64> {
+    let mut reader =
+        csv::Reader::from_path(path).unwrap_or_else(|e| error(&format!("cannot read {path}: {e}")));
+    let position = reader
+        .headers()
+        .unwrap_or_else(|e| error(&format!("cannot read {path}: {e}")))
+        .iter()
+        .position(|name| name == column)
+        .unwrap_or_else(|| error(&format!("{path} has no column {column}")));
+    let mut values = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record.unwrap_or_else(|e| error(&format!("cannot read {path}: {e}")));
+        let field = record.get(position).unwrap_or("").trim();
+        if field.is_empty() {
+            continue;
+        }
+        values.push(field.parse().unwrap_or_else(|_| {
+            error(&format!(
+                "{path}, row {}: {field} is not a number",
+                line + 1
+            ))
+        }));
+    }
+    values
+}
+
+fn read_column(path: &str, options: &Options) -> Vec<f64> {
+    if path.ends_with(".parquet") {
+        #[cfg(feature = "parquet")]
+        return pylars_core::read_parquet_column(path, &options.column, options.filter.as_deref())
+            .unwrap_or_else(|e| error(&e.to_string()));
+        #[cfg(not(feature = "parquet"))]
+        fail("reading parquet files requires the parquet feature")
+    }
+    if options.filter.is_some() {
+        fail("--filter is only supported for parquet files")
+    }
+    read_csv_column(path, &options.column)
+}
+
+/// JSON number, with the non-finite values JSON cannot represent written as null.
+fn json_number(x: f64) -> String {
+    if x.is_finite() {
+        format!("{x}")
+    } else {
+        "null".to_string()
+    }
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1));
+    let a = read_column(&options.path_a, &options);
+    let b = read_column(&options.path_b, &options);
+    let resampler = Resampler::from_seed(options.seed, Some(options.deterministic));
+    let fields: Vec<(&str, String)> = match options.test.as_str() {
+        "bootstrap" => {
+            let (p_value, mean_1, mean_2, uplift, ci) = bootstrap_columns(
+                &[&a, &b],
+                options.confidence_level,
+                options.n_resamples,
+                !options.paired,
+                !options.one_sided,
+                options.bias_corrected,
+                0,
+                &resampler,
+            )
+            .unwrap_or_else(|e| error(&e.to_string()));
+            vec![
+                ("p_value", json_number(p_value)),
+                ("mean_1", json_number(mean_1)),
+                ("mean_2", json_number(mean_2)),
+                ("uplift", json_number(uplift)),
+                (
+                    "ci",
+                    format!("[{}, {}]", json_number(ci.0), json_number(ci.1)),
+                ),
+            ]
+        }
+        "permutation" => {
+            let (p_value, uplift, observed_diff, ci) = permutation_columns(
+                &[&a, &b],
+                options.confidence_level,
+                options.n_resamples,
+                !options.one_sided,
+                &resampler,
+            )
+            .unwrap_or_else(|e| error(&e.to_string()));
+            vec![
+                ("p_value", json_number(p_value)),
+                ("uplift", json_number(uplift)),
+                ("observed_diff", json_number(observed_diff)),
+                (
+                    "ci",
+                    format!("[{}, {}]", json_number(ci.0), json_number(ci.1)),
+                ),
+            ]
+        }
+        test => fail(&format!("unknown test {test}")),
+    };
+    let body: Vec<String> = std::iter::once(("test", format!("\"{}\"", options.test)))
+        .chain([("n_a", a.len().to_string()), ("n_b", b.len().to_string())])
+        .chain(fields)
+        .map(|(key, value)| format!("\"{key}\": {value}"))
+        .collect();
+    println!("{{{}}}", body.join(", "));
+}
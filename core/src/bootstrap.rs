@@ -0,0 +1,985 @@
+use crate::error::*;
+use crate::rng::*;
+use crate::tools::*;
+use rand::prelude::*;
+use std::cmp::Ordering;
+
+/// Default threshold of the Huber mean, in units of the normalized median absolute deviation: the
+/// classical tuning with 95% efficiency at the normal distribution.
+pub const DEFAULT_HUBER_DELTA: f64 = 1.345;
+
+/// Default number of blocks of the median of means.
+pub const DEFAULT_MEDIAN_OF_MEANS_BLOCKS: usize = 10;
+
+/// Default proportion of the trimmed mean cut from each tail.
+pub const DEFAULT_TRIM: f64 = 0.1;
+
+/// Largest number of reweighting steps of the Huber mean.
+const MAX_HUBER_ITERATIONS: usize = 100;
+
+/// Statistics of a single sample computed by the one-sample bootstraps.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Statistic {
+    Mean,
+    Median,
+    /// Sample standard deviation, with `n - 1` in the denominator.
+    Std,
+    /// Sample variance, with `n - 1` in the denominator.
+    Var,
+    /// Quantile at a level in [0, 1], with the interpolation of `MathUtil::quantile`.
+    Quantile(f64),
+    /// Huber M-estimate of location, with its threshold in units of the normalized MAD.
+    HuberMean(f64),
+    /// Median of the means of this number of blocks of consecutive observations.
+    MedianOfMeans(usize),
+    /// Mean of the values left after cutting this proportion of the sample from each tail.
+    TrimmedMean(f64),
+}
+
+/// Huber M-estimate of location by iteratively reweighted means, starting from the median: values
+/// farther than `delta` robust standard deviations (1.4826 MAD) from the estimate get a weight inversely
+/// proportional to their distance. A sample with a zero MAD returns its median.
+fn huber_mean(sample: &[f64], delta: f64) -> f64 {
+    let mut location = median(sample);
+    let deviations: Vec<f64> = sample.iter().map(|x| (x - location).abs()).collect();
+    let threshold = delta * 1.4826 * median(&deviations);
+    if threshold == 0.0 || !threshold.is_finite() {
+        return location;
+    }
+    for _ in 0..MAX_HUBER_ITERATIONS {
+        let (mut weighted, mut total) = (0.0, 0.0);
+        for &x in sample {
+            let distance = (x - location).abs();
+            let weight = if distance <= threshold {
+                1.0
+            } else {
+                threshold / distance
+            };
+            weighted += weight * x;
+            total += weight;
+        }
+        let next = weighted / total;
+        let converged = (next - location).abs() <= 1e-12 * threshold;
+        location = next;
+        if converged {
+            break;
+        }
+    }
+    location
+}
+
+/// Median of the means of `n_blocks` blocks of consecutive observations of nearly equal sizes (at most
+/// one block per observation).
+fn median_of_means(sample: &[f64], n_blocks: usize) -> f64 {
+    let n = sample.len();
+    let k = n_blocks.min(n);
+    let means: Vec<f64> = (0..k)
+        .map(|b| {
+            let block = &sample[b * n / k..(b + 1) * n / k];
+            block.iter().sum::<f64>() / block.len() as f64
+        })
+        .collect();
+    median(&means)
+}
+
+/// Mean of `sample` without the `floor(trim * n)` smallest and largest values, as `scipy.stats.trim_mean`.
+fn trimmed_mean(sample: &[f64], trim: f64) -> f64 {
+    let mut sorted = sample.to_vec();
+    sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+    let cut = (trim * sorted.len() as f64) as usize;
+    let kept = &sorted[cut..sorted.len() - cut];
+    kept.iter().sum::<f64>() / kept.len() as f64
+}
+
+impl Statistic {
+    /// Parses `"mean"`, `"median"`, `"std"`, `"var"`, `"quantile"` (at level `q`), `"huber_mean"`,
+    /// `"median_of_means"` or `"trimmed_mean"`, the last three with their default tuning.
+    pub fn parse(statistic: &str, q: Option<f64>) -> Result<Statistic> {
+        let statistic = match statistic {
+            "mean" => Statistic::Mean,
+            "median" => Statistic::Median,
+            "std" => Statistic::Std,
+            "var" => Statistic::Var,
+            "huber_mean" => Statistic::HuberMean(DEFAULT_HUBER_DELTA),
+            "median_of_means" => Statistic::MedianOfMeans(DEFAULT_MEDIAN_OF_MEANS_BLOCKS),
+            "trimmed_mean" => Statistic::TrimmedMean(DEFAULT_TRIM),
+            "quantile" => match q {
+                Some(q) if (0.0..=1.0).contains(&q) => Statistic::Quantile(q),
+                _ => {
+                    return Err(StatError::InvalidInput(
+                        "statistic 'quantile' requires q in [0, 1]".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(StatError::InvalidInput(format!(
+                    "statistic must be one of 'mean', 'median', 'std', 'var', 'quantile', 'huber_mean', 'median_of_means' or 'trimmed_mean', got '{statistic}'"
+                )))
+            }
+        };
+        if q.is_some() && !matches!(statistic, Statistic::Quantile(_)) {
+            return Err(StatError::InvalidInput(
+                "q is only used with statistic 'quantile'".to_string(),
+            ));
+        }
+        Ok(statistic)
+    }
+
+    /// The same statistic with the threshold `delta` of the Huber mean, the number of blocks
+    /// `n_blocks` of the median of means or the proportion `trim` of the trimmed mean, when given.
+    pub fn with_tuning(
+        self,
+        delta: Option<f64>,
+        n_blocks: Option<usize>,
+        trim: Option<f64>,
+    ) -> Result<Statistic> {
+        if delta.is_some_and(|delta| !(delta > 0.0 && delta.is_finite())) {
+            return Err(StatError::InvalidInput(
+                "delta must be a positive number".to_string(),
+            ));
+        }
+        if n_blocks == Some(0) {
+            return Err(StatError::InvalidInput(
+                "n_blocks must be positive".to_string(),
+            ));
+        }
+        if trim.is_some_and(|trim| !(0.0..0.5).contains(&trim)) {
+            return Err(StatError::InvalidInput(
+                "trim must be in [0, 0.5)".to_string(),
+            ));
+        }
+        match (self, delta, n_blocks, trim) {
+            (Statistic::HuberMean(_), Some(delta), None, None) => Ok(Statistic::HuberMean(delta)),
+            (Statistic::MedianOfMeans(_), None, Some(k), None) => Ok(Statistic::MedianOfMeans(k)),
+            (Statistic::TrimmedMean(_), None, None, Some(trim)) => Ok(Statistic::TrimmedMean(trim)),
+            (statistic, None, None, None) => Ok(statistic),
+            (_, Some(_), _, _) => Err(StatError::InvalidInput(
+                "delta is only used with statistic 'huber_mean'".to_string(),
+            )),
+            (_, _, Some(_), _) => Err(StatError::InvalidInput(
+                "n_blocks is only used with statistic 'median_of_means'".to_string(),
+            )),
+            (_, _, _, Some(_)) => Err(StatError::InvalidInput(
+                "trim is only used with statistic 'trimmed_mean'".to_string(),
+            )),
+        }
+    }
+
+    pub fn compute(&self, sample: &[f64]) -> f64 {
+        let n = sample.len() as f64;
+        match *self {
+            Statistic::Mean => sample.iter().sum::<f64>() / n,
+            Statistic::Median => sample.quantile(&[0.5])[0],
+            Statistic::Quantile(q) => sample.quantile(&[q])[0],
+            Statistic::HuberMean(delta) => huber_mean(sample, delta),
+            Statistic::MedianOfMeans(n_blocks) => median_of_means(sample, n_blocks),
+            Statistic::TrimmedMean(trim) => trimmed_mean(sample, trim),
+            Statistic::Std | Statistic::Var => {
+                let mean = sample.iter().sum::<f64>() / n;
+                let var = sample.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+                if *self == Statistic::Std {
+                    var.sqrt()
+                } else {
+                    var
+                }
+            }
+        }
+    }
+}
+
+/// Checks that `statistic` can be resampled from `vec` and returns its sampler.
+fn statistic_sampler(vec: &[f64], statistic: Statistic) -> Result<IndexSampler<'_, 1>> {
+    let sampler = IndexSampler::new([vec])?;
+    if matches!(statistic, Statistic::Std | Statistic::Var) && vec.len() < 2 {
+        return Err(StatError::InsufficientData(
+            "std and var need at least two observations".to_string(),
+        ));
+    }
+    Ok(sampler)
+}
+
+/// `statistic` of one resample of `vec`. The mean is accumulated while drawing, without materializing
+/// the resample, so its distribution is the one `bootstrap_vec` has always returned.
+#[inline(always)]
+fn resampled_statistic<R: Rng + ?Sized>(
+    sampler: &IndexSampler<'_, 1>,
+    len_vec: usize,
+    statistic: Statistic,
+    rng: &mut R,
+) -> f64 {
+    if statistic == Statistic::Mean {
+        let mut sum = 0.0;
+        for _ in 0..len_vec {
+            let [value] = sampler.draw(rng);
+            sum += value;
+        }
+        sum / len_vec as f64
+    } else {
+        let resample: Vec<f64> = (0..len_vec).map(|_| sampler.draw(rng)[0]).collect();
+        statistic.compute(&resample)
+    }
+}
+
+/// Bootstrap distribution of `statistic` over resamples of `vec`.
+pub fn bootstrap_statistic(
+    vec: &[f64],
+    statistic: Statistic,
+    n_resamples: u64,
+    resampler: &Resampler,
+) -> Result<Vec<f64>> {
+    let sampler = statistic_sampler(vec, statistic)?;
+    Ok(resampler.map(n_resamples, |rng| {
+        resampled_statistic(&sampler, vec.len(), statistic, rng)
+    }))
+}
+
+/// The distribution of `bootstrap_statistic` summarized into a `QuantileSketch` of `capacity` values
+/// per level, holding at most `batch_size` resampled statistics at a time.
+pub fn bootstrap_statistic_sketch(
+    vec: &[f64],
+    statistic: Statistic,
+    n_resamples: u64,
+    batch_size: u64,
+    capacity: usize,
+    resampler: &Resampler,
+) -> Result<QuantileSketch> {
+    let sampler = statistic_sampler(vec, statistic)?;
+    let mut sketch = QuantileSketch::new(capacity);
+    resampler.for_each_batch(
+        n_resamples,
+        batch_size,
+        |rng| resampled_statistic(&sampler, vec.len(), statistic, rng),
+        |batch| batch.into_iter().for_each(|value| sketch.push(value)),
+    );
+    Ok(sketch)
+}
+
+/// Bootstrap distribution of the uplift between the stratified means of two groups, given as the
+/// values of every stratum in the same stratum order. Each group resamples its own strata sizes, and
+/// every stratum contributes its resampled sum over the size of the group.
+pub fn stratified_bootstrap_null(
+    strata_a: &[&[f64]],
+    strata_b: &[&[f64]],
+    n_resamples: u64,
+    resampler: &Resampler,
+) -> Result<Vec<f64>> {
+    if strata_a.len() != strata_b.len() {
+        return Err(StatError::InvalidInput(
+            "Both groups must contain the same strata".to_string(),
+        ));
+    }
+    let len_a: usize = strata_a.iter().map(|stratum| stratum.len()).sum();
+    let len_b: usize = strata_b.iter().map(|stratum| stratum.len()).sum();
+    let samplers = strata_a
+        .iter()
+        .zip(strata_b)
+        .map(|(&a, &b)| {
+            Ok((
+                (a.len(), IndexSampler::new([a])?),
+                (b.len(), IndexSampler::new([b])?),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(resampler.map(n_resamples, |rng| {
+        let mut mean_a = 0.0;
+        let mut mean_b = 0.0;
+        for ((a_size, a_sampler), (b_size, b_sampler)) in &samplers {
+            let part_sum_a: f64 = (0..*a_size).map(|_| a_sampler.draw(rng)[0]).sum();
+            let part_sum_b: f64 = (0..*b_size).map(|_| b_sampler.draw(rng)[0]).sum();
+            mean_a += part_sum_a / len_a as f64;
+            mean_b += part_sum_b / len_b as f64;
+        }
+        calculate_uplift(mean_a, mean_b)
+    }))
+}
+
+/// `(p_value, mean_1, mean_2, uplift, (ci_low, ci_high))`, as returned by `bootstrap_columns`.
+pub type BootstrapOutput = (f64, f64, f64, f64, (f64, f64));
+
+/// Mean of the inner (second-level) bootstrap uplifts, used by the double-bootstrap bias correction.
+///
+/// Every outer resample draws its indices in the same order as the main `bootstrap` loop, so the outer
+/// level reproduces exactly the resamples behind the single-level correction.
+pub fn double_bootstrap_mean(
+    args: &[&[f64]],
+    ind: bool,
+    n_resamples: u64,
+    n_inner: u64,
+    resampler: &Resampler,
+) -> f64 {
+    let ratio = args.len() == 4;
+    let (len_1, len_2) = if ratio {
+        (args[0].len(), args[2].len())
+    } else {
+        (args[0].len(), args[1].len())
+    };
+    let statistic = |ids_1: &[usize], ids_2: &[usize]| -> f64 {
+        if ratio {
+            let (num_1, den_1) = ids_1.iter().fold((0.0, 0.0), |(num, den), &id| {
+                (num + args[0][id], den + args[1][id])
+            });
+            let (num_2, den_2) = ids_2.iter().fold((0.0, 0.0), |(num, den), &id| {
+                (num + args[2][id], den + args[3][id])
+            });
+            calculate_uplift(num_1 / den_1, num_2 / den_2)
+        } else {
+            let mean_1 = ids_1.iter().map(|&id| args[0][id]).sum::<f64>() / len_1 as f64;
+            let mean_2 = ids_2.iter().map(|&id| args[1][id]).sum::<f64>() / len_2 as f64;
+            calculate_uplift(mean_1, mean_2)
+        }
+    };
+    let dist_1 = rand::distributions::Uniform::new(0, len_1);
+    let dist_2 = rand::distributions::Uniform::new(0, len_2);
+    let min_len = len_1.min(len_2);
+
+    let inner_means: Vec<f64> = resampler.map(n_resamples, |rng| {
+        let mut ids_1 = Vec::with_capacity(len_1);
+        let mut ids_2 = Vec::with_capacity(len_2);
+        if ind {
+            for _ in 0..min_len {
+                ids_1.push(dist_1.sample(rng));
+                ids_2.push(dist_2.sample(rng));
+            }
+            while ids_1.len() < len_1 {
+                ids_1.push(dist_1.sample(rng));
+            }
+            while ids_2.len() < len_2 {
+                ids_2.push(dist_2.sample(rng));
+            }
+        } else {
+            for _ in 0..min_len {
+                ids_1.push(dist_1.sample(rng));
+            }
+            ids_2.clone_from(&ids_1);
+        }
+
+        let mut inner_1 = vec![0; len_1];
+        let mut inner_2 = vec![0; len_2];
+        let mut sum = 0.0;
+        for _ in 0..n_inner {
+            if ind {
+                inner_1
+                    .iter_mut()
+                    .for_each(|id| *id = ids_1[dist_1.sample(rng)]);
+                inner_2
+                    .iter_mut()
+                    .for_each(|id| *id = ids_2[dist_2.sample(rng)]);
+            } else {
+                for (id_1, id_2) in inner_1.iter_mut().zip(inner_2.iter_mut()) {
+                    let pos = dist_1.sample(rng);
+                    *id_1 = ids_1[pos];
+                    *id_2 = ids_2[pos];
+                }
+            }
+            sum += statistic(&inner_1, &inner_2);
+        }
+        sum / n_inner as f64
+    });
+    fixed_order_sum(&inner_means) / inner_means.len() as f64
+}
+
+/// Resampling stage of `bootstrap_columns`: the resampled uplifts and the observed statistics, which do
+/// not depend on the confidence level, the sidedness or the bias correction.
+pub struct BootstrapNull {
+    pub uplift_diffs: Vec<f64>,
+    pub mean_1: f64,
+    pub mean_2: f64,
+    pub uplift: f64,
+}
+
+impl BootstrapNull {
+    /// Summarization stage of `bootstrap_columns`. `mean_inner` is the double-bootstrap mean of
+    /// `double_bootstrap_mean`, used when `bias_corrected` is set.
+    ///
+    /// A zero first-group statistic leaves the uplift undefined and every output but the group
+    /// statistics is NaN. When every resample gives the same uplift (both groups constant), the
+    /// p-value is the exact one of a known difference: 1 (0.5 one-sided) for a zero uplift, 0 otherwise.
+    pub fn summarize(
+        &self,
+        confidence_level: f64,
+        two_sided: bool,
+        bias_corrected: bool,
+        mean_inner: Option<f64>,
+    ) -> BootstrapOutput {
+        if !self.uplift.is_finite() {
+            return (
+                f64::NAN,
+                self.mean_1,
+                self.mean_2,
+                f64::NAN,
+                (f64::NAN, f64::NAN),
+            );
+        }
+        let left_q = (1.0 - confidence_level) / 2.0;
+        let right_q = 1.0 - left_q;
+        let n_resamples = self.uplift_diffs.len() as u64;
+        let p: f64 = if is_constant(&self.uplift_diffs) {
+            match self.uplift.total_cmp(&0.0) {
+                Ordering::Greater => 1.0,
+                Ordering::Less => 0.0,
+                Ordering::Equal => 0.5,
+            }
+        } else {
+            (self.uplift_diffs.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0)
+                / (n_resamples + 1) as f64
+        };
+        let p_value = (2.0 - 2.0 * p).min(p * 2.0);
+        let q = self.uplift_diffs.quantile(&[left_q, right_q]);
+        let uplift = if bias_corrected {
+            let mean_resampled = self.uplift_diffs.iter().sum::<f64>() / n_resamples as f64;
+            match mean_inner {
+                Some(mean_inner) => 3.0 * self.uplift - 3.0 * mean_resampled + mean_inner,
+                None => 2.0 * self.uplift - mean_resampled,
+            }
+        } else {
+            self.uplift
+        };
+        (
+            if two_sided { p_value } else { p },
+            self.mean_1,
+            self.mean_2,
+            uplift,
+            (q[0], q[1]),
+        )
+    }
+
+    /// Quantile levels of the BCa interval at `confidence_level`. The bias correction is the normal
+    /// quantile of the share of resampled uplifts below the observed one, ties counting half. None when
+    /// `1 - acceleration * z` is not positive at an endpoint, where the adjustment is undefined.
+    fn bca_levels(&self, confidence_level: f64, acceleration: f64) -> Option<[f64; 2]> {
+        let n = self.uplift_diffs.len() as f64;
+        let below = self.uplift_diffs.iter().fold(0.0, |count, &u| {
+            count
+                + match u.total_cmp(&self.uplift) {
+                    Ordering::Less => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Greater => 0.0,
+                }
+        });
+        let z_0 = crate::special::norm_ppf((below / n).clamp(0.5 / n, 1.0 - 0.5 / n));
+        let adjusted = |q: f64| {
+            let z = z_0 + crate::special::norm_ppf(q);
+            let denominator = 1.0 - acceleration * z;
+            (denominator > 0.0).then(|| crate::special::norm_cdf(z_0 + z / denominator))
+        };
+        let left_q = (1.0 - confidence_level) / 2.0;
+        Some([adjusted(left_q)?, adjusted(1.0 - left_q)?])
+    }
+
+    /// Whether the BCa interval at `confidence_level` is defined, in which case `interval` does not
+    /// fall back to the percentile interval.
+    pub fn bca_defined(&self, confidence_level: f64, acceleration: f64) -> bool {
+        !self.uplift.is_finite() || self.bca_levels(confidence_level, acceleration).is_some()
+    }
+
+    /// Interval of the uplift at `confidence_level` built with `method`; `acceleration` is the
+    /// `jackknife_acceleration` of the columns, used by BCa only, which falls back to the percentile
+    /// interval when its adjustment is undefined (see `bca_defined`).
+    pub fn interval(
+        &self,
+        confidence_level: f64,
+        method: BootstrapCiMethod,
+        acceleration: f64,
+    ) -> (f64, f64) {
+        if !self.uplift.is_finite() {
+            return (f64::NAN, f64::NAN);
+        }
+        let left_q = (1.0 - confidence_level) / 2.0;
+        let right_q = 1.0 - left_q;
+        match method {
+            BootstrapCiMethod::Percentile => {
+                let q = self.uplift_diffs.quantile(&[left_q, right_q]);
+                (q[0], q[1])
+            }
+            BootstrapCiMethod::Basic => {
+                let q = self.uplift_diffs.quantile(&[left_q, right_q]);
+                (2.0 * self.uplift - q[1], 2.0 * self.uplift - q[0])
+            }
+            BootstrapCiMethod::Bca => {
+                let levels = self
+                    .bca_levels(confidence_level, acceleration)
+                    .unwrap_or([left_q, right_q]);
+                let q = self.uplift_diffs.quantile(&levels);
+                (q[0], q[1])
+            }
+        }
+    }
+}
+
+/// Bootstraps the uplift in means (two columns) or mean ratios (four columns) between two groups.
+///
+/// Follows the conventions of the Python `bootstrap` function.
+#[allow(clippy::too_many_arguments)]
+pub fn bootstrap_columns(
+    args: &[&[f64]],
+    confidence_level: f64,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    bias_corrected: bool,
+    n_inner_resamples: u64,
+    resampler: &Resampler,
+) -> Result<BootstrapOutput> {
+    let null = bootstrap_null(args, n_resamples, ind, Accumulator::F64, resampler)?;
+    let mean_inner = (bias_corrected && n_inner_resamples > 0)
+        .then(|| double_bootstrap_mean(args, ind, n_resamples, n_inner_resamples, resampler));
+    Ok(null.summarize(confidence_level, two_sided, bias_corrected, mean_inner))
+}
+
+/// Construction of the relative uplift interval of `bootstrap`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpliftCiMethod {
+    /// Percentile interval of the resampled uplifts.
+    Ratio,
+    /// Fieller interval of the ratio of the group statistics, minus one.
+    Fieller,
+    /// Delta-method interval on the log of the ratio of the group statistics, transformed back.
+    Log,
+}
+
+impl UpliftCiMethod {
+    /// Parses `"ratio"`, `"fieller"` or `"log"`.
+    pub fn parse(method: &str) -> Result<UpliftCiMethod> {
+        match method {
+            "ratio" => Ok(UpliftCiMethod::Ratio),
+            "fieller" => Ok(UpliftCiMethod::Fieller),
+            "log" => Ok(UpliftCiMethod::Log),
+            _ => Err(StatError::InvalidInput(format!(
+                "uplift_ci_method must be one of 'ratio', 'fieller' or 'log', got '{method}'"
+            ))),
+        }
+    }
+}
+
+/// Statistic of a group (the mean, or the ratio of sums with a denominator column) and its
+/// linearization: the deviations whose variance over `n` is the delta-method variance of the statistic.
+fn linearized(values: &[f64], denominators: Option<&[f64]>) -> (f64, Vec<f64>) {
+    let n = values.len() as f64;
+    match denominators {
+        None => {
+            let mean = values.iter().sum::<f64>() / n;
+            (mean, values.iter().map(|x| x - mean).collect())
+        }
+        Some(denominators) => {
+            let mean_den = denominators.iter().sum::<f64>() / n;
+            let ratio = values.iter().sum::<f64>() / denominators.iter().sum::<f64>();
+            let deviations = values
+                .iter()
+                .zip(denominators)
+                .map(|(num, den)| (num - ratio * den) / mean_den)
+                .collect();
+            (ratio, deviations)
+        }
+    }
+}
+
+/// Analytic interval of the relative uplift `stat_2 / stat_1 - 1` with `method` (`Fieller` or `Log`),
+/// from the delta-method variances of the group statistics and, for paired samples, their covariance.
+///
+/// Fails when the first-group statistic is not distinguishable from zero at `confidence_level` (its
+/// interval contains zero), where the ratio is unbounded, and for `Log` when the statistics differ in
+/// sign.
+pub fn analytic_uplift_interval(
+    args: &[&[f64]],
+    ind: bool,
+    confidence_level: f64,
+    method: UpliftCiMethod,
+) -> Result<(f64, f64)> {
+    let ((stat_1, dev_1), (stat_2, dev_2)) = match args.len() {
+        2 => (linearized(args[0], None), linearized(args[1], None)),
+        4 => (
+            linearized(args[0], Some(args[1])),
+            linearized(args[2], Some(args[3])),
+        ),
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 arrays".to_string(),
+            ))
+        }
+    };
+    let (n_1, n_2) = (dev_1.len() as f64, dev_2.len() as f64);
+    if n_1 < 2.0 || n_2 < 2.0 {
+        return Err(StatError::InsufficientData(
+            "analytic uplift intervals need at least two observations per group".to_string(),
+        ));
+    }
+    let var = |dev: &[f64]| dev.iter().map(|d| d * d).sum::<f64>() / (dev.len() as f64 - 1.0);
+    let v_11 = var(&dev_1) / n_1;
+    let v_22 = var(&dev_2) / n_2;
+    let v_12 = if ind {
+        0.0
+    } else {
+        dev_1.iter().zip(&dev_2).map(|(a, b)| a * b).sum::<f64>() / ((n_1 - 1.0) * n_1)
+    };
+    let z = crate::special::norm_ppf((1.0 + confidence_level) / 2.0);
+    let a = stat_1 * stat_1 - z * z * v_11;
+    if a <= 0.0 {
+        return Err(StatError::InvalidInput(format!(
+            "The first-group statistic {stat_1} is within {z:.2} standard errors of zero, so the uplift interval is unbounded"
+        )));
+    }
+    let ratio = stat_2 / stat_1;
+    match method {
+        UpliftCiMethod::Fieller => {
+            let b = -2.0 * (stat_1 * stat_2 - z * z * v_12);
+            let c = stat_2 * stat_2 - z * z * v_22;
+            let root = (b * b - 4.0 * a * c).max(0.0).sqrt();
+            Ok(((-b - root) / (2.0 * a) - 1.0, (-b + root) / (2.0 * a) - 1.0))
+        }
+        UpliftCiMethod::Log => {
+            if ratio <= 0.0 {
+                return Err(StatError::InvalidInput(
+                    "uplift_ci_method 'log' requires group statistics of the same sign".to_string(),
+                ));
+            }
+            let se = (v_22 / (stat_2 * stat_2) + v_11 / (stat_1 * stat_1)
+                - 2.0 * v_12 / (stat_1 * stat_2))
+                .max(0.0)
+                .sqrt();
+            Ok((
+                (ratio.ln() - z * se).exp() - 1.0,
+                (ratio.ln() + z * se).exp() - 1.0,
+            ))
+        }
+        UpliftCiMethod::Ratio => Err(StatError::InvalidInput(
+            "the 'ratio' uplift interval is the bootstrap percentile interval".to_string(),
+        )),
+    }
+}
+
+/// Construction of a bootstrap interval from the resampled uplifts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BootstrapCiMethod {
+    /// Quantiles of the resampled uplifts.
+    Percentile,
+    /// Quantiles of the resampled uplifts reflected around the observed uplift.
+    Basic,
+    /// Bias-corrected and accelerated quantiles of the resampled uplifts.
+    Bca,
+}
+
+impl BootstrapCiMethod {
+    /// Parses `"percentile"`, `"basic"` or `"bca"`.
+    pub fn parse(method: &str) -> Result<BootstrapCiMethod> {
+        match method {
+            "percentile" => Ok(BootstrapCiMethod::Percentile),
+            "basic" => Ok(BootstrapCiMethod::Basic),
+            "bca" => Ok(BootstrapCiMethod::Bca),
+            _ => Err(StatError::InvalidInput(format!(
+                "ci_method must be one of 'percentile', 'basic' or 'bca', got '{method}'"
+            ))),
+        }
+    }
+}
+
+/// Leave-one-out statistics of a group: the means, or the ratios of sums with a denominator column,
+/// of the group without each of its units.
+fn leave_one_out(values: &[f64], denominators: Option<&[f64]>) -> Vec<f64> {
+    let total = fixed_order_sum(values);
+    match denominators {
+        None => {
+            let n = values.len() as f64;
+            values.iter().map(|x| (total - x) / (n - 1.0)).collect()
+        }
+        Some(denominators) => {
+            let total_den = fixed_order_sum(denominators);
+            values
+                .iter()
+                .zip(denominators)
+                .map(|(num, den)| (total - num) / (total_den - den))
+                .collect()
+        }
+    }
+}
+
+/// Jackknife acceleration `sum(u^3) / (6 * sum(u^2)^1.5)` of the uplift of two groups of means (two
+/// columns) or ratios of sums (four columns), for BCa intervals.
+///
+/// The influence values `u` are those of the jackknife of every group with the other one fixed, scaled
+/// by `(n - 1) / n` of their group as in the multi-sample formula of Efron and Tibshirani; paired
+/// samples are jackknifed by pairs. Each leave-one-out statistic comes from the group totals, so the
+/// cost is linear in the number of observations.
+pub fn jackknife_acceleration(args: &[&[f64]], ind: bool) -> Result<f64> {
+    let (loo_1, loo_2, stat_1, stat_2) = match args.len() {
+        2 => (
+            leave_one_out(args[0], None),
+            leave_one_out(args[1], None),
+            fixed_order_sum(args[0]) / args[0].len() as f64,
+            fixed_order_sum(args[1]) / args[1].len() as f64,
+        ),
+        4 => (
+            leave_one_out(args[0], Some(args[1])),
+            leave_one_out(args[2], Some(args[3])),
+            fixed_order_sum(args[0]) / fixed_order_sum(args[1]),
+            fixed_order_sum(args[2]) / fixed_order_sum(args[3]),
+        ),
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 arrays".to_string(),
+            ))
+        }
+    };
+    if loo_1.len() < 2 || loo_2.len() < 2 {
+        return Err(StatError::InsufficientData(
+            "the jackknife acceleration needs at least two observations per group".to_string(),
+        ));
+    }
+    // Influence values of the jackknifed uplifts `values`, scaled by `scale`.
+    let influence = |values: Vec<f64>, scale: f64| -> Vec<f64> {
+        let mean = fixed_order_sum(&values) / values.len() as f64;
+        values.iter().map(|theta| scale * (mean - theta)).collect()
+    };
+    let influences: Vec<f64> = if ind {
+        let (n_1, n_2) = (loo_1.len() as f64, loo_2.len() as f64);
+        let mut influences = influence(
+            loo_1.iter().map(|&s| calculate_uplift(s, stat_2)).collect(),
+            (n_1 - 1.0) / n_1,
+        );
+        influences.extend(influence(
+            loo_2.iter().map(|&s| calculate_uplift(stat_1, s)).collect(),
+            (n_2 - 1.0) / n_2,
+        ));
+        influences
+    } else {
+        if loo_1.len() != loo_2.len() {
+            return Err(StatError::InvalidInput(
+                "For non ind test all arrays must have same size".to_string(),
+            ));
+        }
+        let uplifts = loo_1
+            .iter()
+            .zip(&loo_2)
+            .map(|(&s_1, &s_2)| calculate_uplift(s_1, s_2))
+            .collect();
+        influence(uplifts, 1.0)
+    };
+    let sum_squares: f64 = influences.iter().map(|u| u * u).sum();
+    if sum_squares == 0.0 {
+        return Ok(0.0);
+    }
+    let sum_cubes: f64 = influences.iter().map(|u| u * u * u).sum();
+    Ok(sum_cubes / (6.0 * sum_squares.powf(1.5)))
+}
+
+/// Resampling stage of a two-sample `bootstrap_columns` of `statistic` instead of the mean: the uplifts
+/// of the statistic of both resampled groups, whose observed values are reported as the group means.
+/// Paired samples (`ind` false) are resampled by pairs.
+pub fn statistic_bootstrap_null(
+    args: &[&[f64]],
+    n_resamples: u64,
+    ind: bool,
+    statistic: Statistic,
+    resampler: &Resampler,
+) -> Result<BootstrapNull> {
+    if args.len() != 2 {
+        return Err(StatError::InvalidInput(
+            "A statistic other than the mean requires two samples; for ratio metrics use the mean of ratios estimand".to_string(),
+        ));
+    }
+    let (len_1, len_2) = (args[0].len(), args[1].len());
+    if !ind && len_1 != len_2 {
+        return Err(StatError::InvalidInput(
+            "For non ind test all arrays must have same size".to_string(),
+        ));
+    }
+    if matches!(statistic, Statistic::Std | Statistic::Var) && len_1.min(len_2) < 2 {
+        return Err(StatError::InsufficientData(
+            "std and var need at least two observations".to_string(),
+        ));
+    }
+    let (stat_1, stat_2) = (statistic.compute(args[0]), statistic.compute(args[1]));
+    let sampler_1 = IndexSampler::new([args[0]])?;
+    let sampler_2 = IndexSampler::new([args[1]])?;
+    let paired_sampler = (!ind)
+        .then(|| IndexSampler::new([args[0], args[1]]))
+        .transpose()?;
+    let uplift_diffs = resampler.map(n_resamples, |rng| {
+        let (resample_1, resample_2): (Vec<f64>, Vec<f64>) = match &paired_sampler {
+            Some(sampler) => (0..len_1).map(|_| sampler.draw(rng).into()).unzip(),
+            None => (
+                (0..len_1).map(|_| sampler_1.draw(rng)[0]).collect(),
+                (0..len_2).map(|_| sampler_2.draw(rng)[0]).collect(),
+            ),
+        };
+        calculate_uplift(
+            statistic.compute(&resample_1),
+            statistic.compute(&resample_2),
+        )
+    });
+    Ok(BootstrapNull {
+        uplift_diffs,
+        mean_1: stat_1,
+        mean_2: stat_2,
+        uplift: calculate_uplift(stat_1, stat_2),
+    })
+}
+
+/// Resampling stage of `bootstrap_columns`, with sums accumulated by `accumulator`.
+pub fn bootstrap_null(
+    args: &[&[f64]],
+    n_resamples: u64,
+    ind: bool,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+) -> Result<BootstrapNull> {
+    match accumulator {
+        Accumulator::F64 => bootstrap_kernel::<f64>(args, n_resamples, ind, resampler),
+        Accumulator::Kahan => bootstrap_kernel::<Kahan>(args, n_resamples, ind, resampler),
+        Accumulator::DoubleDouble => {
+            bootstrap_kernel::<DoubleDouble>(args, n_resamples, ind, resampler)
+        }
+    }
+}
+
+fn bootstrap_kernel<A: Accumulate>(
+    args: &[&[f64]],
+    n_resamples: u64,
+    ind: bool,
+    resampler: &Resampler,
+) -> Result<BootstrapNull> {
+    let (uplift_diffs, mean_1, mean_2, uplift): (Vec<f64>, f64, f64, f64) = match args.len() {
+        2 => {
+            let len_vec_1 = args[0].len();
+            let len_vec_2 = args[1].len();
+            if !ind && len_vec_1 != len_vec_2 {
+                return Err(StatError::InvalidInput(
+                    "For non ind test all arrays must have same size".to_string(),
+                ));
+            }
+            let (mean_1, mean_2): (f64, f64) = (
+                A::sum(args[0]) / len_vec_1 as f64,
+                A::sum(args[1]) / len_vec_2 as f64,
+            );
+            let uplift = calculate_uplift(mean_1, mean_2);
+            let min_len = len_vec_1.min(len_vec_2);
+            let sampler_1 = IndexSampler::new([args[0]])?;
+            let sampler_2 = IndexSampler::new([args[1]])?;
+            let paired_sampler = (!ind)
+                .then(|| IndexSampler::new([args[0], args[1]]))
+                .transpose()?;
+
+            let uplift_diffs: Vec<f64> = resampler.map(n_resamples, |rng| {
+                let mut sum_vec_1 = A::default();
+                let mut sum_vec_2 = A::default();
+                if ind {
+                    for _ in 0..min_len {
+                        let [value_1] = sampler_1.draw(rng);
+                        let [value_2] = sampler_2.draw(rng);
+                        sum_vec_1.add(value_1);
+                        sum_vec_2.add(value_2);
+                    }
+                    match len_vec_1.cmp(&len_vec_2) {
+                        Ordering::Greater => {
+                            for _ in 0..(len_vec_1 - len_vec_2) {
+                                let [value_1] = sampler_1.draw(rng);
+                                sum_vec_1.add(value_1);
+                            }
+                        }
+                        Ordering::Less => {
+                            for _ in 0..(len_vec_2 - len_vec_1) {
+                                let [value_2] = sampler_2.draw(rng);
+                                sum_vec_2.add(value_2);
+                            }
+                        }
+                        Ordering::Equal => {}
+                    }
+                } else if let Some(sampler) = &paired_sampler {
+                    for _ in 0..min_len {
+                        let [value_1, value_2] = sampler.draw(rng);
+                        sum_vec_1.add(value_1);
+                        sum_vec_2.add(value_2);
+                    }
+                }
+                let mean_1 = sum_vec_1.value() / len_vec_1 as f64;
+                let mean_2 = sum_vec_2.value() / len_vec_2 as f64;
+                calculate_uplift(mean_1, mean_2)
+            });
+            (uplift_diffs, mean_1, mean_2, uplift)
+        }
+        4 => {
+            let vec_sizes: Vec<usize> = args.iter().map(|vec| vec.len()).collect();
+            if !ind {
+                if !(vec_sizes[0] == vec_sizes[1]
+                    && vec_sizes[2] == vec_sizes[3]
+                    && vec_sizes[0] == vec_sizes[2])
+                {
+                    return Err(StatError::InvalidInput(
+                        "For non ind test all arrays must have same size".to_string(),
+                    ));
+                }
+            } else if vec_sizes[0] != vec_sizes[1] || vec_sizes[2] != vec_sizes[3] {
+                return Err(StatError::InvalidInput(
+                    "Each pair of arrays must be of equal length.".to_string(),
+                ));
+            }
+            let (mean_1, mean_2): (f64, f64) = (
+                A::sum(args[0]) / A::sum(args[1]),
+                A::sum(args[2]) / A::sum(args[3]),
+            );
+            let uplift = calculate_uplift(mean_1, mean_2);
+            let sampler_1 = IndexSampler::new([args[0], args[1]])?;
+            let sampler_2 = IndexSampler::new([args[2], args[3]])?;
+            let paired_sampler = (!ind)
+                .then(|| IndexSampler::new([args[0], args[1], args[2], args[3]]))
+                .transpose()?;
+            let min_len = vec_sizes[0].min(vec_sizes[2]);
+            let uplift_diffs: Vec<f64> = resampler.map(n_resamples, |rng| {
+                let mut sum_num_1 = A::default();
+                let mut sum_denum_1 = A::default();
+                let mut sum_num_2 = A::default();
+                let mut sum_denum_2 = A::default();
+                if ind {
+                    for _ in 0..min_len {
+                        let [num_1, denum_1] = sampler_1.draw(rng);
+                        let [num_2, denum_2] = sampler_2.draw(rng);
+                        sum_num_1.add(num_1);
+                        sum_denum_1.add(denum_1);
+                        sum_num_2.add(num_2);
+                        sum_denum_2.add(denum_2);
+                    }
+                    match vec_sizes[0].cmp(&vec_sizes[2]) {
+                        Ordering::Greater => {
+                            for _ in 0..(vec_sizes[0] - vec_sizes[2]) {
+                                let [num_1, denum_1] = sampler_1.draw(rng);
+                                sum_num_1.add(num_1);
+                                sum_denum_1.add(denum_1);
+                            }
+                        }
+                        Ordering::Less => {
+                            for _ in 0..(vec_sizes[2] - vec_sizes[0]) {
+                                let [num_2, denum_2] = sampler_2.draw(rng);
+                                sum_num_2.add(num_2);
+                                sum_denum_2.add(denum_2);
+                            }
+                        }
+                        Ordering::Equal => {}
+                    }
+                } else if let Some(sampler) = &paired_sampler {
+                    for _ in 0..min_len {
+                        let [num_1, denum_1, num_2, denum_2] = sampler.draw(rng);
+                        sum_num_1.add(num_1);
+                        sum_denum_1.add(denum_1);
+                        sum_num_2.add(num_2);
+                        sum_denum_2.add(denum_2);
+                    }
+                }
+                let mean_1 = sum_num_1.value() / sum_denum_1.value();
+                let mean_2 = sum_num_2.value() / sum_denum_2.value();
+                calculate_uplift(mean_1, mean_2)
+            });
+
+            (uplift_diffs, mean_1, mean_2, uplift)
+        }
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 vectors.".to_string(),
+            ));
+        }
+    };
+    Ok(BootstrapNull {
+        uplift_diffs,
+        mean_1,
+        mean_2,
+        uplift,
+    })
+}
@@ -0,0 +1,381 @@
+//! Descriptive statistics of large samples, such as resampled distributions.
+//!
+//! Sums run over fixed-size chunks in parallel and their partial sums are added in chunk order, so the
+//! results do not depend on the number of threads.
+
+use crate::error::*;
+use crate::tools::{fixed_order_sum, MathUtil, SUM_CHUNK};
+use rayon::prelude::*;
+
+/// Consistency constant of the median absolute deviation for normal data, 1 / Phi^-1(3/4).
+pub const MAD_NORMAL_SCALE: f64 = 1.482_602_218_505_602;
+
+fn check_sample(data: &[f64], min_len: usize) -> Result<()> {
+    if data.len() < min_len {
+        return Err(StatError::InsufficientData(format!(
+            "The sample must contain at least {min_len} observations, got {}",
+            data.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Sum of `f(x)` over `data`, in the fixed chunk order of `fixed_order_sum`.
+fn fixed_order_map_sum(data: &[f64], f: impl Fn(f64) -> f64 + Sync) -> f64 {
+    data.par_chunks(SUM_CHUNK)
+        .map(|chunk| chunk.iter().map(|&x| f(x)).sum::<f64>())
+        .collect::<Vec<f64>>()
+        .iter()
+        .sum()
+}
+
+pub fn mean(data: &[f64]) -> Result<f64> {
+    check_sample(data, 1)?;
+    Ok(fixed_order_sum(data) / data.len() as f64)
+}
+
+/// Variance with `ddof` degrees of freedom removed from the denominator (1 for the sample variance).
+pub fn variance(data: &[f64], ddof: usize) -> Result<f64> {
+    check_sample(data, ddof + 1)?;
+    let mean = mean(data)?;
+    Ok(fixed_order_map_sum(data, |x| (x - mean).powi(2)) / (data.len() - ddof) as f64)
+}
+
+pub fn std_dev(data: &[f64], ddof: usize) -> Result<f64> {
+    variance(data, ddof).map(f64::sqrt)
+}
+
+/// Biased (population) central moments `(mean, m2, m3, m4)`, as `tools::central_moments` in parallel.
+fn central_moments(data: &[f64]) -> Result<(f64, f64, f64, f64)> {
+    check_sample(data, 2)?;
+    let n = data.len() as f64;
+    let mean = mean(data)?;
+    let moments = data
+        .par_chunks(SUM_CHUNK)
+        .map(|chunk| {
+            chunk.iter().fold((0.0, 0.0, 0.0), |(m2, m3, m4), &x| {
+                let d = x - mean;
+                let d2 = d * d;
+                (m2 + d2, m3 + d2 * d, m4 + d2 * d2)
+            })
+        })
+        .collect::<Vec<(f64, f64, f64)>>();
+    let (m2, m3, m4) = moments
+        .iter()
+        .fold((0.0, 0.0, 0.0), |(a, b, c), &(m2, m3, m4)| {
+            (a + m2, b + m3, c + m4)
+        });
+    Ok((mean, m2 / n, m3 / n, m4 / n))
+}
+
+/// Biased sample skewness `m3 / m2^1.5`.
+pub fn skewness(data: &[f64]) -> Result<f64> {
+    let (_, m2, m3, _) = central_moments(data)?;
+    Ok(m3 / m2.powf(1.5))
+}
+
+/// Biased sample excess kurtosis `m4 / m2^2 - 3`.
+pub fn kurtosis(data: &[f64]) -> Result<f64> {
+    let (_, m2, _, m4) = central_moments(data)?;
+    Ok(m4 / (m2 * m2) - 3.0)
+}
+
+pub fn median(data: &[f64]) -> Result<f64> {
+    check_sample(data, 1)?;
+    Ok(data.quantile(&[0.5])[0])
+}
+
+/// Median absolute deviation from the median, multiplied by `scale` (`MAD_NORMAL_SCALE` estimates
+/// the standard deviation of normal data).
+pub fn mad(data: &[f64], scale: f64) -> Result<f64> {
+    let median = median(data)?;
+    let deviations: Vec<f64> = data.par_iter().map(|x| (x - median).abs()).collect();
+    Ok(scale * deviations.quantile(&[0.5])[0])
+}
+
+/// Interquartile range, the difference between the 0.75 and 0.25 quantiles.
+pub fn iqr(data: &[f64]) -> Result<f64> {
+    check_sample(data, 1)?;
+    let q = data.quantile(&[0.25, 0.75]);
+    Ok(q[1] - q[0])
+}
+
+/// Meaning of the weights of a weighted statistic.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WeightKind {
+    /// Every weight is the number of times its value was observed, as for aggregated data.
+    Frequency,
+    /// Every weight is proportional to the inverse of the probability of sampling its value, as for
+    /// survey or importance weights; only relative weights matter.
+    Probability,
+}
+
+impl WeightKind {
+    pub fn parse(kind: &str) -> Result<WeightKind> {
+        match kind {
+            "frequency" => Ok(WeightKind::Frequency),
+            "probability" => Ok(WeightKind::Probability),
+            _ => Err(StatError::InvalidInput(format!(
+                "weights must be either 'frequency' or 'probability', got '{kind}'"
+            ))),
+        }
+    }
+}
+
+fn check_weights(values: &[f64], weights: &[f64]) -> Result<f64> {
+    check_sample(values, 1)?;
+    if weights.len() != values.len() {
+        return Err(StatError::InvalidInput(
+            "values and weights must have equal size".to_string(),
+        ));
+    }
+    if weights.par_iter().any(|&w| !(w >= 0.0 && w.is_finite())) {
+        return Err(StatError::InvalidInput(
+            "weights must be non-negative and finite".to_string(),
+        ));
+    }
+    let total = fixed_order_sum(weights);
+    if total <= 0.0 {
+        return Err(StatError::InvalidInput(
+            "weights must not all be zero".to_string(),
+        ));
+    }
+    Ok(total)
+}
+
+/// Sum of `f(x, w)` over the pairs of `values` and `weights`, in fixed chunk order.
+fn fixed_order_weighted_sum(
+    values: &[f64],
+    weights: &[f64],
+    f: impl Fn(f64, f64) -> f64 + Sync,
+) -> f64 {
+    values
+        .par_chunks(SUM_CHUNK)
+        .zip(weights.par_chunks(SUM_CHUNK))
+        .map(|(values, weights)| {
+            values
+                .iter()
+                .zip(weights)
+                .map(|(&x, &w)| f(x, w))
+                .sum::<f64>()
+        })
+        .collect::<Vec<f64>>()
+        .iter()
+        .sum()
+}
+
+/// Weighted mean `sum(w * x) / sum(w)`, the same for both kinds of weights.
+pub fn weighted_mean(values: &[f64], weights: &[f64]) -> Result<f64> {
+    let total = check_weights(values, weights)?;
+    Ok(fixed_order_weighted_sum(values, weights, |x, w| w * x) / total)
+}
+
+/// Unbiased weighted variance: `sum(w (x - m)^2)` divided by `sum(w) - 1` for frequency weights, and by
+/// `V1 - V2 / V1` with `V1 = sum(w)` and `V2 = sum(w^2)` for probability weights.
+pub fn weighted_variance(values: &[f64], weights: &[f64], kind: WeightKind) -> Result<f64> {
+    let mean = weighted_mean(values, weights)?;
+    let v1 = fixed_order_sum(weights);
+    let denominator = match kind {
+        WeightKind::Frequency => v1 - 1.0,
+        WeightKind::Probability => v1 - fixed_order_map_sum(weights, |w| w * w) / v1,
+    };
+    if denominator <= 0.0 {
+        return Err(StatError::InsufficientData(
+            "The weights leave no degree of freedom for the variance".to_string(),
+        ));
+    }
+    Ok(fixed_order_weighted_sum(values, weights, |x, w| w * (x - mean).powi(2)) / denominator)
+}
+
+/// Weighted `q` quantile. With frequency weights it is the quantile of `MathUtil::quantile` of the
+/// sample where every value is repeated as many times as its weight, interpolating between order
+/// statistics; with probability weights it is the smallest value whose share of the total weight up to
+/// it reaches `q` (the inverse of the weighted empirical distribution function).
+pub fn weighted_quantile(values: &[f64], weights: &[f64], q: f64, kind: WeightKind) -> Result<f64> {
+    let total = check_weights(values, weights)?;
+    if !(0.0..=1.0).contains(&q) {
+        return Err(StatError::InvalidInput(format!(
+            "q must be in [0, 1], got {q}"
+        )));
+    }
+    let mut order: Vec<usize> = (0..values.len()).filter(|&i| weights[i] > 0.0).collect();
+    order.par_sort_unstable_by(|&i, &j| values[i].total_cmp(&values[j]));
+    // Value of the order statistic of 0-based position `k` of the expanded sample.
+    let order_statistic = |k: f64| {
+        let mut cumulative = 0.0;
+        for &i in &order {
+            cumulative += weights[i];
+            if cumulative > k {
+                return values[i];
+            }
+        }
+        values[*order.last().unwrap()]
+    };
+    Ok(match kind {
+        WeightKind::Frequency => {
+            let pos = q * (total - 1.0).max(0.0);
+            let (j, g) = (pos.floor(), pos.fract());
+            (1.0 - g) * order_statistic(j) + g * order_statistic(j + 1.0)
+        }
+        WeightKind::Probability => {
+            let target = q * total;
+            let mut cumulative = 0.0;
+            let mut quantile = values[*order.last().unwrap()];
+            for &i in &order {
+                cumulative += weights[i];
+                if cumulative >= target * (1.0 - 1e-12) {
+                    quantile = values[i];
+                    break;
+                }
+            }
+            quantile
+        }
+    })
+}
+
+/// Number of values binned sequentially by every task of `kde`, large enough for few partial grids.
+const BINNING_CHUNK: usize = 1 << 20;
+
+/// Number of points of the grid the sample is binned on by `kde`.
+const KDE_BINS: usize = 4096;
+
+/// Bandwidths beyond which the Gaussian kernel is treated as zero in `kde`.
+const KDE_CUTOFF: f64 = 8.0;
+
+fn check_finite(values: &[f64]) -> Result<()> {
+    if values.par_iter().any(|x| !x.is_finite()) {
+        return Err(StatError::InvalidInput(
+            "values must not contain NaN or infinite values".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn min_max(values: &[f64]) -> (f64, f64) {
+    values
+        .par_iter()
+        .fold(
+            || (f64::INFINITY, f64::NEG_INFINITY),
+            |(lo, hi), &x| (lo.min(x), hi.max(x)),
+        )
+        .reduce(
+            || (f64::INFINITY, f64::NEG_INFINITY),
+            |(a, b), (c, d)| (a.min(c), b.max(d)),
+        )
+}
+
+/// Counts of `values` in `bins` equal-width bins over `range` (the range of the values by default),
+/// and the `bins + 1` edges. Every bin includes its lower edge, the last one also its upper edge, and
+/// values outside the range are left out, as for `numpy.histogram`.
+pub fn histogram(
+    values: &[f64],
+    bins: usize,
+    range: Option<(f64, f64)>,
+) -> Result<(Vec<u64>, Vec<f64>)> {
+    check_sample(values, 1)?;
+    check_finite(values)?;
+    if bins == 0 {
+        return Err(StatError::InvalidInput("bins must be positive".to_string()));
+    }
+    let (mut lo, mut hi) = range.unwrap_or_else(|| min_max(values));
+    if !(lo <= hi && lo.is_finite() && hi.is_finite()) {
+        return Err(StatError::InvalidInput(
+            "range must be a finite (low, high) pair with low <= high".to_string(),
+        ));
+    }
+    if lo == hi {
+        (lo, hi) = (lo - 0.5, hi + 0.5);
+    }
+    let width = (hi - lo) / bins as f64;
+    let counts = values
+        .par_chunks(BINNING_CHUNK)
+        .fold(
+            || vec![0u64; bins],
+            |mut counts, chunk| {
+                for &x in chunk.iter().filter(|&&x| x >= lo && x <= hi) {
+                    counts[(((x - lo) / width) as usize).min(bins - 1)] += 1;
+                }
+                counts
+            },
+        )
+        .reduce(
+            || vec![0u64; bins],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+        );
+    let edges = (0..=bins).map(|k| lo + k as f64 * width).collect();
+    Ok((counts, edges))
+}
+
+/// Gaussian kernel density estimate of `values` at every point of `grid`, and the bandwidth used
+/// (Silverman's rule `0.9 min(sd, iqr / 1.34) n^(-1/5)` by default).
+///
+/// The sample is first linearly binned on a regular grid of `KDE_BINS` points, in parallel chunks
+/// added in a fixed order, and the density is then a kernel sum over the grid points, so the cost
+/// depends on the sample size only through the binning pass.
+pub fn kde(values: &[f64], grid: &[f64], bandwidth: Option<f64>) -> Result<(Vec<f64>, f64)> {
+    check_sample(values, 2)?;
+    check_finite(values)?;
+    let n = values.len() as f64;
+    let bandwidth = match bandwidth {
+        Some(h) => h,
+        None => {
+            let spread = std_dev(values, 1)?.min(iqr(values)? / 1.34);
+            let spread = if spread > 0.0 {
+                spread
+            } else {
+                std_dev(values, 1)?
+            };
+            0.9 * spread * n.powf(-0.2)
+        }
+    };
+    if !(bandwidth > 0.0 && bandwidth.is_finite()) {
+        return Err(StatError::InvalidInput(
+            "bandwidth must be positive; the sample may be constant".to_string(),
+        ));
+    }
+    let (lo, hi) = min_max(values);
+    let step = ((hi - lo) / (KDE_BINS - 1) as f64).max(f64::MIN_POSITIVE);
+    let bin_weights = values
+        .par_chunks(BINNING_CHUNK)
+        .map(|chunk| {
+            let mut weights = vec![0.0; KDE_BINS];
+            for &x in chunk {
+                let position = ((x - lo) / step).min((KDE_BINS - 1) as f64);
+                let j = (position.floor() as usize).min(KDE_BINS - 2);
+                let fraction = position - j as f64;
+                weights[j] += 1.0 - fraction;
+                weights[j + 1] += fraction;
+            }
+            weights
+        })
+        .collect::<Vec<Vec<f64>>>()
+        .into_iter()
+        .fold(vec![0.0; KDE_BINS], |mut total, weights| {
+            total.iter_mut().zip(weights).for_each(|(t, w)| *t += w);
+            total
+        });
+    let norm = 1.0 / (n * bandwidth * (2.0 * std::f64::consts::PI).sqrt());
+    let density = grid
+        .par_iter()
+        .map(|&g| {
+            bin_weights
+                .iter()
+                .enumerate()
+                .filter(|&(_, &w)| w > 0.0)
+                .map(|(j, &w)| {
+                    let z = (g - (lo + j as f64 * step)) / bandwidth;
+                    if z.abs() < KDE_CUTOFF {
+                        w * (-0.5 * z * z).exp()
+                    } else {
+                        0.0
+                    }
+                })
+                .sum::<f64>()
+                * norm
+        })
+        .collect();
+    Ok((density, bandwidth))
+}
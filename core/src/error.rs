@@ -0,0 +1,32 @@
+use std::fmt;
+
+/// Failure of a statistical routine, carrying the message reported to the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatError {
+    /// The data or parameters cannot be used by the routine, e.g. arrays of unequal length.
+    InvalidInput(String),
+    /// A sample is too small for the routine, e.g. an empty array to resample.
+    InsufficientData(String),
+    /// A data file cannot be read or has an unsupported format.
+    Io(String),
+    /// An iterative fit stopped at its iteration limit before converging.
+    Convergence(String),
+    /// A run would exceed a limit on its size, e.g. the number of exported indices.
+    BudgetExceeded(String),
+}
+
+impl fmt::Display for StatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatError::InvalidInput(message)
+            | StatError::InsufficientData(message)
+            | StatError::Io(message)
+            | StatError::Convergence(message)
+            | StatError::BudgetExceeded(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for StatError {}
+
+pub type Result<T> = std::result::Result<T, StatError>;
@@ -0,0 +1,33 @@
+//! The statistical engine of pylars, without Python bindings.
+//!
+//! The `pylars` extension module wraps these kernels for Python; other Rust services and the
+//! `pylars-cli` binary call them directly, and the `wasm` feature exposes them to JavaScript.
+
+pub mod bootstrap;
+pub mod descriptive;
+pub mod error;
+pub mod npy;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod permutation;
+pub mod reference;
+pub mod rng;
+pub mod special;
+pub mod tools;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use bootstrap::{
+    analytic_uplift_interval, bootstrap_columns, bootstrap_null, bootstrap_statistic,
+    bootstrap_statistic_sketch, jackknife_acceleration, statistic_bootstrap_null,
+    stratified_bootstrap_null, BootstrapCiMethod, BootstrapNull, Statistic, UpliftCiMethod,
+};
+pub use error::{Result, StatError};
+pub use npy::NpyArray;
+#[cfg(feature = "parquet")]
+pub use parquet::read_parquet_column;
+pub use permutation::{
+    exact_permutation_null, permutation_columns, permutation_null, statistic_permutation_null,
+    studentized_permutation_null, PermutationNull,
+};
+pub use rng::Resampler;
@@ -0,0 +1,196 @@
+use crate::error::*;
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+
+/// Element types of the `.npy` files that can be read.
+#[derive(Clone, Copy)]
+enum Dtype {
+    F8,
+    F4,
+    I8,
+    I4,
+    U1,
+    Bool,
+}
+
+/// A one-dimensional `.npy` array mapped into memory.
+///
+/// Little-endian float64 data, the numpy default, is read in place without a copy. Other element
+/// types and byte orders are converted to float64 when the column is requested.
+pub struct NpyArray {
+    mmap: Mmap,
+    offset: usize,
+    len: usize,
+    dtype: Dtype,
+    big_endian: bool,
+}
+
+/// Value of `key` in the header dictionary, up to the next comma at depth 0 or the closing brace.
+fn header_field<'h>(header: &'h str, key: &str) -> Result<&'h str> {
+    let start = header
+        .find(&format!("'{key}'"))
+        .ok_or_else(|| StatError::Io(format!("The npy header has no '{key}' field")))?
+        + key.len()
+        + 2;
+    let rest = header[start..]
+        .trim_start()
+        .trim_start_matches(':')
+        .trim_start();
+    let mut depth = 0;
+    for (pos, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' | '}' if depth == 0 => return Ok(rest[..pos].trim()),
+            _ => {}
+        }
+    }
+    Ok(rest.trim())
+}
+
+impl NpyArray {
+    pub fn open(path: &str) -> Result<Self> {
+        let file =
+            File::open(path).map_err(|e| StatError::Io(format!("Cannot open {path}: {e}")))?;
+        // SAFETY: the map is read-only; the file must not be truncated while the call runs.
+        let mmap = unsafe { Mmap::map(&file) }
+            .map_err(|e| StatError::Io(format!("Cannot map {path}: {e}")))?;
+        if mmap.len() < 10 || &mmap[..6] != b"\x93NUMPY" {
+            return Err(StatError::Io(format!("{path} is not an npy file")));
+        }
+        let (header_start, header_len) = match mmap[6] {
+            1 => (10, u16::from_le_bytes([mmap[8], mmap[9]]) as usize),
+            2 | 3 => (
+                12,
+                u32::from_le_bytes([mmap[8], mmap[9], mmap[10], mmap[11]]) as usize,
+            ),
+            version => {
+                return Err(StatError::Io(format!(
+                    "Unsupported npy format version {version}"
+                )))
+            }
+        };
+        let offset = header_start + header_len;
+        let header = std::str::from_utf8(&mmap[header_start..offset])
+            .map_err(|_| StatError::Io(format!("{path} has a malformed npy header")))?;
+
+        let descr = header_field(header, "descr")?.trim_matches(|c| c == '\'' || c == '"');
+        let big_endian = descr.starts_with('>');
+        let dtype = match descr.trim_start_matches(['<', '>', '|', '=']) {
+            "f8" => Dtype::F8,
+            "f4" => Dtype::F4,
+            "i8" => Dtype::I8,
+            "i4" => Dtype::I4,
+            "u1" => Dtype::U1,
+            "b1" => Dtype::Bool,
+            _ => {
+                return Err(StatError::Io(format!(
+                    "Unsupported npy dtype {descr}, expected float64, float32, int64, int32, uint8 or bool"
+                )))
+            }
+        };
+        if header_field(header, "fortran_order")? != "False" {
+            return Err(StatError::Io(
+                "Fortran-ordered npy arrays are not supported".to_string(),
+            ));
+        }
+        let shape: Vec<usize> = header_field(header, "shape")?
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(str::trim)
+            .filter(|dim| !dim.is_empty())
+            .map(|dim| {
+                dim.parse()
+                    .map_err(|_| StatError::Io("The npy shape must contain integers".to_string()))
+            })
+            .collect::<Result<_>>()?;
+        if shape.len() != 1 {
+            return Err(StatError::Io(format!(
+                "Only one-dimensional npy arrays are supported, {path} has shape {shape:?}"
+            )));
+        }
+        let array = NpyArray {
+            mmap,
+            offset,
+            len: shape[0],
+            dtype,
+            big_endian,
+        };
+        if array.mmap.len() < offset + array.len * array.item_size() {
+            return Err(StatError::Io(format!(
+                "{path} is shorter than its npy header declares"
+            )));
+        }
+        Ok(array)
+    }
+
+    fn item_size(&self) -> usize {
+        match self.dtype {
+            Dtype::F8 | Dtype::I8 => 8,
+            Dtype::F4 | Dtype::I4 => 4,
+            Dtype::U1 | Dtype::Bool => 1,
+        }
+    }
+
+    /// The data as float64, borrowed from the map when no conversion is required.
+    pub fn column(&self) -> Cow<'_, [f64]> {
+        let bytes = &self.mmap[self.offset..self.offset + self.len * self.item_size()];
+        if matches!(self.dtype, Dtype::F8) && self.big_endian == cfg!(target_endian = "big") {
+            // SAFETY: any bit pattern is a valid f64, and the prefix check rejects unaligned data.
+            let (prefix, values, _) = unsafe { bytes.align_to::<f64>() };
+            if prefix.is_empty() {
+                return Cow::Borrowed(values);
+            }
+        }
+        let be = self.big_endian;
+        let column = match self.dtype {
+            Dtype::F8 => bytes
+                .chunks_exact(8)
+                .map(|b| {
+                    let b = b.try_into().unwrap();
+                    if be {
+                        f64::from_be_bytes(b)
+                    } else {
+                        f64::from_le_bytes(b)
+                    }
+                })
+                .collect(),
+            Dtype::F4 => bytes
+                .chunks_exact(4)
+                .map(|b| {
+                    let b = b.try_into().unwrap();
+                    (if be {
+                        f32::from_be_bytes(b)
+                    } else {
+                        f32::from_le_bytes(b)
+                    }) as f64
+                })
+                .collect(),
+            Dtype::I8 => bytes
+                .chunks_exact(8)
+                .map(|b| {
+                    let b = b.try_into().unwrap();
+                    (if be {
+                        i64::from_be_bytes(b)
+                    } else {
+                        i64::from_le_bytes(b)
+                    }) as f64
+                })
+                .collect(),
+            Dtype::I4 => bytes
+                .chunks_exact(4)
+                .map(|b| {
+                    let b = b.try_into().unwrap();
+                    (if be {
+                        i32::from_be_bytes(b)
+                    } else {
+                        i32::from_le_bytes(b)
+                    }) as f64
+                })
+                .collect(),
+            Dtype::U1 | Dtype::Bool => bytes.iter().map(|&b| b as f64).collect(),
+        };
+        Cow::Owned(column)
+    }
+}
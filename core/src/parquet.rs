@@ -0,0 +1,148 @@
+use crate::error::*;
+use parquet::errors::ParquetError;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::Field;
+use parquet::schema::types::Type;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::sync::Arc;
+
+/// Row filter of the form `"<column> <op> <value>"`, e.g. `"country == 'US'"` or `"age >= 18"`.
+struct Filter {
+    column: String,
+    op: String,
+    value: FilterValue,
+}
+
+enum FilterValue {
+    Number(f64),
+    Text(String),
+}
+
+impl Filter {
+    fn parse(filter: &str) -> Result<Filter> {
+        let ops = ["==", "!=", "<=", ">=", "<", ">"];
+        let (pos, op) = ops
+            .iter()
+            .filter_map(|op| filter.find(op).map(|pos| (pos, *op)))
+            .min_by_key(|(pos, op)| (*pos, std::cmp::Reverse(op.len())))
+            .ok_or_else(|| {
+                StatError::InvalidInput(format!(
+                    "filter must look like \"column <op> value\" with op in {ops:?}"
+                ))
+            })?;
+        let column = filter[..pos].trim().to_string();
+        let raw = filter[pos + op.len()..].trim();
+        let value = if raw.len() >= 2
+            && (raw.starts_with('\'') && raw.ends_with('\'')
+                || raw.starts_with('"') && raw.ends_with('"'))
+        {
+            FilterValue::Text(raw[1..raw.len() - 1].to_string())
+        } else {
+            FilterValue::Number(raw.parse().map_err(|_| {
+                StatError::InvalidInput(format!(
+                    "filter value {raw} is neither a number nor a quoted string"
+                ))
+            })?)
+        };
+        Ok(Filter {
+            column,
+            op: op.to_string(),
+            value,
+        })
+    }
+
+    fn matches(&self, field: &Field) -> bool {
+        let ordering = match (&self.value, field) {
+            (FilterValue::Text(value), Field::Str(s)) => s.as_str().cmp(value.as_str()),
+            (FilterValue::Number(value), field) => match field_value(field) {
+                Some(x) => match x.partial_cmp(value) {
+                    Some(ordering) => ordering,
+                    None => return false,
+                },
+                None => return false,
+            },
+            _ => return false,
+        };
+        match self.op.as_str() {
+            "==" => ordering == Ordering::Equal,
+            "!=" => ordering != Ordering::Equal,
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            _ => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// Numeric value of a parquet field, or None for nulls and non-numeric fields.
+fn field_value(field: &Field) -> Option<f64> {
+    match *field {
+        Field::Bool(x) => Some(x as u8 as f64),
+        Field::Byte(x) => Some(x as f64),
+        Field::Short(x) => Some(x as f64),
+        Field::Int(x) => Some(x as f64),
+        Field::Long(x) => Some(x as f64),
+        Field::UByte(x) => Some(x as f64),
+        Field::UShort(x) => Some(x as f64),
+        Field::UInt(x) => Some(x as f64),
+        Field::ULong(x) => Some(x as f64),
+        Field::Float(x) => Some(x as f64),
+        Field::Double(x) => Some(x),
+        _ => None,
+    }
+}
+
+/// Streams the non-null values of `column` from the rows of a parquet file that pass `filter`.
+///
+/// Only the value column and the filter column are decoded.
+pub fn read_parquet_column(path: &str, column: &str, filter: Option<&str>) -> Result<Vec<f64>> {
+    let read_error = |e: ParquetError| StatError::Io(format!("Cannot read {path}: {e}"));
+    let file = File::open(path).map_err(|e| StatError::Io(format!("Cannot open {path}: {e}")))?;
+    let reader = SerializedFileReader::new(file).map_err(read_error)?;
+    let filter = filter.map(Filter::parse).transpose()?;
+
+    let schema = reader.metadata().file_metadata().schema();
+    let wanted: Vec<&str> = std::iter::once(column)
+        .chain(filter.as_ref().map(|f| f.column.as_str()))
+        .collect();
+    let fields: Vec<Arc<Type>> = schema
+        .get_fields()
+        .iter()
+        .filter(|field| wanted.contains(&field.name()))
+        .cloned()
+        .collect();
+    for name in &wanted {
+        if !fields.iter().any(|field| field.name() == *name) {
+            return Err(StatError::InvalidInput(format!(
+                "{path} has no column {name}"
+            )));
+        }
+    }
+    let projection = Type::group_type_builder(schema.name())
+        .with_fields(fields)
+        .build()
+        .map_err(read_error)?;
+
+    let mut values = Vec::with_capacity(reader.metadata().file_metadata().num_rows() as usize);
+    let rows = reader.get_row_iter(Some(projection)).map_err(read_error)?;
+    for row in rows {
+        let row = row.map_err(read_error)?;
+        let mut value = None;
+        let mut passes = filter.is_none();
+        for (name, field) in row.get_column_iter() {
+            if name == column {
+                value = field_value(field);
+            }
+            if let Some(filter) = &filter {
+                if name == &filter.column {
+                    passes = filter.matches(field);
+                }
+            }
+        }
+        if let (true, Some(value)) = (passes, value) {
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
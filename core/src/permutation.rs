@@ -0,0 +1,437 @@
+use crate::bootstrap::Statistic;
+use crate::error::*;
+use crate::rng::*;
+use crate::tools::*;
+use rand::prelude::*;
+
+/// Resampling stage of `permutation_columns`: the permuted differences and the observed statistics,
+/// which do not depend on the confidence level or the sidedness.
+///
+/// For a studentized test, `diffs` and `observed_statistic` are t-statistics and `scale` is the
+/// observed standard error, which converts their quantiles back to differences; otherwise
+/// `observed_statistic` is `observed_diff` and `scale` is 1. When `exact`, `diffs` holds the
+/// differences of every assignment of the labels rather than of random ones.
+pub struct PermutationNull {
+    pub diffs: Vec<f64>,
+    pub uplift: f64,
+    pub observed_diff: f64,
+    pub observed_statistic: f64,
+    pub scale: f64,
+    pub exact: bool,
+}
+
+impl PermutationNull {
+    /// Summarization stage of `permutation_columns`.
+    ///
+    /// When every permutation ties with the observed difference (all values equal), the p-value is 1
+    /// (0.5 one-sided). A zero first-group statistic leaves the uplift undefined, returned as NaN; the
+    /// p-value and interval of the difference are unaffected.
+    pub fn summarize(&self, confidence_level: f64, two_sided: bool) -> (f64, f64, f64, (f64, f64)) {
+        let left_q = (1.0 - confidence_level) / 2.0;
+        let right_q = 1.0 - left_q;
+        let observed = self.observed_statistic;
+        if self.exact {
+            return self.summarize_exact(confidence_level, two_sided);
+        }
+        let p = if self.diffs.iter().all(|&d| d == observed) {
+            0.5
+        } else {
+            (self.diffs.iter().filter(|i| observed > **i).count() + 1) as f64
+                / (self.diffs.len() + 1) as f64
+        };
+        let p_value = (2.0 - 2.0 * p).min(p * 2.0);
+        let q = self.diffs.quantile(&[left_q, right_q]);
+        (
+            if two_sided { p_value } else { p },
+            if self.uplift.is_finite() {
+                self.uplift
+            } else {
+                f64::NAN
+            },
+            self.observed_diff,
+            (q[0] * self.scale, q[1] * self.scale),
+        )
+    }
+
+    /// `summarize` of an enumerated null: the one-sided p-value is the share of assignments with a
+    /// difference at most the observed one, which includes the observed assignment, and the two-sided
+    /// one doubles the smaller tail, capped at 1. Differences within a relative 1e-9 of the observed
+    /// one, which recomputing the observed assignment in another order can give, count as ties.
+    fn summarize_exact(
+        &self,
+        confidence_level: f64,
+        two_sided: bool,
+    ) -> (f64, f64, f64, (f64, f64)) {
+        let left_q = (1.0 - confidence_level) / 2.0;
+        let right_q = 1.0 - left_q;
+        let observed = self.observed_statistic;
+        let scale = self
+            .diffs
+            .iter()
+            .fold(observed.abs(), |max, d| max.max(d.abs()));
+        let tolerance = 1e-9 * scale;
+        let n = self.diffs.len() as f64;
+        let below = self
+            .diffs
+            .iter()
+            .filter(|&&d| d <= observed + tolerance)
+            .count() as f64;
+        let above = self
+            .diffs
+            .iter()
+            .filter(|&&d| d >= observed - tolerance)
+            .count() as f64;
+        let q = self.diffs.quantile(&[left_q, right_q]);
+        (
+            if two_sided {
+                (2.0 * below.min(above) / n).min(1.0)
+            } else {
+                below / n
+            },
+            if self.uplift.is_finite() {
+                self.uplift
+            } else {
+                f64::NAN
+            },
+            self.observed_diff,
+            (q[0] * self.scale, q[1] * self.scale),
+        )
+    }
+}
+
+/// Permutation test of the difference in means (two columns) or mean ratios (four columns) between two
+/// groups.
+///
+/// Returns `(p_value, uplift, observed_diff, (ci_low, ci_high))` with the conventions of the Python
+/// `permutation_test` function.
+pub fn permutation_columns(
+    args: &[&[f64]],
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    resampler: &Resampler,
+) -> Result<(f64, f64, f64, (f64, f64))> {
+    Ok(
+        permutation_null(args, n_resamples, Accumulator::F64, resampler)?
+            .summarize(confidence_level, two_sided),
+    )
+}
+
+/// Resampling stage of a two-sample `permutation_columns` of `statistic` instead of the mean: the
+/// differences of the statistic of both groups under random relabelling.
+pub fn statistic_permutation_null(
+    args: &[&[f64]],
+    n_resamples: u64,
+    statistic: Statistic,
+    resampler: &Resampler,
+) -> Result<PermutationNull> {
+    if args.len() != 2 {
+        return Err(StatError::InvalidInput(
+            "A statistic other than the mean requires two samples; for ratio metrics use the mean of ratios estimand".to_string(),
+        ));
+    }
+    let len_a = args[0].len();
+    if len_a == 0 || args[1].is_empty() {
+        return Err(StatError::InsufficientData(
+            "Both samples must be non-empty".to_string(),
+        ));
+    }
+    let combined = [args[0], args[1]].concat();
+    let (stat_a, stat_b) = (statistic.compute(args[0]), statistic.compute(args[1]));
+    let observed_diff = stat_b - stat_a;
+    let diffs = resampler.map(n_resamples, |rng| {
+        let mut permuted = combined.clone();
+        permuted.shuffle(rng);
+        statistic.compute(&permuted[len_a..]) - statistic.compute(&permuted[..len_a])
+    });
+    Ok(PermutationNull {
+        diffs,
+        uplift: observed_diff / stat_a,
+        observed_diff,
+        observed_statistic: observed_diff,
+        scale: 1.0,
+        exact: false,
+    })
+}
+
+/// Resampling stage of `permutation_columns`, with sums accumulated by `accumulator`.
+pub fn permutation_null(
+    args: &[&[f64]],
+    n_resamples: u64,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+) -> Result<PermutationNull> {
+    match accumulator {
+        Accumulator::F64 => permutation_kernel::<f64>(args, n_resamples, resampler),
+        Accumulator::Kahan => permutation_kernel::<Kahan>(args, n_resamples, resampler),
+        Accumulator::DoubleDouble => {
+            permutation_kernel::<DoubleDouble>(args, n_resamples, resampler)
+        }
+    }
+}
+
+fn permutation_kernel<A: Accumulate>(
+    args: &[&[f64]],
+    n_resamples: u64,
+    resampler: &Resampler,
+) -> Result<PermutationNull> {
+    let (vec_diffs, uplift, observed_diff): (Vec<f64>, f64, f64) = match args.len() {
+        2 => {
+            let (len_a, len_b) = (args[0].len(), args[1].len());
+            let mut combined: Vec<f64> = Vec::with_capacity(len_a + len_b);
+            combined.extend_from_slice(args[0]);
+            combined.extend_from_slice(args[1]);
+            let len_comb = combined.len();
+            let (a_mean, b_mean) = (
+                A::sum(args[0]) / len_a as f64,
+                A::sum(args[1]) / len_b as f64,
+            );
+
+            let observed_diff = b_mean - a_mean;
+            let uplift = observed_diff / a_mean;
+
+            let vec_diffs: Vec<f64> = resampler.map(n_resamples, |rng| {
+                let mut ids: Vec<usize> = (0..len_comb).collect();
+                ids.shuffle(rng);
+
+                let sum_a = A::sum(ids[..len_a].iter().map(|&id| &combined[id]));
+                let sum_b = A::sum(ids[len_a..].iter().map(|&id| &combined[id]));
+                (sum_b / len_b as f64) - (sum_a / len_a as f64)
+            });
+
+            (vec_diffs, uplift, observed_diff)
+        }
+        4 => {
+            let (len_a, len_b) = (args[0].len(), args[2].len());
+
+            if len_a != args[1].len() || len_b != args[3].len() {
+                return Err(StatError::InvalidInput(
+                    "Each pair of arrays must be of equal length.".to_string(),
+                ));
+            }
+
+            let (ratio_a, ratio_b) = (
+                A::sum(args[0]) / A::sum(args[1]),
+                A::sum(args[2]) / A::sum(args[3]),
+            );
+
+            let observed_diff = ratio_b - ratio_a;
+            let uplift = observed_diff / ratio_a;
+
+            let mut numerators = Vec::with_capacity(len_a + len_b);
+            let mut denominators = Vec::with_capacity(len_a + len_b);
+
+            numerators.extend_from_slice(args[0]);
+            denominators.extend_from_slice(args[1]);
+            numerators.extend_from_slice(args[2]);
+            denominators.extend_from_slice(args[3]);
+
+            let len_comb = numerators.len();
+
+            let vec_diffs: Vec<f64> = resampler.map(n_resamples, |rng| {
+                let mut ids: Vec<usize> = (0..len_comb).collect();
+                ids.shuffle(rng);
+
+                let ratio = |ids: &[usize]| {
+                    let (mut num, mut den) = (A::default(), A::default());
+                    for &id in ids {
+                        num.add(numerators[id]);
+                        den.add(denominators[id]);
+                    }
+                    num.value() / den.value()
+                };
+
+                ratio(&ids[len_a..]) - ratio(&ids[..len_a])
+            });
+
+            (vec_diffs, uplift, observed_diff)
+        }
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 vectors.".to_string(),
+            ));
+        }
+    };
+    Ok(PermutationNull {
+        diffs: vec_diffs,
+        uplift,
+        observed_diff,
+        observed_statistic: observed_diff,
+        scale: 1.0,
+        exact: false,
+    })
+}
+
+/// Exact counterpart of `permutation_null`: the differences in means (two columns) or mean ratios
+/// (four columns) of every assignment of `len(args[0])` of the pooled units to the first group, in
+/// lexicographic order of the assigned units. The number of assignments grows as the binomial
+/// coefficient of the group sizes, which the caller is expected to bound.
+pub fn exact_permutation_null(args: &[&[f64]]) -> Result<PermutationNull> {
+    let (numerators, denominators, len_a) = match args.len() {
+        2 => {
+            let len_comb = args[0].len() + args[1].len();
+            (
+                [args[0], args[1]].concat(),
+                vec![1.0; len_comb],
+                args[0].len(),
+            )
+        }
+        4 => {
+            if args[0].len() != args[1].len() || args[2].len() != args[3].len() {
+                return Err(StatError::InvalidInput(
+                    "Each pair of arrays must be of equal length.".to_string(),
+                ));
+            }
+            (
+                [args[0], args[2]].concat(),
+                [args[1], args[3]].concat(),
+                args[0].len(),
+            )
+        }
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 vectors.".to_string(),
+            ));
+        }
+    };
+    let len_comb = numerators.len();
+    if len_a == 0 || len_a == len_comb {
+        return Err(StatError::InsufficientData(
+            "Both samples must be non-empty".to_string(),
+        ));
+    }
+    let (total_num, total_den) = (
+        numerators.iter().sum::<f64>(),
+        denominators.iter().sum::<f64>(),
+    );
+    let diff = |ids: &[usize]| {
+        let (mut num, mut den) = (0.0, 0.0);
+        for &id in ids {
+            num += numerators[id];
+            den += denominators[id];
+        }
+        ((total_num - num) / (total_den - den) - num / den, num / den)
+    };
+    let mut ids: Vec<usize> = (0..len_a).collect();
+    let (observed_diff, ratio_a) = diff(&ids);
+    let mut diffs = Vec::new();
+    loop {
+        diffs.push(diff(&ids).0);
+        // The next combination: increment the last index that can still move right.
+        let Some(i) = (0..len_a).rev().find(|&i| ids[i] < len_comb - len_a + i) else {
+            break;
+        };
+        ids[i] += 1;
+        for j in i + 1..len_a {
+            ids[j] = ids[j - 1] + 1;
+        }
+    }
+    Ok(PermutationNull {
+        diffs,
+        uplift: observed_diff / ratio_a,
+        observed_diff,
+        observed_statistic: observed_diff,
+        scale: 1.0,
+        exact: true,
+    })
+}
+
+/// Resampling stage of a studentized `permutation_columns`: the permuted statistic is the Welch
+/// t-statistic of the difference in means, or of mean ratios with delta-method standard errors, which
+/// keeps the test valid when the group variances differ.
+pub fn studentized_permutation_null(
+    args: &[&[f64]],
+    n_resamples: u64,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+) -> Result<PermutationNull> {
+    match accumulator {
+        Accumulator::F64 => studentized_kernel::<f64>(args, n_resamples, resampler),
+        Accumulator::Kahan => studentized_kernel::<Kahan>(args, n_resamples, resampler),
+        Accumulator::DoubleDouble => {
+            studentized_kernel::<DoubleDouble>(args, n_resamples, resampler)
+        }
+    }
+}
+
+/// Statistic of the group `ids` (the mean, or the ratio of sums with denominators) and the variance of
+/// its linearization, i.e. `n` times its squared delta-method standard error.
+fn group_moments<A: Accumulate>(
+    numerators: &[f64],
+    denominators: Option<&[f64]>,
+    ids: &[usize],
+) -> (f64, f64) {
+    let n = ids.len() as f64;
+    let (stat, mean_den) = match denominators {
+        None => (A::sum(ids.iter().map(|&id| &numerators[id])) / n, 1.0),
+        Some(denominators) => {
+            let den = A::sum(ids.iter().map(|&id| &denominators[id]));
+            (A::sum(ids.iter().map(|&id| &numerators[id])) / den, den / n)
+        }
+    };
+    let mut squares = A::default();
+    for &id in ids {
+        let den = denominators.map_or(1.0, |denominators| denominators[id]);
+        let deviation = (numerators[id] - stat * den) / mean_den;
+        squares.add(deviation * deviation);
+    }
+    (stat, squares.value() / (n - 1.0))
+}
+
+fn studentized_kernel<A: Accumulate>(
+    args: &[&[f64]],
+    n_resamples: u64,
+    resampler: &Resampler,
+) -> Result<PermutationNull> {
+    let (numerators, denominators, len_a) = match args.len() {
+        2 => ([args[0], args[1]].concat(), None, args[0].len()),
+        4 => {
+            if args[0].len() != args[1].len() || args[2].len() != args[3].len() {
+                return Err(StatError::InvalidInput(
+                    "Each pair of arrays must be of equal length.".to_string(),
+                ));
+            }
+            (
+                [args[0], args[2]].concat(),
+                Some([args[1], args[3]].concat()),
+                args[0].len(),
+            )
+        }
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 vectors.".to_string(),
+            ));
+        }
+    };
+    let len_comb = numerators.len();
+    if len_a < 2 || len_comb - len_a < 2 {
+        return Err(StatError::InsufficientData(
+            "standardize requires at least two observations per group".to_string(),
+        ));
+    }
+    let denominators = denominators.as_deref();
+    // The difference of the groups and its Welch standard error; a zero difference has a statistic of 0.
+    let t_statistic = |ids: &[usize]| {
+        let (stat_a, var_a) = group_moments::<A>(&numerators, denominators, &ids[..len_a]);
+        let (stat_b, var_b) = group_moments::<A>(&numerators, denominators, &ids[len_a..]);
+        let diff = stat_b - stat_a;
+        let se = (var_a / len_a as f64 + var_b / (len_comb - len_a) as f64).sqrt();
+        (diff, se, if diff == 0.0 { 0.0 } else { diff / se })
+    };
+    let identity: Vec<usize> = (0..len_comb).collect();
+    let (observed_diff, se, observed_statistic) = t_statistic(&identity);
+    let ratio_a = group_moments::<A>(&numerators, denominators, &identity[..len_a]).0;
+    let diffs = resampler.map(n_resamples, |rng| {
+        let mut ids = identity.clone();
+        ids.shuffle(rng);
+        t_statistic(&ids).2
+    });
+    Ok(PermutationNull {
+        diffs,
+        uplift: observed_diff / ratio_a,
+        observed_diff,
+        observed_statistic,
+        scale: se,
+        exact: false,
+    })
+}
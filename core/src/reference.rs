@@ -0,0 +1,164 @@
+//! Naive single-threaded implementations of the resampling tests.
+//!
+//! Every resample is written out with explicit index vectors and a single sequential generator,
+//! mirroring the textbook definition, so the optimized kernels can be checked against them: both use
+//! the same p-value and quantile conventions, so on any input their p-values and intervals agree up
+//! to Monte Carlo error. The `pylars` module exposes them under hidden `_reference_*` names, and the
+//! property tests of this crate compare them with the fast paths.
+
+use crate::bootstrap::BootstrapOutput;
+use crate::error::*;
+use crate::tools::*;
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+/// Mean of `column` over the resampled positions `ids`.
+fn resampled_mean(column: &[f64], ids: &[usize]) -> f64 {
+    ids.iter().map(|&id| column[id]).sum::<f64>() / ids.len() as f64
+}
+
+/// Ratio of sums of `num` and `den` over the resampled positions `ids`.
+fn resampled_ratio(num: &[f64], den: &[f64], ids: &[usize]) -> f64 {
+    ids.iter().map(|&id| num[id]).sum::<f64>() / ids.iter().map(|&id| den[id]).sum::<f64>()
+}
+
+/// Group statistic of `bootstrap` and `permutation_test`: the mean, or the ratio of sums for pairs.
+fn group_statistic(args: &[&[f64]], group: usize, ids: &[usize]) -> f64 {
+    match args.len() {
+        2 => resampled_mean(args[group], ids),
+        4 => resampled_ratio(args[2 * group], args[2 * group + 1], ids),
+        _ => unreachable!("the number of vectors is checked by the callers"),
+    }
+}
+
+fn group_len(args: &[&[f64]], group: usize) -> usize {
+    args[if args.len() == 4 { 2 * group } else { group }].len()
+}
+
+fn draw_ids(rng: &mut Xoshiro256PlusPlus, len: usize) -> Vec<usize> {
+    (0..len).map(|_| rng.gen_range(0..len)).collect()
+}
+
+fn check_args(args: &[&[f64]]) -> Result<()> {
+    if args.len() != 2 && args.len() != 4 {
+        return Err(StatError::InvalidInput(
+            "Input must contain either 2 or 4 vectors.".to_string(),
+        ));
+    }
+    if args.len() == 4 && (args[0].len() != args[1].len() || args[2].len() != args[3].len()) {
+        return Err(StatError::InvalidInput(
+            "Each pair of arrays must be of equal length.".to_string(),
+        ));
+    }
+    if group_len(args, 0) == 0 || group_len(args, 1) == 0 {
+        return Err(StatError::InsufficientData(
+            "Both samples must be non-empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Slow reference implementation of `bootstrap_columns` without bias correction, drawing every
+/// resample from a single generator seeded with `seed`.
+pub fn reference_bootstrap(
+    args: &[&[f64]],
+    confidence_level: f64,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    seed: u64,
+) -> Result<BootstrapOutput> {
+    check_args(args)?;
+    let (len_1, len_2) = (group_len(args, 0), group_len(args, 1));
+    if !ind && len_1 != len_2 {
+        return Err(StatError::InvalidInput(
+            "For non ind test all arrays must have same size".to_string(),
+        ));
+    }
+    let (all_1, all_2): (Vec<usize>, Vec<usize>) = ((0..len_1).collect(), (0..len_2).collect());
+    let mean_1 = group_statistic(args, 0, &all_1);
+    let mean_2 = group_statistic(args, 1, &all_2);
+    let uplift = calculate_uplift(mean_1, mean_2);
+
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let uplift_diffs: Vec<f64> = (0..n_resamples)
+        .map(|_| {
+            let ids_1 = draw_ids(&mut rng, len_1);
+            let ids_2 = if ind {
+                draw_ids(&mut rng, len_2)
+            } else {
+                ids_1.clone()
+            };
+            calculate_uplift(
+                group_statistic(args, 0, &ids_1),
+                group_statistic(args, 1, &ids_2),
+            )
+        })
+        .collect();
+
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let p =
+        (uplift_diffs.iter().filter(|&&d| d > 0.0).count() + 1) as f64 / (n_resamples + 1) as f64;
+    let q = uplift_diffs.quantile(&[left_q, 1.0 - left_q]);
+    Ok((
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+        mean_1,
+        mean_2,
+        uplift,
+        (q[0], q[1]),
+    ))
+}
+
+/// Slow reference implementation of `permutation_columns`, shuffling the pooled labels with a single
+/// generator seeded with `seed`.
+pub fn reference_permutation(
+    args: &[&[f64]],
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    seed: u64,
+) -> Result<(f64, f64, f64, (f64, f64))> {
+    check_args(args)?;
+    let (len_1, len_2) = (group_len(args, 0), group_len(args, 1));
+    // Pool both groups so that a permutation is a relabelling of the pooled positions.
+    let pooled: Vec<Vec<f64>> = if args.len() == 4 {
+        vec![[args[0], args[2]].concat(), [args[1], args[3]].concat()]
+    } else {
+        vec![[args[0], args[1]].concat()]
+    };
+    let statistic = |ids: &[usize]| match pooled.len() {
+        2 => resampled_ratio(&pooled[0], &pooled[1], ids),
+        _ => resampled_mean(&pooled[0], ids),
+    };
+    let mut labels: Vec<usize> = (0..len_1 + len_2).collect();
+    let observed_1 = statistic(&labels[..len_1]);
+    let observed_diff = statistic(&labels[len_1..]) - observed_1;
+    let uplift = observed_diff / observed_1;
+
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let vec_diffs: Vec<f64> = (0..n_resamples)
+        .map(|_| {
+            labels.shuffle(&mut rng);
+            statistic(&labels[len_1..]) - statistic(&labels[..len_1])
+        })
+        .collect();
+
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let p = (vec_diffs.iter().filter(|&&d| observed_diff > d).count() + 1) as f64
+        / (n_resamples + 1) as f64;
+    let q = vec_diffs.quantile(&[left_q, 1.0 - left_q]);
+    Ok((
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+        uplift,
+        observed_diff,
+        (q[0], q[1]),
+    ))
+}
@@ -0,0 +1,160 @@
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::*;
+use std::time::Instant;
+
+/// Generator of the `i`-th resample, independent of the thread that runs it.
+#[inline(always)]
+pub fn resample_rng(base_seed: u64, i: u64) -> Xoshiro256PlusPlus {
+    let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
+    Xoshiro256PlusPlus::seed_from_u64(seed ^ base_seed)
+}
+
+/// Number of consecutive resamples drawn from one jumped substream in deterministic mode.
+const BLOCK_SIZE: u64 = 256;
+
+/// Default of the `deterministic` switch, read from the `PYLARS_DETERMINISTIC` environment variable.
+fn deterministic_default() -> bool {
+    std::env::var("PYLARS_DETERMINISTIC")
+        .is_ok_and(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+}
+
+/// Builds the per-resample generators of a call.
+///
+/// Both modes are bit-reproducible whatever the number of threads. In deterministic mode resamples are
+/// drawn in fixed blocks from jumped substreams of a single generator, which guarantees non-overlapping
+/// streams. In fast mode every resample is seeded from its index, which needs no setup.
+///
+/// With a deadline, resamples are run in batches and the runs stop after the first batch that ends past
+/// it, so they return a prefix of the resamples of the same call without a deadline.
+#[derive(Clone, Copy)]
+pub struct Resampler {
+    base_seed: u64,
+    deterministic: bool,
+    deadline: Option<Instant>,
+}
+
+impl Resampler {
+    /// Resampler mixing `base_seed` into every generator; a base seed of 0 keeps the historical fixed
+    /// seeds.
+    pub fn from_seed(base_seed: u64, deterministic: Option<bool>) -> Self {
+        Resampler {
+            base_seed,
+            deterministic: deterministic.unwrap_or_else(deterministic_default),
+            deadline: None,
+        }
+    }
+
+    /// The same resampler, stopping its runs once `deadline` has passed.
+    pub fn with_deadline(self, deadline: Option<Instant>) -> Self {
+        Resampler { deadline, ..self }
+    }
+
+    /// The deadline of the runs, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// The seed and mode of the generators, which together determine every resample.
+    pub fn key(&self) -> (u64, bool) {
+        (self.base_seed, self.deterministic)
+    }
+
+    /// Runs `f` once per resample and returns the results in resample order.
+    pub fn map<T, F>(&self, n_resamples: u64, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&mut Xoshiro256PlusPlus) -> T + Sync,
+    {
+        self.map_indexed(n_resamples, |_, rng| f(rng))
+    }
+
+    /// Like `map`, with the index of the resample also passed to `f`.
+    pub fn map_indexed<T, F>(&self, n_resamples: u64, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(u64, &mut Xoshiro256PlusPlus) -> T + Sync,
+    {
+        let mut stream = Xoshiro256PlusPlus::seed_from_u64(self.base_seed);
+        let Some(deadline) = self.deadline else {
+            return self.map_range(0, n_resamples, &mut stream, &f);
+        };
+        let batch_size = BLOCK_SIZE * rayon::current_num_threads() as u64;
+        let mut results = Vec::new();
+        let mut start = 0;
+        while start < n_resamples {
+            let end = n_resamples.min(start.saturating_add(batch_size));
+            results.extend(self.map_range(start, end, &mut stream, &f));
+            start = end;
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        results
+    }
+
+    /// Like `map`, handing the results to `consume` in consecutive batches of about `batch_size`
+    /// resamples instead of collecting them, so that only one batch is held at a time. The resamples
+    /// are those of `map`, in the same order.
+    pub fn for_each_batch<T, F, G>(&self, n_resamples: u64, batch_size: u64, f: F, mut consume: G)
+    where
+        T: Send,
+        F: Fn(&mut Xoshiro256PlusPlus) -> T + Sync,
+        G: FnMut(Vec<T>),
+    {
+        let f = |_, rng: &mut Xoshiro256PlusPlus| f(rng);
+        let batch_size = batch_size.div_ceil(BLOCK_SIZE).max(1) * BLOCK_SIZE;
+        let mut stream = Xoshiro256PlusPlus::seed_from_u64(self.base_seed);
+        let mut start = 0;
+        while start < n_resamples {
+            let end = n_resamples.min(start.saturating_add(batch_size));
+            consume(self.map_range(start, end, &mut stream, &f));
+            start = end;
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+        }
+    }
+
+    /// Runs the resamples `start..end`, `start` being a multiple of `BLOCK_SIZE`. In deterministic mode,
+    /// `stream` is the substream of the block of `start` and is left at the one of the block of `end`.
+    fn map_range<T, F>(
+        &self,
+        start: u64,
+        end: u64,
+        stream: &mut Xoshiro256PlusPlus,
+        f: &F,
+    ) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(u64, &mut Xoshiro256PlusPlus) -> T + Sync,
+    {
+        if self.deterministic {
+            let blocks = start / BLOCK_SIZE..end.div_ceil(BLOCK_SIZE);
+            let substreams: Vec<Xoshiro256PlusPlus> = blocks
+                .map(|_| {
+                    let substream = stream.clone();
+                    stream.jump();
+                    substream
+                })
+                .collect();
+            substreams
+                .into_par_iter()
+                .enumerate()
+                .flat_map_iter(|(block, mut rng)| {
+                    let first = start + block as u64 * BLOCK_SIZE;
+                    let last = (first + BLOCK_SIZE).min(end);
+                    (first..last).map(move |i| f(i, &mut rng))
+                })
+                .collect()
+        } else {
+            (start..end)
+                .into_par_iter()
+                .map(|i| f(i, &mut resample_rng(self.base_seed, i)))
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,677 @@
+use crate::error::*;
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+use rayon::prelude::*;
+use std::sync::OnceLock;
+
+pub trait MathUtil {
+    fn quantile(&self, q: &[f64]) -> Vec<f64>;
+}
+
+impl MathUtil for [f64] {
+    /// Quantiles at the levels `q`, interpolated between order statistics; NaN for an empty slice.
+    fn quantile(&self, q: &[f64]) -> Vec<f64> {
+        if self.is_empty() {
+            return vec![f64::NAN; q.len()];
+        }
+        let n = self.len() as f64;
+        let mut sorted = self.to_vec();
+        sorted.par_sort_unstable_by(|a, b| a.total_cmp(b));
+        q.iter()
+            .map(|&quantile| {
+                let m = 1.0 - quantile;
+                let pos = quantile * n + m - 1.0;
+                let j = pos.floor().max(0.0) as usize;
+                let g = pos.fract();
+                if j + 1 < sorted.len() {
+                    (1.0 - g) * sorted[j] + g * sorted[j + 1]
+                } else {
+                    sorted[j]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether all `values` are equal, as for a constant sample or a degenerate resampled distribution.
+pub fn is_constant(values: &[f64]) -> bool {
+    values.iter().all(|&x| x == values[0])
+}
+
+#[inline(always)]
+pub fn calculate_uplift(before: f64, after: f64) -> f64 {
+    (after - before) / before
+}
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alternative {
+    TwoSided,
+    Less,
+    Greater,
+}
+
+impl Alternative {
+    pub fn parse(alternative: &str) -> Result<Alternative> {
+        match alternative {
+            "two-sided" => Ok(Alternative::TwoSided),
+            "less" => Ok(Alternative::Less),
+            "greater" => Ok(Alternative::Greater),
+            _ => Err(StatError::InvalidInput(
+                "alternative must be one of 'two-sided', 'less' or 'greater'".to_string(),
+            )),
+        }
+    }
+
+    /// Combines the lower and upper tail probabilities of a statistic into a p-value.
+    pub fn p_value(&self, p_less: f64, p_greater: f64) -> f64 {
+        match self {
+            Alternative::TwoSided => (2.0 * p_less.min(p_greater)).min(1.0),
+            Alternative::Less => p_less,
+            Alternative::Greater => p_greater,
+        }
+    }
+}
+
+/// Average (1-based) ranks of `values` together with the tie term `sum(t^3 - t)` over tie groups.
+pub fn rankdata(values: &[f64]) -> (Vec<f64>, f64) {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_unstable_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut tie_term = 0.0;
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &idx in &order[start..end] {
+            ranks[idx] = rank;
+        }
+        let t = (end - start) as f64;
+        tie_term += t * t * t - t;
+        start = end;
+    }
+    (ranks, tie_term)
+}
+
+/// Biased (population) central moments `(mean, m2, m3, m4)`.
+pub fn central_moments(data: &[f64]) -> (f64, f64, f64, f64) {
+    let n = data.len() as f64;
+    let mean = data.iter().sum::<f64>() / n;
+    let (m2, m3, m4) = data.iter().fold((0.0, 0.0, 0.0), |(m2, m3, m4), &x| {
+        let d = x - mean;
+        let d2 = d * d;
+        (m2 + d2, m3 + d2 * d, m4 + d2 * d2)
+    });
+    (mean, m2 / n, m3 / n, m4 / n)
+}
+
+/// Biased sample skewness `m3 / m2^1.5`.
+pub fn skewness(data: &[f64]) -> f64 {
+    let (_, m2, m3, _) = central_moments(data);
+    m3 / m2.powf(1.5)
+}
+
+/// Biased sample excess kurtosis `m4 / m2^2 - 3`.
+pub fn kurtosis(data: &[f64]) -> f64 {
+    let (_, m2, _, m4) = central_moments(data);
+    m4 / (m2 * m2) - 3.0
+}
+
+pub fn median(data: &[f64]) -> f64 {
+    data.quantile(&[0.5])[0]
+}
+
+/// Dense ids of `values` (0 for the smallest distinct value) and the number of distinct values.
+///
+/// Resampling kernels rank a resample in linear time by counting the multiplicity of every id
+/// instead of sorting the resample again.
+pub fn dense_ids(values: &[f64]) -> (Vec<usize>, usize) {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_unstable_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ids = vec![0; values.len()];
+    let mut n_distinct = 0;
+    for (pos, &idx) in order.iter().enumerate() {
+        if pos > 0 && values[idx] != values[order[pos - 1]] {
+            n_distinct += 1;
+        }
+        ids[idx] = n_distinct;
+    }
+    (ids, if values.is_empty() { 0 } else { n_distinct + 1 })
+}
+
+/// Average (1-based) rank of every dense id, given how many times each id occurs.
+pub fn ranks_from_counts(counts: &[usize]) -> Vec<f64> {
+    let mut below = 0;
+    counts
+        .iter()
+        .map(|&count| {
+            let rank = below as f64 + (count as f64 + 1.0) / 2.0;
+            below += count;
+            rank
+        })
+        .collect()
+}
+
+/// Running sum of the resampling kernels, selected by the `accumulator` argument.
+pub trait Accumulate: Copy + Default {
+    fn add(&mut self, value: f64);
+    fn value(&self) -> f64;
+
+    fn sum<'a>(values: impl IntoIterator<Item = &'a f64>) -> f64 {
+        let mut acc = Self::default();
+        values.into_iter().for_each(|&value| acc.add(value));
+        acc.value()
+    }
+}
+
+/// Plain f64 addition, the historical behaviour.
+impl Accumulate for f64 {
+    #[inline(always)]
+    fn add(&mut self, value: f64) {
+        *self += value;
+    }
+
+    #[inline(always)]
+    fn value(&self) -> f64 {
+        *self
+    }
+
+    fn sum<'a>(values: impl IntoIterator<Item = &'a f64>) -> f64 {
+        values.into_iter().sum()
+    }
+}
+
+/// Kahan–Babuška–Neumaier compensated summation: the rounding error of every addition is carried in a
+/// second term, so the error no longer grows with the number of values.
+#[derive(Clone, Copy, Default)]
+pub struct Kahan {
+    sum: f64,
+    compensation: f64,
+}
+
+impl Accumulate for Kahan {
+    #[inline(always)]
+    fn add(&mut self, value: f64) {
+        let t = self.sum + value;
+        self.compensation += if self.sum.abs() >= value.abs() {
+            (self.sum - t) + value
+        } else {
+            (value - t) + self.sum
+        };
+        self.sum = t;
+    }
+
+    #[inline(always)]
+    fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+/// Double-double summation: the sum is kept as an unevaluated pair `hi + lo` with about 106 bits of
+/// precision, an emulation of quad precision that is exact for sums of up to ~2^50 values.
+#[derive(Clone, Copy, Default)]
+pub struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+impl Accumulate for DoubleDouble {
+    #[inline(always)]
+    fn add(&mut self, value: f64) {
+        // TwoSum of the high part and the value, then renormalization of the pair.
+        let s = self.hi + value;
+        let v = s - self.hi;
+        let e = (self.hi - (s - v)) + (value - v);
+        let lo = self.lo + e;
+        self.hi = s + lo;
+        self.lo = lo - (self.hi - s);
+    }
+
+    #[inline(always)]
+    fn value(&self) -> f64 {
+        self.hi + self.lo
+    }
+}
+
+/// Choice of `Accumulate` implementation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Accumulator {
+    F64,
+    Kahan,
+    DoubleDouble,
+}
+
+impl Accumulator {
+    /// Parses `"f64"`, `"kahan"` or `"f128-emulated"`.
+    pub fn parse(accumulator: &str) -> Result<Accumulator> {
+        match accumulator {
+            "f64" => Ok(Accumulator::F64),
+            "kahan" => Ok(Accumulator::Kahan),
+            "f128-emulated" => Ok(Accumulator::DoubleDouble),
+            _ => Err(StatError::InvalidInput(format!(
+                "accumulator must be one of 'f64', 'kahan' or 'f128-emulated', got '{accumulator}'"
+            ))),
+        }
+    }
+}
+
+/// Number of values summed sequentially by every task of `fixed_order_sum`.
+pub(crate) const SUM_CHUNK: usize = 4096;
+
+/// Number of interleaved partial sums of a chunk of `fixed_order_sum`.
+const SUM_LANES: usize = 8;
+
+/// Sum of a chunk in `SUM_LANES` interleaved partial sums, added in lane order, then the remainder.
+/// The order of the additions is fixed by the source, so the compiler can vectorize it into AVX
+/// registers and every instruction set rounds the same way.
+#[inline(always)]
+fn lane_sum(chunk: &[f64]) -> f64 {
+    let mut lanes = [0.0; SUM_LANES];
+    let mut blocks = chunk.chunks_exact(SUM_LANES);
+    for block in &mut blocks {
+        for (lane, &value) in lanes.iter_mut().zip(block) {
+            *lane += value;
+        }
+    }
+    let rest: f64 = blocks.remainder().iter().sum();
+    lanes.iter().sum::<f64>() + rest
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn lane_sum_avx512(chunk: &[f64]) -> f64 {
+    lane_sum(chunk)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn lane_sum_avx2(chunk: &[f64]) -> f64 {
+    lane_sum(chunk)
+}
+
+/// A chunk-sum kernel and the name of the instruction set it was compiled for.
+type SumKernel = (fn(&[f64]) -> f64, &'static str);
+
+/// The chunk-sum kernel of the widest instruction set of the CPU, detected on the first call.
+pub fn sum_kernel() -> SumKernel {
+    static KERNEL: OnceLock<SumKernel> = OnceLock::new();
+    *KERNEL.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                // SAFETY: the CPU supports AVX-512F.
+                return (|chunk| unsafe { lane_sum_avx512(chunk) }, "avx512");
+            }
+            if is_x86_feature_detected!("avx2") {
+                // SAFETY: the CPU supports AVX2.
+                return (|chunk| unsafe { lane_sum_avx2(chunk) }, "avx2");
+            }
+        }
+        (lane_sum, "portable")
+    })
+}
+
+/// Parallel sum whose rounding does not depend on the number of threads nor on the instruction set:
+/// fixed-size chunks are summed in parallel and their partial sums are added in chunk order.
+pub fn fixed_order_sum(values: &[f64]) -> f64 {
+    let (kernel, _) = sum_kernel();
+    values
+        .par_chunks(SUM_CHUNK)
+        .map(kernel)
+        .collect::<Vec<f64>>()
+        .iter()
+        .sum()
+}
+
+/// Caps the units of a two-group test above the `level` quantile of the pooled units.
+///
+/// With two columns the values are capped. With four, the per-unit ratios `num / den` are capped, by
+/// lowering the numerator to `cap * den` and keeping the denominator, so that a unit's pair stays
+/// consistent; units with a zero denominator are kept as they are.
+pub fn winsorize_columns(columns: &[&[f64]], level: f64) -> Result<Vec<Vec<f64>>> {
+    if !(0.0..=1.0).contains(&level) {
+        return Err(StatError::InvalidInput(format!(
+            "winsorize must be a quantile level in [0, 1], got {level}"
+        )));
+    }
+    match columns.len() {
+        2 => {
+            let pooled = [columns[0], columns[1]].concat();
+            let cap = pooled.quantile(&[level])[0];
+            Ok(columns
+                .iter()
+                .map(|column| column.iter().map(|&x| x.min(cap)).collect())
+                .collect())
+        }
+        4 => {
+            if columns[0].len() != columns[1].len() || columns[2].len() != columns[3].len() {
+                return Err(StatError::InvalidInput(
+                    "Each pair of arrays must be of equal length.".to_string(),
+                ));
+            }
+            let ratios: Vec<f64> = [(columns[0], columns[1]), (columns[2], columns[3])]
+                .iter()
+                .flat_map(|(num, den)| num.iter().zip(*den))
+                .filter(|(_, &den)| den != 0.0)
+                .map(|(num, den)| num / den)
+                .collect();
+            let cap = ratios.quantile(&[level])[0];
+            let capped = |num: &[f64], den: &[f64]| -> Vec<f64> {
+                num.iter()
+                    .zip(den)
+                    .map(|(&num, &den)| {
+                        if den != 0.0 && num / den > cap {
+                            cap * den
+                        } else {
+                            num
+                        }
+                    })
+                    .collect()
+            };
+            Ok(vec![
+                capped(columns[0], columns[1]),
+                columns[1].to_vec(),
+                capped(columns[2], columns[3]),
+                columns[3].to_vec(),
+            ])
+        }
+        _ => Err(StatError::InvalidInput(
+            "Input must contain either 2 or 4 arrays".to_string(),
+        )),
+    }
+}
+
+/// Target quantity of a four-column (ratio) test.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Estimand {
+    /// `sum(num) / sum(den)` of every group, resampling the (num, den) pairs.
+    RatioOfSums,
+    /// `mean(num / den)` of every group, resampling the per-unit ratios.
+    MeanOfRatios,
+}
+
+impl Estimand {
+    /// Parses `"ratio_of_sums"` or `"mean_of_ratios"`.
+    pub fn parse(estimand: &str) -> Result<Estimand> {
+        match estimand {
+            "ratio_of_sums" => Ok(Estimand::RatioOfSums),
+            "mean_of_ratios" => Ok(Estimand::MeanOfRatios),
+            _ => Err(StatError::InvalidInput(format!(
+                "estimand must be 'ratio_of_sums' or 'mean_of_ratios', got '{estimand}'"
+            ))),
+        }
+    }
+}
+
+/// The two columns of per-unit ratios of a four-column test with the `MeanOfRatios` estimand, and the
+/// number of units left out for a zero denominator.
+pub fn unit_ratio_columns(columns: &[&[f64]]) -> Result<(Vec<Vec<f64>>, usize)> {
+    if columns.len() != 4 {
+        return Err(StatError::InvalidInput(
+            "estimand 'mean_of_ratios' requires four arrays: [num_1, den_1, num_2, den_2]"
+                .to_string(),
+        ));
+    }
+    if columns[0].len() != columns[1].len() || columns[2].len() != columns[3].len() {
+        return Err(StatError::InvalidInput(
+            "Each pair of arrays must be of equal length.".to_string(),
+        ));
+    }
+    let mut dropped = 0;
+    let mut ratios = |num: &[f64], den: &[f64]| -> Vec<f64> {
+        num.iter()
+            .zip(den)
+            .filter_map(|(&num, &den)| {
+                if den == 0.0 {
+                    dropped += 1;
+                    None
+                } else {
+                    Some(num / den)
+                }
+            })
+            .collect()
+    };
+    let ratios = vec![
+        ratios(columns[0], columns[1]),
+        ratios(columns[2], columns[3]),
+    ];
+    Ok((ratios, dropped))
+}
+
+/// The (numerator, denominator) columns of a weighted two-group test: every unit contributes its
+/// weighted value (or weighted numerator) and its weight (or weighted denominator), so the ratio of
+/// sums of every group is its weighted mean (or weighted ratio) and the weights move with the units.
+pub fn weighted_columns(columns: &[&[f64]], weights: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+    if weights.len() != 2 {
+        return Err(StatError::InvalidInput(
+            "weights must contain two arrays, one per group".to_string(),
+        ));
+    }
+    let groups: Vec<(&[f64], Option<&[f64]>)> = match columns.len() {
+        2 => vec![(columns[0], None), (columns[1], None)],
+        4 => vec![
+            (columns[0], Some(columns[1])),
+            (columns[2], Some(columns[3])),
+        ],
+        _ => {
+            return Err(StatError::InvalidInput(
+                "Input must contain either 2 or 4 arrays".to_string(),
+            ))
+        }
+    };
+    let mut weighted = Vec::with_capacity(4);
+    for ((values, denominators), weights) in groups.into_iter().zip(weights) {
+        if values.len() != weights.len() || denominators.is_some_and(|d| d.len() != values.len()) {
+            return Err(StatError::InvalidInput(
+                "Every group must have one weight per observation".to_string(),
+            ));
+        }
+        if weights.iter().any(|&w| !(w >= 0.0 && w.is_finite())) {
+            return Err(StatError::InvalidInput(
+                "weights must be non-negative and finite".to_string(),
+            ));
+        }
+        weighted.push(values.iter().zip(weights).map(|(x, w)| x * w).collect());
+        weighted.push(match denominators {
+            None => weights.clone(),
+            Some(denominators) => denominators
+                .iter()
+                .zip(weights)
+                .map(|(d, w)| d * w)
+                .collect(),
+        });
+    }
+    Ok(weighted)
+}
+
+/// Simultaneous confidence band of a curve from bootstrap replicates of the whole curve.
+///
+/// The band is `estimate ± c * sd` where `sd` is the pointwise bootstrap standard deviation and `c` the
+/// `confidence_level` quantile of the max-modulus `max_p |replicate(p) - estimate(p)| / sd(p)`, so it covers
+/// the whole curve with the requested family-wise probability. Also returns the single-step adjusted
+/// p-value of every point against the same max-modulus distribution; the smallest one is the p-value of
+/// the sup-test that the curve is zero everywhere. Points without bootstrap variation are left out.
+pub fn max_modulus_band(
+    estimate: &[f64],
+    replicates: &[Vec<f64>],
+    confidence_level: f64,
+) -> (Vec<(f64, f64)>, Vec<f64>) {
+    let n_resamples = replicates.len() as f64;
+    let sd: Vec<f64> = (0..estimate.len())
+        .map(|p| {
+            let mean = replicates.iter().map(|r| r[p]).sum::<f64>() / n_resamples;
+            let var = replicates
+                .iter()
+                .map(|r| (r[p] - mean).powi(2))
+                .sum::<f64>()
+                / (n_resamples - 1.0);
+            var.sqrt()
+        })
+        .collect();
+    let moduli: Vec<f64> = replicates
+        .par_iter()
+        .map(|r| {
+            (0..r.len())
+                .filter(|&p| sd[p] > 0.0)
+                .map(|p| (r[p] - estimate[p]).abs() / sd[p])
+                .fold(0.0, f64::max)
+        })
+        .collect();
+    let critical = moduli.quantile(&[confidence_level])[0];
+    let p_values = estimate
+        .iter()
+        .zip(&sd)
+        .map(|(&e, &s)| {
+            let observed = if s > 0.0 { e.abs() / s } else { 0.0 };
+            (moduli.iter().filter(|&&m| m >= observed).count() + 1) as f64 / (n_resamples + 1.0)
+        })
+        .collect();
+    let band = estimate
+        .iter()
+        .zip(&sd)
+        .map(|(&e, &s)| (e - critical * s, e + critical * s))
+        .collect();
+    (band, p_values)
+}
+
+/// Draws uniform positions of one or more equally long columns and reads them without bounds checks.
+///
+/// The sampler can only be built from the columns it reads, after checking that they share the same
+/// non-zero length, and every position comes from `Uniform::new(0, len)`. Resampling kernels read
+/// through it instead of calling `get_unchecked` themselves, so a new kernel cannot read out of bounds.
+pub struct IndexSampler<'a, const N: usize> {
+    columns: [&'a [f64]; N],
+    dist: Uniform<usize>,
+}
+
+impl<'a, const N: usize> IndexSampler<'a, N> {
+    pub fn new(columns: [&'a [f64]; N]) -> Result<Self> {
+        let len = columns[0].len();
+        if len == 0 {
+            return Err(StatError::InsufficientData(
+                "Cannot resample an empty array".to_string(),
+            ));
+        }
+        if columns.iter().any(|column| column.len() != len) {
+            return Err(StatError::InvalidInput(
+                "Columns resampled together must have equal length".to_string(),
+            ));
+        }
+        Ok(IndexSampler {
+            columns,
+            dist: Uniform::new(0, len),
+        })
+    }
+
+    /// Draws a uniform position, consuming the generator exactly like `draw`.
+    #[inline(always)]
+    pub fn draw_index<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.dist.sample(rng)
+    }
+
+    /// Reads all columns at one uniformly drawn position.
+    #[inline(always)]
+    pub fn draw<R: Rng + ?Sized>(&self, rng: &mut R) -> [f64; N] {
+        let idx = self.draw_index(rng);
+        // SAFETY: `idx < len` by construction of `dist`, and every column has length `len`.
+        std::array::from_fn(|k| unsafe { *self.columns[k].get_unchecked(idx) })
+    }
+}
+
+/// Summary of a stream of values answering quantile queries in bounded memory, for resampled
+/// distributions too large to be kept.
+///
+/// Values are buffered by level; a full level is sorted and every other value, alternating between
+/// the odd and the even ones, moves up a level with twice the weight. A sketch of `n` values keeps
+/// about `capacity * log2(n / capacity)` of them, and the rank error of a quantile is of the order of
+/// `1 / capacity`.
+pub struct QuantileSketch {
+    capacity: usize,
+    levels: Vec<Vec<f64>>,
+    count: u64,
+    compactions: u64,
+}
+
+impl QuantileSketch {
+    pub fn new(capacity: usize) -> Self {
+        QuantileSketch {
+            capacity: capacity.max(2),
+            levels: vec![Vec::new()],
+            count: 0,
+            compactions: 0,
+        }
+    }
+
+    /// Number of values pushed into the sketch.
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.levels[0].push(value);
+        self.count += 1;
+        let mut level = 0;
+        while self.levels[level].len() >= self.capacity {
+            let mut full = std::mem::take(&mut self.levels[level]);
+            full.sort_unstable_by(|a, b| a.total_cmp(b));
+            let offset = (self.compactions % 2) as usize;
+            self.compactions += 1;
+            // An odd level keeps its smallest or largest value back, so that no weight is lost.
+            if full.len() % 2 == 1 {
+                let held = if offset == 0 { full.len() - 1 } else { 0 };
+                self.levels[level].push(full.remove(held));
+            }
+            if level + 1 == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level + 1].extend(full.iter().skip(offset).step_by(2));
+            level += 1;
+        }
+    }
+
+    /// Quantiles at the levels `q`, interpolated like `MathUtil::quantile` between the midpoints of the
+    /// ranks covered by the kept values.
+    pub fn quantile(&self, q: &[f64]) -> Vec<f64> {
+        if self.is_empty() {
+            return vec![f64::NAN; q.len()];
+        }
+        let mut weighted: Vec<(f64, f64)> = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, values)| {
+                let weight = (1u64 << level) as f64;
+                values.iter().map(move |&value| (value, weight))
+            })
+            .collect();
+        weighted.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let mut rank = 0.0;
+        let midpoints: Vec<f64> = weighted
+            .iter()
+            .map(|&(_, weight)| {
+                let midpoint = rank + (weight - 1.0) / 2.0;
+                rank += weight;
+                midpoint
+            })
+            .collect();
+        q.iter()
+            .map(|&quantile| {
+                let pos = quantile * (rank - 1.0);
+                let j = midpoints.partition_point(|&m| m <= pos);
+                if j == 0 {
+                    weighted[0].0
+                } else if j == weighted.len() {
+                    weighted[j - 1].0
+                } else {
+                    let g = (pos - midpoints[j - 1]) / (midpoints[j] - midpoints[j - 1]);
+                    (1.0 - g) * weighted[j - 1].0 + g * weighted[j].0
+                }
+            })
+            .collect()
+    }
+}
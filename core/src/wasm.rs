@@ -0,0 +1,88 @@
+//! JavaScript facade over the core kernels, built with `wasm-pack build core -- --features wasm`.
+//!
+//! The functions run the same kernels with the same fixed seeds as the Python package, so a browser
+//! calculator reproduces its results exactly. Resamples run on the calling thread.
+
+use crate::{bootstrap_columns, permutation_columns, Resampler};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct BootstrapResult {
+    pub p_value: f64,
+    pub mean_1: f64,
+    pub mean_2: f64,
+    pub uplift: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+#[wasm_bindgen]
+pub struct PermutationResult {
+    pub p_value: f64,
+    pub uplift: f64,
+    pub observed_diff: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+/// Bootstraps the uplift in means between `a` and `b`, like `pylars.bootstrap([a, b], ...)`.
+#[wasm_bindgen]
+pub fn bootstrap(
+    a: &[f64],
+    b: &[f64],
+    confidence_level: f64,
+    n_resamples: u32,
+    ind: bool,
+    two_sided: bool,
+    seed: u32,
+) -> Result<BootstrapResult, JsError> {
+    let resampler = Resampler::from_seed(seed as u64, Some(false));
+    let (p_value, mean_1, mean_2, uplift, (ci_low, ci_high)) = bootstrap_columns(
+        &[a, b],
+        confidence_level,
+        n_resamples as u64,
+        ind,
+        two_sided,
+        false,
+        0,
+        &resampler,
+    )
+    .map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(BootstrapResult {
+        p_value,
+        mean_1,
+        mean_2,
+        uplift,
+        ci_low,
+        ci_high,
+    })
+}
+
+/// Permutation test of the difference in means between `a` and `b`, like
+/// `pylars.permutation_test([a, b], ...)`.
+#[wasm_bindgen(js_name = permutationTest)]
+pub fn permutation_test(
+    a: &[f64],
+    b: &[f64],
+    confidence_level: f64,
+    n_resamples: u32,
+    two_sided: bool,
+    seed: u32,
+) -> Result<PermutationResult, JsError> {
+    let resampler = Resampler::from_seed(seed as u64, Some(false));
+    let (p_value, uplift, observed_diff, (ci_low, ci_high)) = permutation_columns(
+        &[a, b],
+        confidence_level,
+        n_resamples as u64,
+        two_sided,
+        &resampler,
+    )
+    .map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(PermutationResult {
+        p_value,
+        uplift,
+        observed_diff,
+        ci_low,
+        ci_high,
+    })
+}
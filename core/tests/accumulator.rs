@@ -0,0 +1,52 @@
+//! Accuracy of the compensated accumulators against plain f64 addition on ill-conditioned sums.
+
+use pylars_core::bootstrap::bootstrap_null;
+use pylars_core::rng::Resampler;
+use pylars_core::tools::{Accumulate, Accumulator, DoubleDouble, Kahan};
+
+/// `n` unit values between a large value and its negation: every unit is absorbed by the large
+/// partial sum in f64, while the exact sum is `n`.
+fn cancelling(n: usize) -> Vec<f64> {
+    let mut values = vec![1e16];
+    values.extend(std::iter::repeat_n(1.0, n));
+    values.push(-1e16);
+    values
+}
+
+#[test]
+fn compensated_sums_recover_absorbed_values() {
+    let values = cancelling(1_000);
+    assert_eq!(<f64 as Accumulate>::sum(&values), 0.0);
+    assert_eq!(Kahan::sum(&values), 1_000.0);
+    assert_eq!(DoubleDouble::sum(&values), 1_000.0);
+}
+
+#[test]
+fn compensated_sums_of_small_increments_are_closer_to_exact() {
+    // A large revenue total followed by a million one-cent payments; 1e9 + 1e4 is exactly
+    // representable, while a cent is not, so f64 accumulates a rounding error on every addition.
+    let mut values = vec![1e9];
+    values.extend(std::iter::repeat_n(0.01, 1_000_000));
+    let exact = 1e9 + 1e4;
+    let error = |sum: f64| (sum - exact).abs();
+    let plain = error(<f64 as Accumulate>::sum(&values));
+    assert!(plain > 1e-3, "f64 error {plain} is unexpectedly small");
+    assert!(error(Kahan::sum(&values)) < plain / 100.0);
+    assert!(error(DoubleDouble::sum(&values)) < plain / 100.0);
+}
+
+#[test]
+fn bootstrap_group_means_use_the_accumulator() {
+    let values = cancelling(1_000);
+    let args: [&[f64]; 2] = [&values, &values];
+    let resampler = Resampler::from_seed(0, Some(true));
+    let mean = |accumulator| {
+        bootstrap_null(&args, 10, true, accumulator, &resampler)
+            .unwrap()
+            .mean_1
+    };
+    let exact = 1_000.0 / values.len() as f64;
+    assert_eq!(mean(Accumulator::F64), 0.0);
+    assert!((mean(Accumulator::Kahan) - exact).abs() < 1e-12);
+    assert!((mean(Accumulator::DoubleDouble) - exact).abs() < 1e-12);
+}
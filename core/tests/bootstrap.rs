@@ -0,0 +1,41 @@
+//! Intervals and resampling of the uplift.
+
+use pylars_core::bootstrap::bootstrap_null;
+use pylars_core::rng::Resampler;
+use pylars_core::tools::{calculate_uplift, Accumulator};
+use pylars_core::{stratified_bootstrap_null, BootstrapCiMethod, StatError};
+
+#[test]
+fn bca_falls_back_to_percentile_when_undefined() {
+    let a: Vec<f64> = (1..=30).map(f64::from).collect();
+    let b: Vec<f64> = (1..=30).map(|x| f64::from(x * x)).collect();
+    let null = bootstrap_null(
+        &[&a, &b],
+        2_000,
+        true,
+        Accumulator::F64,
+        &Resampler::from_seed(1, None),
+    )
+    .unwrap();
+    let percentile = null.interval(0.95, BootstrapCiMethod::Percentile, 0.0);
+    // 1 - a * z is negative at the upper endpoint once a exceeds about 1 / 2.
+    assert!(null.bca_defined(0.95, 0.1));
+    assert!(!null.bca_defined(0.95, 5.0));
+    assert_eq!(null.interval(0.95, BootstrapCiMethod::Bca, 5.0), percentile);
+    assert_ne!(null.interval(0.95, BootstrapCiMethod::Bca, 0.1), percentile);
+}
+
+#[test]
+fn stratified_groups_resample_their_own_strata_sizes() {
+    // Constant strata of different sizes in the two groups: every resample that keeps the strata sizes
+    // of its own group has the observed stratified means.
+    let (a_1, a_2) = ([1.0; 3], [4.0; 2]);
+    let (b_1, b_2) = ([5.0; 1], [7.0; 6]);
+    let resampler = Resampler::from_seed(1, None);
+    let uplifts = stratified_bootstrap_null(&[&a_1, &a_2], &[&b_1, &b_2], 100, &resampler).unwrap();
+    let observed = calculate_uplift((3.0 + 8.0) / 5.0, (5.0 + 42.0) / 7.0);
+    assert!(uplifts.iter().all(|&u| (u - observed).abs() < 1e-12));
+
+    let mismatched = stratified_bootstrap_null(&[&a_1, &a_2], &[&b_1], 100, &resampler);
+    assert!(matches!(mismatched, Err(StatError::InvalidInput(_))));
+}
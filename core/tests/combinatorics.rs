@@ -0,0 +1,50 @@
+//! Accuracy of the log-combinatorics when `n` is large against `k`, where the difference of two
+//! `ln(n!)` values loses every digit.
+
+use pylars_core::special::{ln_binomial, ln_factorial, ln_falling_factorial, ln_multinomial};
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() <= 1e-12 * expected.abs().max(1.0),
+        "{actual} != {expected}"
+    );
+}
+
+#[test]
+fn small_k_against_huge_n() {
+    assert_close(ln_binomial(10u64.pow(15), 1), 34.538_776_394_910_684);
+    assert_close(
+        ln_falling_factorial(10u64.pow(13), 1),
+        29.933_606_208_922_594,
+    );
+    assert_close(ln_multinomial(&[10u64.pow(14), 1]), (1e14_f64 + 1.0).ln());
+    assert_close(
+        ln_binomial(10u64.pow(15), 3),
+        (1e15_f64 * (1e15 - 1.0) * (1e15 - 2.0) / 6.0).ln(),
+    );
+}
+
+#[test]
+fn every_branch_matches_the_direct_sum() {
+    // Direct sums of ln(n - i): below, at and above the switch to the Stirling difference, and with
+    // a small remainder n - k.
+    for (n, k) in [
+        (1_000, 32),
+        (1_000, 33),
+        (1_000, 500),
+        (1_000, 990),
+        (40, 30),
+        (40, 40),
+    ] {
+        let direct: f64 = (0..k).map(|i| ((n - i) as f64).ln()).sum();
+        let actual = ln_falling_factorial(n, k);
+        assert!(
+            (actual - direct).abs() <= 1e-10 * direct.max(1.0),
+            "{n} {k}"
+        );
+    }
+    assert_close(ln_binomial(10, 3), 120f64.ln());
+    assert_close(ln_binomial(10, 10), 0.0);
+    assert_close(ln_multinomial(&[2, 3, 5]), 2_520f64.ln());
+    assert_close(ln_factorial(0), 0.0);
+}
@@ -0,0 +1,128 @@
+//! Property tests checking the optimized resampling kernels against the naive reference
+//! implementations in `pylars_core::reference`.
+//!
+//! The two sides draw different resamples, so the point estimates must agree exactly while the
+//! p-values and interval bounds only have to agree up to Monte Carlo error.
+
+use proptest::prelude::*;
+use pylars_core::bootstrap::bootstrap_columns;
+use pylars_core::permutation::permutation_columns;
+use pylars_core::reference::{reference_bootstrap, reference_permutation};
+use pylars_core::rng::Resampler;
+
+const N_RESAMPLES: u64 = 2_000;
+const P_TOLERANCE: f64 = 0.12;
+const BOUND_TOLERANCE: f64 = 0.15;
+
+fn sample(len: std::ops::Range<usize>) -> impl Strategy<Value = Vec<f64>> {
+    prop::collection::vec(1.0f64..100.0, len)
+}
+
+/// Two groups of values, or two groups of (numerator, denominator) pairs when `pairs` is set.
+fn columns(pairs: bool) -> BoxedStrategy<Vec<Vec<f64>>> {
+    if pairs {
+        (10usize..60, 10usize..60)
+            .prop_flat_map(|(len_1, len_2)| {
+                (
+                    sample(len_1..len_1 + 1),
+                    sample(len_1..len_1 + 1),
+                    sample(len_2..len_2 + 1),
+                    sample(len_2..len_2 + 1),
+                )
+            })
+            .prop_map(|(a, b, c, d)| vec![a, b, c, d])
+            .boxed()
+    } else {
+        (sample(10..60), sample(10..60))
+            .prop_map(|(a, b)| vec![a, b])
+            .boxed()
+    }
+}
+
+/// Paired columns of one common length, as required by the dependent bootstrap.
+fn paired_columns() -> BoxedStrategy<Vec<Vec<f64>>> {
+    (10usize..60)
+        .prop_flat_map(|len| (sample(len..len + 1), sample(len..len + 1)))
+        .prop_map(|(a, b)| vec![a, b])
+        .boxed()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    let scale = actual.abs().max(expected.abs()).max(1.0);
+    assert!(
+        (actual - expected).abs() <= 1e-9 * scale,
+        "{actual} differs from {expected}"
+    );
+}
+
+fn assert_interval(actual: (f64, f64), expected: (f64, f64)) {
+    let width = (expected.1 - expected.0).abs().max(1e-12);
+    assert!(
+        (actual.0 - expected.0).abs() <= BOUND_TOLERANCE * width
+            && (actual.1 - expected.1).abs() <= BOUND_TOLERANCE * width,
+        "interval {actual:?} differs from {expected:?}"
+    );
+}
+
+fn check_bootstrap(args: &[Vec<f64>], ind: bool, seed: u64) {
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let resampler = Resampler::from_seed(seed, Some(true));
+    let fast =
+        bootstrap_columns(&columns, 0.95, N_RESAMPLES, ind, true, false, 0, &resampler).unwrap();
+    let reference = reference_bootstrap(&columns, 0.95, N_RESAMPLES, ind, true, seed).unwrap();
+    assert_close(fast.1, reference.1);
+    assert_close(fast.2, reference.2);
+    assert_close(fast.3, reference.3);
+    assert!(
+        (fast.0 - reference.0).abs() <= P_TOLERANCE,
+        "p-value {} differs from {}",
+        fast.0,
+        reference.0
+    );
+    assert_interval(fast.4, reference.4);
+}
+
+fn check_permutation(args: &[Vec<f64>], seed: u64) {
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let resampler = Resampler::from_seed(seed, Some(true));
+    let fast = permutation_columns(&columns, 0.95, N_RESAMPLES, true, &resampler).unwrap();
+    let reference = reference_permutation(&columns, 0.95, N_RESAMPLES, true, seed).unwrap();
+    assert_close(fast.1, reference.1);
+    assert_close(fast.2, reference.2);
+    assert!(
+        (fast.0 - reference.0).abs() <= P_TOLERANCE,
+        "p-value {} differs from {}",
+        fast.0,
+        reference.0
+    );
+    assert_interval(fast.3, reference.3);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn bootstrap_matches_reference(args in columns(false), seed in any::<u64>()) {
+        check_bootstrap(&args, true, seed);
+    }
+
+    #[test]
+    fn ratio_bootstrap_matches_reference(args in columns(true), seed in any::<u64>()) {
+        check_bootstrap(&args, true, seed);
+    }
+
+    #[test]
+    fn dependent_bootstrap_matches_reference(args in paired_columns(), seed in any::<u64>()) {
+        check_bootstrap(&args, false, seed);
+    }
+
+    #[test]
+    fn permutation_matches_reference(args in columns(false), seed in any::<u64>()) {
+        check_permutation(&args, seed);
+    }
+
+    #[test]
+    fn ratio_permutation_matches_reference(args in columns(true), seed in any::<u64>()) {
+        check_permutation(&args, seed);
+    }
+}
@@ -0,0 +1,77 @@
+//! R bindings over the core kernels, with the same fixed seeds as the Python package so an R session
+//! reproduces pylars results exactly. Built by `R CMD INSTALL r` (or `rextendr::document("r")`).
+
+use extendr_api::prelude::*;
+use pylars_core::{bootstrap_columns, permutation_columns, Resampler};
+
+/// Bootstraps the uplift in means between two samples, like `pylars.bootstrap([a, b], ...)`.
+/// Returns a list with p_value, mean_1, mean_2, uplift, ci_low and ci_high.
+/// @export
+#[extendr]
+fn bootstrap(
+    a: &[f64],
+    b: &[f64],
+    #[extendr(default = "0.95")] confidence_level: f64,
+    #[extendr(default = "10000L")] n_resamples: i32,
+    #[extendr(default = "TRUE")] ind: bool,
+    #[extendr(default = "TRUE")] two_sided: bool,
+    #[extendr(default = "0L")] seed: i32,
+) -> Result<List> {
+    let resampler = Resampler::from_seed(seed as u64, None);
+    let (p_value, mean_1, mean_2, uplift, (ci_low, ci_high)) = bootstrap_columns(
+        &[a, b],
+        confidence_level,
+        n_resamples as u64,
+        ind,
+        two_sided,
+        false,
+        0,
+        &resampler,
+    )
+    .map_err(|e| Error::Other(e.to_string()))?;
+    Ok(list!(
+        p_value = p_value,
+        mean_1 = mean_1,
+        mean_2 = mean_2,
+        uplift = uplift,
+        ci_low = ci_low,
+        ci_high = ci_high
+    ))
+}
+
+/// Permutation test of the difference in means between two samples, like
+/// `pylars.permutation_test([a, b], ...)`.
+/// Returns a list with p_value, uplift, observed_diff, ci_low and ci_high.
+/// @export
+#[extendr]
+fn permutation_test(
+    a: &[f64],
+    b: &[f64],
+    #[extendr(default = "0.95")] confidence_level: f64,
+    #[extendr(default = "10000L")] n_resamples: i32,
+    #[extendr(default = "TRUE")] two_sided: bool,
+    #[extendr(default = "0L")] seed: i32,
+) -> Result<List> {
+    let resampler = Resampler::from_seed(seed as u64, None);
+    let (p_value, uplift, observed_diff, (ci_low, ci_high)) = permutation_columns(
+        &[a, b],
+        confidence_level,
+        n_resamples as u64,
+        two_sided,
+        &resampler,
+    )
+    .map_err(|e| Error::Other(e.to_string()))?;
+    Ok(list!(
+        p_value = p_value,
+        uplift = uplift,
+        observed_diff = observed_diff,
+        ci_low = ci_low,
+        ci_high = ci_high
+    ))
+}
+
+extendr_module! {
+    mod pylars;
+    fn bootstrap;
+    fn permutation_test;
+}
@@ -0,0 +1,86 @@
+use crate::errors::*;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Per-key totals: (sum of values, sum of denominators, count).
+type Totals<'a> = HashMap<&'a str, (f64, f64, f64)>;
+
+#[pyfunction(signature = (values, keys, agg = "sum", denominators = None))]
+#[pyo3(text_signature = "(values, keys, agg='sum', denominators=None)")]
+/// """
+/// Collapses event-level values to one value per key, e.g. user-level metrics before a test.
+///
+/// The events are aggregated in parallel and the keys are returned in sorted order.
+///
+/// Args:
+///     values (List[float]): The value of every event.
+///     keys (List[str]): The key (e.g. user id) of every event.
+///     agg (str, optional): "sum", "mean", "count" or "ratio", the latter being
+///         sum(values) / sum(denominators) for every key. Default is "sum".
+///     denominators (List[float], optional): The denominator of every event, required by "ratio".
+///         Default is None.
+///
+/// Returns:
+///     Tuple[List[str], List[float]]: The distinct keys and their aggregated values.
+/// """
+pub fn aggregate_by_key(
+    py: Python<'_>,
+    values: Vec<f64>,
+    keys: Vec<String>,
+    agg: &str,
+    denominators: Option<Vec<f64>>,
+) -> PyResult<(Vec<String>, Vec<f64>)> {
+    if values.len() != keys.len() {
+        raise!(InvalidInputError, "values and keys must have equal size")
+    }
+    if !matches!(agg, "sum" | "mean" | "count" | "ratio") {
+        raise!(
+            InvalidInputError,
+            "agg must be one of 'sum', 'mean', 'count' or 'ratio'"
+        )
+    }
+    let denominators = match (agg, denominators) {
+        ("ratio", Some(denominators)) if denominators.len() == values.len() => denominators,
+        ("ratio", Some(_)) => raise!(
+            InvalidInputError,
+            "denominators and values must have equal size"
+        ),
+        ("ratio", None) => raise!(InvalidInputError, "agg='ratio' requires denominators"),
+        _ => Vec::new(),
+    };
+    Ok(py.allow_threads(|| {
+        let totals = (0..values.len())
+            .into_par_iter()
+            .fold(Totals::new, |mut totals, i| {
+                let total = totals.entry(keys[i].as_str()).or_default();
+                total.0 += values[i];
+                total.1 += denominators.get(i).copied().unwrap_or(0.0);
+                total.2 += 1.0;
+                totals
+            })
+            .reduce(Totals::new, |mut left, right| {
+                for (key, (sum, den, count)) in right {
+                    let total = left.entry(key).or_default();
+                    total.0 += sum;
+                    total.1 += den;
+                    total.2 += count;
+                }
+                left
+            });
+        let mut totals: Vec<(&str, (f64, f64, f64))> = totals.into_iter().collect();
+        totals.par_sort_unstable_by_key(|(key, _)| *key);
+        totals
+            .into_iter()
+            .map(|(key, (sum, den, count))| {
+                let value = match agg {
+                    "sum" => sum,
+                    "mean" => sum / count,
+                    "count" => count,
+                    _ => sum / den,
+                };
+                (key.to_string(), value)
+            })
+            .unzip()
+    }))
+}
@@ -0,0 +1,380 @@
+use crate::errors::*;
+use crate::special::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+
+/// Largest number of nonzero differences of `wilcoxon` with `mode="exact"`: the distribution has
+/// `n (n + 1) + 1` entries, each updated once per difference.
+const MAX_EXACT_DIFFERENCES: usize = 1_000;
+
+/// Exact null distribution of the signed-rank statistic for the given doubled (integer) ranks:
+/// `dist[s]` is the probability that the positive ranks sum to `s / 2`.
+fn signed_rank_distribution(doubled_ranks: &[usize]) -> Vec<f64> {
+    let total: usize = doubled_ranks.iter().sum();
+    let mut dist = vec![0.0; total + 1];
+    dist[0] = 1.0;
+    let mut reached = 0;
+    for &rank in doubled_ranks {
+        reached += rank;
+        for s in (rank..=reached).rev() {
+            dist[s] = 0.5 * (dist[s] + dist[s - rank]);
+        }
+        for p in dist[..rank].iter_mut() {
+            *p *= 0.5;
+        }
+    }
+    dist
+}
+
+#[pyfunction(signature = (x, y = None, mode = "auto", alternative = "two-sided"))]
+#[pyo3(text_signature = "(x, y=None, mode='auto', alternative='two-sided')")]
+/// """
+/// Performs the Wilcoxon signed-rank test on the differences `x - y` (or on `x` when `y` is omitted).
+///
+/// Zero differences are discarded and tied absolute differences receive average ranks.
+/// The exact null distribution is computed by dynamic programming over the observed ranks,
+/// so it stays exact in the presence of ties.
+///
+/// Args:
+///     x (List[float]): The first sample, or the differences when `y` is omitted.
+///     y (List[float], optional): The second paired sample. Default is None.
+///     mode (str, optional): "exact", "approx" (normal approximation with tie correction) or "auto",
+///         which is exact for at most 50 nonzero differences. "exact" raises BudgetExceededError beyond
+///         1000 nonzero differences. Default is "auto".
+///     alternative (str, optional): "two-sided", "less" or "greater"; "greater" means the differences
+///         tend to be positive. Default is "two-sided".
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The sum of the ranks of the positive differences.
+///         - p_value (float): The p-value for the chosen alternative.
+/// """
+pub fn wilcoxon(
+    x: Vec<f64>,
+    y: Option<Vec<f64>>,
+    mode: &str,
+    alternative: &str,
+) -> PyResult<(f64, f64)> {
+    check_no_nan("x", &x)?;
+    if let Some(y) = &y {
+        check_no_nan("y", y)?;
+    }
+    let alternative = Alternative::parse(alternative).or_raise()?;
+    let diffs: Vec<f64> = match y {
+        Some(y) => {
+            if x.len() != y.len() {
+                raise!(InvalidInputError, "x and y must have same size")
+            }
+            x.iter().zip(&y).map(|(a, b)| a - b).collect()
+        }
+        None => x,
+    };
+    let diffs: Vec<f64> = diffs.into_iter().filter(|&d| d != 0.0).collect();
+    let n = diffs.len();
+    if n == 0 {
+        raise!(InvalidInputError, "All differences are zero")
+    }
+    let abs_diffs: Vec<f64> = diffs.iter().map(|d| d.abs()).collect();
+    let (ranks, tie_term) = rankdata(&abs_diffs);
+    let w_plus: f64 = ranks
+        .iter()
+        .zip(&diffs)
+        .filter(|(_, &d)| d > 0.0)
+        .map(|(r, _)| r)
+        .sum();
+
+    let exact = match mode {
+        "exact" => true,
+        "approx" => false,
+        "auto" => n <= 50,
+        _ => raise!(
+            InvalidInputError,
+            "mode must be one of 'exact', 'approx' or 'auto'"
+        ),
+    };
+    if exact && n > MAX_EXACT_DIFFERENCES {
+        raise!(
+            BudgetExceededError,
+            "exact would compute the distribution of {n} nonzero differences, more than the limit of {MAX_EXACT_DIFFERENCES}; use mode='approx' instead"
+        )
+    }
+    let (p_less, p_greater) = if exact {
+        let doubled: Vec<usize> = ranks.iter().map(|r| (2.0 * r).round() as usize).collect();
+        let dist = signed_rank_distribution(&doubled);
+        let w = (2.0 * w_plus).round() as usize;
+        (
+            dist[..=w].iter().sum::<f64>().min(1.0),
+            dist[w..].iter().sum::<f64>().min(1.0),
+        )
+    } else {
+        let n = n as f64;
+        let mean = n * (n + 1.0) / 4.0;
+        let var = n * (n + 1.0) * (2.0 * n + 1.0) / 24.0 - tie_term / 48.0;
+        let z = (w_plus - mean) / var.sqrt();
+        (norm_cdf(z), norm_sf(z))
+    };
+    Ok((w_plus, alternative.p_value(p_less, p_greater)))
+}
+
+#[pyfunction(signature = (args))]
+#[pyo3(text_signature = "(args)")]
+/// """
+/// Performs the Kruskal–Wallis H-test for independent samples, the rank-based analogue of one-way ANOVA.
+///
+/// Ties receive average ranks and the statistic is divided by the tie correction
+/// `1 - sum(t^3 - t) / (N^3 - N)`. The p-value uses the chi-square approximation with k - 1 degrees of freedom.
+///
+/// Args:
+///     args (List[List[float]]): Two or more samples.
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The tie-corrected H statistic.
+///         - p_value (float): The p-value of the test.
+/// """
+pub fn kruskal(args: Vec<Vec<f64>>) -> PyResult<(f64, f64)> {
+    if args.len() < 2 {
+        raise!(InvalidInputError, "At least two samples are required")
+    }
+    if args.iter().any(|group| group.is_empty()) {
+        raise!(InsufficientDataError, "All samples must be non-empty")
+    }
+    let pooled: Vec<f64> = args.iter().flatten().copied().collect();
+    check_no_nan("The samples", &pooled)?;
+    let n = pooled.len() as f64;
+    let (ranks, tie_term) = rankdata(&pooled);
+    let mut offset = 0;
+    let mut h = 0.0;
+    for group in &args {
+        let rank_sum: f64 = ranks[offset..offset + group.len()].iter().sum();
+        h += rank_sum * rank_sum / group.len() as f64;
+        offset += group.len();
+    }
+    h = 12.0 / (n * (n + 1.0)) * h - 3.0 * (n + 1.0);
+    let correction = 1.0 - tie_term / (n * n * n - n);
+    if correction == 0.0 {
+        raise!(InvalidInputError, "All values are identical")
+    }
+    h /= correction;
+    Ok((h, chi2_sf(h, (args.len() - 1) as f64)))
+}
+
+#[pyfunction(signature = (args))]
+#[pyo3(text_signature = "(args)")]
+/// """
+/// Performs the Friedman test for repeated measurements of the same blocks under k treatments.
+///
+/// Values are ranked within every block (ties receive average ranks) and the statistic is divided by
+/// the tie correction `1 - sum(t^3 - t) / (n * (k^3 - k))`. The p-value uses the chi-square approximation
+/// with k - 1 degrees of freedom.
+///
+/// Args:
+///     args (List[List[float]]): Three or more treatments, each a list with one value per block.
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The tie-corrected Friedman chi-square statistic.
+///         - p_value (float): The p-value of the test.
+/// """
+pub fn friedmanchisquare(args: Vec<Vec<f64>>) -> PyResult<(f64, f64)> {
+    let k = args.len();
+    if k < 3 {
+        raise!(InvalidInputError, "At least three treatments are required")
+    }
+    let n = args[0].len();
+    if n == 0 || args.iter().any(|treatment| treatment.len() != n) {
+        raise!(
+            InvalidInputError,
+            "All treatments must have the same non-zero number of blocks"
+        )
+    }
+    let mut rank_sums = vec![0.0; k];
+    let mut tie_term = 0.0;
+    let mut block = vec![0.0; k];
+    for b in 0..n {
+        for (value, treatment) in block.iter_mut().zip(&args) {
+            *value = treatment[b];
+        }
+        let (ranks, ties) = rankdata(&block);
+        for (sum, rank) in rank_sums.iter_mut().zip(&ranks) {
+            *sum += rank;
+        }
+        tie_term += ties;
+    }
+    let (n, k) = (n as f64, k as f64);
+    let statistic = 12.0 / (n * k * (k + 1.0)) * rank_sums.iter().map(|r| r * r).sum::<f64>()
+        - 3.0 * n * (k + 1.0);
+    let correction = 1.0 - tie_term / (n * (k * k * k - k));
+    if correction == 0.0 {
+        raise!(InvalidInputError, "All values are tied within every block")
+    }
+    let statistic = statistic / correction;
+    Ok((statistic, chi2_sf(statistic, k - 1.0)))
+}
+
+/// D'Agostino's z-score of the sample skewness.
+fn skewness_z(skew: f64, n: f64) -> f64 {
+    let y = skew * ((n + 1.0) * (n + 3.0) / (6.0 * (n - 2.0))).sqrt();
+    let beta2 = 3.0 * (n * n + 27.0 * n - 70.0) * (n + 1.0) * (n + 3.0)
+        / ((n - 2.0) * (n + 5.0) * (n + 7.0) * (n + 9.0));
+    let w2 = -1.0 + (2.0 * (beta2 - 1.0)).sqrt();
+    let delta = 1.0 / (0.5 * w2.ln()).sqrt();
+    let alpha = (2.0 / (w2 - 1.0)).sqrt();
+    let y = if y == 0.0 { 1.0 } else { y };
+    delta * (y / alpha + ((y / alpha).powi(2) + 1.0).sqrt()).ln()
+}
+
+/// Anscombe–Glynn z-score of the sample (excess) kurtosis.
+fn kurtosis_z(excess_kurtosis: f64, n: f64) -> f64 {
+    let b2 = excess_kurtosis + 3.0;
+    let expected = 3.0 * (n - 1.0) / (n + 1.0);
+    let var_b2 = 24.0 * n * (n - 2.0) * (n - 3.0) / ((n + 1.0) * (n + 1.0) * (n + 3.0) * (n + 5.0));
+    let x = (b2 - expected) / var_b2.sqrt();
+    let sqrt_beta1 = 6.0 * (n * n - 5.0 * n + 2.0) / ((n + 7.0) * (n + 9.0))
+        * (6.0 * (n + 3.0) * (n + 5.0) / (n * (n - 2.0) * (n - 3.0))).sqrt();
+    let a = 6.0
+        + 8.0 / sqrt_beta1 * (2.0 / sqrt_beta1 + (1.0 + 4.0 / (sqrt_beta1 * sqrt_beta1)).sqrt());
+    let term1 = 1.0 - 2.0 / (9.0 * a);
+    let denom = 1.0 + x * (2.0 / (a - 4.0)).sqrt();
+    let term2 = denom.signum() * ((1.0 - 2.0 / a) / denom.abs()).cbrt();
+    (term1 - term2) / (2.0 / (9.0 * a)).sqrt()
+}
+
+#[pyfunction(signature = (x))]
+#[pyo3(text_signature = "(x)")]
+/// """
+/// Performs D'Agostino and Pearson's K^2 omnibus test of normality, combining the z-scores of the
+/// sample skewness and kurtosis.
+///
+/// Args:
+///     x (List[float]): The sample, with at least 8 observations (20 or more is recommended).
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The K^2 statistic (sum of the squared skewness and kurtosis z-scores).
+///         - p_value (float): The p-value from the chi-square distribution with 2 degrees of freedom.
+/// """
+pub fn dagostino_k2(x: Vec<f64>) -> PyResult<(f64, f64)> {
+    if x.len() < 8 {
+        raise!(
+            InsufficientDataError,
+            "dagostino_k2 requires at least 8 observations"
+        )
+    }
+    let n = x.len() as f64;
+    let z_skew = skewness_z(skewness(&x), n);
+    let z_kurt = kurtosis_z(kurtosis(&x), n);
+    let statistic = z_skew * z_skew + z_kurt * z_kurt;
+    Ok((statistic, chi2_sf(statistic, 2.0)))
+}
+
+#[pyfunction(signature = (x))]
+#[pyo3(text_signature = "(x)")]
+/// """
+/// Performs the Jarque–Bera test of normality, `n / 6 * (S^2 + K^2 / 4)` with S the sample skewness
+/// and K the sample excess kurtosis.
+///
+/// Args:
+///     x (List[float]): The sample. The asymptotic p-value is only reliable for large samples.
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The Jarque–Bera statistic.
+///         - p_value (float): The p-value from the chi-square distribution with 2 degrees of freedom.
+/// """
+pub fn jarque_bera(x: Vec<f64>) -> PyResult<(f64, f64)> {
+    if x.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "jarque_bera requires at least 2 observations"
+        )
+    }
+    let n = x.len() as f64;
+    let (skew, kurt) = (skewness(&x), kurtosis(&x));
+    let statistic = n / 6.0 * (skew * skew + kurt * kurt / 4.0);
+    Ok((statistic, chi2_sf(statistic, 2.0)))
+}
+
+#[pyfunction(signature = (args, center = "median"))]
+#[pyo3(text_signature = "(args, center='median')")]
+/// """
+/// Performs Levene's test for equality of variances across two or more samples.
+///
+/// The test runs a one-way ANOVA on the absolute deviations from each group's center. With
+/// `center="median"` this is the Brown–Forsythe test, which is robust to skewed data; `center="mean"`
+/// gives the original Levene test. A small p-value suggests preferring Welch's test over a pooled t-test.
+/// When the deviations are constant within every group, the statistic is 0 with a p-value of 1 if they
+/// are also equal across groups, and infinite with a p-value of 0 otherwise.
+///
+/// Args:
+///     args (List[List[float]]): Two or more samples.
+///     center (str, optional): Either "median" or "mean". Default is "median".
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The W statistic.
+///         - p_value (float): The p-value from the F distribution with (k - 1, N - k) degrees of freedom.
+/// """
+pub fn levene(args: Vec<Vec<f64>>, center: &str) -> PyResult<(f64, f64)> {
+    let k = args.len();
+    if k < 2 {
+        raise!(InvalidInputError, "At least two samples are required")
+    }
+    if args.iter().any(|group| group.is_empty()) {
+        raise!(InsufficientDataError, "All samples must be non-empty")
+    }
+    let center_of = match center {
+        "median" => median,
+        "mean" => |group: &[f64]| group.iter().sum::<f64>() / group.len() as f64,
+        _ => raise!(
+            InvalidInputError,
+            "center must be either 'median' or 'mean'"
+        ),
+    };
+    let deviations: Vec<Vec<f64>> = args
+        .iter()
+        .map(|group| {
+            let c = center_of(group);
+            group.iter().map(|x| (x - c).abs()).collect()
+        })
+        .collect();
+    let n: usize = deviations.iter().map(Vec::len).sum();
+    if n <= k {
+        raise!(
+            InsufficientDataError,
+            "The total number of observations must exceed the number of samples"
+        )
+    }
+    let group_means: Vec<f64> = deviations
+        .iter()
+        .map(|z| z.iter().sum::<f64>() / z.len() as f64)
+        .collect();
+    let grand_mean = deviations.iter().flatten().sum::<f64>() / n as f64;
+    let between: f64 = deviations
+        .iter()
+        .zip(&group_means)
+        .map(|(z, m)| z.len() as f64 * (m - grand_mean).powi(2))
+        .sum();
+    let within: f64 = deviations
+        .iter()
+        .zip(&group_means)
+        .map(|(z, m)| z.iter().map(|v| (v - m).powi(2)).sum::<f64>())
+        .sum();
+    // Deviations constant within every group: equal spreads if they also agree across groups.
+    if within == 0.0 {
+        return Ok(if between == 0.0 {
+            (0.0, 1.0)
+        } else {
+            (f64::INFINITY, 0.0)
+        });
+    }
+    let (df1, df2) = ((k - 1) as f64, (n - k) as f64);
+    let statistic = df2 / df1 * between / within;
+    Ok((statistic, f_sf(statistic, df1, df2)))
+}
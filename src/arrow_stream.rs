@@ -0,0 +1,378 @@
+use crate::bootstrapping::*;
+use crate::errors::*;
+use crate::levels::*;
+use crate::perm::*;
+use crate::rng::*;
+use pylars_core::tools::Accumulator;
+use pylars_core::{BootstrapCiMethod, Statistic, UpliftCiMethod};
+use pyo3::prelude::*;
+use pyo3::types::PyCapsule;
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+// Structures of the Arrow C data and C stream interfaces.
+
+#[repr(C)]
+struct ArrowSchema {
+    format: *const c_char,
+    name: *const c_char,
+    metadata: *const c_char,
+    flags: i64,
+    n_children: i64,
+    children: *mut *mut ArrowSchema,
+    dictionary: *mut ArrowSchema,
+    release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    private_data: *mut c_void,
+}
+
+#[repr(C)]
+struct ArrowArray {
+    length: i64,
+    null_count: i64,
+    offset: i64,
+    n_buffers: i64,
+    n_children: i64,
+    buffers: *mut *const c_void,
+    children: *mut *mut ArrowArray,
+    dictionary: *mut ArrowArray,
+    release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    private_data: *mut c_void,
+}
+
+#[repr(C)]
+struct ArrowArrayStream {
+    get_schema: Option<unsafe extern "C" fn(*mut ArrowArrayStream, *mut ArrowSchema) -> c_int>,
+    get_next: Option<unsafe extern "C" fn(*mut ArrowArrayStream, *mut ArrowArray) -> c_int>,
+    get_last_error: Option<unsafe extern "C" fn(*mut ArrowArrayStream) -> *const c_char>,
+    release: Option<unsafe extern "C" fn(*mut ArrowArrayStream)>,
+    private_data: *mut c_void,
+}
+
+impl ArrowSchema {
+    fn empty() -> Self {
+        ArrowSchema {
+            format: std::ptr::null(),
+            name: std::ptr::null(),
+            metadata: std::ptr::null(),
+            flags: 0,
+            n_children: 0,
+            children: std::ptr::null_mut(),
+            dictionary: std::ptr::null_mut(),
+            release: None,
+            private_data: std::ptr::null_mut(),
+        }
+    }
+}
+
+impl Drop for ArrowSchema {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            // SAFETY: the producer's release callback is called once on a structure we own.
+            unsafe { release(self) }
+        }
+    }
+}
+
+impl ArrowArray {
+    fn empty() -> Self {
+        ArrowArray {
+            length: 0,
+            null_count: 0,
+            offset: 0,
+            n_buffers: 0,
+            n_children: 0,
+            buffers: std::ptr::null_mut(),
+            children: std::ptr::null_mut(),
+            dictionary: std::ptr::null_mut(),
+            release: None,
+            private_data: std::ptr::null_mut(),
+        }
+    }
+}
+
+impl Drop for ArrowArray {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            // SAFETY: as for `ArrowSchema`.
+            unsafe { release(self) }
+        }
+    }
+}
+
+impl Drop for ArrowArrayStream {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            // SAFETY: as for `ArrowSchema`.
+            unsafe { release(self) }
+        }
+    }
+}
+
+/// Physical types a numeric column can have, from their Arrow format strings.
+#[derive(Clone, Copy)]
+enum ColumnType {
+    F64,
+    F32,
+    I64,
+    I32,
+    I16,
+    I8,
+    U64,
+    U32,
+    U16,
+    U8,
+}
+
+impl ColumnType {
+    fn parse(format: &str) -> PyResult<ColumnType> {
+        Ok(match format {
+            "g" => ColumnType::F64,
+            "f" => ColumnType::F32,
+            "l" => ColumnType::I64,
+            "i" => ColumnType::I32,
+            "s" => ColumnType::I16,
+            "c" => ColumnType::I8,
+            "L" => ColumnType::U64,
+            "I" => ColumnType::U32,
+            "S" => ColumnType::U16,
+            "C" => ColumnType::U8,
+            _ => raise!(
+                InvalidInputError,
+                "Unsupported Arrow column format '{format}', expected a numeric column"
+            ),
+        })
+    }
+
+    /// Value at position `i` of a data buffer of this type.
+    ///
+    /// SAFETY: `data` must point to at least `i + 1` values of this type.
+    unsafe fn read(&self, data: *const c_void, i: usize) -> f64 {
+        match self {
+            ColumnType::F64 => *(data as *const f64).add(i),
+            ColumnType::F32 => *(data as *const f32).add(i) as f64,
+            ColumnType::I64 => *(data as *const i64).add(i) as f64,
+            ColumnType::I32 => *(data as *const i32).add(i) as f64,
+            ColumnType::I16 => *(data as *const i16).add(i) as f64,
+            ColumnType::I8 => *(data as *const i8).add(i) as f64,
+            ColumnType::U64 => *(data as *const u64).add(i) as f64,
+            ColumnType::U32 => *(data as *const u32).add(i) as f64,
+            ColumnType::U16 => *(data as *const u16).add(i) as f64,
+            ColumnType::U8 => *(data as *const u8).add(i) as f64,
+        }
+    }
+}
+
+/// Takes ownership of the Arrow C stream exported by `source.__arrow_c_stream__()`.
+fn import_stream(source: &Bound<'_, PyAny>) -> PyResult<ArrowArrayStream> {
+    let capsule = source.call_method0("__arrow_c_stream__")?;
+    let capsule = capsule.downcast::<PyCapsule>()?;
+    if capsule.name()? != Some(c"arrow_array_stream") {
+        raise!(
+            InvalidInputError,
+            "__arrow_c_stream__ must return an \"arrow_array_stream\" capsule"
+        )
+    }
+    let pointer = capsule.pointer() as *mut ArrowArrayStream;
+    if pointer.is_null() {
+        raise!(InvalidInputError, "The Arrow stream capsule is empty")
+    }
+    // SAFETY: the capsule holds a valid stream; moving it out and clearing the source's release
+    // callback is how the C stream interface transfers ownership to the consumer.
+    unsafe {
+        let stream = std::ptr::read(pointer);
+        (*pointer).release = None;
+        Ok(stream)
+    }
+}
+
+fn stream_error(stream: &mut ArrowArrayStream, code: c_int) -> PyErr {
+    let message = stream
+        .get_last_error
+        // SAFETY: the callback belongs to the live stream and returns null or a C string.
+        .map(|get_last_error| unsafe { get_last_error(stream) })
+        .filter(|message| !message.is_null())
+        .map(|message| {
+            unsafe { CStr::from_ptr(message) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .unwrap_or_default();
+    StatError::new_err(format!(
+        "The Arrow stream failed with error code {code}: {message}"
+    ))
+}
+
+/// Accumulates the non-null values of `column` batch by batch from an Arrow C stream of record
+/// batches, releasing every batch as soon as it has been read.
+fn read_stream_column(mut stream: ArrowArrayStream, column: &str) -> PyResult<Vec<f64>> {
+    let mut schema = ArrowSchema::empty();
+    // SAFETY: the stream is live and the callbacks follow the C stream interface; every pointer read
+    // below comes from structures the producer filled in.
+    Ok(unsafe {
+        let Some(get_schema) = stream.get_schema else {
+            raise!(InvalidInputError, "The Arrow stream is released")
+        };
+        let Some(get_next) = stream.get_next else {
+            raise!(InvalidInputError, "The Arrow stream is released")
+        };
+        let code = get_schema(&mut stream, &mut schema);
+        if code != 0 {
+            return Err(stream_error(&mut stream, code));
+        }
+        if CStr::from_ptr(schema.format).to_bytes() != b"+s" {
+            raise!(
+                InvalidInputError,
+                "The Arrow stream must contain record batches"
+            )
+        }
+        let position = (0..schema.n_children as usize)
+            .find(|&k| {
+                let child = &**schema.children.add(k);
+                !child.name.is_null() && CStr::from_ptr(child.name).to_bytes() == column.as_bytes()
+            })
+            .ok_or_else(|| {
+                InvalidInputError::new_err(format!("The Arrow stream has no column {column}"))
+            })?;
+        let child = &**schema.children.add(position);
+        if !child.dictionary.is_null() {
+            raise!(
+                InvalidInputError,
+                "The column {column} is dictionary-encoded, decode it before passing the stream"
+            )
+        }
+        let column_type = ColumnType::parse(&CStr::from_ptr(child.format).to_string_lossy())?;
+
+        let mut values = Vec::new();
+        loop {
+            let mut batch = ArrowArray::empty();
+            let code = get_next(&mut stream, &mut batch);
+            if code != 0 {
+                return Err(stream_error(&mut stream, code));
+            }
+            if batch.release.is_none() {
+                break;
+            }
+            let array = &**batch.children.add(position);
+            let offset = (batch.offset + array.offset) as usize;
+            let validity = *array.buffers as *const u8;
+            let data = *array.buffers.add(1);
+            values.reserve(batch.length as usize);
+            for i in offset..offset + batch.length as usize {
+                if validity.is_null() || *validity.add(i / 8) >> (i % 8) & 1 == 1 {
+                    values.push(column_type.read(data, i));
+                }
+            }
+        }
+        values
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (source_a, source_b, column, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, ind = true, two_sided = true, bias_corrected = false, n_inner_resamples = 0, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(source_a, source_b, column, confidence_level=0.95, n_resamples=10000, ind=True, two_sided=True, bias_corrected=False, n_inner_resamples=0, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `bootstrap` on a numeric column of two Arrow record-batch streams.
+///
+/// Any object implementing the Arrow PyCapsule interface (`__arrow_c_stream__`) is accepted, such as
+/// a DuckDB or ADBC query result, a pyarrow Table or RecordBatchReader, or a polars DataFrame. The
+/// column is accumulated batch by batch in Rust and every batch is released once read, so the result
+/// never has to be materialized as a Python list. Null values are skipped.
+///
+/// Args:
+///     source_a (object): The Arrow stream of the first sample.
+///     source_b (object): The Arrow stream of the second sample.
+///     column (str): The name of the numeric column to analyse.
+///     The remaining arguments are those of `bootstrap`.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]: The same tuple as `bootstrap`.
+/// """
+pub fn bootstrap_from_arrow(
+    py: Python<'_>,
+    source_a: &Bound<'_, PyAny>,
+    source_b: &Bound<'_, PyAny>,
+    column: &str,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    bias_corrected: bool,
+    n_inner_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let column_a = read_stream_column(import_stream(source_a)?, column)?;
+    let column_b = read_stream_column(import_stream(source_b)?, column)?;
+    bootstrap_levels(
+        py,
+        &[&column_a, &column_b],
+        &confidence_level,
+        n_resamples,
+        ind,
+        two_sided,
+        bias_corrected,
+        n_inner_resamples,
+        UpliftCiMethod::Ratio,
+        BootstrapCiMethod::Percentile,
+        Statistic::Mean,
+        Accumulator::F64,
+        &resampler,
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (source_a, source_b, column, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(source_a, source_b, column, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `permutation_test` on a numeric column of two Arrow record-batch streams.
+///
+/// Reads the column like `bootstrap_from_arrow`.
+///
+/// Args:
+///     source_a (object): The Arrow stream of the first sample.
+///     source_b (object): The Arrow stream of the second sample.
+///     column (str): The name of the numeric column to analyse.
+///     The remaining arguments are those of `permutation_test`.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float)]: The same tuple as `permutation_test`.
+/// """
+pub fn permutation_test_from_arrow(
+    py: Python<'_>,
+    source_a: &Bound<'_, PyAny>,
+    source_b: &Bound<'_, PyAny>,
+    column: &str,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PermutationLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let (column_a, column_b) = (
+        read_stream_column(import_stream(source_a)?, column)?,
+        read_stream_column(import_stream(source_b)?, column)?,
+    );
+    permutation_levels(
+        py,
+        &[&column_a, &column_b],
+        &confidence_level,
+        n_resamples,
+        two_sided,
+        false,
+        false,
+        Statistic::Mean,
+        Accumulator::F64,
+        &resampler,
+        None,
+    )
+}
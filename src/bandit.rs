@@ -0,0 +1,347 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_distr::Beta;
+use std::collections::BTreeMap;
+
+type BanditSimulation = (Vec<f64>, Vec<f64>, f64);
+
+/// Number of trajectories simulated one after the other by every parallel task, which bounds the
+/// memory of the regret curves to one per task.
+const SIMS_PER_TASK: u64 = 16;
+
+/// Arm-selection rule of a simulated policy.
+#[derive(Clone, Copy)]
+enum Policy {
+    Thompson,
+    Ucb,
+    EpsilonGreedy(f64),
+}
+
+impl Policy {
+    fn parse(name: &str, epsilon: f64) -> PyResult<Policy> {
+        Ok(match name {
+            "thompson" => Policy::Thompson,
+            "ucb" => Policy::Ucb,
+            "epsilon_greedy" => {
+                if !(0.0..=1.0).contains(&epsilon) {
+                    raise!(InvalidInputError, "epsilon must be in [0, 1]")
+                }
+                Policy::EpsilonGreedy(epsilon)
+            }
+            _ => raise!(
+                InvalidInputError,
+                "policy must be 'thompson', 'ucb' or 'epsilon_greedy', got '{name}'"
+            ),
+        })
+    }
+
+    /// Arm pulled at step `t` (from 0) given the successes and pulls of every arm so far.
+    fn choose<R: Rng>(&self, successes: &[f64], pulls: &[f64], t: usize, rng: &mut R) -> usize {
+        let argmax = |score: &dyn Fn(usize) -> f64| {
+            (0..pulls.len())
+                .max_by(|&i, &j| score(i).total_cmp(&score(j)))
+                .unwrap()
+        };
+        match self {
+            Policy::Thompson => {
+                let draws: Vec<f64> = (0..pulls.len())
+                    .map(|arm| {
+                        Beta::new(1.0 + successes[arm], 1.0 + pulls[arm] - successes[arm])
+                            .unwrap()
+                            .sample(rng)
+                    })
+                    .collect();
+                argmax(&|arm| draws[arm])
+            }
+            Policy::Ucb => match pulls.iter().position(|&n| n == 0.0) {
+                // UCB1 pulls every arm once before using its bound.
+                Some(arm) => arm,
+                None => argmax(&|arm| {
+                    successes[arm] / pulls[arm] + (2.0 * (t as f64).ln() / pulls[arm]).sqrt()
+                }),
+            },
+            Policy::EpsilonGreedy(epsilon) => {
+                if rng.gen::<f64>() < *epsilon {
+                    rng.gen_range(0..pulls.len())
+                } else {
+                    argmax(&|arm| {
+                        if pulls[arm] == 0.0 {
+                            f64::INFINITY
+                        } else {
+                            successes[arm] / pulls[arm]
+                        }
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (arms_params, horizon, policy = "thompson", n_sims = 1_000, epsilon = 0.1, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(arms_params, horizon, policy='thompson', n_sims=1000, epsilon=0.1, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Simulates a bandit policy on Bernoulli arms to evaluate an adaptive experiment design.
+///
+/// Every trajectory pulls arms for `horizon` steps and the trajectories run in parallel. Thompson
+/// sampling draws from Beta(1 + successes, 1 + failures) posteriors, UCB uses the UCB1 bound
+/// mean + sqrt(2 ln t / n), and epsilon-greedy explores uniformly with probability epsilon.
+///
+/// Args:
+///     arms_params (List[float]): The success probability of every arm.
+///     horizon (int): The number of pulls of every trajectory.
+///     policy (str, optional): "thompson", "ucb" or "epsilon_greedy". Default is "thompson".
+///     n_sims (int, optional): The number of simulated trajectories. Default is 1000.
+///     epsilon (float, optional): The exploration rate of "epsilon_greedy". Default is 0.1.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], float]: The mean cumulative regret after every step, measured
+///         against always pulling the best arm, the mean share of pulls of every arm, and the share of
+///         trajectories whose most pulled arm is a best arm.
+/// """
+pub fn simulate_bandit(
+    py: Python<'_>,
+    arms_params: Vec<f64>,
+    horizon: usize,
+    policy: &str,
+    n_sims: u64,
+    epsilon: f64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BanditSimulation> {
+    if arms_params.len() < 2 {
+        raise!(InsufficientDataError, "A bandit needs at least two arms")
+    }
+    if arms_params.iter().any(|p| !(0.0..=1.0).contains(p)) {
+        raise!(
+            InvalidInputError,
+            "arms_params must be success probabilities in [0, 1]"
+        )
+    }
+    if horizon == 0 || n_sims == 0 {
+        raise!(InvalidInputError, "horizon and n_sims must be positive")
+    }
+    let policy = Policy::parse(policy, epsilon)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let best = arms_params.iter().cloned().fold(f64::MIN, f64::max);
+    let n_arms = arms_params.len();
+
+    let tasks = py.allow_threads(|| {
+        resampler.map_indexed(n_sims.div_ceil(SIMS_PER_TASK), |task, rng| {
+            let mut regret = vec![0.0; horizon];
+            let mut pull_share = vec![0.0; n_arms];
+            let mut hits = 0.0;
+            for _ in task * SIMS_PER_TASK..n_sims.min((task + 1) * SIMS_PER_TASK) {
+                let mut successes = vec![0.0; n_arms];
+                let mut pulls = vec![0.0; n_arms];
+                let mut cumulative = 0.0;
+                for (t, step_regret) in regret.iter_mut().enumerate() {
+                    let arm = policy.choose(&successes, &pulls, t, rng);
+                    pulls[arm] += 1.0;
+                    if rng.gen::<f64>() < arms_params[arm] {
+                        successes[arm] += 1.0;
+                    }
+                    cumulative += best - arms_params[arm];
+                    *step_regret += cumulative;
+                }
+                let most_pulled = (0..n_arms)
+                    .max_by(|&i, &j| pulls[i].total_cmp(&pulls[j]))
+                    .unwrap();
+                if arms_params[most_pulled] == best {
+                    hits += 1.0;
+                }
+                for (share, n) in pull_share.iter_mut().zip(&pulls) {
+                    *share += n / horizon as f64;
+                }
+            }
+            (regret, pull_share, hits)
+        })
+    });
+
+    let total = n_sims as f64;
+    let mut regret = vec![0.0; horizon];
+    let mut pull_share = vec![0.0; n_arms];
+    let mut hits = 0.0;
+    for (task_regret, task_share, task_hits) in &tasks {
+        regret
+            .iter_mut()
+            .zip(task_regret)
+            .for_each(|(r, x)| *r += x);
+        pull_share
+            .iter_mut()
+            .zip(task_share)
+            .for_each(|(s, x)| *s += x);
+        hits += task_hits;
+    }
+    regret.iter_mut().for_each(|r| *r /= total);
+    pull_share.iter_mut().for_each(|s| *s /= total);
+    Ok((regret, pull_share, hits / total))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (rewards, propensities, target_probs, estimator = "ips", reward_hat = None, target_reward_hat = None, clusters = None, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(rewards, propensities, target_probs, estimator='ips', reward_hat=None, target_reward_hat=None, clusters=None, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Estimates the value of a target policy from bandit logs, with a bootstrap confidence interval.
+///
+/// Every logged event has the reward of the logged action, the probability the logging policy gave to
+/// that action and the probability the target policy gives to it, whose ratio is the importance weight
+/// w. "ips" averages w * reward, "snips" divides the sum of w * reward by the sum of the weights, and
+/// "dr" (doubly robust) averages target_reward_hat + w * (reward - reward_hat), using a reward model.
+/// When clusters are given, the bootstrap resamples whole clusters (e.g. users with several events).
+///
+/// Args:
+///     rewards (List[float]): The reward of every logged event.
+///     propensities (List[float]): The logging probability of every logged action, in (0, 1].
+///     target_probs (List[float]): The target policy probability of every logged action.
+///     estimator (str, optional): "ips", "snips" or "dr". Default is "ips".
+///     reward_hat (List[float], optional): The reward model prediction for the logged action, required
+///         by "dr". Default is None.
+///     target_reward_hat (List[float], optional): The reward model prediction averaged over the target
+///         policy's actions, required by "dr". Default is None.
+///     clusters (List[str], optional): The cluster of every event. Default is None, which resamples events.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples, at least 2. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, (float, float)]: The estimated policy value, its bootstrap standard error and
+///         the percentile confidence interval.
+/// """
+pub fn off_policy_value(
+    py: Python<'_>,
+    rewards: Vec<f64>,
+    propensities: Vec<f64>,
+    target_probs: Vec<f64>,
+    estimator: &str,
+    reward_hat: Option<Vec<f64>>,
+    target_reward_hat: Option<Vec<f64>>,
+    clusters: Option<Vec<String>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, (f64, f64))> {
+    if n_resamples < 2 {
+        raise!(
+            InvalidInputError,
+            "n_resamples must be at least 2 for the bootstrap standard error"
+        )
+    }
+    check_level(confidence_level)?;
+    let n = rewards.len();
+    if n == 0 {
+        raise!(
+            InsufficientDataError,
+            "Cannot evaluate a policy on empty logs"
+        )
+    }
+    if propensities.len() != n || target_probs.len() != n {
+        raise!(
+            InvalidInputError,
+            "rewards, propensities and target_probs must have equal size"
+        )
+    }
+    if propensities.iter().any(|&p| !(p > 0.0 && p <= 1.0)) {
+        raise!(InvalidInputError, "propensities must be in (0, 1]")
+    }
+    if target_probs.iter().any(|p| !(0.0..=1.0).contains(p)) {
+        raise!(InvalidInputError, "target_probs must be in [0, 1]")
+    }
+    let weights: Vec<f64> = target_probs
+        .iter()
+        .zip(&propensities)
+        .map(|(t, p)| t / p)
+        .collect();
+    // Per event value of the numerator; the denominator is the event count, or the weight for "snips".
+    let (terms, self_normalized): (Vec<f64>, bool) = match estimator {
+        "ips" | "snips" => (
+            weights.iter().zip(&rewards).map(|(w, r)| w * r).collect(),
+            estimator == "snips",
+        ),
+        "dr" => {
+            let (reward_hat, target_reward_hat) = match (reward_hat, target_reward_hat) {
+                (Some(q), Some(v)) if q.len() == n && v.len() == n => (q, v),
+                (Some(_), Some(_)) => {
+                    raise!(
+                        InvalidInputError,
+                        "reward_hat and target_reward_hat must match rewards in size"
+                    )
+                }
+                _ => raise!(
+                    InvalidInputError,
+                    "estimator='dr' requires reward_hat and target_reward_hat"
+                ),
+            };
+            (
+                (0..n)
+                    .map(|i| target_reward_hat[i] + weights[i] * (rewards[i] - reward_hat[i]))
+                    .collect(),
+                false,
+            )
+        }
+        _ => raise!(
+            InvalidInputError,
+            "estimator must be 'ips', 'snips' or 'dr', got '{estimator}'"
+        ),
+    };
+
+    // (numerator, denominator) of every resampling unit.
+    let units: Vec<(f64, f64)> = match clusters {
+        Some(clusters) => {
+            if clusters.len() != n {
+                raise!(InvalidInputError, "clusters must have one label per event")
+            }
+            let mut sums: BTreeMap<String, (f64, f64)> = BTreeMap::new();
+            for (i, cluster) in clusters.into_iter().enumerate() {
+                let sum = sums.entry(cluster).or_default();
+                sum.0 += terms[i];
+                sum.1 += if self_normalized { weights[i] } else { 1.0 };
+            }
+            sums.into_values().collect()
+        }
+        None => (0..n)
+            .map(|i| (terms[i], if self_normalized { weights[i] } else { 1.0 }))
+            .collect(),
+    };
+    let value = |num: f64, den: f64| if den > 0.0 { num / den } else { f64::NAN };
+    let (num, den) = units
+        .iter()
+        .fold((0.0, 0.0), |acc, unit| (acc.0 + unit.0, acc.1 + unit.1));
+    let estimate = value(num, den);
+
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let distribution = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let (mut num, mut den) = (0.0, 0.0);
+            for _ in 0..units.len() {
+                let unit = units[rng.gen_range(0..units.len())];
+                num += unit.0;
+                den += unit.1;
+            }
+            value(num, den)
+        })
+    });
+    let mean = distribution.iter().sum::<f64>() / n_resamples as f64;
+    let std_error = (distribution.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+        / (n_resamples as f64 - 1.0))
+        .sqrt();
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let bounds = distribution.quantile(&[left_q, 1.0 - left_q]);
+    Ok((estimate, std_error, (bounds[0], bounds[1])))
+}
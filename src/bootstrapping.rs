@@ -1,59 +1,380 @@
+use crate::cache::*;
+use crate::diagnostics::*;
+use crate::errors::*;
+use crate::levels::*;
+use crate::result::*;
+use crate::rng::*;
 use crate::tools::*;
 use rand::prelude::*;
-use rand::SeedableRng;
 use rand_xoshiro::Xoshiro256PlusPlus;
 use rayon::prelude::*;
+use pylars_core::bootstrap::{double_bootstrap_mean, BootstrapOutput};
+use pylars_core::{
+    analytic_uplift_interval, bootstrap_statistic, bootstrap_statistic_sketch,
+    jackknife_acceleration, statistic_bootstrap_null, stratified_bootstrap_null, BootstrapCiMethod,
+    Statistic, UpliftCiMethod,
+};
 use pyo3::prelude::*;
-use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
-#[pyfunction(signature = (vec, n_resamples = 10_000))]
-#[pyo3(text_signature = "(vec, n_resamples=10000)")]
+/// Largest number of resample indices `bootstrap_vec` exports with `return_indices`.
+const MAX_EXPORTED_INDICES: u64 = 100_000_000;
+
+/// Bytes held per resample by a returned distribution: the float in Rust and its Python float and list
+/// slot.
+const DISTRIBUTION_BYTES: u64 = 8 + 32;
+
+/// Values kept per level of the sketch that replaces a distribution over `max_memory_mb`.
+const SKETCH_CAPACITY: usize = 4096;
+
+/// Number of equally spaced quantiles returned in place of a sketched distribution.
+const SKETCH_QUANTILES: usize = 1001;
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (vec, n_resamples = 10_000, statistic = None, confidence_level = 0.95, q = None, delta = None, n_blocks = None, trim = None, return_ = "distribution", return_indices = false, max_memory_mb = None, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(vec, n_resamples=10000, statistic='mean', confidence_level=0.95, q=None, delta=None, n_blocks=None, trim=None, return_='distribution', return_indices=False, max_memory_mb=None, random_state=None, seed=None, deterministic=None)")]
 /// """
-/// Performs bootstrap resampling on a vector of floating-point numbers, returning a distribution of sample means.
+/// Performs bootstrap resampling on a vector of floating-point numbers, returning the distribution of a
+/// statistic over the resamples, its percentile confidence interval, or both.
 ///
 /// Args:
 ///     vec (List[float]): The input vector of floats.
 ///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     statistic (str or Callable[[List[float]], float], optional): One of the statistics of `bootstrap_ci`
+///         ("mean", "median", "std", "var", "quantile", "huber_mean", "median_of_means", "trimmed_mean")
+///         or a function of a resample returning a float.
+///         Built-in statistics run in parallel without the GIL; a function is called under the GIL for
+///         every resample. Default is None, which is "mean".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     q (float, optional): The quantile level in [0, 1], required when statistic is "quantile". Default is None.
+///     delta (float, optional): The threshold of "huber_mean" in robust standard deviations. Default is
+///         None, which is 1.345.
+///     n_blocks (int, optional): The number of blocks of "median_of_means". Default is None, which is 10.
+///     trim (float, optional): The proportion in [0, 0.5) of "trimmed_mean" cut from each tail. Default
+///         is None, which is 0.1.
+///     return_ (str, optional): "distribution" for the resampled statistics, "ci" for the tuple of
+///         `bootstrap_ci`, or "both" for the two of them. Default is "distribution".
+///     return_indices (bool, optional): If True, also returns the positions in `vec` drawn by every
+///         resample, so that `[vec[i] for i in indices[k]]` is exactly resample k. This holds
+///         n_resamples * len(vec) integers and is meant for audits with modest n_resamples. Default is False.
+///     max_memory_mb (float, optional): The memory in megabytes the resampled statistics may take, about
+///         40 bytes per resample when the distribution is returned and 8 otherwise. Past the limit, the
+///         resamples are run in batches summarized into a quantile sketch with a rank error of about
+///         1e-4: the interval comes from the sketch, and the distribution is replaced by its 1001
+///         quantiles at 0, 0.001, ..., 1. Default is None, which keeps the whole distribution.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
 ///
 /// Returns:
-///     List[float]: A list of bootstrap sample means.
+///     List[float] | Tuple[float, float, float] | Tuple[List[float], Tuple[float, float, float]]:
+///         The resampled statistics, the `(estimate, low, high)` tuple, or both, depending on `return_`.
+///         With `max_memory_mb`, a tuple of that result and whether the distribution was sketched.
+///         With `return_indices`, a tuple of that result and the list of resample indices.
 /// """
-pub fn bootstrap_vec(vec: Vec<f64>, n_resamples: u64) -> Vec<f64> {
-    let len_vec = vec.len();
-    let dist = rand::distributions::Uniform::new(0, len_vec);
-
-    (0..n_resamples)
-        .into_par_iter()
-        .map(|i| {
-            let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
-            let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
-            let mut sum = 0.0;
-            for _ in 0..len_vec {
-                let idx = dist.sample(&mut rng);
-                unsafe {
-                    sum += *vec.get_unchecked(idx);
-                }
+pub fn bootstrap_vec(
+    py: Python<'_>,
+    vec: Vec<f64>,
+    n_resamples: u64,
+    statistic: Option<&Bound<'_, PyAny>>,
+    confidence_level: f64,
+    q: Option<f64>,
+    delta: Option<f64>,
+    n_blocks: Option<usize>,
+    trim: Option<f64>,
+    return_: &str,
+    return_indices: bool,
+    max_memory_mb: Option<f64>,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PyObject> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    if max_memory_mb.is_some_and(|mb| mb.is_nan() || mb <= 0.0) {
+        raise!(InvalidInputError, "max_memory_mb must be positive")
+    }
+    if return_indices && n_resamples.saturating_mul(vec.len() as u64) > MAX_EXPORTED_INDICES {
+        raise!(BudgetExceededError, "return_indices would export more than {MAX_EXPORTED_INDICES} indices, use fewer resamples")
+    }
+    let (with_distribution, with_ci) = match return_ {
+        "distribution" => (true, false),
+        "ci" => (false, true),
+        "both" => (true, true),
+        _ => raise!(InvalidInputError, "return_ must be one of 'distribution', 'ci' or 'both'"),
+    };
+    let bytes_per_resample = if with_distribution { DISTRIBUTION_BYTES } else { 8 };
+    let memory_limit = max_memory_mb.map(|mb| (mb * 1_048_576.0) as u64);
+    // Half of the limit goes to the batch of resampled statistics while the sketch is filled.
+    let batch_size = memory_limit
+        .filter(|&limit| n_resamples.saturating_mul(bytes_per_resample) > limit)
+        .map(|limit| limit / 16);
+    let mut sketch = None;
+    let (distribution, estimate) = match statistic {
+        Some(callable) if !callable.is_instance_of::<pyo3::types::PyString>() => {
+            if !callable.is_callable() {
+                raise!(InvalidInputError, "statistic must be a string or a callable")
+            }
+            if q.is_some() || delta.is_some() || n_blocks.is_some() || trim.is_some() {
+                raise!(InvalidInputError, "q, delta, n_blocks and trim only apply to the built-in statistics, not to a callable")
             }
-            sum / len_vec as f64
+            let len_vec = vec.len();
+            let sampler = IndexSampler::new([vec.as_slice()]).or_raise()?;
+            let function = callable.clone().unbind();
+            let draw = |rng: &mut Xoshiro256PlusPlus| {
+                let resample: Vec<f64> = (0..len_vec).map(|_| sampler.draw(rng)[0]).collect();
+                Python::with_gil(|py| function.call1(py, (resample,))?.extract::<f64>(py))
+            };
+            let distribution = match batch_size {
+                Some(batch_size) => {
+                    let mut summary = QuantileSketch::new(SKETCH_CAPACITY);
+                    let mut error = None;
+                    py.allow_threads(|| {
+                        resampler.for_each_batch(n_resamples, batch_size, draw, |batch| {
+                            for value in batch {
+                                match value {
+                                    Ok(value) => summary.push(value),
+                                    Err(err) => {
+                                        error.get_or_insert(err);
+                                    }
+                                }
+                            }
+                        })
+                    });
+                    if let Some(err) = error {
+                        return Err(err);
+                    }
+                    sketch = Some(summary);
+                    Vec::new()
+                }
+                None => py
+                    .allow_threads(|| resampler.map(n_resamples, draw))
+                    .into_iter()
+                    .collect::<PyResult<Vec<f64>>>()?,
+            };
+            let estimate = if with_ci {
+                callable.call1((vec.clone(),))?.extract::<f64>()?
+            } else {
+                f64::NAN
+            };
+            (distribution, estimate)
+        }
+        _ => {
+            let name: String = match statistic {
+                Some(name) => name.extract()?,
+                None => "mean".to_string(),
+            };
+            let statistic = Statistic::parse(&name, q)
+                .and_then(|statistic| statistic.with_tuning(delta, n_blocks, trim))
+                .or_raise()?;
+            let distribution = match batch_size {
+                Some(batch_size) => {
+                    sketch = Some(
+                        py.allow_threads(|| {
+                            bootstrap_statistic_sketch(
+                                &vec,
+                                statistic,
+                                n_resamples,
+                                batch_size,
+                                SKETCH_CAPACITY,
+                                &resampler,
+                            )
+                        })
+                        .or_raise()?,
+                    );
+                    Vec::new()
+                }
+                None => py
+                    .allow_threads(|| bootstrap_statistic(&vec, statistic, n_resamples, &resampler))
+                    .or_raise()?,
+            };
+            (distribution, statistic.compute(&vec))
+        }
+    };
+    let quantile = |levels: &[f64]| match &sketch {
+        Some(sketch) => sketch.quantile(levels),
+        None => distribution.quantile(levels),
+    };
+    let ci = with_ci.then(|| {
+        let left_q = (1.0 - confidence_level) / 2.0;
+        let bounds = quantile(&[left_q, 1.0 - left_q]);
+        (estimate, bounds[0], bounds[1])
+    });
+    let sketched = sketch.is_some();
+    let distribution = if sketched && with_distribution {
+        let levels: Vec<f64> = (0..SKETCH_QUANTILES)
+            .map(|k| k as f64 / (SKETCH_QUANTILES - 1) as f64)
+            .collect();
+        quantile(&levels)
+    } else {
+        distribution
+    };
+    let result = match (with_distribution, ci) {
+        (true, None) => distribution.into_pyobject(py)?.into_any().unbind(),
+        (false, Some(ci)) => ci.into_pyobject(py)?.into_any().unbind(),
+        (_, ci) => (distribution, ci).into_pyobject(py)?.into_any().unbind(),
+    };
+    let result = match max_memory_mb {
+        Some(_) => (result, sketched).into_pyobject(py)?.into_any().unbind(),
+        None => result,
+    };
+    if !return_indices {
+        return Ok(result);
+    }
+    // Replays the draws of every resample: the generators and the number of draws are the same as above.
+    let sampler = IndexSampler::new([vec.as_slice()]).or_raise()?;
+    let indices: Vec<Vec<usize>> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            (0..vec.len()).map(|_| sampler.draw_index(rng)).collect()
         })
-        .collect()
+    });
+    Ok((result, indices).into_pyobject(py)?.into_any().unbind())
 }
 
-#[pyfunction(signature = (args, confidence_level = 0.95, n_resamples = 10_000, ind = true, two_sided = true))]
-#[pyo3(text_signature = "(args, confidence_level=0.95, n_resamples=10000, ind=True, two_sided=True)")]
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (vec, statistic = "mean", confidence_level = 0.95, n_resamples = 10_000, q = None, delta = None, n_blocks = None, trim = None, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(vec, statistic='mean', confidence_level=0.95, n_resamples=10000, q=None, delta=None, n_blocks=None, trim=None, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Computes a percentile bootstrap confidence interval for a statistic of a single sample.
+///
+/// Equivalent to `bootstrap_vec(vec, statistic=statistic, return_="ci")` for the built-in statistics.
+///
+/// Args:
+///     vec (List[float]): The input vector of floats.
+///     statistic (str, optional): The statistic, one of "mean", "median", "std", "var" (sample standard
+///         deviation and variance, with n - 1 in the denominator), "quantile", "huber_mean" (Huber
+///         M-estimate of location), "median_of_means" (median of the means of blocks of consecutive
+///         observations) or "trimmed_mean" (mean without the smallest and largest values). Default is
+///         "mean".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     q (float, optional): The quantile level in [0, 1], required when statistic is "quantile". Default is None.
+///     delta (float, optional): The threshold of "huber_mean" in robust standard deviations. Default is
+///         None, which is 1.345.
+///     n_blocks (int, optional): The number of blocks of "median_of_means". Default is None, which is 10.
+///     trim (float, optional): The proportion in [0, 0.5) of "trimmed_mean" cut from each tail, as in
+///         `scipy.stats.trim_mean`. Default is None, which is 0.1.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float]:
+///         A tuple containing:
+///         - estimate (float): The statistic of `vec`.
+///         - low (float): The lower bound of the confidence interval.
+///         - high (float): The upper bound of the confidence interval.
+/// """
+pub fn bootstrap_ci(
+    py: Python<'_>,
+    vec: Vec<f64>,
+    statistic: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    q: Option<f64>,
+    delta: Option<f64>,
+    n_blocks: Option<usize>,
+    trim: Option<f64>,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, f64)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let statistic = Statistic::parse(statistic, q)
+        .and_then(|statistic| statistic.with_tuning(delta, n_blocks, trim))
+        .or_raise()?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let distribution = py
+        .allow_threads(|| bootstrap_statistic(&vec, statistic, n_resamples, &resampler))
+        .or_raise()?;
+    let bounds = distribution.quantile(&[left_q, right_q]);
+    Ok((statistic.compute(&vec), bounds[0], bounds[1]))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, ind = true, two_sided = true, bias_corrected = false, n_inner_resamples = 0, uplift_ci_method = "ratio", ci_method = "percentile", winsorize = None, estimand = "ratio_of_sums", statistic = "mean", delta = None, n_blocks = None, accumulator = "f64", max_seconds = None, timings = false, return_object = false, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(args, confidence_level=0.95, n_resamples=10000, ind=True, two_sided=True, bias_corrected=False, n_inner_resamples=0, uplift_ci_method='ratio', ci_method='percentile', winsorize=None, estimand='ratio_of_sums', statistic='mean', delta=None, n_blocks=None, accumulator='f64', max_seconds=None, timings=False, return_object=False, random_state=None, seed=None, deterministic=None)")]
 /// """
 /// Performs a bootstrap analysis to evaluate the statistical significance of the difference in means 
 /// (or mean ratios) between two or four sets of samples.
 ///
+/// Constant inputs: when the first group's mean (or ratio) is 0, the uplift is undefined and the p-value,
+/// uplift and interval are NaN. When both groups are constant, every resample gives the same uplift and
+/// the p-value is the exact one of a known difference: 1.0 if the groups are equal (0.5 one-sided), 0.0
+/// otherwise.
+///
 /// Args:
 ///     args (List[List[float]]): A list containing either two or four lists of floats.
 ///         If two are provided, they represent two independent samples to compare.
 ///         If four are provided, they represent two pairs of (numerator, denominator) data to compare ratios.
-///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     confidence_level (float or List[float], optional): The confidence level for the interval, or a list of
+///         levels to get one interval per level from the same resamples. Default is 0.95.
 ///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
 ///     ind (bool, optional): If True, samples are treated as independent. If False, samples are treated as paired. Default is True.
 ///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     bias_corrected (bool, optional): If True, the returned uplift is bias-corrected as
+///         `2 * uplift - mean(resampled uplifts)`, which matters for nonlinear statistics such as ratios
+///         in small samples. Default is False.
+///     n_inner_resamples (int, optional): If positive (and `bias_corrected` is True), applies the
+///         double-bootstrap correction `3 * uplift - 3 * mean(resampled) + mean(inner resampled)` using
+///         this many inner resamples per outer resample. The cost grows by this factor. Default is 0.
+///     uplift_ci_method (str, optional): The construction of the uplift interval: "ratio" for the
+///         percentile interval of the resampled uplifts, "fieller" for the Fieller interval of the ratio of
+///         the group statistics, or "log" for the delta-method interval of the log ratio, transformed back.
+///         The analytic methods stay stable when the resampled ratios are heavy-tailed, and raise an error
+///         when the first-group statistic is within the interval's margin of zero, where the uplift is
+///         unbounded. Default is "ratio".
+///     ci_method (str, optional): The construction of the "ratio" interval from the resampled uplifts:
+///         "percentile" for their quantiles, "basic" for the quantiles reflected around the observed
+///         uplift, or "bca" for the bias-corrected and accelerated interval, whose quantile levels are
+///         shifted by the share of resampled uplifts below the observed one and by the skewness of the
+///         jackknifed uplift, computed from the group totals in linear time. BCa corrects the interval
+///         of skewed, biased statistics such as the uplift of small or heavy-tailed samples, and
+///         requires the mean statistic. When the acceleration is so large that the adjustment is
+///         undefined, it warns and returns the percentile interval. Default is "percentile".
+///     winsorize (float, optional): A quantile level, e.g. 0.99, above which the pooled units of both
+///         groups are capped before the test. With four lists the per-unit ratios num / den are capped
+///         by lowering the numerator, so every unit's pair stays consistent, which cannot be done after
+///         aggregating the pairs. Default is None, which keeps the data as is.
+///     estimand (str, optional): With four lists, the target quantity of every group: "ratio_of_sums"
+///         for sum(num) / sum(den), resampling the (num, den) pairs, or "mean_of_ratios" for the mean of
+///         the per-unit ratios num / den, resampling the ratios (units with a zero denominator are left
+///         out, with a warning). Default is "ratio_of_sums".
+///     statistic (str, optional): The statistic compared between two samples: "mean", or an
+///         outlier-robust location for adversarially heavy-tailed metrics such as payments with fraud
+///         spikes: "huber_mean" (Huber M-estimate) or "median_of_means" (median of the means of blocks of
+///         consecutive observations, so shuffle ordered data first); "median", "std", "var",
+///         "trimmed_mean" (cutting 10% from each tail) and "quantile" are also accepted. A statistic
+///         other than the mean resamples and recomputes it on every resample, without
+///         bias_corrected, analytic uplift intervals or accumulators, and reports its values in
+///         place of mean_1 and mean_2. Default is "mean".
+///     delta (float, optional): The threshold of "huber_mean" in robust standard deviations
+///         (1.4826 MAD); smaller values downweight more observations. Default is None, which is 1.345.
+///     n_blocks (int, optional): The number of blocks of "median_of_means". Default is None, which is 10.
+///     accumulator (str, optional): The summation of the resampled sums: "f64" for plain addition,
+///         "kahan" for compensated summation or "f128-emulated" for double-double summation (about 32
+///         significant digits). Plain addition loses about log10(n) digits over n values, which shows on
+///         large values such as revenue in cents over millions of rows; "kahan" makes the error
+///         independent of n at a small cost, "f128-emulated" is slower still. Default is "f64".
+///     max_seconds (float, optional): A soft limit on the running time. The resamples run in batches and
+///         stop after the first batch that ends past the limit, with a RuntimeWarning reporting the number
+///         of resamples actually used. Default is None, which runs all `n_resamples`.
+///     timings (bool, optional): If True, also returns a dictionary of the seconds spent converting
+///         `args` from Python ("conversion"), resampling ("resampling") and sorting the resampled
+///         statistics into the p-value and intervals ("quantiles"), and of the number of threads
+///         ("threads"), to tell whether the transfer of the data or the computation dominates.
+///         Default is False.
+///     return_object (bool, optional): If True, returns a `BootstrapResult` with the fields of the tuple as
+///         named attributes instead of the tuple. Default is False.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
 ///
 /// Returns:
 ///     Tuple[float, float, float, float, (float, float)]:
@@ -61,189 +382,206 @@ pub fn bootstrap_vec(vec: Vec<f64>, n_resamples: u64) -> Vec<f64> {
 ///         - p_value (float): The p-value for the test (two-sided or one-sided depending on `two_sided`).
 ///         - mean_1 (float): The mean (or ratio) of the first dataset.
 ///         - mean_2 (float): The mean (or ratio) of the second dataset.
-///         - uplift (float): The observed difference uplift in means or ratios (mean_2 - mean_1) / mean_1.
-///         - (float, float): The confidence interval bounds for the uplift.
+///         - uplift (float): The observed difference uplift in means or ratios (mean_2 - mean_1) / mean_1,
+///           bias-corrected if `bias_corrected` is True.
+///         - (float, float): The confidence interval bounds for the uplift, or a list of them when
+///           `confidence_level` is a list.
+///         With `return_object`, a `BootstrapResult` in place of the tuple.
+///         With `timings`, a tuple of that result and the dictionary of timings.
 /// """
 pub fn bootstrap(
-    args: Vec<Vec<f64>>,
-    confidence_level: f64,
+    py: Python<'_>,
+    args: &Bound<'_, PyAny>,
+    confidence_level: ConfidenceLevel,
     n_resamples: u64,
     ind: bool,
     two_sided: bool,
-) -> (f64, f64, f64, f64, (f64, f64)) {
-    let left_q = (1.0 - confidence_level) / 2.0;
-    let right_q = 1.0 - left_q;
-    let (uplift_diffs, mean_1, mean_2, uplift): (Vec<f64>, f64, f64, f64) = match args.len() {
-        2 => {
-            let len_vec_1 = args[0].len();
-            let len_vec_2 = args[1].len();
-            if !ind && len_vec_1 != len_vec_2 {
-                panic!("For non ind test all arrays must have same size")
-            }
-            let (mean_1, mean_2): (f64, f64) = (
-                args[0].iter().sum::<f64>() / len_vec_1 as f64,
-                args[1].iter().sum::<f64>() / len_vec_2 as f64,
-            );
-            let uplift = calculate_uplift(mean_1, mean_2);
-            let min_len = len_vec_1.min(len_vec_2);
-            let dist_1 = rand::distributions::Uniform::new(0, len_vec_1);
-            let dist_2 = rand::distributions::Uniform::new(0, len_vec_2);
-
-            let uplift_diffs: Vec<f64> = (0..n_resamples)
-                .into_par_iter()
-                .map(|i| {
-                    let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
-                    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
-
-                    let mut sum_vec_1 = 0.0;
-                    let mut sum_vec_2 = 0.0;
-                    if ind {
-                        for _ in 0..min_len {
-                            let idx_1 = dist_1.sample(&mut rng);
-                            let idx_2 = dist_2.sample(&mut rng);
-                            unsafe {
-                                sum_vec_1 += *args[0].get_unchecked(idx_1);
-                                sum_vec_2 += *args[1].get_unchecked(idx_2);
-                            }
-                        }
-                        match len_vec_1.cmp(&len_vec_2) {
-                            Ordering::Greater => {
-                                for _ in 0..(len_vec_1 - len_vec_2) {
-                                    let idx_1 = dist_1.sample(&mut rng);
-                                    unsafe {
-                                        sum_vec_1 += *args[0].get_unchecked(idx_1);
-                                    }
-                                }
-                            }
-                            Ordering::Less => {
-                                for _ in 0..(len_vec_2 - len_vec_1) {
-                                    let idx_2 = dist_2.sample(&mut rng);
-                                    unsafe {
-                                        sum_vec_2 += *args[1].get_unchecked(idx_2);
-                                    }
-                                }
-                            }
-                            Ordering::Equal => {}
-                        }
-                    } else {
-                        for _ in 0..min_len {
-                            let idx_1 = dist_1.sample(&mut rng);
-                            unsafe {
-                                sum_vec_1 += *args[0].get_unchecked(idx_1);
-                                sum_vec_2 += *args[1].get_unchecked(idx_1);
-                            }
-                        }
-                    }
-                    let mean_1 = sum_vec_1 / len_vec_1 as f64;
-                    let mean_2 = sum_vec_2 / len_vec_2 as f64;
-                    calculate_uplift(mean_1, mean_2)
-                })
-                .collect();
-            (uplift_diffs, mean_1, mean_2, uplift)
+    bias_corrected: bool,
+    n_inner_resamples: u64,
+    uplift_ci_method: &str,
+    ci_method: &str,
+    winsorize: Option<f64>,
+    estimand: &str,
+    statistic: &str,
+    delta: Option<f64>,
+    n_blocks: Option<usize>,
+    accumulator: &str,
+    max_seconds: Option<f64>,
+    timings: bool,
+    return_object: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PyObject> {
+    check_resamples(n_resamples)?;
+    let resampler =
+        new_resampler(random_state, seed, deterministic)?.with_deadline(deadline(max_seconds)?);
+    let mut breakdown = Timings::default();
+    let args: Vec<Vec<f64>> = Timings::time(&mut breakdown.conversion, || args.extract())?;
+    let uplift_ci_method = UpliftCiMethod::parse(uplift_ci_method).or_raise()?;
+    let ci_method = BootstrapCiMethod::parse(ci_method).or_raise()?;
+    let accumulator = Accumulator::parse(accumulator).or_raise()?;
+    let statistic = Statistic::parse(statistic, None)
+        .and_then(|statistic| statistic.with_tuning(delta, n_blocks, None))
+        .or_raise()?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let winsorized = winsorize
+        .map(|level| winsorize_columns(&columns, level).or_raise())
+        .transpose()?;
+    let columns: Vec<&[f64]> = match &winsorized {
+        Some(capped) => capped.iter().map(Vec::as_slice).collect(),
+        None => columns,
+    };
+    let ratios = match Estimand::parse(estimand).or_raise()? {
+        Estimand::RatioOfSums => None,
+        Estimand::MeanOfRatios => {
+            let (ratios, dropped) = unit_ratio_columns(&columns).or_raise()?;
+            check_zero_denominators(py, dropped, columns[0].len() + columns[2].len())?;
+            Some(ratios)
         }
-        4 => {
-            let vec_sizes: Vec<usize> = args.iter().map(|vec| vec.len()).collect();
-            if !ind {
-                if !(vec_sizes[0] == vec_sizes[1]
-                    && vec_sizes[2] == vec_sizes[3]
-                    && vec_sizes[0] == vec_sizes[2])
-                {
-                    panic!("For non ind test all arrays must have same size")
-                }
-            } else if vec_sizes[0] != vec_sizes[1] || vec_sizes[2] != vec_sizes[3] {
-                panic!("Each pair of arrays must be of equal length.");
-            }
-            let (mean_1, mean_2): (f64, f64) = (
-                args[0].iter().sum::<f64>() / args[1].iter().sum::<f64>(),
-                args[2].iter().sum::<f64>() / args[3].iter().sum::<f64>(),
-            );
-            let uplift = calculate_uplift(mean_1, mean_2);
-            let dist_1 = rand::distributions::Uniform::new(0, vec_sizes[0]);
-            let dist_2 = rand::distributions::Uniform::new(0, vec_sizes[2]);
-            let min_len = vec_sizes[0].min(vec_sizes[2]);
-            let uplift_diffs: Vec<f64> = (0..n_resamples)
-                .into_par_iter()
-                .map(|i| {
-                    let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
-                    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
-
-                    let mut sum_num_1 = 0.0;
-                    let mut sum_denum_1 = 0.0;
-                    let mut sum_num_2 = 0.0;
-                    let mut sum_denum_2 = 0.0;
-                    if ind {
-                        for _ in 0..min_len {
-                            let idx_1 = dist_1.sample(&mut rng);
-                            let idx_2 = dist_2.sample(&mut rng);
-                            unsafe {
-                                sum_num_1 += *args[0].get_unchecked(idx_1);
-                                sum_denum_1 += *args[1].get_unchecked(idx_1);
-                                sum_num_2 += *args[2].get_unchecked(idx_2);
-                                sum_denum_2 += *args[3].get_unchecked(idx_2);
-                            }
-                        }
-                        match vec_sizes[0].cmp(&vec_sizes[2]) {
-                            Ordering::Greater => {
-                                for _ in 0..(vec_sizes[0] - vec_sizes[2]) {
-                                    let idx_1 = dist_1.sample(&mut rng);
-                                    unsafe {
-                                        sum_num_1 += *args[0].get_unchecked(idx_1);
-                                        sum_denum_1 += *args[1].get_unchecked(idx_1);
-                                    }
-                                }
-                            }
-                            Ordering::Less => {
-                                for _ in 0..(vec_sizes[2] - vec_sizes[0]) {
-                                    let idx_2 = dist_2.sample(&mut rng);
-                                    unsafe {
-                                        sum_num_2 += *args[2].get_unchecked(idx_2);
-                                        sum_denum_2 += *args[3].get_unchecked(idx_2);
-                                    }
-                                }
-                            }
-                            Ordering::Equal => {}
-                        }
-                    } else {
-                        for _ in 0..min_len {
-                            let idx_1 = dist_1.sample(&mut rng);
-                            unsafe {
-                                sum_num_1 += *args[0].get_unchecked(idx_1);
-                                sum_denum_1 += *args[1].get_unchecked(idx_1);
-                                sum_num_2 += *args[2].get_unchecked(idx_1);
-                                sum_denum_2 += *args[3].get_unchecked(idx_1);
-                            }
-                        }
-                    }
-                    let mean_1 = sum_num_1 / sum_denum_1;
-                    let mean_2 = sum_num_2 / sum_denum_2;
-                    calculate_uplift(mean_1, mean_2)
-                })
-                .collect();
+    };
+    let columns: Vec<&[f64]> = match &ratios {
+        Some(ratios) => ratios.iter().map(Vec::as_slice).collect(),
+        None => columns,
+    };
+    let result = bootstrap_levels(
+        py,
+        &columns,
+        &confidence_level,
+        n_resamples,
+        ind,
+        two_sided,
+        bias_corrected,
+        n_inner_resamples,
+        uplift_ci_method,
+        ci_method,
+        statistic,
+        accumulator,
+        &resampler,
+        timings.then_some(&mut breakdown),
+    )?;
+    let result = if return_object {
+        Bound::new(py, BootstrapResult::new(py, result, &confidence_level)?)?.into_any()
+    } else {
+        result.into_pyobject(py)?.into_any()
+    };
+    if !timings {
+        return Ok(result.unbind());
+    }
+    Ok((result, breakdown.into_dict(py)?)
+        .into_pyobject(py)?
+        .into_any()
+        .unbind())
+}
 
-            (uplift_diffs, mean_1, mean_2, uplift)
+/// `bootstrap` on borrowed columns, summarizing one resampling run at every level. With `timings`, the
+/// durations of the run and of the summaries are added to it.
+#[allow(clippy::too_many_arguments)]
+pub fn bootstrap_levels(
+    py: Python<'_>,
+    columns: &[&[f64]],
+    confidence_level: &ConfidenceLevel,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    bias_corrected: bool,
+    n_inner_resamples: u64,
+    uplift_ci_method: UpliftCiMethod,
+    ci_method: BootstrapCiMethod,
+    statistic: Statistic,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+    timings: Option<&mut Timings>,
+) -> PyResult<BootstrapLevels> {
+    if ci_method != BootstrapCiMethod::Percentile && uplift_ci_method != UpliftCiMethod::Ratio {
+        raise!(InvalidInputError, "ci_method only applies to the 'ratio' uplift interval")
+    }
+    if ci_method == BootstrapCiMethod::Bca && statistic != Statistic::Mean {
+        raise!(InvalidInputError, "ci_method 'bca' requires the mean statistic")
+    }
+    let started = Instant::now();
+    let null = if statistic == Statistic::Mean {
+        py.allow_threads(|| cached_bootstrap_null(columns, n_resamples, ind, accumulator, resampler))
+            .or_raise()?
+    } else {
+        if bias_corrected || uplift_ci_method != UpliftCiMethod::Ratio || accumulator != Accumulator::F64 {
+            raise!(
+                InvalidInputError,
+                "bias_corrected, uplift_ci_method and accumulator only apply to the mean statistic"
+            )
         }
-        _ => {
-            panic!("Input must contain either 2 or 4 vectors.");
+        Arc::new(
+            py.allow_threads(|| statistic_bootstrap_null(columns, n_resamples, ind, statistic, resampler))
+                .or_raise()?,
+        )
+    };
+    warn_truncated(py, null.uplift_diffs.len(), n_resamples)?;
+    check_groups(py, columns)?;
+    let mean_inner = (bias_corrected && n_inner_resamples > 0).then(|| {
+        py.allow_threads(|| double_bootstrap_mean(columns, ind, n_resamples, n_inner_resamples, resampler))
+    });
+    let resampled = Instant::now();
+    let summaries: Vec<BootstrapOutput> = confidence_level
+        .levels()?
+        .into_iter()
+        .map(|level| null.summarize(level, two_sided, bias_corrected, mean_inner))
+        .collect();
+    let (p_value, mean_1, mean_2, uplift, _) = summaries[0];
+    let intervals: Vec<(f64, f64)> = match uplift_ci_method {
+        UpliftCiMethod::Ratio => {
+            let percentile: Vec<(f64, f64)> =
+                summaries.into_iter().map(|summary| summary.4).collect();
+            let acceleration = match ci_method {
+                BootstrapCiMethod::Bca => {
+                    py.allow_threads(|| jackknife_acceleration(columns, ind)).or_raise()?
+                }
+                _ => 0.0,
+            };
+            if ci_method == BootstrapCiMethod::Bca
+                && confidence_level.levels()?.into_iter().any(|level| !null.bca_defined(level, acceleration))
+            {
+                warn(py, "the BCa adjustment is undefined for this acceleration and confidence level, the percentile interval is returned instead")?;
+            }
+            let intervals: Vec<(f64, f64)> = match ci_method {
+                BootstrapCiMethod::Percentile => percentile.clone(),
+                method => confidence_level
+                    .levels()?
+                    .into_iter()
+                    .map(|level| null.interval(level, method, acceleration))
+                    .collect(),
+            };
+            // The basic interval reflects the percentile quantiles, whose resolution is the one to check.
+            let quantiles = match ci_method {
+                BootstrapCiMethod::Bca => &intervals,
+                _ => &percentile,
+            };
+            check_interval(py, &null.uplift_diffs, widest(quantiles))?;
+            intervals
         }
+        method => confidence_level
+            .levels()?
+            .into_iter()
+            .map(|level| analytic_uplift_interval(columns, ind, level, method).or_raise())
+            .collect::<PyResult<_>>()?,
     };
-    let p: f64 =
-        (uplift_diffs.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
-    let p_value = (2.0 - 2.0 * p).min(p * 2.0);
-    let q = uplift_diffs.quantile(&[left_q, right_q]);
-    (
-        if two_sided { p_value } else { p },
+    if let Some(timings) = timings {
+        timings.resampling += (resampled - started).as_secs_f64();
+        timings.quantiles += resampled.elapsed().as_secs_f64();
+    }
+    Ok((
+        p_value,
         mean_1,
         mean_2,
         uplift,
-        (q[0], q[1]),
-    )
+        confidence_level.intervals(py, intervals)?,
+    ))
 }
 
-
-
-#[pyfunction(signature = (a_value, a_strat, b_value, b_strat, n_resamples = 10_000, confidence_level = 0.95, two_sided = true))]
-#[pyo3(text_signature = "(a_value, a_strat, b_value, b_strat, n_resamples=10000, confidence_level=0.95, two_sided=True)")]
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a_value, a_strat, b_value, b_strat, n_resamples = 10_000, confidence_level = 0.95, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(a_value, a_strat, b_value, b_strat, n_resamples=10000, confidence_level=0.95, two_sided=True, random_state=None, seed=None, deterministic=None)")]
 pub fn stratified_bootstrap(
+    py: Python<'_>,
     a_value: Vec<f64>,
     a_strat: Vec<String>,
     b_value: Vec<f64>,
@@ -251,8 +589,14 @@ pub fn stratified_bootstrap(
     n_resamples: u64,
     confidence_level: f64,
     two_sided: bool,
-) -> (f64, f64, f64, f64, (f64, f64))
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapOutput>
 {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
     let left_q = (1.0 - confidence_level) / 2.0;
     let right_q = 1.0 - left_q;
 
@@ -263,8 +607,13 @@ pub fn stratified_bootstrap(
     let b_len = b_value.len();
 
     if a_len != a_strat.len() || b_len != b_strat.len() || a_len != b_len {
-        panic!("All arrays must have equal size")
+        raise!(InvalidInputError, "All arrays must have equal size")
+    }
+    if a_len == 0 {
+        raise!(InsufficientDataError, "Both samples must be non-empty")
     }
+    check_no_nan("a_value", &a_value)?;
+    check_no_nan("b_value", &b_value)?;
 
     for (value, category) in a_value.iter().zip(a_strat.iter()) {
         a_groups
@@ -279,70 +628,771 @@ pub fn stratified_bootstrap(
             .push(*value);
     }
 
-    let a_mean: f64 = a_groups
-        .par_iter()
-        .map(|(_, value)| value.iter().sum::<f64>() / a_len as f64)
-        .sum();
-    let b_mean: f64 = b_groups
-        .par_iter()
-        .map(|(_, value)| value.iter().sum::<f64>() / b_len as f64)
-        .sum();
+    // Strata are reduced in key order, hashing order would make the sum irreproducible.
+    let stratified_mean = |groups: &HashMap<String, Vec<f64>>, len: usize| -> f64 {
+        let mut strata: Vec<(&String, &Vec<f64>)> = groups.iter().collect();
+        strata.sort_unstable_by_key(|(category, _)| *category);
+        let means: Vec<f64> = strata
+            .par_iter()
+            .map(|(_, value)| value.iter().sum::<f64>() / len as f64)
+            .collect();
+        fixed_order_sum(&means)
+    };
+    let a_mean = stratified_mean(&a_groups, a_len);
+    let b_mean = stratified_mean(&b_groups, b_len);
 
     let uplift = calculate_uplift(a_mean, b_mean);
     let mut all_categories: Vec<_> = a_groups.keys().cloned().collect();
     all_categories.sort();
-
-    let mut groups_dist = Vec::new();
-    for category in &all_categories {
-        let data = a_groups.get(category).map(Vec::as_slice).unwrap_or(&[]);
-        let len = data.len();
-        groups_dist.push((
-            category.clone(),
-            len,
-            rand::distributions::Uniform::new(0, len),
-        ));
+    if b_groups.len() != all_categories.len() || all_categories.iter().any(|c| !b_groups.contains_key(c)) {
+        raise!(InvalidInputError, "Both groups must contain the same strata")
     }
-    let uplift_diffs: Vec<f64> = (0..n_resamples)
-        .into_par_iter()
-        .map(|i| {
-            let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
-            let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
-
-            let mut mean_a = 0.0;
-            let mut mean_b = 0.0;
-            for (category, len, dist) in &groups_dist {
-                let part_sum_a: f64 = (0..*len)
-                    .map(|_| unsafe {
-                        a_groups
-                            .get(category)
-                            .unwrap()
-                            .get_unchecked(dist.sample(&mut rng))
-                    })
-                    .sum();
-                let part_sum_b: f64 = (0..*len)
-                    .map(|_| unsafe {
-                        b_groups
-                            .get(category)
-                            .unwrap()
-                            .get_unchecked(dist.sample(&mut rng))
-                    })
-                    .sum();
-                mean_a += part_sum_a / a_len as f64;
-                mean_b += part_sum_b / b_len as f64;
-            }
-            calculate_uplift(mean_a, mean_b)
-        })
-        .collect();
+
+    let strata_a: Vec<&[f64]> = all_categories.iter().map(|c| a_groups[c].as_slice()).collect();
+    let strata_b: Vec<&[f64]> = all_categories.iter().map(|c| b_groups[c].as_slice()).collect();
+    let uplift_diffs: Vec<f64> = py
+        .allow_threads(|| stratified_bootstrap_null(&strata_a, &strata_b, n_resamples, &resampler))
+        .or_raise()?;
 
     let p: f64 =
         (uplift_diffs.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
     let p_value = (2.0 - 2.0 * p).min(p * 2.0);
     let q = uplift_diffs.quantile(&[left_q, right_q]);
-    (
+    Ok((
         if two_sided { p_value } else { p },
         a_mean,
         b_mean,
         uplift,
         (q[0], q[1]),
-    )
+    ))
+}
+
+/// `shape_bootstrap` output: the skewness and the excess kurtosis, each with its interval.
+type ShapeOutput = (f64, (f64, f64), f64, (f64, f64));
+
+#[pyfunction(signature = (vec, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(vec, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Bootstraps the sample skewness and excess kurtosis, which helps deciding between parametric and
+/// resampling tests for a metric.
+///
+/// The shape of a constant sample is undefined and raises InsufficientDataError; resamples that happen
+/// to be constant are left out of the intervals, with a warning when they are many.
+///
+/// Args:
+///     vec (List[float]): The input vector of floats.
+///     confidence_level (float, optional): The confidence level for the intervals. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, (float, float), float, (float, float)]:
+///         A tuple containing:
+///         - skewness (float): The (biased) sample skewness.
+///         - (float, float): The confidence interval bounds for the skewness.
+///         - kurtosis (float): The (biased) sample excess kurtosis.
+///         - (float, float): The confidence interval bounds for the kurtosis.
+/// """
+pub fn shape_bootstrap(
+    py: Python<'_>,
+    vec: Vec<f64>,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<ShapeOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let len_vec = vec.len();
+    if len_vec < 2 {
+        raise!(InsufficientDataError, "At least 2 observations are required")
+    }
+    if is_constant(&vec) {
+        raise!(
+            InsufficientDataError,
+            "The sample is constant, its skewness and kurtosis are undefined"
+        )
+    }
+    let sampler = IndexSampler::new([vec.as_slice()]).or_raise()?;
+
+    let (skews, kurts): (Vec<f64>, Vec<f64>) = py.allow_threads(|| {
+        resampler
+            .map(n_resamples, |rng| {
+                let resample: Vec<f64> = (0..len_vec)
+                    .map(|_| sampler.draw(rng)[0])
+                    .collect();
+                let (_, m2, m3, m4) = central_moments(&resample);
+                (m3 / m2.powf(1.5), m4 / (m2 * m2) - 3.0)
+            })
+            .into_iter()
+            .unzip()
+    });
+    // Constant resamples have undefined shape.
+    let skews: Vec<f64> = skews.into_iter().filter(|s| !s.is_nan()).collect();
+    let kurts: Vec<f64> = kurts.into_iter().filter(|k| !k.is_nan()).collect();
+    check_dropped(py, n_resamples as usize - kurts.len(), n_resamples as usize)?;
+    check_any_defined(skews.len().min(kurts.len()))?;
+    let q_skew = skews.quantile(&[left_q, right_q]);
+    let q_kurt = kurts.quantile(&[left_q, right_q]);
+    Ok((
+        skewness(&vec),
+        (q_skew[0], q_skew[1]),
+        kurtosis(&vec),
+        (q_kurt[0], q_kurt[1]),
+    ))
+}
+
+#[derive(Clone, Copy)]
+enum JointStatistic {
+    Ratio,
+    ConditionalMean,
+    ProductMean,
+    Covariance,
+    Correlation,
+}
+
+#[derive(Default)]
+struct JointMoments {
+    n: f64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_yy: f64,
+    sum_xy: f64,
+    sum_x_engaged: f64,
+    n_engaged: f64,
+}
+
+impl JointMoments {
+    #[inline(always)]
+    fn push(&mut self, x: f64, y: f64) {
+        self.n += 1.0;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_yy += y * y;
+        self.sum_xy += x * y;
+        if y > 0.0 {
+            self.sum_x_engaged += x;
+            self.n_engaged += 1.0;
+        }
+    }
+
+    fn statistic(&self, statistic: JointStatistic) -> f64 {
+        let cov = self.sum_xy / self.n - self.sum_x * self.sum_y / (self.n * self.n);
+        match statistic {
+            JointStatistic::Ratio => self.sum_x / self.sum_y,
+            JointStatistic::ConditionalMean => self.sum_x_engaged / self.n_engaged,
+            JointStatistic::ProductMean => self.sum_xy / self.n,
+            JointStatistic::Covariance => cov,
+            JointStatistic::Correlation => {
+                let var_x = self.sum_xx / self.n - (self.sum_x / self.n).powi(2);
+                let var_y = self.sum_yy / self.n - (self.sum_y / self.n).powi(2);
+                cov / (var_x * var_y).sqrt()
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, statistic = "conditional_mean", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(args, statistic='conditional_mean', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Performs a bootstrap comparison of a statistic defined from two metric columns, resampling whole rows
+/// so that the dependence between the two metrics of a unit is preserved in every resample.
+///
+/// Resamples where the statistic is undefined, e.g. without rows with y > 0, are left out of the
+/// p-value and interval, with a warning when they are many; when none is left, InsufficientDataError
+/// is raised.
+///
+/// Args:
+///     args (List[List[float]]): Four lists [x_1, y_1, x_2, y_2]: the two metric columns of the first
+///         and of the second group. Columns of the same group must have equal length.
+///     statistic (str, optional): The function of both columns to compare:
+///         - "conditional_mean": mean of x over rows with y > 0 (e.g. revenue per engaged user),
+///         - "ratio": sum(x) / sum(y),
+///         - "product_mean": mean of x * y,
+///         - "covariance": covariance of x and y,
+///         - "correlation": Pearson correlation of x and y.
+///         Default is "conditional_mean".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]:
+///         A tuple containing:
+///         - p_value (float): The p-value for the test (two-sided or one-sided depending on `two_sided`).
+///         - stat_1 (float): The statistic of the first group.
+///         - stat_2 (float): The statistic of the second group.
+///         - uplift (float): The observed uplift (stat_2 - stat_1) / stat_1.
+///         - (float, float): The confidence interval bounds for the uplift.
+/// """
+pub fn joint_bootstrap(
+    py: Python<'_>,
+    args: Vec<Vec<f64>>,
+    statistic: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let statistic = match statistic {
+        "ratio" => JointStatistic::Ratio,
+        "conditional_mean" => JointStatistic::ConditionalMean,
+        "product_mean" => JointStatistic::ProductMean,
+        "covariance" => JointStatistic::Covariance,
+        "correlation" => JointStatistic::Correlation,
+        _ => raise!(InvalidInputError, "statistic must be one of 'conditional_mean', 'ratio', 'product_mean', 'covariance' or 'correlation'"),
+    };
+    if args.len() != 4 {
+        raise!(InvalidInputError, "Input must contain 4 vectors: [x_1, y_1, x_2, y_2].");
+    }
+    let (len_1, len_2) = (args[0].len(), args[2].len());
+    if len_1 != args[1].len() || len_2 != args[3].len() {
+        raise!(InvalidInputError, "Each pair of arrays must be of equal length.");
+    }
+    if len_1 == 0 || len_2 == 0 {
+        raise!(InsufficientDataError, "All arrays must be non-empty")
+    }
+    let group_statistic = |x: &[f64], y: &[f64]| {
+        let mut moments = JointMoments::default();
+        x.iter().zip(y).for_each(|(&x, &y)| moments.push(x, y));
+        moments.statistic(statistic)
+    };
+    let stat_1 = group_statistic(&args[0], &args[1]);
+    let stat_2 = group_statistic(&args[2], &args[3]);
+    let uplift = calculate_uplift(stat_1, stat_2);
+
+    let sampler_1 = IndexSampler::new([args[0].as_slice(), args[1].as_slice()]).or_raise()?;
+    let sampler_2 = IndexSampler::new([args[2].as_slice(), args[3].as_slice()]).or_raise()?;
+    let uplift_diffs: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut moments_1 = JointMoments::default();
+            let mut moments_2 = JointMoments::default();
+            for _ in 0..len_1 {
+                let [x, y] = sampler_1.draw(rng);
+                moments_1.push(x, y);
+            }
+            for _ in 0..len_2 {
+                let [x, y] = sampler_2.draw(rng);
+                moments_2.push(x, y);
+            }
+            calculate_uplift(moments_1.statistic(statistic), moments_2.statistic(statistic))
+        })
+    });
+    // Resamples without engaged rows (or with a constant column) leave the statistic undefined.
+    let uplift_diffs: Vec<f64> = uplift_diffs.into_iter().filter(|u| !u.is_nan()).collect();
+    let n_valid = uplift_diffs.len() as u64;
+    check_dropped(py, (n_resamples - n_valid) as usize, n_resamples as usize)?;
+    check_any_defined(uplift_diffs.len())?;
+
+    let p: f64 =
+        (uplift_diffs.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0) / (n_valid + 1) as f64;
+    let p_value = (2.0 - 2.0 * p).min(p * 2.0);
+    let q = uplift_diffs.quantile(&[left_q, right_q]);
+    Ok((
+        if two_sided { p_value } else { p },
+        stat_1,
+        stat_2,
+        uplift,
+        (q[0], q[1]),
+    ))
+}
+
+/// `shared_control_test` output: both uplifts against the control, their difference, the three
+/// intervals, the p-value of the difference and the correlation of the resampled uplifts.
+type SharedControlOutput = (f64, f64, f64, (f64, f64), (f64, f64), (f64, f64), f64, f64);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (control, treatment_1, treatment_2, confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(control, treatment_1, treatment_2, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Compares two treatments that share the same control group, with a joint bootstrap.
+///
+/// Both uplifts are measured against the same control mean, so their errors are positively correlated,
+/// and comparing them as two independent tests overstates the uncertainty of their difference. Here the
+/// control and both treatments are resampled together: in every resample the same resampled control
+/// mean enters both uplifts, and the difference of uplifts (treatment_2 minus treatment_1, both relative
+/// to the control) is bootstrapped with its correlation preserved. The p-value of the difference follows
+/// the conventions of `bootstrap`.
+///
+/// Args:
+///     control (List[float]): The shared control sample.
+///     treatment_1 (List[float]): The sample of the first treatment.
+///     treatment_2 (List[float]): The sample of the second treatment.
+///     confidence_level (float, optional): The confidence level for the intervals. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float), (float, float), (float, float), float, float]:
+///         A tuple containing:
+///         - uplift_1 (float): The uplift of the first treatment over the control.
+///         - uplift_2 (float): The uplift of the second treatment over the control.
+///         - diff (float): The difference of uplifts, uplift_2 - uplift_1.
+///         - (float, float): The confidence interval of uplift_1.
+///         - (float, float): The confidence interval of uplift_2.
+///         - (float, float): The confidence interval of the difference of uplifts.
+///         - p_value (float): The p-value of a zero difference of uplifts.
+///         - correlation (float): The correlation of the resampled uplifts induced by the shared control.
+/// """
+pub fn shared_control_test(
+    py: Python<'_>,
+    control: Vec<f64>,
+    treatment_1: Vec<f64>,
+    treatment_2: Vec<f64>,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<SharedControlOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    check_groups(py, &[&control, &treatment_1, &treatment_2])?;
+    let groups = [&control, &treatment_1, &treatment_2];
+    let samplers: Vec<IndexSampler<1>> = groups
+        .iter()
+        .map(|group| IndexSampler::new([group.as_slice()]).or_raise())
+        .collect::<PyResult<_>>()?;
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let means = groups.map(|group| mean(group));
+    let uplift_1 = calculate_uplift(means[0], means[1]);
+    let uplift_2 = calculate_uplift(means[0], means[2]);
+
+    let uplifts: Vec<(f64, f64)> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut means = [0.0; 3];
+            for (g, group) in groups.iter().enumerate() {
+                means[g] = (0..group.len())
+                    .map(|_| samplers[g].draw(rng)[0])
+                    .sum::<f64>()
+                    / group.len() as f64;
+            }
+            (
+                calculate_uplift(means[0], means[1]),
+                calculate_uplift(means[0], means[2]),
+            )
+        })
+    });
+    let (uplifts_1, uplifts_2): (Vec<f64>, Vec<f64>) = uplifts.into_iter().unzip();
+    let diffs: Vec<f64> = uplifts_2.iter().zip(&uplifts_1).map(|(u2, u1)| u2 - u1).collect();
+    let interval = |values: &[f64]| {
+        let q = values.quantile(&[left_q, right_q]);
+        (q[0], q[1])
+    };
+    let (ci_1, ci_2, ci_diff) = (interval(&uplifts_1), interval(&uplifts_2), interval(&diffs));
+    check_interval(py, &diffs, ci_diff)?;
+
+    let p = (diffs.iter().filter(|&&d| d > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
+    let (mean_1, mean_2) = (mean(&uplifts_1), mean(&uplifts_2));
+    let (cov, var_1, var_2) = uplifts_1.iter().zip(&uplifts_2).fold(
+        (0.0, 0.0, 0.0),
+        |(cov, var_1, var_2), (u1, u2)| {
+            let (d1, d2) = (u1 - mean_1, u2 - mean_2);
+            (cov + d1 * d2, var_1 + d1 * d1, var_2 + d2 * d2)
+        },
+    );
+    Ok((
+        uplift_1,
+        uplift_2,
+        uplift_2 - uplift_1,
+        ci_1,
+        ci_2,
+        ci_diff,
+        if two_sided { (2.0 - 2.0 * p).min(2.0 * p) } else { p },
+        cov / (var_1 * var_2).sqrt(),
+    ))
+}
+
+/// Interaction contrast `(m11 - m10) - (m01 - m00)` of the means of a 2×2 design.
+fn interaction_contrast(means: [[f64; 2]; 2]) -> f64 {
+    (means[1][1] - means[1][0]) - (means[0][1] - means[0][0])
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (cells_2x2, method = "bootstrap", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(cells_2x2, method='bootstrap', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Tests the interaction between two overlapping experiments, from the four cells of their 2×2
+/// factorial design.
+///
+/// The interaction contrast is `(m11 - m10) - (m01 - m00)`, where `m_ij` is the mean of the cell with
+/// variant `i` of the first experiment and variant `j` of the second: the effect of the second
+/// experiment among users treated by the first, minus its effect among the others. A non-zero
+/// interaction means the experiments interfere and their effects do not add up.
+///
+/// With "bootstrap", every cell is resampled independently, and the interval and p-value follow the
+/// conventions of `bootstrap`. With "permutation", the residuals of the additive model (the row and
+/// column effects of the cell means) are permuted across all cells, which keeps both main effects under
+/// the null of no interaction; the interval is then that of the null contrasts.
+///
+/// Args:
+///     cells_2x2 (List[List[List[float]]]): The samples of the cells, `[[c00, c01], [c10, c11]]`, the
+///         first index being the variant of the first experiment.
+///     method (str, optional): "bootstrap" or "permutation". Default is "bootstrap".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of resamples or permutations. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, (float, float), float]:
+///         A tuple containing:
+///         - interaction (float): The observed interaction contrast.
+///         - (float, float): The bootstrap interval of the contrast, or the interval of the null contrasts
+///           with "permutation".
+///         - p_value (float): The p-value of a zero interaction.
+/// """
+pub fn interaction_test(
+    py: Python<'_>,
+    cells_2x2: Vec<Vec<Vec<f64>>>,
+    method: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, (f64, f64), f64)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    if cells_2x2.len() != 2 || cells_2x2.iter().any(|row| row.len() != 2) {
+        raise!(InvalidInputError, "cells_2x2 must be a 2x2 nested list of samples: [[c00, c01], [c10, c11]]")
+    }
+    let cells: Vec<&[f64]> = cells_2x2.iter().flatten().map(Vec::as_slice).collect();
+    if cells.iter().any(|cell| cell.is_empty()) {
+        raise!(InsufficientDataError, "All cells must be non-empty")
+    }
+    check_groups(py, &cells)?;
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let cell_means = |means: Vec<f64>| [[means[0], means[1]], [means[2], means[3]]];
+    let observed = interaction_contrast(cell_means(cells.iter().map(|cell| mean(cell)).collect()));
+
+    let contrasts: Vec<f64> = match method {
+        "bootstrap" => {
+            let samplers: Vec<IndexSampler<1>> = cells
+                .iter()
+                .map(|&cell| IndexSampler::new([cell]).or_raise())
+                .collect::<PyResult<_>>()?;
+            py.allow_threads(|| {
+                resampler.map(n_resamples, |rng| {
+                    let means = cells
+                        .iter()
+                        .zip(&samplers)
+                        .map(|(cell, sampler)| {
+                            (0..cell.len()).map(|_| sampler.draw(rng)[0]).sum::<f64>() / cell.len() as f64
+                        })
+                        .collect();
+                    interaction_contrast(cell_means(means))
+                })
+            })
+        }
+        "permutation" => {
+            let m = cell_means(cells.iter().map(|cell| mean(cell)).collect());
+            let grand = (m[0][0] + m[0][1] + m[1][0] + m[1][1]) / 4.0;
+            let residuals: Vec<f64> = cells
+                .iter()
+                .enumerate()
+                .flat_map(|(c, cell)| {
+                    let (i, j) = (c / 2, c % 2);
+                    let fitted = (m[i][0] + m[i][1]) / 2.0 + (m[0][j] + m[1][j]) / 2.0 - grand;
+                    cell.iter().map(move |x| x - fitted)
+                })
+                .collect();
+            let bounds: Vec<usize> = cells
+                .iter()
+                .scan(0, |end, cell| {
+                    *end += cell.len();
+                    Some(*end)
+                })
+                .collect();
+            py.allow_threads(|| {
+                resampler.map(n_resamples, |rng| {
+                    let mut permuted = residuals.clone();
+                    permuted.shuffle(rng);
+                    let means = (0..4)
+                        .map(|c| {
+                            let start = if c == 0 { 0 } else { bounds[c - 1] };
+                            mean(&permuted[start..bounds[c]])
+                        })
+                        .collect();
+                    interaction_contrast(cell_means(means))
+                })
+            })
+        }
+        _ => raise!(InvalidInputError, "method must be 'bootstrap' or 'permutation', got '{method}'"),
+    };
+    let q = contrasts.quantile(&[left_q, right_q]);
+    check_interval(py, &contrasts, (q[0], q[1]))?;
+    let p = match method {
+        "bootstrap" => {
+            (contrasts.iter().filter(|&&c| c > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64
+        }
+        _ => (contrasts.iter().filter(|&&c| observed > c).count() as f64 + 1.0) / (n_resamples + 1) as f64,
+    };
+    Ok((
+        observed,
+        (q[0], q[1]),
+        if two_sided { (2.0 - 2.0 * p).min(2.0 * p) } else { p },
+    ))
+}
+
+/// Length in seconds of a window such as "30s", "15m", "12h", "7d" or "2w".
+fn parse_window(window: &str) -> PyResult<f64> {
+    let unit = window.chars().last().unwrap_or(' ');
+    let seconds = match unit {
+        's' => 1.0,
+        'm' => 60.0,
+        'h' => 3_600.0,
+        'd' => 86_400.0,
+        'w' => 604_800.0,
+        _ => raise!(InvalidInputError, "window must be a number followed by s, m, h, d or w, got '{window}'"),
+    };
+    Ok(match window[..window.len() - 1].parse::<f64>() {
+        Ok(count) if count > 0.0 => count * seconds,
+        _ => raise!(InvalidInputError, "window must be a positive number followed by s, m, h, d or w, got '{window}'"),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (values, timestamps, unit_ids, window = "7d", aggregation = "sum", confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(values, timestamps, unit_ids, window='7d', aggregation='sum', confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Bootstraps the mean of a metric aggregated per unit and time window, resampling units.
+///
+/// Events are grouped into windows of fixed length counted from the earliest timestamp, and the values
+/// of every unit in every window are aggregated, e.g. the weekly revenue of every user. The estimate is
+/// the mean over all unit-windows with at least one event. Units are resampled with all their windows,
+/// so the interval accounts for the dependence between windows of the same unit.
+///
+/// Args:
+///     values (List[float]): The value of every event.
+///     timestamps (List[float]): The time of every event in seconds, e.g. a Unix timestamp.
+///     unit_ids (List[str]): The unit of every event.
+///     window (str, optional): The window length, a number followed by s, m, h, d or w. Default is "7d".
+///     aggregation (str, optional): "sum", "mean" or "count" of the values of a unit-window. Default is "sum".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, int]:
+///         A tuple containing:
+///         - estimate (float): The mean of the unit-window aggregates.
+///         - low (float): The lower bound of the confidence interval.
+///         - high (float): The upper bound of the confidence interval.
+///         - n_windows (int): The number of unit-windows.
+/// """
+pub fn windowed_bootstrap(
+    py: Python<'_>,
+    values: Vec<f64>,
+    timestamps: Vec<f64>,
+    unit_ids: Vec<String>,
+    window: &str,
+    aggregation: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, f64, u64)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    if values.len() != timestamps.len() || values.len() != unit_ids.len() {
+        raise!(InvalidInputError, "values, timestamps and unit_ids must have equal size")
+    }
+    if values.is_empty() {
+        raise!(InsufficientDataError, "All arrays must be non-empty")
+    }
+    if !matches!(aggregation, "sum" | "mean" | "count") {
+        raise!(InvalidInputError, "aggregation must be one of 'sum', 'mean' or 'count'")
+    }
+    let width = parse_window(window)?;
+    let origin = timestamps.iter().cloned().fold(f64::INFINITY, f64::min);
+
+    // (sum, count) of every window of every unit.
+    let mut windows: HashMap<(&str, i64), (f64, f64)> = HashMap::new();
+    for ((value, timestamp), unit) in values.iter().zip(&timestamps).zip(&unit_ids) {
+        let index = ((timestamp - origin) / width).floor() as i64;
+        let cell = windows.entry((unit.as_str(), index)).or_default();
+        cell.0 += value;
+        cell.1 += 1.0;
+    }
+    let n_windows = windows.len() as u64;
+    // (total of the window aggregates, number of windows) of every unit.
+    let mut units: HashMap<&str, (f64, f64)> = HashMap::new();
+    for ((unit, _), (sum, count)) in windows {
+        let aggregate = match aggregation {
+            "sum" => sum,
+            "mean" => sum / count,
+            _ => count,
+        };
+        let total = units.entry(unit).or_default();
+        total.0 += aggregate;
+        total.1 += 1.0;
+    }
+    let mut units: Vec<(&str, (f64, f64))> = units.into_iter().collect();
+    units.sort_unstable_by_key(|(unit, _)| *unit);
+    let units: Vec<(f64, f64)> = units.into_iter().map(|(_, total)| total).collect();
+
+    let (total, count) = units
+        .iter()
+        .fold((0.0, 0.0), |acc, unit| (acc.0 + unit.0, acc.1 + unit.1));
+    let distribution = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let (mut total, mut count) = (0.0, 0.0);
+            for _ in 0..units.len() {
+                let unit = units[rng.gen_range(0..units.len())];
+                total += unit.0;
+                count += unit.1;
+            }
+            total / count
+        })
+    });
+    let bounds = distribution.quantile(&[left_q, right_q]);
+    Ok((total / count, bounds[0], bounds[1], n_windows))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (values, cluster_ids, within = "resample", weighting = "observations", confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(values, cluster_ids, within='resample', weighting='observations', confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Two-stage bootstrap of a mean for nested data: resamples clusters, then observations within them.
+///
+/// Every resample draws as many clusters as there are, with replacement, and then, if `within` is
+/// "resample", redraws the observations of every drawn cluster with replacement. Resampling clusters
+/// only ("keep") is the usual cluster bootstrap; resampling both stages mimics two-stage sampling and
+/// gives slightly wider, conservative intervals.
+///
+/// Args:
+///     values (List[float]): The observations.
+///     cluster_ids (List[str]): The cluster of every observation (e.g. the city of every user).
+///     within (str, optional): "resample" to redraw the observations of every cluster or "keep" to keep
+///         them. Default is "resample".
+///     weighting (str, optional): "observations" for the mean of all observations or "clusters" for the
+///         mean of the cluster means. Default is "observations".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float]:
+///         A tuple containing:
+///         - estimate (float): The mean of `values` under the chosen weighting.
+///         - low (float): The lower bound of the confidence interval.
+///         - high (float): The upper bound of the confidence interval.
+/// """
+pub fn two_stage_bootstrap(
+    py: Python<'_>,
+    values: Vec<f64>,
+    cluster_ids: Vec<String>,
+    within: &str,
+    weighting: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, f64)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    if values.len() != cluster_ids.len() {
+        raise!(InvalidInputError, "values and cluster_ids must have equal size")
+    }
+    if values.is_empty() {
+        raise!(InsufficientDataError, "All arrays must be non-empty")
+    }
+    let resample_within = match within {
+        "resample" => true,
+        "keep" => false,
+        _ => raise!(InvalidInputError, "within must be 'resample' or 'keep'"),
+    };
+    let by_cluster_mean = match weighting {
+        "observations" => false,
+        "clusters" => true,
+        _ => raise!(InvalidInputError, "weighting must be 'observations' or 'clusters'"),
+    };
+    let mut clusters: HashMap<&str, Vec<f64>> = HashMap::new();
+    for (value, cluster) in values.iter().zip(&cluster_ids) {
+        clusters.entry(cluster.as_str()).or_default().push(*value);
+    }
+    let mut clusters: Vec<(&str, Vec<f64>)> = clusters.into_iter().collect();
+    clusters.sort_unstable_by_key(|(cluster, _)| *cluster);
+    let clusters: Vec<Vec<f64>> = clusters.into_iter().map(|(_, values)| values).collect();
+
+    // Accumulates a cluster as (sum, weight) of the chosen mean.
+    let accumulate = |sum: f64, len: usize| {
+        if by_cluster_mean {
+            (sum / len as f64, 1.0)
+        } else {
+            (sum, len as f64)
+        }
+    };
+    let (total, weight) = clusters.iter().fold((0.0, 0.0), |acc, cluster| {
+        let (sum, weight) = accumulate(cluster.iter().sum(), cluster.len());
+        (acc.0 + sum, acc.1 + weight)
+    });
+    let sums: Vec<f64> = clusters.iter().map(|cluster| cluster.iter().sum()).collect();
+    let distribution = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let (mut total, mut weight) = (0.0, 0.0);
+            for _ in 0..clusters.len() {
+                let k = rng.gen_range(0..clusters.len());
+                let cluster = &clusters[k];
+                let sum = if resample_within {
+                    (0..cluster.len())
+                        .map(|_| cluster[rng.gen_range(0..cluster.len())])
+                        .sum()
+                } else {
+                    sums[k]
+                };
+                let (sum, w) = accumulate(sum, cluster.len());
+                total += sum;
+                weight += w;
+            }
+            total / weight
+        })
+    });
+    let bounds = distribution.quantile(&[left_q, right_q]);
+    Ok((total / weight, bounds[0], bounds[1]))
 }
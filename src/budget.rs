@@ -0,0 +1,185 @@
+use crate::errors::*;
+use crate::levels::*;
+use crate::rng::*;
+use pylars_core::tools::Accumulator;
+use pylars_core::{bootstrap_null, permutation_null};
+use pyo3::prelude::*;
+
+/// `timed_bootstrap` output: the `bootstrap` output, the resamples run and the Monte Carlo error.
+type TimedBootstrap = (f64, f64, f64, f64, PyObject, usize, f64);
+
+/// `timed_permutation_test` output: the `permutation_test` output, the resamples run and the Monte
+/// Carlo error.
+type TimedPermutation = (f64, f64, f64, PyObject, usize, f64);
+
+/// Standard error of a resampled p-value over `n` resamples. A two-sided p-value doubles the smaller
+/// tail, and so its error.
+fn p_value_mc_error(p_value: f64, two_sided: bool, n: usize) -> f64 {
+    let (tail, factor) = if two_sided {
+        (p_value / 2.0, 2.0)
+    } else {
+        (p_value, 1.0)
+    };
+    factor * (tail * (1.0 - tail) / n as f64).sqrt()
+}
+
+/// Resampler of a `time_budget` call.
+fn timed_resampler(
+    time_budget: f64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Resampler> {
+    Ok(new_resampler(random_state, seed, deterministic)?
+        .with_deadline(deadline(Some(time_budget))?))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, time_budget, confidence_level = ConfidenceLevel::Single(0.95), ind = true, two_sided = true, max_resamples = 10_000_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(args, time_budget, confidence_level=0.95, ind=True, two_sided=True, max_resamples=10000000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `bootstrap` with as many resamples as fit in a time budget.
+///
+/// The resamples run in batches until the first batch that ends past `time_budget`, so the call takes
+/// about `time_budget` seconds (at least one batch) and the resamples are the first ones of `bootstrap`
+/// with the same seed. The achieved precision is reported as the Monte Carlo standard error of the
+/// p-value, which shrinks as 1 / sqrt(n_resamples).
+///
+/// Args:
+///     args (List[List[float]]): Two samples, or two (numerator, denominator) pairs, as for `bootstrap`.
+///     time_budget (float): The running time in seconds.
+///     confidence_level (float or List[float], optional): The confidence level for the interval, or a list of
+///         levels to get one interval per level. Default is 0.95.
+///     ind (bool, optional): Whether the samples are independent rather than paired. Default is True.
+///     two_sided (bool, optional): Whether to return a two-sided p-value. Default is True.
+///     max_resamples (int, optional): The number of resamples after which the call stops even within
+///         the budget. Default is 10000000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float), int, float]:
+///         A tuple containing:
+///         - p_value (float): The p-value, as in `bootstrap`.
+///         - mean_1 (float): The mean (or ratio) of the first group.
+///         - mean_2 (float): The mean (or ratio) of the second group.
+///         - uplift (float): The relative difference between the groups.
+///         - (float, float): The interval bounds, or a list of them when `confidence_level` is a list.
+///         - n_resamples (int): The number of resamples run within the budget.
+///         - mc_error (float): The Monte Carlo standard error of the p-value.
+/// """
+pub fn timed_bootstrap(
+    py: Python<'_>,
+    args: Vec<Vec<f64>>,
+    time_budget: f64,
+    confidence_level: ConfidenceLevel,
+    ind: bool,
+    two_sided: bool,
+    max_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<TimedBootstrap> {
+    if max_resamples == 0 {
+        raise!(InvalidInputError, "max_resamples must be positive")
+    }
+    let resampler = timed_resampler(time_budget, random_state, seed, deterministic)?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let null = py
+        .allow_threads(|| {
+            bootstrap_null(&columns, max_resamples, ind, Accumulator::F64, &resampler)
+        })
+        .or_raise()?;
+    let summaries: Vec<_> = confidence_level
+        .levels()?
+        .into_iter()
+        .map(|level| null.summarize(level, two_sided, false, None))
+        .collect();
+    let (p_value, mean_1, mean_2, uplift, _) = summaries[0];
+    let intervals = summaries.into_iter().map(|summary| summary.4).collect();
+    let n_resamples = null.uplift_diffs.len();
+    Ok((
+        p_value,
+        mean_1,
+        mean_2,
+        uplift,
+        confidence_level.intervals(py, intervals)?,
+        n_resamples,
+        p_value_mc_error(p_value, two_sided, n_resamples),
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, time_budget, confidence_level = ConfidenceLevel::Single(0.95), two_sided = true, max_resamples = 10_000_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(args, time_budget, confidence_level=0.95, two_sided=True, max_resamples=10000000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `permutation_test` with as many permutations as fit in a time budget.
+///
+/// The permutations run in batches until the first batch that ends past `time_budget`, and are the
+/// first ones of `permutation_test` with the same seed. The achieved precision is reported as the Monte
+/// Carlo standard error of the p-value.
+///
+/// Args:
+///     args (List[List[float]]): Two samples, or two (numerator, denominator) pairs, as for
+///         `permutation_test`.
+///     time_budget (float): The running time in seconds.
+///     confidence_level (float or List[float], optional): The confidence level for the interval, or a list of
+///         levels to get one interval per level. Default is 0.95.
+///     two_sided (bool, optional): Whether to return a two-sided p-value. Default is True.
+///     max_resamples (int, optional): The number of permutations after which the call stops even
+///         within the budget. Default is 10000000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float), int, float]:
+///         A tuple containing:
+///         - p_value (float): The p-value, as in `permutation_test`.
+///         - uplift (float): The relative difference between the groups.
+///         - diff (float): The observed difference.
+///         - (float, float): The interval bounds, or a list of them when `confidence_level` is a list.
+///         - n_resamples (int): The number of permutations run within the budget.
+///         - mc_error (float): The Monte Carlo standard error of the p-value.
+/// """
+pub fn timed_permutation_test(
+    py: Python<'_>,
+    args: Vec<Vec<f64>>,
+    time_budget: f64,
+    confidence_level: ConfidenceLevel,
+    two_sided: bool,
+    max_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<TimedPermutation> {
+    if max_resamples == 0 {
+        raise!(InvalidInputError, "max_resamples must be positive")
+    }
+    let resampler = timed_resampler(time_budget, random_state, seed, deterministic)?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let null = py
+        .allow_threads(|| permutation_null(&columns, max_resamples, Accumulator::F64, &resampler))
+        .or_raise()?;
+    let summaries: Vec<_> = confidence_level
+        .levels()?
+        .into_iter()
+        .map(|level| null.summarize(level, two_sided))
+        .collect();
+    let (p_value, uplift, diff, _) = summaries[0];
+    let intervals = summaries.into_iter().map(|summary| summary.3).collect();
+    let n_resamples = null.diffs.len();
+    Ok((
+        p_value,
+        uplift,
+        diff,
+        confidence_level.intervals(py, intervals)?,
+        n_resamples,
+        p_value_mc_error(p_value, two_sided, n_resamples),
+    ))
+}
@@ -0,0 +1,207 @@
+use crate::errors::*;
+use pylars_core::tools::Accumulator;
+use pylars_core::{
+    bootstrap_null, permutation_null, studentized_permutation_null, BootstrapNull, PermutationNull,
+    Resampler, Result,
+};
+use pyo3::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// A resampled null distribution kept by the cache.
+#[derive(Clone)]
+enum CachedNull {
+    Bootstrap(Arc<BootstrapNull>),
+    Permutation(Arc<PermutationNull>),
+}
+
+/// Least recently used cache of null distributions, the most recently used entry last.
+struct NullCache {
+    max_entries: usize,
+    entries: VecDeque<(u64, CachedNull)>,
+    hits: u64,
+    misses: u64,
+}
+
+/// The process-wide cache, None until `enable_null_cache` is called.
+static NULL_CACHE: Mutex<Option<NullCache>> = Mutex::new(None);
+
+/// Locks the cache. The lock is never held while resampling or calling into Python, and a panic while
+/// it is held leaves the entries consistent, so a poisoned lock is taken over.
+fn lock_cache() -> MutexGuard<'static, Option<NullCache>> {
+    NULL_CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Hash of the data and of every parameter that changes the resamples.
+fn cache_key(test: &str, columns: &[&[f64]], params: &[u64], resampler: &Resampler) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    test.hash(&mut hasher);
+    for column in columns {
+        column.len().hash(&mut hasher);
+        column
+            .iter()
+            .for_each(|value| value.to_bits().hash(&mut hasher));
+    }
+    params.hash(&mut hasher);
+    resampler.key().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the cached entry of `key`, or computes it with `compute` (without holding the lock) and
+/// inserts it when the cache is enabled.
+/// Runs with a deadline may stop early, so they bypass the cache.
+fn get_or_compute(
+    resampler: &Resampler,
+    key: impl FnOnce() -> u64,
+    compute: impl FnOnce() -> Result<CachedNull>,
+) -> Result<CachedNull> {
+    if resampler.deadline().is_some() {
+        return compute();
+    }
+    let key = {
+        let mut cache = lock_cache();
+        let Some(cache) = cache.as_mut() else {
+            return compute();
+        };
+        let key = key();
+        if let Some(position) = cache.entries.iter().position(|(k, _)| *k == key) {
+            let entry = cache.entries.remove(position).unwrap();
+            cache.entries.push_back(entry.clone());
+            cache.hits += 1;
+            return Ok(entry.1);
+        }
+        cache.misses += 1;
+        key
+    };
+    let null = compute()?;
+    if let Some(cache) = lock_cache().as_mut() {
+        // Another thread may have computed the same entry meanwhile.
+        if cache.entries.iter().any(|(k, _)| *k == key) {
+            return Ok(null);
+        }
+        if cache.entries.len() == cache.max_entries {
+            cache.entries.pop_front();
+        }
+        cache.entries.push_back((key, null.clone()));
+    }
+    Ok(null)
+}
+
+/// `bootstrap_null`, through the cache.
+pub fn cached_bootstrap_null(
+    columns: &[&[f64]],
+    n_resamples: u64,
+    ind: bool,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+) -> Result<Arc<BootstrapNull>> {
+    let null = get_or_compute(
+        resampler,
+        || {
+            let params = [n_resamples, ind as u64, accumulator as u64];
+            cache_key("bootstrap", columns, &params, resampler)
+        },
+        || {
+            bootstrap_null(columns, n_resamples, ind, accumulator, resampler)
+                .map(|null| CachedNull::Bootstrap(Arc::new(null)))
+        },
+    )?;
+    match null {
+        CachedNull::Bootstrap(null) => Ok(null),
+        CachedNull::Permutation(_) => unreachable!("cache keys include the test"),
+    }
+}
+
+/// `permutation_null`, or `studentized_permutation_null` with `standardize`, through the cache.
+pub fn cached_permutation_null(
+    columns: &[&[f64]],
+    n_resamples: u64,
+    standardize: bool,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+) -> Result<Arc<PermutationNull>> {
+    let null = get_or_compute(
+        resampler,
+        || {
+            let params = [n_resamples, standardize as u64, accumulator as u64];
+            cache_key("permutation", columns, &params, resampler)
+        },
+        || {
+            let null = if standardize {
+                studentized_permutation_null(columns, n_resamples, accumulator, resampler)
+            } else {
+                permutation_null(columns, n_resamples, accumulator, resampler)
+            };
+            null.map(|null| CachedNull::Permutation(Arc::new(null)))
+        },
+    )?;
+    match null {
+        CachedNull::Permutation(null) => Ok(null),
+        CachedNull::Bootstrap(_) => unreachable!("cache keys include the test"),
+    }
+}
+
+#[pyfunction(signature = (max_entries = 32))]
+#[pyo3(text_signature = "(max_entries=32)")]
+/// """
+/// Enables the in-process cache of null distributions of `bootstrap` and `permutation_test`.
+///
+/// Re-running a test on the same data with the same resampling parameters (n_resamples, ind,
+/// standardize, accumulator, seed and deterministic mode) then reuses the resampled distribution and only recomputes
+/// the summary, so changing `confidence_level`, `two_sided` or `bias_corrected` is immediate. Calls with a
+/// `random_state` draw a new seed every time and calls with `max_seconds` may stop early, so neither
+/// are served from the cache. Enabling the cache again resizes it and keeps its entries.
+///
+/// Args:
+///     max_entries (int, optional): The number of distributions kept, the least recently used being
+///         evicted first. Default is 32.
+/// """
+pub fn enable_null_cache(max_entries: usize) -> PyResult<()> {
+    if max_entries == 0 {
+        raise!(InvalidInputError, "max_entries must be positive")
+    }
+    let mut cache = lock_cache();
+    let cache = cache.get_or_insert_with(|| NullCache {
+        max_entries,
+        entries: VecDeque::new(),
+        hits: 0,
+        misses: 0,
+    });
+    cache.max_entries = max_entries;
+    while cache.entries.len() > max_entries {
+        cache.entries.pop_front();
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+/// """
+/// Disables the cache of null distributions and frees its entries.
+/// """
+pub fn disable_null_cache() {
+    *lock_cache() = None;
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+/// """
+/// Reports the use of the cache of null distributions.
+///
+/// Returns:
+///     Tuple[int, int, int, int]: The numbers of hits and misses since the cache was enabled, the number
+///         of cached distributions and the maximum number of entries, all 0 when the cache is disabled.
+/// """
+pub fn null_cache_info() -> (u64, u64, usize, usize) {
+    match lock_cache().as_ref() {
+        Some(cache) => (
+            cache.hits,
+            cache.misses,
+            cache.entries.len(),
+            cache.max_entries,
+        ),
+        None => (0, 0, 0, 0),
+    }
+}
@@ -0,0 +1,532 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::special::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::distributions::Uniform;
+use rand::prelude::*;
+
+/// An integer-encoded categorical sample, with codes `0..n_categories`.
+pub struct Categorical {
+    pub codes: Vec<usize>,
+    pub n_categories: usize,
+}
+
+impl Categorical {
+    /// Builds a sample from per-observation codes, or from per-category counts if `counts` is True.
+    pub fn new(values: &[i64], counts: bool) -> PyResult<Self> {
+        if values.iter().any(|&v| v < 0) {
+            raise!(
+                InvalidInputError,
+                "Category codes and counts must be non-negative"
+            )
+        }
+        Ok(if counts {
+            let codes = values
+                .iter()
+                .enumerate()
+                .flat_map(|(category, &count)| std::iter::repeat_n(category, count as usize))
+                .collect();
+            Categorical {
+                codes,
+                n_categories: values.len(),
+            }
+        } else {
+            Categorical {
+                codes: values.iter().map(|&v| v as usize).collect(),
+                n_categories: values.iter().max().map_or(0, |&v| v as usize + 1),
+            }
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Number of observations of every category among `codes`.
+    pub fn counts(codes: &[usize], n_categories: usize) -> Vec<u64> {
+        let mut counts = vec![0; n_categories];
+        for &code in codes {
+            counts[code] += 1;
+        }
+        counts
+    }
+}
+
+/// Pearson chi-square statistic of the 2 x k table with rows `counts_a` and `totals - counts_a`.
+fn chi2_statistic(counts_a: &[u64], totals: &[u64], len_a: usize, len_b: usize) -> f64 {
+    let n = (len_a + len_b) as f64;
+    counts_a
+        .iter()
+        .zip(totals)
+        .filter(|(_, &total)| total > 0)
+        .map(|(&a, &total)| {
+            let b = total - a;
+            let expected_a = total as f64 * len_a as f64 / n;
+            let expected_b = total as f64 * len_b as f64 / n;
+            (a as f64 - expected_a).powi(2) / expected_a
+                + (b as f64 - expected_b).powi(2) / expected_b
+        })
+        .sum()
+}
+
+/// Difference in mean category score (`b - a`), with scores equal to the codes.
+fn trend_statistic(counts_a: &[u64], totals: &[u64], len_a: usize, len_b: usize) -> f64 {
+    let (score_a, score_total) = counts_a.iter().zip(totals).enumerate().fold(
+        (0.0, 0.0),
+        |(sa, st), (score, (&a, &total))| {
+            (
+                sa + (score as u64 * a) as f64,
+                st + (score as u64 * total) as f64,
+            )
+        },
+    );
+    (score_total - score_a) / len_b as f64 - score_a / len_a as f64
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, test = "chi2", counts = false, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, test='chi2', counts=False, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Performs a permutation test comparing the distribution of a categorical outcome between two groups,
+/// such as ratings from 1 to 5 coded as 0 to 4.
+///
+/// Both groups are pooled and group labels are permuted, so the p-value is exact up to Monte Carlo error
+/// even for sparse tables where the chi-square approximation fails. Each permutation only recounts
+/// the first group, as the category totals are fixed.
+///
+/// Args:
+///     a (List[int]): The category codes (0, 1, ...) of the first group, or its per-category counts if `counts` is True.
+///     b (List[int]): The category codes of the second group, or its per-category counts.
+///     test (str, optional): Either "chi2" (Pearson chi-square, any difference in distribution) or "trend"
+///         (difference in mean code, for ordered categories). Default is "chi2".
+///     counts (bool, optional): If True, `a` and `b` are per-category counts instead of codes. Default is False.
+///     n_resamples (int, optional): The number of permutation resamples. Default is 10000.
+///     two_sided (bool, optional): For the trend test, if True computes a two-sided p-value. Otherwise,
+///         one-sided. The chi-square test is always upper-tailed. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, List[int], List[int]]:
+///         A tuple containing:
+///         - statistic (float): The chi-square statistic, or the difference in mean code (b - a).
+///         - p_value (float): The permutation p-value.
+///         - counts_a (List[int]): The per-category counts of the first group.
+///         - counts_b (List[int]): The per-category counts of the second group.
+/// """
+pub fn categorical_test(
+    py: Python<'_>,
+    a: Vec<i64>,
+    b: Vec<i64>,
+    test: &str,
+    counts: bool,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, Vec<u64>, Vec<u64>)> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let statistic = match test {
+        "chi2" => chi2_statistic,
+        "trend" => trend_statistic,
+        _ => raise!(InvalidInputError, "test must be either 'chi2' or 'trend'"),
+    };
+    let (a, b) = (Categorical::new(&a, counts)?, Categorical::new(&b, counts)?);
+    let (len_a, len_b) = (a.len(), b.len());
+    if len_a == 0 || len_b == 0 {
+        raise!(
+            InsufficientDataError,
+            "Both groups must contain observations"
+        )
+    }
+    let n_categories = a.n_categories.max(b.n_categories);
+    let counts_a = Categorical::counts(&a.codes, n_categories);
+    let counts_b = Categorical::counts(&b.codes, n_categories);
+    let totals: Vec<u64> = counts_a.iter().zip(&counts_b).map(|(x, y)| x + y).collect();
+    let observed = statistic(&counts_a, &totals, len_a, len_b);
+
+    let pooled: Vec<usize> = a.codes.iter().chain(&b.codes).copied().collect();
+    let null: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut codes = pooled.clone();
+            let (group_a, _) = codes.partial_shuffle(rng, len_a);
+            statistic(
+                &Categorical::counts(group_a, n_categories),
+                &totals,
+                len_a,
+                len_b,
+            )
+        })
+    });
+
+    let p_value = if test == "chi2" {
+        (null
+            .iter()
+            .filter(|&&s| s >= observed * (1.0 - 1e-12))
+            .count()
+            + 1) as f64
+            / (n_resamples + 1) as f64
+    } else {
+        let p =
+            (null.iter().filter(|&&s| observed > s).count() + 1) as f64 / (n_resamples + 1) as f64;
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        }
+    };
+    Ok((observed, p_value, counts_a, counts_b))
+}
+
+/// Distinct values of `data` in increasing order, with the dense id of every observation.
+fn distinct_values(data: &[f64]) -> (Vec<f64>, Vec<usize>) {
+    let (ids, n_distinct) = dense_ids(data);
+    let mut values = vec![0.0; n_distinct];
+    for (&id, &x) in ids.iter().zip(data) {
+        values[id] = x;
+    }
+    (values, ids)
+}
+
+/// Hodges–Lehmann shift `median(b_j - a_i)` over all pairs, computed from the multiplicities of the
+/// distinct values, so its cost depends on the number of categories rather than on the sample sizes.
+fn weighted_shift(values_a: &[f64], counts_a: &[u64], values_b: &[f64], counts_b: &[u64]) -> f64 {
+    let mut diffs: Vec<(f64, u64)> = Vec::with_capacity(values_a.len() * values_b.len());
+    for (&x, &count_a) in values_a.iter().zip(counts_a).filter(|(_, &c)| c > 0) {
+        for (&y, &count_b) in values_b.iter().zip(counts_b).filter(|(_, &c)| c > 0) {
+            diffs.push((y - x, count_a * count_b));
+        }
+    }
+    diffs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    let total: u64 = diffs.iter().map(|d| d.1).sum();
+    // 0-based positions of the two middle differences, equal when the number of pairs is odd.
+    let (lower, upper) = ((total - 1) / 2, total / 2);
+    let mut seen = 0;
+    let mut lower_value = f64::NAN;
+    for &(diff, weight) in &diffs {
+        seen += weight;
+        if lower_value.is_nan() && seen > lower {
+            lower_value = diff;
+        }
+        if seen > upper {
+            return (lower_value + diff) / 2.0;
+        }
+    }
+    unreachable!()
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, confidence_level = 0.95, n_resamples = 10_000, alternative = "two-sided", random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, confidence_level=0.95, n_resamples=10000, alternative='two-sided', random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares an ordinal outcome, such as satisfaction scores, between two independent groups.
+///
+/// The shift is the Hodges–Lehmann estimate, the median of all pairwise differences `b_j - a_i`, with a
+/// percentile bootstrap confidence interval. The p-value comes from the Mann–Whitney U test with the
+/// normal approximation and tie correction, which suits outcomes with few distinct values.
+///
+/// Args:
+///     a (List[float]): The scores of the first group.
+///     b (List[float]): The scores of the second group.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     alternative (str, optional): "two-sided", "less" or "greater"; "greater" means `b` tends to
+///         score higher than `a`. Default is "two-sided".
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, (float, float), float, float]:
+///         A tuple containing:
+///         - shift (float): The Hodges–Lehmann shift estimate of `b` relative to `a`.
+///         - (float, float): The bootstrap confidence interval bounds for the shift.
+///         - u_statistic (float): The Mann–Whitney U statistic of `b`.
+///         - p_value (float): The p-value for the chosen alternative.
+/// """
+pub fn ordinal_test(
+    py: Python<'_>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+    confidence_level: f64,
+    n_resamples: u64,
+    alternative: &str,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, (f64, f64), f64, f64)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let alternative = Alternative::parse(alternative).or_raise()?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let (len_a, len_b) = (a.len(), b.len());
+    if len_a == 0 || len_b == 0 {
+        raise!(
+            InsufficientDataError,
+            "Both groups must contain observations"
+        )
+    }
+    check_no_nan("a", &a)?;
+    check_no_nan("b", &b)?;
+
+    let pooled: Vec<f64> = a.iter().chain(&b).copied().collect();
+    let (ranks, tie_term) = rankdata(&pooled);
+    let (n_a, n_b, n) = (len_a as f64, len_b as f64, (len_a + len_b) as f64);
+    let u = ranks[len_a..].iter().sum::<f64>() - n_b * (n_b + 1.0) / 2.0;
+    let var = n_a * n_b / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    let z = (u - n_a * n_b / 2.0) / var.sqrt();
+    let p_value = alternative.p_value(norm_cdf(z), norm_sf(z));
+
+    let (values_a, ids_a) = distinct_values(&a);
+    let (values_b, ids_b) = distinct_values(&b);
+    let full_counts = |ids: &[usize], n_distinct: usize| {
+        let mut counts = vec![0; n_distinct];
+        ids.iter().for_each(|&id| counts[id] += 1);
+        counts
+    };
+    let shift = weighted_shift(
+        &values_a,
+        &full_counts(&ids_a, values_a.len()),
+        &values_b,
+        &full_counts(&ids_b, values_b.len()),
+    );
+
+    let dist_a = rand::distributions::Uniform::new(0, len_a);
+    let dist_b = rand::distributions::Uniform::new(0, len_b);
+    let shifts: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut counts_a = vec![0; values_a.len()];
+            let mut counts_b = vec![0; values_b.len()];
+            (0..len_a).for_each(|_| counts_a[ids_a[dist_a.sample(rng)]] += 1);
+            (0..len_b).for_each(|_| counts_b[ids_b[dist_b.sample(rng)]] += 1);
+            weighted_shift(&values_a, &counts_a, &values_b, &counts_b)
+        })
+    });
+    let q = shifts.quantile(&[left_q, right_q]);
+    Ok((shift, (q[0], q[1]), u, p_value))
+}
+
+type ClusteredProportions = (f64, f64, f64, (f64, f64), f64, (f64, f64));
+
+/// Clustered binary outcomes of a group: the successes and trials of every cluster.
+struct ClusteredGroup<'a> {
+    successes: &'a [f64],
+    trials: &'a [f64],
+}
+
+impl ClusteredGroup<'_> {
+    fn proportion(&self) -> f64 {
+        self.successes.iter().sum::<f64>() / self.trials.iter().sum::<f64>()
+    }
+
+    /// Cluster-robust variance of the proportion, a ratio of sums linearized over the clusters.
+    fn cluster_variance(&self, proportion: f64) -> f64 {
+        let k = self.successes.len() as f64;
+        let trials = self.trials.iter().sum::<f64>();
+        let residuals = self
+            .successes
+            .iter()
+            .zip(self.trials)
+            .map(|(y, m)| (y - proportion * m).powi(2))
+            .sum::<f64>();
+        k / (k - 1.0) * residuals / (trials * trials)
+    }
+
+    /// Proportion of a resample of the clusters drawn with replacement.
+    fn resampled_proportion<R: Rng>(&self, dist: &Uniform<usize>, rng: &mut R) -> f64 {
+        let (mut successes, mut trials) = (0.0, 0.0);
+        for _ in 0..self.successes.len() {
+            let k = dist.sample(rng);
+            successes += self.successes[k];
+            trials += self.trials[k];
+        }
+        successes / trials
+    }
+
+    /// Ratio of the cluster-robust variance to the binomial one, floored at 1 and 1 when both vanish.
+    ///
+    /// Clusters sharing the pooled proportion have no cluster-robust variance at all, and a design effect
+    /// of 0 would make the effective counts infinite; under-dispersed groups are treated as binomial.
+    fn design_effect(&self, proportion: f64) -> f64 {
+        let binomial = proportion * (1.0 - proportion) / self.trials.iter().sum::<f64>();
+        if binomial > 0.0 {
+            (self.cluster_variance(proportion) / binomial).max(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+fn clustered_group<'a>(
+    successes: &'a [f64],
+    trials: &'a [f64],
+    name: &str,
+) -> PyResult<ClusteredGroup<'a>> {
+    if successes.len() != trials.len() {
+        raise!(
+            InvalidInputError,
+            "successes_per_cluster_{name} and trials_per_cluster_{name} must have equal size"
+        )
+    }
+    if successes.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "group {name} must contain at least two clusters"
+        )
+    }
+    if successes
+        .iter()
+        .zip(trials)
+        .any(|(&y, &m)| !(y >= 0.0 && y <= m && m.is_finite()))
+    {
+        raise!(
+            InvalidInputError,
+            "Every cluster of group {name} must have between 0 and trials successes"
+        )
+    }
+    if trials.iter().sum::<f64>() <= 0.0 {
+        raise!(InsufficientDataError, "group {name} must contain trials")
+    }
+    Ok(ClusteredGroup { successes, trials })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (successes_per_cluster_a, trials_per_cluster_a, successes_per_cluster_b, trials_per_cluster_b, method = "bootstrap", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(successes_per_cluster_a, trials_per_cluster_a, successes_per_cluster_b, trials_per_cluster_b, method='bootstrap', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares two proportions of binary outcomes that are correlated within clusters, such as the
+/// conversions of the sessions of every user when users are randomized.
+///
+/// A naive two-proportion z-test treats every trial as independent and overstates the significance when
+/// the outcomes of a cluster are correlated. "bootstrap" resamples whole clusters within every group and
+/// follows the conventions of `bootstrap` for the interval and the p-value. "rao_scott" is the
+/// first-order Rao–Scott correction: the trials and successes of every group are divided by its design
+/// effect, the ratio of the cluster-robust variance of its proportion to the binomial one (floored at 1),
+/// and the pooled two-proportion z-test is applied to these effective counts; the interval uses the
+/// cluster-robust variances with the normal approximation.
+///
+/// Args:
+///     successes_per_cluster_a (List[float]): The number of successes of every cluster of the first group.
+///     trials_per_cluster_a (List[float]): The number of trials of every cluster of the first group.
+///     successes_per_cluster_b (List[float]): The number of successes of every cluster of the second group.
+///     trials_per_cluster_b (List[float]): The number of trials of every cluster of the second group.
+///     method (str, optional): "bootstrap" or "rao_scott". Default is "bootstrap".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples, unused by "rao_scott".
+///         Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float), float, (float, float)]:
+///         A tuple containing:
+///         - proportion_a (float): The pooled proportion of the first group.
+///         - proportion_b (float): The pooled proportion of the second group.
+///         - difference (float): proportion_b - proportion_a.
+///         - (float, float): The confidence interval of the difference.
+///         - p_value (float): The p-value of equal proportions; one-sided, it is small when
+///           proportion_b is below proportion_a, as for `bootstrap`.
+///         - (float, float): The design effects of the two groups, at least 1 and above 1 when clustering
+///           inflates the variance.
+/// """
+pub fn clustered_proportion_test(
+    py: Python<'_>,
+    successes_per_cluster_a: Vec<f64>,
+    trials_per_cluster_a: Vec<f64>,
+    successes_per_cluster_b: Vec<f64>,
+    trials_per_cluster_b: Vec<f64>,
+    method: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<ClusteredProportions> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let a = clustered_group(&successes_per_cluster_a, &trials_per_cluster_a, "a")?;
+    let b = clustered_group(&successes_per_cluster_b, &trials_per_cluster_b, "b")?;
+    let (p_a, p_b) = (a.proportion(), b.proportion());
+    let difference = p_b - p_a;
+    let design_effects = (a.design_effect(p_a), b.design_effect(p_b));
+
+    let (interval, p) = match method {
+        "bootstrap" => {
+            let dist_a = Uniform::new(0, a.successes.len());
+            let dist_b = Uniform::new(0, b.successes.len());
+            let differences: Vec<f64> = py.allow_threads(|| {
+                resampler
+                    .map(n_resamples, |rng| {
+                        let p_a = a.resampled_proportion(&dist_a, rng);
+                        b.resampled_proportion(&dist_b, rng) - p_a
+                    })
+                    .into_iter()
+                    .filter(|d| !d.is_nan())
+                    .collect()
+            });
+            let q = differences.quantile(&[left_q, right_q]);
+            let p = (differences.iter().filter(|&&d| d > 0.0).count() as f64 + 1.0)
+                / (differences.len() + 1) as f64;
+            ((q[0], q[1]), p)
+        }
+        "rao_scott" => {
+            let effective = |group: &ClusteredGroup, design_effect: f64| {
+                (
+                    group.successes.iter().sum::<f64>() / design_effect,
+                    group.trials.iter().sum::<f64>() / design_effect,
+                )
+            };
+            let (y_a, n_a) = effective(&a, design_effects.0);
+            let (y_b, n_b) = effective(&b, design_effects.1);
+            let pooled = (y_a + y_b) / (n_a + n_b);
+            let se_null = (pooled * (1.0 - pooled) * (1.0 / n_a + 1.0 / n_b)).sqrt();
+            // A zero null error only arises with a pooled proportion of 0 or 1, so without difference.
+            let z = if se_null == 0.0 {
+                0.0
+            } else {
+                difference / se_null
+            };
+            let se = (a.cluster_variance(p_a) + b.cluster_variance(p_b)).sqrt();
+            let z_interval = norm_ppf(right_q);
+            (
+                (difference - z_interval * se, difference + z_interval * se),
+                norm_cdf(z),
+            )
+        }
+        _ => raise!(
+            InvalidInputError,
+            "method must be either 'bootstrap' or 'rao_scott', got '{method}'"
+        ),
+    };
+    Ok((
+        p_a,
+        p_b,
+        difference,
+        interval,
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+        design_effects,
+    ))
+}
@@ -0,0 +1,285 @@
+use crate::errors::*;
+use crate::special::*;
+use ndarray::Zip;
+use num_bigint::BigUint;
+use numpy::{IntoPyArray, PyArrayDyn, PyReadonlyArrayDyn};
+use num_traits::{One, Zero};
+use pyo3::prelude::*;
+
+#[pyfunction]
+pub fn binom(n: u16, k: u16) -> f64
+{
+    if k > n {
+        0.0
+    }
+    else if k == 0 || n == k {
+        1.0
+    }
+    else {
+        fn fold(mut start: f64, end: f64) -> f64 {
+            let mut mul = 1.0;
+            loop {
+                mul *= start;
+                start += 1.0;
+                if start > end {
+                    break mul;
+                }
+            }
+        }
+        if k > n-k {
+            fold(k as f64 + 1.0, n as f64) / fold(1.0, (n - k) as f64)
+            }
+        else {
+            fold((n - k) as f64 + 1.0, n as f64) / fold(1.0, k as f64)
+            }
+    }
+}
+
+#[pyfunction(signature = (n_array, k_array))]
+#[pyo3(text_signature = "(n_array, k_array)")]
+/// """
+/// Computes binomial coefficients elementwise over two integer NumPy arrays, in parallel.
+///
+/// Args:
+///     n_array (numpy.ndarray): The int64 array of n, with values in [0, 65535].
+///     k_array (numpy.ndarray): The int64 array of k, with the shape of `n_array` and 0 <= k <= n.
+///
+/// Returns:
+///     numpy.ndarray: The float64 array of `binom(n, k)`, with the shape of the inputs.
+/// """
+pub fn binom_array<'py>(
+    py: Python<'py>,
+    n_array: PyReadonlyArrayDyn<'py, i64>,
+    k_array: PyReadonlyArrayDyn<'py, i64>,
+) -> PyResult<Bound<'py, PyArrayDyn<f64>>> {
+    let (n_array, k_array) = (n_array.as_array(), k_array.as_array());
+    if n_array.shape() != k_array.shape() {
+        raise!(InvalidInputError, "n_array and k_array must have the same shape")
+    }
+    if Zip::from(&n_array)
+        .and(&k_array)
+        .fold(false, |invalid, &n, &k| {
+            invalid || !(0..=u16::MAX as i64).contains(&n) || k < 0 || k > n
+        })
+    {
+        raise!(InvalidInputError, "binom_array requires 0 <= k <= n <= 65535")
+    }
+    let result = py.allow_threads(|| {
+        Zip::from(&n_array)
+            .and(&k_array)
+            .par_map_collect(|&n, &k| binom(n as u16, k as u16))
+    });
+    Ok(result.into_pyarray(py))
+}
+
+/// Product of the integers in `range`, exactly.
+fn product(range: std::ops::RangeInclusive<u64>) -> BigUint {
+    range.fold(BigUint::one(), |acc, x| acc * x)
+}
+
+/// Exact binomial coefficient, built so that every intermediate value is itself a binomial coefficient.
+fn comb_big(n: u64, k: u64) -> BigUint {
+    if k > n {
+        return BigUint::zero();
+    }
+    let k = k.min(n - k);
+    (0..k).fold(BigUint::one(), |acc, i| acc * (n - i) / (i + 1))
+}
+
+#[pyfunction(signature = (n))]
+#[pyo3(text_signature = "(n)")]
+/// """
+/// Computes n! exactly.
+///
+/// Args:
+///     n (int): A non-negative integer.
+///
+/// Returns:
+///     int: The factorial of `n`.
+/// """
+pub fn factorial(py: Python<'_>, n: u64) -> BigUint {
+    py.allow_threads(|| product(1..=n))
+}
+
+#[pyfunction(signature = (n))]
+#[pyo3(text_signature = "(n)")]
+/// """
+/// Computes ln(n!) without overflow.
+///
+/// Args:
+///     n (int): A non-negative integer.
+///
+/// Returns:
+///     float: The natural logarithm of the factorial of `n`.
+/// """
+pub fn lfactorial(n: u64) -> f64 {
+    ln_factorial(n)
+}
+
+#[pyfunction(name = "perm", signature = (n, k))]
+#[pyo3(text_signature = "(n, k)")]
+/// """
+/// Computes the number of ordered arrangements of k items out of n, n! / (n - k)!, exactly.
+///
+/// Args:
+///     n (int): The number of items.
+///     k (int): The number of items arranged.
+///
+/// Returns:
+///     int: The number of k-permutations of `n`, 0 when k > n.
+/// """
+pub fn perm_exact(n: u64, k: u64) -> BigUint {
+    if k > n {
+        BigUint::zero()
+    } else {
+        product(n - k + 1..=n)
+    }
+}
+
+#[pyfunction(signature = (n, k))]
+#[pyo3(text_signature = "(n, k)")]
+/// """
+/// Computes ln(n! / (n - k)!) without overflow.
+///
+/// Args:
+///     n (int): The number of items.
+///     k (int): The number of items arranged.
+///
+/// Returns:
+///     float: The natural logarithm of `perm(n, k)`, -inf when k > n.
+/// """
+pub fn lperm(n: u64, k: u64) -> f64 {
+    if k > n {
+        return f64::NEG_INFINITY;
+    }
+    ln_falling_factorial(n, k)
+}
+
+#[pyfunction(signature = (n, k))]
+#[pyo3(text_signature = "(n, k)")]
+/// """
+/// Computes the binomial coefficient exactly, unlike `binom` which returns a float.
+///
+/// Args:
+///     n (int): The number of items.
+///     k (int): The number of items chosen.
+///
+/// Returns:
+///     int: The number of k-combinations of `n`, 0 when k > n.
+/// """
+pub fn comb_exact(n: u64, k: u64) -> BigUint {
+    comb_big(n, k)
+}
+
+#[pyfunction(signature = (n, k))]
+#[pyo3(text_signature = "(n, k)")]
+/// """
+/// Computes the logarithm of the binomial coefficient without overflow.
+///
+/// Args:
+///     n (int): The number of items.
+///     k (int): The number of items chosen.
+///
+/// Returns:
+///     float: The natural logarithm of `comb_exact(n, k)`, -inf when k > n.
+/// """
+pub fn lcomb(n: u64, k: u64) -> f64 {
+    if k > n {
+        return f64::NEG_INFINITY;
+    }
+    ln_binomial(n, k)
+}
+
+#[pyfunction(signature = (ks))]
+#[pyo3(text_signature = "(ks)")]
+/// """
+/// Computes the multinomial coefficient (k_1 + ... + k_m)! / (k_1! ... k_m!) exactly.
+///
+/// Args:
+///     ks (List[int]): The group sizes.
+///
+/// Returns:
+///     int: The number of ways to split sum(ks) items into groups of these sizes.
+/// """
+pub fn multinomial(ks: Vec<u64>) -> BigUint {
+    let mut total = 0;
+    ks.iter().fold(BigUint::one(), |acc, &k| {
+        total += k;
+        acc * comb_big(total, k)
+    })
+}
+
+#[pyfunction(signature = (ks))]
+#[pyo3(text_signature = "(ks)")]
+/// """
+/// Computes the logarithm of the multinomial coefficient without overflow.
+///
+/// Args:
+///     ks (List[int]): The group sizes.
+///
+/// Returns:
+///     float: The natural logarithm of `multinomial(ks)`.
+/// """
+pub fn lmultinomial(ks: Vec<u64>) -> f64 {
+    ln_multinomial(&ks)
+}
+
+#[pyfunction(signature = (n, k))]
+#[pyo3(text_signature = "(n, k)")]
+/// """
+/// Computes the Stirling number of the second kind S(n, k) exactly: the number of ways to partition n
+/// labelled items into k non-empty unlabelled groups.
+///
+/// Args:
+///     n (int): The number of items.
+///     k (int): The number of groups.
+///
+/// Returns:
+///     int: S(n, k), 0 when k > n.
+/// """
+pub fn stirling2(py: Python<'_>, n: u64, k: u64) -> BigUint {
+    if k > n {
+        return BigUint::zero();
+    }
+    py.allow_threads(|| {
+        // Row i of the recurrence S(i, j) = j * S(i - 1, j) + S(i - 1, j - 1), for j = 0..=k.
+        let mut row = vec![BigUint::zero(); k as usize + 1];
+        row[0] = BigUint::one();
+        for i in 1..=n {
+            for j in (1..=k.min(i) as usize).rev() {
+                row[j] = &row[j] * j + &row[j - 1];
+            }
+            row[0] = BigUint::zero();
+        }
+        row.swap_remove(k as usize)
+    })
+}
+
+#[pyfunction(signature = (n))]
+#[pyo3(text_signature = "(n)")]
+/// """
+/// Computes the Bell number B(n) exactly: the number of partitions of n labelled items.
+///
+/// Args:
+///     n (int): The number of items.
+///
+/// Returns:
+///     int: B(n), the sum of S(n, k) over k.
+/// """
+pub fn bell(py: Python<'_>, n: u64) -> BigUint {
+    py.allow_threads(|| {
+        // Bell triangle: every row starts with the last value of the previous one, and B(n) is the
+        // first value of row n.
+        let mut row = vec![BigUint::one()];
+        for _ in 0..n {
+            let mut next = Vec::with_capacity(row.len() + 1);
+            next.push(row[row.len() - 1].clone());
+            for value in &row {
+                let sum = &next[next.len() - 1] + value;
+                next.push(sum);
+            }
+            row = next;
+        }
+        row.swap_remove(0)
+    })
+}
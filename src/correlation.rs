@@ -0,0 +1,209 @@
+use crate::diagnostics::*;
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::prelude::*;
+
+fn pearson(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let (mean_x, mean_y) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let (cov, var_x, var_y) =
+        x.iter()
+            .zip(y)
+            .fold((0.0, 0.0, 0.0), |(cov, var_x, var_y), (a, b)| {
+                let (dx, dy) = (a - mean_x, b - mean_y);
+                (cov + dx * dy, var_x + dx * dx, var_y + dy * dy)
+            });
+    cov / (var_x * var_y).sqrt()
+}
+
+/// Spearman's rho from dense ids: Pearson correlation of the average ranks, which is tie-corrected.
+fn spearman_ids(x_ids: &[usize], y_ids: &[usize], n_x: usize, n_y: usize) -> f64 {
+    let mut counts_x = vec![0; n_x];
+    let mut counts_y = vec![0; n_y];
+    for (&a, &b) in x_ids.iter().zip(y_ids) {
+        counts_x[a] += 1;
+        counts_y[b] += 1;
+    }
+    let (ranks_x, ranks_y) = (ranks_from_counts(&counts_x), ranks_from_counts(&counts_y));
+    let rx: Vec<f64> = x_ids.iter().map(|&a| ranks_x[a]).collect();
+    let ry: Vec<f64> = y_ids.iter().map(|&b| ranks_y[b]).collect();
+    pearson(&rx, &ry)
+}
+
+/// Number of tied pairs within runs of equal values of a sorted slice.
+fn tied_pairs<T: PartialEq>(sorted: &[T]) -> u64 {
+    let mut total = 0;
+    let mut run = 1u64;
+    for pos in 1..=sorted.len() {
+        if pos < sorted.len() && sorted[pos] == sorted[pos - 1] {
+            run += 1;
+        } else {
+            total += run * (run - 1) / 2;
+            run = 1;
+        }
+    }
+    total
+}
+
+/// Merge sort counting the number of swaps (discordant pairs).
+fn sort_counting_swaps(values: &mut [usize], buffer: &mut [usize]) -> u64 {
+    let n = values.len();
+    if n < 2 {
+        return 0;
+    }
+    let mid = n / 2;
+    let mut swaps = sort_counting_swaps(&mut values[..mid], &mut buffer[..mid])
+        + sort_counting_swaps(&mut values[mid..], &mut buffer[mid..]);
+    let (mut i, mut j, mut k) = (0, mid, 0);
+    while i < mid && j < n {
+        if values[j] < values[i] {
+            buffer[k] = values[j];
+            swaps += (mid - i) as u64;
+            j += 1;
+        } else {
+            buffer[k] = values[i];
+            i += 1;
+        }
+        k += 1;
+    }
+    buffer[k..k + mid - i].copy_from_slice(&values[i..mid]);
+    let k = k + mid - i;
+    buffer[k..n].copy_from_slice(&values[j..n]);
+    values.copy_from_slice(&buffer[..n]);
+    swaps
+}
+
+/// Kendall's tau-b from dense ids in O(n log n) (Knight's algorithm).
+fn kendall_ids(x_ids: &[usize], y_ids: &[usize]) -> f64 {
+    let n = x_ids.len() as u64;
+    let mut pairs: Vec<(usize, usize)> = x_ids.iter().copied().zip(y_ids.iter().copied()).collect();
+    pairs.sort_unstable();
+    let x_sorted: Vec<usize> = pairs.iter().map(|p| p.0).collect();
+    let ties_x = tied_pairs(&x_sorted);
+    let ties_xy = tied_pairs(&pairs);
+    let mut y_sorted: Vec<usize> = pairs.iter().map(|p| p.1).collect();
+    let mut buffer = vec![0; y_sorted.len()];
+    let swaps = sort_counting_swaps(&mut y_sorted, &mut buffer);
+    let ties_y = tied_pairs(&y_sorted);
+    let total = n * (n - 1) / 2;
+    let numerator =
+        total as f64 - ties_x as f64 - ties_y as f64 + ties_xy as f64 - 2.0 * swaps as f64;
+    numerator / (((total - ties_x) as f64) * ((total - ties_y) as f64)).sqrt()
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (x, y, method = "spearman", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(x, y, method='spearman', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Computes Spearman's rho or Kendall's tau-b with a percentile bootstrap confidence interval.
+///
+/// Tied values receive average ranks, so both statistics are tie-corrected. This matters for bootstrap
+/// resamples, which are full of ties by construction: every resample is re-ranked in linear time from
+/// the multiplicities of the original distinct values, and tau-b uses an O(n log n) merge-sort kernel.
+/// A constant `x` or `y` has no rank correlation and raises InsufficientDataError; resamples that
+/// happen to be constant are left out of the interval, with a warning when they are many.
+///
+/// Args:
+///     x (List[float]): The first variable.
+///     y (List[float]): The second variable, paired with `x`.
+///     method (str, optional): Either "spearman" or "kendall". Default is "spearman".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, (float, float)]:
+///         A tuple containing:
+///         - statistic (float): The tie-corrected correlation coefficient.
+///         - p_value (float): The bootstrap p-value for the hypothesis of no correlation
+///           (two-sided or one-sided depending on `two_sided`).
+///         - (float, float): The confidence interval bounds for the coefficient.
+/// """
+pub fn correlation_bootstrap(
+    py: Python<'_>,
+    x: Vec<f64>,
+    y: Vec<f64>,
+    method: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, (f64, f64))> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let n = x.len();
+    if n != y.len() {
+        raise!(InvalidInputError, "x and y must have same size")
+    }
+    check_no_nan("x", &x)?;
+    check_no_nan("y", &y)?;
+    if n < 2 {
+        raise!(
+            InsufficientDataError,
+            "At least 2 observations are required"
+        )
+    }
+    let kendall = match method {
+        "spearman" => false,
+        "kendall" => true,
+        _ => raise!(
+            InvalidInputError,
+            "method must be either 'spearman' or 'kendall'"
+        ),
+    };
+    let (x_ids, n_x) = dense_ids(&x);
+    let (y_ids, n_y) = dense_ids(&y);
+    if n_x == 1 || n_y == 1 {
+        raise!(
+            InsufficientDataError,
+            "x and y must not be constant, the rank correlation of a constant sample is undefined"
+        )
+    }
+    let statistic = |x_ids: &[usize], y_ids: &[usize]| {
+        if kendall {
+            kendall_ids(x_ids, y_ids)
+        } else {
+            spearman_ids(x_ids, y_ids, n_x, n_y)
+        }
+    };
+    let observed = statistic(&x_ids, &y_ids);
+
+    let dist = rand::distributions::Uniform::new(0, n);
+    let resampled: Vec<f64> = py.allow_threads(|| {
+        resampler
+            .map(n_resamples, |rng| {
+                let (rx, ry): (Vec<usize>, Vec<usize>) = (0..n)
+                    .map(|_| {
+                        let idx = dist.sample(rng);
+                        (x_ids[idx], y_ids[idx])
+                    })
+                    .unzip();
+                statistic(&rx, &ry)
+            })
+            .into_iter()
+            .filter(|s| !s.is_nan())
+            .collect()
+    });
+    let n_valid = resampled.len() as u64;
+    check_dropped(py, n_resamples as usize - resampled.len(), n_resamples as usize)?;
+    check_any_defined(resampled.len())?;
+
+    let p: f64 =
+        (resampled.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0) / (n_valid + 1) as f64;
+    let p_value = (2.0 - 2.0 * p).min(p * 2.0);
+    let q = resampled.quantile(&[left_q, right_q]);
+    Ok((observed, if two_sided { p_value } else { p }, (q[0], q[1])))
+}
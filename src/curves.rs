@@ -0,0 +1,232 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+
+/// Evaluation points used when `points` is omitted: the deciles 0.1, ..., 0.9.
+fn default_points(points: Option<Vec<f64>>) -> PyResult<Vec<f64>> {
+    let points = points.unwrap_or_else(|| (1..10).map(|d| d as f64 / 10.0).collect());
+    if points.iter().any(|p| !(0.0..=1.0).contains(p)) {
+        raise!(InvalidInputError, "points must lie in [0, 1]")
+    }
+    Ok(points)
+}
+
+/// Lorenz curve of a sorted sample at `points`: the share of the total held by the lowest fraction `p`
+/// of observations, linearly interpolated between observations.
+fn lorenz_curve(sorted: &[f64], points: &[f64]) -> Vec<f64> {
+    let mut cumulative = Vec::with_capacity(sorted.len() + 1);
+    cumulative.push(0.0);
+    for &x in sorted {
+        cumulative.push(cumulative[cumulative.len() - 1] + x);
+    }
+    let n = sorted.len();
+    let total = cumulative[n];
+    points
+        .iter()
+        .map(|&p| {
+            let h = p * n as f64;
+            let k = (h.floor() as usize).min(n);
+            let partial = if k < n {
+                cumulative[k] + (h - k as f64) * sorted[k]
+            } else {
+                total
+            };
+            partial / total
+        })
+        .collect()
+}
+
+type LorenzOutput = (Vec<f64>, Vec<f64>, Vec<f64>, Vec<(f64, f64)>, f64);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, points = None, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, points=None, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares the Lorenz curves of two non-negative samples, such as revenue per user, to analyse
+/// a change in inequality.
+///
+/// Both samples are bootstrapped independently and the difference of their Lorenz curves is recomputed
+/// at every point. The confidence band is simultaneous: it covers the whole difference curve with the
+/// requested probability (max-modulus over the bootstrap distribution), so it can be read at all
+/// points at once without multiple-comparison inflation.
+///
+/// Args:
+///     a (List[float]): The first sample of non-negative values.
+///     b (List[float]): The second sample of non-negative values.
+///     points (List[float], optional): The population shares in [0, 1] at which the curves are evaluated.
+///         Default is None, which uses the deciles 0.1, ..., 0.9.
+///     confidence_level (float, optional): The family-wise confidence level of the band. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples, at least 2. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], List[float], List[(float, float)], float]:
+///         A tuple containing:
+///         - lorenz_a (List[float]): The Lorenz curve of `a` at every point.
+///         - lorenz_b (List[float]): The Lorenz curve of `b` at every point.
+///         - diffs (List[float]): The pointwise differences `lorenz_b - lorenz_a`.
+///         - band (List[(float, float)]): The simultaneous confidence band for the differences.
+///         - p_value (float): The p-value of the sup-test that the two Lorenz curves are equal.
+/// """
+pub fn lorenz_test(
+    py: Python<'_>,
+    mut a: Vec<f64>,
+    mut b: Vec<f64>,
+    points: Option<Vec<f64>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<LorenzOutput> {
+    if n_resamples < 2 {
+        raise!(
+            InvalidInputError,
+            "n_resamples must be at least 2, the band scales by the bootstrap standard deviation"
+        )
+    }
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let points = default_points(points)?;
+    for sample in [&a, &b] {
+        if sample.iter().any(|&x| x < 0.0) {
+            raise!(
+                InvalidInputError,
+                "Lorenz curves require non-negative values"
+            )
+        }
+        if sample.iter().sum::<f64>() <= 0.0 {
+            raise!(InvalidInputError, "Lorenz curves require a positive total")
+        }
+    }
+    a.sort_unstable_by(|x, y| x.total_cmp(y));
+    b.sort_unstable_by(|x, y| x.total_cmp(y));
+    let lorenz_a = lorenz_curve(&a, &points);
+    let lorenz_b = lorenz_curve(&b, &points);
+    let diffs: Vec<f64> = lorenz_a.iter().zip(&lorenz_b).map(|(x, y)| y - x).collect();
+
+    let sampler_a = IndexSampler::new([a.as_slice()]).or_raise()?;
+    let sampler_b = IndexSampler::new([b.as_slice()]).or_raise()?;
+    let replicates: Vec<Vec<f64>> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut resample_a: Vec<f64> = (0..a.len()).map(|_| sampler_a.draw(rng)[0]).collect();
+            let mut resample_b: Vec<f64> = (0..b.len()).map(|_| sampler_b.draw(rng)[0]).collect();
+            resample_a.sort_unstable_by(|x, y| x.total_cmp(y));
+            resample_b.sort_unstable_by(|x, y| x.total_cmp(y));
+            lorenz_curve(&resample_a, &points)
+                .iter()
+                .zip(&lorenz_curve(&resample_b, &points))
+                .map(|(x, y)| y - x)
+                .collect()
+        })
+    });
+    let (band, p_values) = max_modulus_band(&diffs, &replicates, confidence_level);
+    let p_value = p_values.into_iter().fold(1.0, f64::min);
+    Ok((lorenz_a, lorenz_b, diffs, band, p_value))
+}
+
+type QuantileOutput = (Vec<f64>, Vec<f64>, Vec<f64>, Vec<(f64, f64)>, Vec<f64>);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, quantiles = None, confidence_level = 0.95, n_resamples = 10_000, simultaneous = false, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, quantiles=None, confidence_level=0.95, n_resamples=10000, simultaneous=False, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Estimates quantile treatment effects, the differences `quantile_b(q) - quantile_a(q)` between two
+/// independent samples, with bootstrap confidence intervals and p-values.
+///
+/// Args:
+///     a (List[float]): The first (control) sample.
+///     b (List[float]): The second (treatment) sample.
+///     quantiles (List[float], optional): The quantile levels in [0, 1]. Default is None, which uses
+///         the deciles 0.1, ..., 0.9.
+///     confidence_level (float, optional): The confidence level for the intervals. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples, at least 2. Default is 10000.
+///     simultaneous (bool, optional): If True, the intervals form a band covering the whole difference
+///         curve with family-wise probability `confidence_level` (max-modulus over the bootstrap
+///         distribution), and the p-values are adjusted for the number of quantiles. If False, the
+///         intervals are pointwise percentile intervals. Default is False.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], List[float], List[(float, float)], List[float]]:
+///         A tuple containing:
+///         - quantiles_a (List[float]): The quantiles of `a`.
+///         - quantiles_b (List[float]): The quantiles of `b`.
+///         - diffs (List[float]): The quantile differences `quantiles_b - quantiles_a`.
+///         - intervals (List[(float, float)]): The confidence intervals for the differences.
+///         - p_values (List[float]): The two-sided p-value of every difference.
+/// """
+pub fn quantile_test(
+    py: Python<'_>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+    quantiles: Option<Vec<f64>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    simultaneous: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<QuantileOutput> {
+    if n_resamples < 2 {
+        raise!(
+            InvalidInputError,
+            "n_resamples must be at least 2, the band scales by the bootstrap standard deviation"
+        )
+    }
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let levels = default_points(quantiles)?;
+    if a.is_empty() || b.is_empty() {
+        raise!(InsufficientDataError, "Both samples must be non-empty")
+    }
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let quantiles_a = a.quantile(&levels);
+    let quantiles_b = b.quantile(&levels);
+    let diffs: Vec<f64> = quantiles_a
+        .iter()
+        .zip(&quantiles_b)
+        .map(|(x, y)| y - x)
+        .collect();
+
+    let sampler_a = IndexSampler::new([a.as_slice()]).or_raise()?;
+    let sampler_b = IndexSampler::new([b.as_slice()]).or_raise()?;
+    let replicates: Vec<Vec<f64>> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let resample_a: Vec<f64> = (0..a.len()).map(|_| sampler_a.draw(rng)[0]).collect();
+            let resample_b: Vec<f64> = (0..b.len()).map(|_| sampler_b.draw(rng)[0]).collect();
+            resample_a
+                .quantile(&levels)
+                .iter()
+                .zip(&resample_b.quantile(&levels))
+                .map(|(x, y)| y - x)
+                .collect()
+        })
+    });
+
+    let (intervals, p_values) = if simultaneous {
+        max_modulus_band(&diffs, &replicates, confidence_level)
+    } else {
+        (0..levels.len())
+            .map(|k| {
+                let column: Vec<f64> = replicates.iter().map(|r| r[k]).collect();
+                let p = (column.iter().filter(|&&d| d > 0.0).count() + 1) as f64
+                    / (n_resamples + 1) as f64;
+                let q = column.quantile(&[left_q, right_q]);
+                ((q[0], q[1]), (2.0 - 2.0 * p).min(2.0 * p))
+            })
+            .unzip()
+    };
+    Ok((quantiles_a, quantiles_b, diffs, intervals, p_values))
+}
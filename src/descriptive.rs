@@ -0,0 +1,328 @@
+use crate::errors::*;
+use numpy::PyReadonlyArray1;
+use pylars_core::descriptive::{self, WeightKind};
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyTuple};
+use std::borrow::Cow;
+
+/// A sample given as a float64 NumPy array, read in place when contiguous, or as a list of floats.
+pub enum Sample<'py> {
+    Array(PyReadonlyArray1<'py, f64>),
+    List(Vec<f64>),
+}
+
+impl<'py> FromPyObject<'py> for Sample<'py> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyTuple>() {
+            Ok(Sample::List(ob.extract()?))
+        } else {
+            Ok(Sample::Array(ob.extract()?))
+        }
+    }
+}
+
+impl Sample<'_> {
+    pub fn values(&self) -> Cow<'_, [f64]> {
+        match self {
+            Sample::Array(array) => match array.as_slice() {
+                Ok(slice) => Cow::Borrowed(slice),
+                Err(_) => Cow::Owned(array.as_array().to_vec()),
+            },
+            Sample::List(list) => Cow::Borrowed(list),
+        }
+    }
+}
+
+/// Runs a descriptive statistic on a sample without the GIL.
+fn describe(
+    py: Python<'_>,
+    values: &Sample<'_>,
+    f: impl Fn(&[f64]) -> pylars_core::Result<f64> + Sync,
+) -> PyResult<f64> {
+    let values = values.values();
+    py.allow_threads(|| f(&values)).or_raise()
+}
+
+#[pyfunction(signature = (values))]
+#[pyo3(text_signature = "(values)")]
+/// """
+/// Computes the mean of a sample in parallel.
+///
+/// Like the other descriptive statistics, it suits large samples such as the distributions returned
+/// by `bootstrap_vec`, and its rounding does not depend on the number of threads.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///
+/// Returns:
+///     float: The mean.
+/// """
+pub fn mean(py: Python<'_>, values: Sample<'_>) -> PyResult<f64> {
+    describe(py, &values, descriptive::mean)
+}
+
+#[pyfunction(signature = (values, ddof = 1))]
+#[pyo3(text_signature = "(values, ddof=1)")]
+/// """
+/// Computes the variance of a sample in parallel.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     ddof (int, optional): The degrees of freedom removed from the denominator, 1 for the sample
+///         variance and 0 for the population variance. Default is 1.
+///
+/// Returns:
+///     float: The variance.
+/// """
+pub fn var(py: Python<'_>, values: Sample<'_>, ddof: usize) -> PyResult<f64> {
+    describe(py, &values, |values| descriptive::variance(values, ddof))
+}
+
+#[pyfunction(name = "std", signature = (values, ddof = 1))]
+#[pyo3(text_signature = "(values, ddof=1)")]
+/// """
+/// Computes the standard deviation of a sample in parallel.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     ddof (int, optional): The degrees of freedom removed from the denominator of the variance.
+///         Default is 1.
+///
+/// Returns:
+///     float: The standard deviation.
+/// """
+pub fn std_dev(py: Python<'_>, values: Sample<'_>, ddof: usize) -> PyResult<f64> {
+    describe(py, &values, |values| descriptive::std_dev(values, ddof))
+}
+
+#[pyfunction(signature = (values))]
+#[pyo3(text_signature = "(values)")]
+/// """
+/// Computes the biased sample skewness `m3 / m2^1.5` of a sample in parallel.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///
+/// Returns:
+///     float: The skewness.
+/// """
+pub fn skewness(py: Python<'_>, values: Sample<'_>) -> PyResult<f64> {
+    describe(py, &values, descriptive::skewness)
+}
+
+#[pyfunction(signature = (values))]
+#[pyo3(text_signature = "(values)")]
+/// """
+/// Computes the biased sample excess kurtosis `m4 / m2^2 - 3` of a sample in parallel.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///
+/// Returns:
+///     float: The excess kurtosis, 0 for normal data.
+/// """
+pub fn kurtosis(py: Python<'_>, values: Sample<'_>) -> PyResult<f64> {
+    describe(py, &values, descriptive::kurtosis)
+}
+
+#[pyfunction(signature = (values))]
+#[pyo3(text_signature = "(values)")]
+/// """
+/// Computes the median of a sample, sorting a copy in parallel.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///
+/// Returns:
+///     float: The median.
+/// """
+pub fn median(py: Python<'_>, values: Sample<'_>) -> PyResult<f64> {
+    describe(py, &values, descriptive::median)
+}
+
+#[pyfunction(signature = (values, normal = false))]
+#[pyo3(text_signature = "(values, normal=False)")]
+/// """
+/// Computes the median absolute deviation from the median of a sample.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     normal (bool, optional): If True, multiplies the deviation by 1.4826, which estimates the
+///         standard deviation of normal data. Default is False.
+///
+/// Returns:
+///     float: The median absolute deviation.
+/// """
+pub fn mad(py: Python<'_>, values: Sample<'_>, normal: bool) -> PyResult<f64> {
+    let scale = if normal {
+        descriptive::MAD_NORMAL_SCALE
+    } else {
+        1.0
+    };
+    describe(py, &values, |values| descriptive::mad(values, scale))
+}
+
+#[pyfunction(signature = (values))]
+#[pyo3(text_signature = "(values)")]
+/// """
+/// Computes the interquartile range of a sample, the difference between its 0.75 and 0.25 quantiles.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///
+/// Returns:
+///     float: The interquartile range.
+/// """
+pub fn iqr(py: Python<'_>, values: Sample<'_>) -> PyResult<f64> {
+    describe(py, &values, descriptive::iqr)
+}
+
+/// Runs a weighted descriptive statistic on a sample and its weights without the GIL.
+fn describe_weighted(
+    py: Python<'_>,
+    values: &Sample<'_>,
+    weights: &Sample<'_>,
+    f: impl Fn(&[f64], &[f64]) -> pylars_core::Result<f64> + Sync,
+) -> PyResult<f64> {
+    let (values, weights) = (values.values(), weights.values());
+    py.allow_threads(|| f(&values, &weights)).or_raise()
+}
+
+#[pyfunction(signature = (values, weights))]
+#[pyo3(text_signature = "(values, weights)")]
+/// """
+/// Computes the weighted mean `sum(w * x) / sum(w)` of a sample in parallel.
+///
+/// It is the estimate compared by `permutation_test` with `weights`, and is the same for frequency and
+/// probability weights.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     weights (numpy.ndarray or List[float]): The non-negative weight of every value.
+///
+/// Returns:
+///     float: The weighted mean.
+/// """
+pub fn weighted_mean(py: Python<'_>, values: Sample<'_>, weights: Sample<'_>) -> PyResult<f64> {
+    describe_weighted(py, &values, &weights, descriptive::weighted_mean)
+}
+
+#[pyfunction(signature = (values, weights, kind = "frequency"))]
+#[pyo3(text_signature = "(values, weights, kind='frequency')")]
+/// """
+/// Computes the unbiased weighted variance of a sample in parallel.
+///
+/// With frequency weights, every weight counts the observations of its value and the variance is that
+/// of the expanded sample, `sum(w (x - m)^2) / (sum(w) - 1)`. With probability weights (survey or
+/// importance weights, where only relative weights matter) the denominator is `V1 - V2 / V1`, with
+/// `V1 = sum(w)` and `V2 = sum(w^2)`, which is unchanged when all weights are scaled.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     weights (numpy.ndarray or List[float]): The non-negative weight of every value.
+///     kind (str, optional): "frequency" or "probability". Default is "frequency".
+///
+/// Returns:
+///     float: The weighted variance.
+/// """
+pub fn weighted_var(
+    py: Python<'_>,
+    values: Sample<'_>,
+    weights: Sample<'_>,
+    kind: &str,
+) -> PyResult<f64> {
+    let kind = WeightKind::parse(kind).or_raise()?;
+    describe_weighted(py, &values, &weights, |values, weights| {
+        descriptive::weighted_variance(values, weights, kind)
+    })
+}
+
+#[pyfunction(signature = (values, weights, q, kind = "frequency"))]
+#[pyo3(text_signature = "(values, weights, q, kind='frequency')")]
+/// """
+/// Computes a weighted quantile of a sample.
+///
+/// With frequency weights, it is the quantile of the sample where every value is repeated as many
+/// times as its weight, interpolated between order statistics as the quantiles of `bootstrap_ci`.
+/// With probability weights, it is the smallest value at which the weighted empirical distribution
+/// function reaches `q`, which does not depend on the scale of the weights.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     weights (numpy.ndarray or List[float]): The non-negative weight of every value.
+///     q (float): The quantile level in [0, 1].
+///     kind (str, optional): "frequency" or "probability". Default is "frequency".
+///
+/// Returns:
+///     float: The weighted quantile.
+/// """
+pub fn weighted_quantile(
+    py: Python<'_>,
+    values: Sample<'_>,
+    weights: Sample<'_>,
+    q: f64,
+    kind: &str,
+) -> PyResult<f64> {
+    let kind = WeightKind::parse(kind).or_raise()?;
+    describe_weighted(py, &values, &weights, |values, weights| {
+        descriptive::weighted_quantile(values, weights, q, kind)
+    })
+}
+
+#[pyfunction(signature = (values, bins = 10, range = None))]
+#[pyo3(text_signature = "(values, bins=10, range=None)")]
+/// """
+/// Counts the values of a sample in equal-width bins, in parallel.
+///
+/// Every bin includes its lower edge and the last one also its upper edge, and values outside the
+/// range are left out, as for `numpy.histogram`, so a distribution of hundreds of millions of values
+/// can be plotted without downsampling.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     bins (int, optional): The number of bins. Default is 10.
+///     range (Tuple[float, float], optional): The lower and upper edges of the bins. Default is None,
+///         which is the range of the values.
+///
+/// Returns:
+///     Tuple[List[int], List[float]]: The count of every bin and the bins + 1 edges.
+/// """
+pub fn histogram(
+    py: Python<'_>,
+    values: Sample<'_>,
+    bins: usize,
+    range: Option<(f64, f64)>,
+) -> PyResult<(Vec<u64>, Vec<f64>)> {
+    let values = values.values();
+    py.allow_threads(|| descriptive::histogram(&values, bins, range))
+        .or_raise()
+}
+
+#[pyfunction(signature = (values, grid, bandwidth = None))]
+#[pyo3(text_signature = "(values, grid, bandwidth=None)")]
+/// """
+/// Evaluates a Gaussian kernel density estimate of a sample on a grid, in parallel.
+///
+/// The sample is linearly binned on a regular grid of 4096 points spanning its range before the kernel
+/// sums, so the cost is a single pass over the values plus a term that depends only on the size of the
+/// grid, and the binning error is negligible unless the bandwidth is below a few bin widths.
+///
+/// Args:
+///     values (numpy.ndarray or List[float]): The sample, a float64 array or a list of floats.
+///     grid (List[float]): The points where the density is evaluated.
+///     bandwidth (float, optional): The standard deviation of the kernel. Default is None, which is
+///         Silverman's rule 0.9 * min(sd, iqr / 1.34) * n^(-1/5).
+///
+/// Returns:
+///     Tuple[List[float], float]: The density at every grid point, and the bandwidth used.
+/// """
+pub fn kde(
+    py: Python<'_>,
+    values: Sample<'_>,
+    grid: Vec<f64>,
+    bandwidth: Option<f64>,
+) -> PyResult<(Vec<f64>, f64)> {
+    let values = values.values();
+    py.allow_threads(|| descriptive::kde(&values, &grid, bandwidth))
+        .or_raise()
+}
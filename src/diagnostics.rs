@@ -0,0 +1,155 @@
+use crate::errors::*;
+use crate::tools::*;
+use pyo3::create_exception;
+use pyo3::exceptions::PyUserWarning;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::ffi::CString;
+use std::time::Instant;
+
+create_exception!(
+    pylars,
+    PylarsWarning,
+    PyUserWarning,
+    "Warning about inputs or results that make a test unreliable."
+);
+
+/// Groups smaller than this get a warning, the resampling distributions being too coarse below it.
+const MIN_GROUP_SIZE: usize = 10;
+
+/// Groups whose absolute skewness exceeds this get a warning, the mean converging slowly.
+const MAX_ABS_SKEWNESS: f64 = 10.0;
+
+/// Share of dropped NaN statistics above which a warning is emitted.
+const MAX_NAN_SHARE: f64 = 0.01;
+
+/// Emits a `PylarsWarning` from the calling Python line, raising it if warnings are turned into errors.
+pub fn warn(py: Python<'_>, message: &str) -> PyResult<()> {
+    PyErr::warn(
+        py,
+        &py.get_type::<PylarsWarning>(),
+        &CString::new(message)?,
+        1,
+    )
+}
+
+/// Warns about tiny, constant or extremely skewed groups of a two-sample test. With four columns, the
+/// groups are the numerators.
+pub fn check_groups(py: Python<'_>, columns: &[&[f64]]) -> PyResult<()> {
+    let groups: Vec<&[f64]> = match columns.len() {
+        4 => vec![columns[0], columns[2]],
+        _ => columns.to_vec(),
+    };
+    for (i, group) in groups.iter().enumerate() {
+        let n = group.len();
+        if n == 0 {
+            continue;
+        }
+        if n < MIN_GROUP_SIZE {
+            warn(
+                py,
+                &format!("group {} has only {n} observations, the resampled p-value and interval are unreliable below {MIN_GROUP_SIZE}", i + 1),
+            )?;
+        }
+        if is_constant(group) {
+            warn(py, &format!("group {} is constant (zero variance)", i + 1))?;
+        } else if n >= 3 {
+            let skew = skewness(group);
+            if skew.abs() > MAX_ABS_SKEWNESS {
+                warn(
+                    py,
+                    &format!("group {} is extremely skewed (skewness {skew:.1}), the distribution of its mean may be far from its asymptotic form; consider capping outliers", i + 1),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Warns when more than 1% of the resampled statistics were undefined and dropped.
+pub fn check_dropped(py: Python<'_>, dropped: usize, total: usize) -> PyResult<()> {
+    if total > 0 && dropped as f64 > MAX_NAN_SHARE * total as f64 {
+        warn(
+            py,
+            &format!("{dropped} of {total} resampled statistics were NaN and dropped, the interval only reflects the resamples where the statistic is defined"),
+        )?;
+    }
+    Ok(())
+}
+
+/// Raises when every resampled statistic was NaN and dropped, so there is no distribution to summarize.
+pub fn check_any_defined(kept: usize) -> PyResult<()> {
+    if kept == 0 {
+        raise!(
+            InsufficientDataError,
+            "Every resampled statistic was NaN, the statistic is undefined on these data"
+        )
+    }
+    Ok(())
+}
+
+/// Warns when units with a zero denominator were left out of a mean of ratios.
+pub fn check_zero_denominators(py: Python<'_>, dropped: usize, total: usize) -> PyResult<()> {
+    if dropped > 0 {
+        warn(
+            py,
+            &format!("{dropped} of {total} units have a zero denominator and were left out of the mean of ratios"),
+        )?;
+    }
+    Ok(())
+}
+
+/// Warns when an interval endpoint lies at or next to the minimum or maximum of the resampled
+/// distribution (at most one resample beyond it), i.e. when there are too few resamples for the
+/// confidence level.
+pub fn check_interval(py: Python<'_>, values: &[f64], interval: (f64, f64)) -> PyResult<()> {
+    let below = values.iter().filter(|&&x| x < interval.0).count();
+    let above = values.iter().filter(|&&x| x > interval.1).count();
+    let constant = is_constant(values);
+    if !constant && (below <= 1 || above <= 1) {
+        warn(
+            py,
+            "an interval endpoint is an extreme of the resampled distribution, increase n_resamples for this confidence level",
+        )?;
+    }
+    Ok(())
+}
+
+/// The lowest and highest bounds of several intervals.
+pub fn widest(intervals: &[(f64, f64)]) -> (f64, f64) {
+    intervals.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(low, high), &(l, h)| (low.min(l), high.max(h)),
+    )
+}
+
+/// Wall-clock breakdown of a call in seconds, reported with `timings=True`.
+#[derive(Default)]
+pub struct Timings {
+    /// Conversion of the Python inputs to Rust vectors.
+    pub conversion: f64,
+    /// Resampling runs, cached or not.
+    pub resampling: f64,
+    /// Sorting the resampled statistics into p-values and intervals.
+    pub quantiles: f64,
+}
+
+impl Timings {
+    /// Runs `f`, adding its duration to `phase`.
+    pub fn time<T>(phase: &mut f64, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        *phase += started.elapsed().as_secs_f64();
+        result
+    }
+
+    /// Dictionary of the phases and of the number of threads of the resampling pool.
+    pub fn into_dict(self, py: Python<'_>) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("conversion", self.conversion)?;
+        dict.set_item("resampling", self.resampling)?;
+        dict.set_item("quantiles", self.quantiles)?;
+        dict.set_item("threads", rayon::current_num_threads())?;
+        Ok(dict.into_any().unbind())
+    }
+}
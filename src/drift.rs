@@ -0,0 +1,275 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+use rand::seq::SliceRandom;
+
+type Psi = (f64, (f64, f64), f64, Vec<f64>, Vec<f64>);
+
+/// How the bins of `psi` are derived from the expected sample.
+#[derive(Clone, Copy)]
+enum Binning {
+    /// Edges at the quantiles of the expected sample, for bins of equal expected share.
+    Quantile,
+    /// Equal-width bins over the range of the expected sample.
+    Uniform,
+}
+
+impl Binning {
+    fn parse(binning: &str) -> PyResult<Binning> {
+        Ok(match binning {
+            "quantile" => Binning::Quantile,
+            "uniform" => Binning::Uniform,
+            _ => raise!(
+                InvalidInputError,
+                "binning must be either 'quantile' or 'uniform', got '{binning}'"
+            ),
+        })
+    }
+
+    /// Inner edges of `bins` bins of the expected sample, without duplicates; a value `x` falls in the
+    /// bin of the number of edges below it, the outer bins extending to infinity.
+    fn edges(&self, expected: &[f64], bins: usize) -> Vec<f64> {
+        let mut edges = match self {
+            Binning::Quantile => {
+                let levels: Vec<f64> = (1..bins).map(|k| k as f64 / bins as f64).collect();
+                expected.quantile(&levels)
+            }
+            Binning::Uniform => {
+                let (lo, hi) = expected
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &x| {
+                        (lo.min(x), hi.max(x))
+                    });
+                (1..bins)
+                    .map(|k| lo + (hi - lo) * k as f64 / bins as f64)
+                    .collect()
+            }
+        };
+        edges.dedup();
+        edges
+    }
+}
+
+/// Share of `sample` in every bin of the inner `edges`.
+fn bin_shares(sample: &[f64], edges: &[f64]) -> Vec<f64> {
+    let mut counts = vec![0.0; edges.len() + 1];
+    for &x in sample {
+        counts[edges.partition_point(|&e| e < x)] += 1.0;
+    }
+    counts.iter().map(|c| c / sample.len() as f64).collect()
+}
+
+/// Contribution `(a - e) ln(a / e)` of every bin to the population stability index, the shares being
+/// floored at `epsilon` so that empty bins stay finite.
+fn psi_contributions(expected: &[f64], actual: &[f64], edges: &[f64], epsilon: f64) -> Vec<f64> {
+    bin_shares(expected, edges)
+        .iter()
+        .zip(bin_shares(actual, edges))
+        .map(|(&e, a)| {
+            let (e, a) = (e.max(epsilon), a.max(epsilon));
+            (a - e) * (a / e).ln()
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (expected, actual, bins = 10, binning = "quantile", epsilon = 1e-4, confidence_level = 0.95, n_resamples = 2_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(expected, actual, bins=10, binning='quantile', epsilon=0.0001, confidence_level=0.95, n_resamples=2000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Computes the population stability index (PSI) between a reference and a current sample, with a
+/// bootstrap interval and a permutation p-value.
+///
+/// The bins are derived from the expected sample, by default at its deciles, and the index is
+/// `sum((a_i - e_i) * ln(a_i / e_i))` over the shares of the bins, floored at `epsilon`. Values
+/// around 0.1 and 0.25 are the usual thresholds of a moderate and a major shift, but the index grows
+/// with the number of bins and shrinks with the sample sizes; the interval resamples both samples, and
+/// the p-value is the share of permutations of the pooled samples with an index at least as large,
+/// the bins being derived again from every resampled or permuted expected sample.
+///
+/// Args:
+///     expected (List[float]): The reference sample, e.g. the training data of a model.
+///     actual (List[float]): The current sample.
+///     bins (int, optional): The number of bins. Default is 10.
+///     binning (str, optional): "quantile" for bins of equal expected share, or "uniform" for
+///         equal-width bins over the range of the expected sample. Default is "quantile".
+///     epsilon (float, optional): The floor of the shares of empty bins. Default is 0.0001.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples and of permutations.
+///         Default is 2000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, (float, float), float, List[float], List[float]]:
+///         A tuple containing:
+///         - psi (float): The population stability index.
+///         - (float, float): The bootstrap interval of the index.
+///         - p_value (float): The permutation p-value of identical distributions.
+///         - contributions (List[float]): The contribution of every bin to the index.
+///         - edges (List[float]): The inner edges of the bins, the outer bins extending to infinity.
+/// """
+pub fn psi(
+    py: Python<'_>,
+    expected: Vec<f64>,
+    actual: Vec<f64>,
+    bins: usize,
+    binning: &str,
+    epsilon: f64,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Psi> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let binning = Binning::parse(binning)?;
+    if expected.is_empty() || actual.is_empty() {
+        raise!(
+            InsufficientDataError,
+            "Both samples must contain observations"
+        )
+    }
+    if expected.iter().chain(&actual).any(|x| x.is_nan()) {
+        raise!(InvalidInputError, "The samples must not contain NaN values")
+    }
+    if bins < 2 {
+        raise!(InvalidInputError, "bins must be at least 2")
+    }
+    if epsilon.is_nan() || epsilon <= 0.0 {
+        raise!(InvalidInputError, "epsilon must be positive")
+    }
+    let index = |expected: &[f64], actual: &[f64]| {
+        let edges = binning.edges(expected, bins);
+        psi_contributions(expected, actual, &edges, epsilon)
+            .iter()
+            .sum::<f64>()
+    };
+    let edges = binning.edges(&expected, bins);
+    let contributions = psi_contributions(&expected, &actual, &edges, epsilon);
+    let observed = contributions.iter().sum::<f64>();
+
+    let (len_e, len_a) = (expected.len(), actual.len());
+    let pooled: Vec<f64> = expected.iter().chain(&actual).copied().collect();
+    let (dist_e, dist_a) = (Uniform::new(0, len_e), Uniform::new(0, len_a));
+    let (indices, null): (Vec<f64>, Vec<f64>) = py.allow_threads(|| {
+        resampler
+            .map(n_resamples, |rng| {
+                let resampled_e: Vec<f64> =
+                    (0..len_e).map(|_| expected[dist_e.sample(rng)]).collect();
+                let resampled_a: Vec<f64> =
+                    (0..len_a).map(|_| actual[dist_a.sample(rng)]).collect();
+                let mut permuted = pooled.clone();
+                permuted.shuffle(rng);
+                let (permuted_e, permuted_a) = permuted.split_at(len_e);
+                (
+                    index(&resampled_e, &resampled_a),
+                    index(permuted_e, permuted_a),
+                )
+            })
+            .into_iter()
+            .unzip()
+    });
+    let q = indices.quantile(&[left_q, right_q]);
+    let exceed = null
+        .iter()
+        .filter(|&&s| s >= observed * (1.0 - 1e-12))
+        .count();
+    let p_value = (exceed + 1) as f64 / (n_resamples + 1) as f64;
+    Ok((observed, (q[0], q[1]), p_value, contributions, edges))
+}
+
+/// 1-Wasserstein distance `integral |F_a - F_b|` between the two groups of the sorted pooled `values`,
+/// `in_a[k]` telling whether `values[k]` belongs to the first group of `len_a` values.
+fn wasserstein_sorted(values: &[f64], in_a: &[bool], len_a: usize) -> f64 {
+    let len_b = values.len() - len_a;
+    let (mut count_a, mut count_b) = (0.0, 0.0);
+    let mut distance = 0.0;
+    for k in 0..values.len() - 1 {
+        if in_a[k] {
+            count_a += 1.0;
+        } else {
+            count_b += 1.0;
+        }
+        let gap = (count_a / len_a as f64 - count_b / len_b as f64).abs();
+        distance += gap * (values[k + 1] - values[k]);
+    }
+    distance
+}
+
+#[pyfunction(signature = (a, b, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(a, b, n_resamples=10000, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Computes the Wasserstein (earth mover's) distance between two samples, with a permutation p-value.
+///
+/// The 1-D distance is the area between the two empirical distribution functions, in the units of the
+/// data, so it measures the magnitude of a shift of any shape while staying robust to a few extreme
+/// values. The pooled sample is sorted once, and every permutation only shuffles the group labels, so
+/// it costs a linear pass; the p-value is the share of permutations with a distance at least as large.
+///
+/// Args:
+///     a (List[float]): The first sample.
+///     b (List[float]): The second sample.
+///     n_resamples (int, optional): The number of permutations. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - distance (float): The 1-Wasserstein distance between the samples.
+///         - p_value (float): The permutation p-value of identical distributions.
+/// """
+pub fn wasserstein_test(
+    py: Python<'_>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64)> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    if a.is_empty() || b.is_empty() {
+        raise!(
+            InsufficientDataError,
+            "Both samples must contain observations"
+        )
+    }
+    if a.iter().chain(&b).any(|x| x.is_nan()) {
+        raise!(InvalidInputError, "The samples must not contain NaN values")
+    }
+    let len_a = a.len();
+    let mut pooled: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&x| (x, true))
+        .chain(b.iter().map(|&x| (x, false)))
+        .collect();
+    pooled.sort_unstable_by(|x, y| x.0.total_cmp(&y.0));
+    let (values, in_a): (Vec<f64>, Vec<bool>) = pooled.into_iter().unzip();
+    let observed = wasserstein_sorted(&values, &in_a, len_a);
+
+    let null = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut labels = in_a.clone();
+            labels.shuffle(rng);
+            wasserstein_sorted(&values, &labels, len_a)
+        })
+    });
+    let exceed = null
+        .iter()
+        .filter(|&&s| s >= observed * (1.0 - 1e-12))
+        .count();
+    Ok((observed, (exceed + 1) as f64 / (n_resamples + 1) as f64))
+}
@@ -0,0 +1,72 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(
+    pylars,
+    StatError,
+    PyException,
+    "Base class of the errors raised by pylars functions."
+);
+create_exception!(
+    pylars,
+    InvalidInputError,
+    StatError,
+    "The data or parameters cannot be used by the function, e.g. arrays of unequal length."
+);
+create_exception!(
+    pylars,
+    InsufficientDataError,
+    StatError,
+    "A sample is too small for the function, e.g. an empty array to resample."
+);
+create_exception!(
+    pylars,
+    ConvergenceError,
+    StatError,
+    "An iterative fit stopped at its iteration limit before converging."
+);
+create_exception!(
+    pylars,
+    BudgetExceededError,
+    StatError,
+    "A run would exceed a limit on its size, e.g. the number of exported indices."
+);
+
+/// Returns early from a Python-facing function with an error of the given `StatError` subclass,
+/// formatting its message like `format!`.
+macro_rules! raise {
+    ($kind:ident, $($message:tt)+) => {
+        return Err($crate::errors::$kind::new_err(format!($($message)+)))
+    };
+}
+pub(crate) use raise;
+
+/// Converts the result of a core kernel inside a Python-facing function.
+///
+/// Every core error is raised as the matching subclass of `StatError`, with its message; a data file
+/// that cannot be read raises `StatError` itself.
+pub trait OrRaise<T> {
+    fn or_raise(self) -> PyResult<T>;
+}
+
+impl<T> OrRaise<T> for Result<T, pylars_core::StatError> {
+    fn or_raise(self) -> PyResult<T> {
+        use pylars_core::StatError as Core;
+        self.map_err(|e| match e {
+            Core::InvalidInput(message) => InvalidInputError::new_err(message),
+            Core::InsufficientData(message) => InsufficientDataError::new_err(message),
+            Core::Io(message) => StatError::new_err(message),
+            Core::Convergence(message) => ConvergenceError::new_err(message),
+            Core::BudgetExceeded(message) => BudgetExceededError::new_err(message),
+        })
+    }
+}
+
+/// Rejects NaN values in the sample `name`, which have no place in its order or ranks.
+pub fn check_no_nan(name: &str, values: &[f64]) -> PyResult<()> {
+    if values.iter().any(|x| x.is_nan()) {
+        raise!(InvalidInputError, "{name} must not contain NaN values")
+    }
+    Ok(())
+}
@@ -0,0 +1,360 @@
+use crate::diagnostics::*;
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+use rayon::prelude::*;
+
+/// Largest number of projected gradient steps of a synthetic control fit.
+const MAX_FIT_ITERATIONS: usize = 10_000;
+
+/// Frank-Wolfe gap, relative to the squared norm of the centred series, below which a synthetic control
+/// fit has converged.
+const FIT_TOLERANCE: f64 = 1e-12;
+
+/// `synthetic_control` output: the average post-period effect, the donor weights, the gap of every
+/// period, the placebo p-value and the pre-period fit error.
+type SyntheticControlOutput = (f64, Vec<f64>, Vec<f64>, f64, f64);
+
+/// `geo_pair_test` output: the mean effect per pair, the relative lift, the interval and the p-value.
+type GeoPairOutput = (f64, f64, (f64, f64), f64);
+
+/// Inner product of two series of equal length.
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Euclidean projection of `v` onto the probability simplex.
+fn project_to_simplex(v: &[f64]) -> Vec<f64> {
+    let mut sorted = v.to_vec();
+    sorted.sort_unstable_by(|a, b| b.total_cmp(a));
+    let mut cumulative = 0.0;
+    let mut theta = 0.0;
+    for (k, &x) in sorted.iter().enumerate() {
+        cumulative += x;
+        let candidate = (cumulative - 1.0) / (k + 1) as f64;
+        if x - candidate > 0.0 {
+            theta = candidate;
+        }
+    }
+    v.iter().map(|x| (x - theta).max(0.0)).collect()
+}
+
+/// Non-negative weights summing to one that minimise the squared error between `target` and the
+/// weighted `donors` over the first `pre_periods` periods, by accelerated projected gradient descent.
+/// None when the descent has not converged after `MAX_FIT_ITERATIONS` steps.
+fn fit_weights(target: &[f64], donors: &[&[f64]], pre_periods: usize) -> Option<Vec<f64>> {
+    let j = donors.len();
+    // The weights sum to one, so removing the mean donor of every period from all the series leaves
+    // the squared error unchanged, and removes their common level: it would otherwise dominate the
+    // Lipschitz constant while playing no part on the simplex, and stall the descent.
+    let centre: Vec<f64> = (0..pre_periods)
+        .map(|t| donors.iter().map(|d| d[t]).sum::<f64>() / j as f64)
+        .collect();
+    let target: Vec<f64> = (0..pre_periods).map(|t| target[t] - centre[t]).collect();
+    let donors: Vec<Vec<f64>> = donors
+        .iter()
+        .map(|d| (0..pre_periods).map(|t| d[t] - centre[t]).collect())
+        .collect();
+    // Gram matrix and cross products of the pre-period series.
+    let gram: Vec<Vec<f64>> = donors
+        .iter()
+        .map(|a| donors.iter().map(|b| dot(a, b)).collect())
+        .collect();
+    let cross: Vec<f64> = donors.iter().map(|a| dot(a, &target)).collect();
+    let gradient =
+        |w: &[f64]| -> Vec<f64> { (0..j).map(|k| dot(&gram[k], w) - cross[k]).collect() };
+    // The trace bounds the largest eigenvalue, and so the Lipschitz constant of the gradient.
+    let trace = (0..j).map(|k| gram[k][k]).sum::<f64>();
+    let lipschitz = trace.max(f64::MIN_POSITIVE);
+    let scale = (trace + dot(&target, &target)).max(f64::MIN_POSITIVE);
+    let mut weights = vec![1.0 / j as f64; j];
+    let mut momentum = weights.clone();
+    let mut t: f64 = 1.0;
+    for _ in 0..MAX_FIT_ITERATIONS {
+        // The Frank-Wolfe gap bounds the excess squared error of the weights over the optimum. It
+        // converges even when similar donors leave the optimal weights themselves ill-determined.
+        let at_weights = gradient(&weights);
+        let gap = weights
+            .iter()
+            .zip(&at_weights)
+            .map(|(w, g)| w * g)
+            .sum::<f64>()
+            - at_weights.iter().copied().fold(f64::INFINITY, f64::min);
+        if gap <= FIT_TOLERANCE * scale {
+            return Some(weights);
+        }
+        let at_momentum = gradient(&momentum);
+        let step: Vec<f64> = momentum
+            .iter()
+            .zip(&at_momentum)
+            .map(|(w, g)| w - g / lipschitz)
+            .collect();
+        let next = project_to_simplex(&step);
+        // Adaptive restart: the momentum is dropped as soon as it points uphill, which keeps the
+        // descent fast on the ill-conditioned Gram matrices of similar donors.
+        let uphill = at_momentum
+            .iter()
+            .zip(next.iter().zip(&weights))
+            .map(|(g, (a, b))| g * (a - b))
+            .sum::<f64>()
+            > 0.0;
+        if uphill {
+            t = 1.0;
+        }
+        let t_next = (1.0 + (1.0 + 4.0 * t * t).sqrt()) / 2.0;
+        momentum = next
+            .iter()
+            .zip(&weights)
+            .map(|(a, b)| a + (t - 1.0) / t_next * (a - b))
+            .collect();
+        weights = next;
+        t = t_next;
+    }
+    None
+}
+
+/// Gap between `target` and its synthetic control of every period.
+fn gaps(target: &[f64], donors: &[&[f64]], weights: &[f64]) -> Vec<f64> {
+    (0..target.len())
+        .map(|t| {
+            target[t]
+                - donors
+                    .iter()
+                    .zip(weights)
+                    .map(|(d, w)| d[t] * w)
+                    .sum::<f64>()
+        })
+        .collect()
+}
+
+/// Root mean squared gap of a range of periods.
+fn rmspe(gaps: &[f64]) -> f64 {
+    (gaps.iter().map(|g| g * g).sum::<f64>() / gaps.len() as f64).sqrt()
+}
+
+#[pyfunction(signature = (treated_series, donor_matrix, pre_periods))]
+#[pyo3(text_signature = "(treated_series, donor_matrix, pre_periods)")]
+/// """
+/// Estimates the effect of an intervention on a single treated unit, such as a region, with a synthetic
+/// control, and tests it with placebo-in-space permutations.
+///
+/// The synthetic control is the weighted average of the donor (untreated) series, with non-negative
+/// weights summing to one, that best reproduces the treated series over the pre-intervention periods,
+/// fitted by constrained least squares. The effect of every period is the gap between the treated
+/// series and its synthetic control, and the estimate is the average gap after the intervention.
+///
+/// Inference follows Abadie et al.: every donor in turn is treated as if it had received the
+/// intervention and fitted on the other donors. The p-value is the rank of the treated unit among all
+/// units by the ratio of post- to pre-period root mean squared gap, which discounts placebos that were
+/// poorly fitted before the intervention. With `J` donors, the smallest attainable p-value is
+/// `1 / (J + 1)`.
+///
+/// Args:
+///     treated_series (List[float]): The metric of the treated unit in every period.
+///     donor_matrix (List[List[float]]): The series of every donor unit, one row per donor, over the same
+///         periods.
+///     pre_periods (int): The number of periods before the intervention, at the start of the series.
+///
+/// Returns:
+///     Tuple[float, List[float], List[float], float, float]:
+///         A tuple containing:
+///         - effect (float): The average gap over the post-intervention periods.
+///         - weights (List[float]): The weight of every donor in the synthetic control.
+///         - gaps (List[float]): The gap between the treated series and its synthetic control in every
+///           period.
+///         - p_value (float): The placebo-in-space p-value.
+///         - pre_rmspe (float): The root mean squared gap over the pre-intervention periods.
+/// """
+pub fn synthetic_control(
+    py: Python<'_>,
+    treated_series: Vec<f64>,
+    donor_matrix: Vec<Vec<f64>>,
+    pre_periods: usize,
+) -> PyResult<SyntheticControlOutput> {
+    let n_periods = treated_series.len();
+    if donor_matrix.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "At least two donor units are required"
+        )
+    }
+    if donor_matrix.iter().any(|donor| donor.len() != n_periods) {
+        raise!(
+            InvalidInputError,
+            "Every donor series must cover the periods of treated_series"
+        )
+    }
+    if pre_periods == 0 || pre_periods >= n_periods {
+        raise!(
+            InvalidInputError,
+            "pre_periods must leave at least one period before and one after the intervention"
+        )
+    }
+    // Unit 0 is the treated unit, the others the donors.
+    let units: Vec<&[f64]> = std::iter::once(treated_series.as_slice())
+        .chain(donor_matrix.iter().map(Vec::as_slice))
+        .collect();
+    let fit = |unit: usize| {
+        let donors: Vec<&[f64]> = (1..units.len())
+            .filter(|&k| k != unit)
+            .map(|k| units[k])
+            .collect();
+        let weights = fit_weights(units[unit], &donors, pre_periods)?;
+        let gaps = gaps(units[unit], &donors, &weights);
+        Some((weights, gaps))
+    };
+    let ratio = |gaps: &[f64]| rmspe(&gaps[pre_periods..]) / rmspe(&gaps[..pre_periods]);
+
+    let (weights, treated_gaps, placebo_ratios) = py
+        .allow_threads(|| {
+            let (weights, treated_gaps) = fit(0)?;
+            let placebo_ratios: Vec<f64> = (1..units.len())
+                .into_par_iter()
+                .map(|unit| Some(ratio(&fit(unit)?.1)))
+                .collect::<Option<_>>()?;
+            Some((weights, treated_gaps, placebo_ratios))
+        })
+        .ok_or_else(|| {
+            ConvergenceError::new_err(format!(
+                "The synthetic control fit has not converged after {MAX_FIT_ITERATIONS} iterations"
+            ))
+        })?;
+    let treated_ratio = ratio(&treated_gaps);
+    let more_extreme = placebo_ratios
+        .iter()
+        .filter(|&&r| r >= treated_ratio)
+        .count();
+    let p_value = (more_extreme + 1) as f64 / (placebo_ratios.len() + 1) as f64;
+    let post = &treated_gaps[pre_periods..];
+    let effect = post.iter().sum::<f64>() / post.len() as f64;
+    let pre_rmspe = rmspe(&treated_gaps[..pre_periods]);
+    Ok((effect, weights, treated_gaps, p_value, pre_rmspe))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (metric_by_geo_pre, metric_by_geo_post, treatment_geo_flags, pairs, confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(metric_by_geo_pre, metric_by_geo_post, treatment_geo_flags, pairs, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Analyses a matched-pairs geo experiment, where the geos were paired on their pre-period metric and
+/// one geo of every pair was randomly assigned to treatment.
+///
+/// The effect of a pair is the difference in differences `(post_t - pre_t) - (post_c - pre_c)` of its
+/// treated and control geos, and the estimate is the mean over pairs. The relative lift divides the sum
+/// of the pair effects by the sum of the counterfactual post-period metric of the treated geos. The
+/// p-value comes from randomization inference: under the null, swapping the assignment within any pair
+/// is as likely as the observed one, so it compares the estimate with random sign flips of the pair
+/// effects, with the conventions of `permutation_test`. The interval bootstraps whole pairs.
+///
+/// Args:
+///     metric_by_geo_pre (List[float]): The metric of every geo over the pre-period.
+///     metric_by_geo_post (List[float]): The metric of every geo over the post-period.
+///     treatment_geo_flags (List[bool]): Whether every geo is treated.
+///     pairs (List[Tuple[int, int]]): The indices of the geos of every pair, one treated and one control.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples and of sign flips. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, (float, float), float]:
+///         A tuple containing:
+///         - effect (float): The mean difference in differences per pair.
+///         - lift (float): The total effect relative to the counterfactual metric of the treated geos.
+///         - (float, float): The pair bootstrap interval of the effect.
+///         - p_value (float): The randomization p-value of a zero effect.
+/// """
+pub fn geo_pair_test(
+    py: Python<'_>,
+    metric_by_geo_pre: Vec<f64>,
+    metric_by_geo_post: Vec<f64>,
+    treatment_geo_flags: Vec<bool>,
+    pairs: Vec<(usize, usize)>,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<GeoPairOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let n_geos = metric_by_geo_pre.len();
+    if metric_by_geo_post.len() != n_geos || treatment_geo_flags.len() != n_geos {
+        raise!(
+            InvalidInputError,
+            "metric_by_geo_pre, metric_by_geo_post and treatment_geo_flags must have equal size"
+        )
+    }
+    if pairs.is_empty() {
+        raise!(InsufficientDataError, "At least one pair is required")
+    }
+    let mut paired = vec![false; n_geos];
+    let mut effects = Vec::with_capacity(pairs.len());
+    let mut counterfactual = 0.0;
+    for &(a, b) in &pairs {
+        if a >= n_geos || b >= n_geos {
+            raise!(
+                InvalidInputError,
+                "Pair ({a}, {b}) refers to a geo out of range"
+            )
+        }
+        if a == b || paired[a] || paired[b] {
+            raise!(
+                InvalidInputError,
+                "Every geo must belong to at most one pair"
+            )
+        }
+        (paired[a], paired[b]) = (true, true);
+        let (treated, control) = match (treatment_geo_flags[a], treatment_geo_flags[b]) {
+            (true, false) => (a, b),
+            (false, true) => (b, a),
+            _ => raise!(
+                InvalidInputError,
+                "Pair ({a}, {b}) must contain one treated and one control geo"
+            ),
+        };
+        let change = |geo: usize| metric_by_geo_post[geo] - metric_by_geo_pre[geo];
+        effects.push(change(treated) - change(control));
+        counterfactual += metric_by_geo_pre[treated] + change(control);
+    }
+    let n_pairs = effects.len();
+    let effect = effects.iter().sum::<f64>() / n_pairs as f64;
+    let lift = effect * n_pairs as f64 / counterfactual;
+
+    let dist = Uniform::new(0, n_pairs);
+    let draws: Vec<(f64, f64)> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let resampled = (0..n_pairs).map(|_| effects[dist.sample(rng)]).sum::<f64>();
+            let flipped = effects
+                .iter()
+                .map(|&e| if rng.gen::<bool>() { e } else { -e })
+                .sum::<f64>();
+            (resampled / n_pairs as f64, flipped / n_pairs as f64)
+        })
+    });
+    let (resampled, flipped): (Vec<f64>, Vec<f64>) = draws.into_iter().unzip();
+    let q = resampled.quantile(&[left_q, right_q]);
+    check_interval(py, &resampled, (q[0], q[1]))?;
+    let p = (flipped.iter().filter(|&&f| effect > f).count() + 1) as f64 / (n_resamples + 1) as f64;
+    Ok((
+        effect,
+        lift,
+        (q[0], q[1]),
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+    ))
+}
@@ -0,0 +1,202 @@
+use crate::errors::*;
+use crate::rng::*;
+use crate::special::*;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_distr::{Exp, LogNormal, Normal};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+#[derive(Clone, Copy)]
+enum Family {
+    Norm,
+    Expon,
+    LogNorm,
+}
+
+impl Family {
+    fn parse(family: &str) -> PyResult<Family> {
+        Ok(match family {
+            "norm" => Family::Norm,
+            "expon" => Family::Expon,
+            "lognorm" => Family::LogNorm,
+            _ => raise!(
+                InvalidInputError,
+                "family must be one of 'norm', 'expon' or 'lognorm'"
+            ),
+        })
+    }
+
+    fn validate(&self, data: &[f64]) -> PyResult<()> {
+        match self {
+            Family::Norm => {}
+            Family::Expon => {
+                if data.iter().any(|&x| x < 0.0) {
+                    raise!(
+                        InvalidInputError,
+                        "The exponential family requires non-negative data"
+                    )
+                }
+            }
+            Family::LogNorm => {
+                if data.iter().any(|&x| x <= 0.0) {
+                    raise!(
+                        InvalidInputError,
+                        "The lognormal family requires positive data"
+                    )
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Maximum likelihood estimates: `(mu, sigma)` for norm and lognorm, `(scale,)` for expon.
+    fn fit(&self, data: &[f64]) -> Vec<f64> {
+        let n = data.len() as f64;
+        let mean_std = |values: &mut dyn Iterator<Item = f64>| {
+            let (sum, sum_sq) = values.fold((0.0, 0.0), |(s, s2), x| (s + x, s2 + x * x));
+            let mean = sum / n;
+            (mean, (sum_sq / n - mean * mean).max(0.0).sqrt())
+        };
+        match self {
+            Family::Norm => {
+                let (mu, sigma) = mean_std(&mut data.iter().copied());
+                vec![mu, sigma]
+            }
+            Family::Expon => vec![data.iter().sum::<f64>() / n],
+            Family::LogNorm => {
+                let (mu, sigma) = mean_std(&mut data.iter().map(|x| x.ln()));
+                vec![mu, sigma]
+            }
+        }
+    }
+
+    fn cdf(&self, x: f64, params: &[f64]) -> f64 {
+        match self {
+            Family::Norm => norm_cdf((x - params[0]) / params[1]),
+            Family::Expon => 1.0 - (-x / params[0]).exp(),
+            Family::LogNorm => norm_cdf((x.ln() - params[0]) / params[1]),
+        }
+    }
+
+    fn sample(&self, params: &[f64], n: usize, rng: &mut Xoshiro256PlusPlus) -> Vec<f64> {
+        match self {
+            Family::Norm => Normal::new(params[0], params[1])
+                .unwrap()
+                .sample_iter(rng)
+                .take(n)
+                .collect(),
+            Family::Expon => Exp::new(1.0 / params[0])
+                .unwrap()
+                .sample_iter(rng)
+                .take(n)
+                .collect(),
+            Family::LogNorm => LogNormal::new(params[0], params[1])
+                .unwrap()
+                .sample_iter(rng)
+                .take(n)
+                .collect(),
+        }
+    }
+}
+
+/// Kolmogorov–Smirnov (`ad = false`) or Anderson–Darling (`ad = true`) distance between the
+/// sorted sample and the fitted distribution.
+fn gof_statistic(sorted: &[f64], family: Family, params: &[f64], ad: bool) -> f64 {
+    let n = sorted.len() as f64;
+    let cdf: Vec<f64> = sorted
+        .iter()
+        .map(|&x| family.cdf(x, params).clamp(1e-300, 1.0 - 1e-16))
+        .collect();
+    if ad {
+        let sum: f64 = (0..cdf.len())
+            .map(|i| (2 * i + 1) as f64 * (cdf[i].ln() + (1.0 - cdf[cdf.len() - 1 - i]).ln()))
+            .sum();
+        -n - sum / n
+    } else {
+        cdf.iter()
+            .enumerate()
+            .map(|(i, &f)| ((i + 1) as f64 / n - f).max(f - i as f64 / n))
+            .fold(0.0, f64::max)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (data, family = "norm", statistic = "ks", n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(data, family='norm', statistic='ks', n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Performs a parametric-bootstrap goodness-of-fit test of `data` against a fitted parametric family.
+///
+/// The family is fitted by maximum likelihood, then every resample simulates a dataset of the same size
+/// from the fitted distribution, refits the parameters and recomputes the distance. Refitting makes the
+/// p-value valid when the parameters are estimated from the data, unlike the classical KS tables.
+///
+/// Args:
+///     data (List[float]): The observed sample.
+///     family (str, optional): "norm", "expon" (scale only, location 0) or "lognorm". Default is "norm".
+///     statistic (str, optional): Either "ks" (Kolmogorov–Smirnov) or "ad" (Anderson–Darling). Default is "ks".
+///     n_resamples (int, optional): The number of parametric bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, List[float]]:
+///         A tuple containing:
+///         - statistic (float): The observed distance between the data and the fitted distribution.
+///         - p_value (float): The share of simulated distances at least as large as the observed one.
+///         - params (List[float]): The fitted parameters, (mu, sigma) for norm and lognorm, (scale,) for expon.
+/// """
+pub fn gof_bootstrap(
+    py: Python<'_>,
+    mut data: Vec<f64>,
+    family: &str,
+    statistic: &str,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, Vec<f64>)> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let family = Family::parse(family)?;
+    check_no_nan("data", &data)?;
+    let ad = match statistic {
+        "ks" => false,
+        "ad" => true,
+        _ => raise!(InvalidInputError, "statistic must be either 'ks' or 'ad'"),
+    };
+    if data.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "At least 2 observations are required"
+        )
+    }
+    family.validate(&data)?;
+    data.sort_unstable_by(|a, b| a.total_cmp(b));
+    let params = family.fit(&data);
+    if params[params.len() - 1] <= 0.0 {
+        raise!(
+            InvalidInputError,
+            "The data is constant, the fitted scale is zero"
+        )
+    }
+    let observed = gof_statistic(&data, family, &params, ad);
+
+    let n = data.len();
+    let exceed = py.allow_threads(|| {
+        resampler
+            .map(n_resamples, |rng| {
+                let mut simulated = family.sample(&params, n, rng);
+                simulated.sort_unstable_by(|a, b| a.total_cmp(b));
+                let refitted = family.fit(&simulated);
+                gof_statistic(&simulated, family, &refitted, ad) >= observed
+            })
+            .into_iter()
+            .filter(|&exceeds| exceeds)
+            .count()
+    });
+    let p_value = (exceed + 1) as f64 / (n_resamples + 1) as f64;
+    Ok((observed, p_value, params))
+}
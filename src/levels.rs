@@ -0,0 +1,56 @@
+use crate::errors::*;
+use pyo3::prelude::*;
+
+/// Rejects a confidence level outside (0, 1), which has no interval of resampled quantiles.
+pub fn check_level(level: f64) -> PyResult<()> {
+    if !(level > 0.0 && level < 1.0) {
+        raise!(
+            InvalidInputError,
+            "confidence_level must be between 0 and 1 exclusive, got {level}"
+        )
+    }
+    Ok(())
+}
+
+#[derive(FromPyObject)]
+/// A `confidence_level` argument: one level, or several levels summarized from the same resamples.
+pub enum ConfidenceLevel {
+    Single(f64),
+    Many(Vec<f64>),
+}
+
+impl ConfidenceLevel {
+    /// The levels in the order given, each checked to lie strictly between 0 and 1.
+    pub fn levels(&self) -> PyResult<Vec<f64>> {
+        let levels = match self {
+            ConfidenceLevel::Single(level) => vec![*level],
+            ConfidenceLevel::Many(levels) => {
+                if levels.is_empty() {
+                    raise!(
+                        InvalidInputError,
+                        "confidence_level must contain at least one level"
+                    )
+                }
+                levels.clone()
+            }
+        };
+        for &level in &levels {
+            check_level(level)?;
+        }
+        Ok(levels)
+    }
+
+    /// The interval of a single level, or the list of intervals in the order of the levels.
+    pub fn intervals(&self, py: Python<'_>, intervals: Vec<(f64, f64)>) -> PyResult<PyObject> {
+        Ok(match self {
+            ConfidenceLevel::Single(_) => intervals[0].into_pyobject(py)?.into_any().unbind(),
+            ConfidenceLevel::Many(_) => intervals.into_pyobject(py)?.into_any().unbind(),
+        })
+    }
+}
+
+/// `bootstrap` output with the interval, or intervals, of a `ConfidenceLevel`.
+pub type BootstrapLevels = (f64, f64, f64, f64, PyObject);
+
+/// `permutation_test` output with the interval, or intervals, of a `ConfidenceLevel`.
+pub type PermutationLevels = (f64, f64, f64, PyObject);
@@ -1,20 +1,235 @@
+mod errors;
+mod cache;
+mod runtime;
+mod diagnostics;
+mod budget;
+mod levels;
+mod null;
 mod perm;
-mod tools;
-mod binom_coef;
+mod rng;
+mod combinatorics;
+mod sampling;
+mod bandit;
+mod sequential;
+mod surrogate;
+mod privacy;
+mod panel;
+mod geo;
+mod validation;
+#[cfg(feature = "timeseries")]
+mod timeseries;
+mod descriptive;
+mod drift;
 mod bootstrapping;
+#[cfg(feature = "ml-metrics")]
+mod ml_metrics;
+#[cfg(feature = "analytic-tests")]
+mod analytic;
+#[cfg(feature = "survival")]
+mod survival;
+mod gof;
+mod correlation;
+mod reference;
+mod categorical;
+mod robust;
+mod curves;
+#[cfg(feature = "io")]
+mod npy;
+#[cfg(feature = "io")]
+mod arrow_stream;
+mod result;
+mod aggregation;
+#[cfg(feature = "parquet")]
+mod parquet_reader;
 
-use binom_coef::*;
+use cache::*;
+use runtime::*;
+use diagnostics::PylarsWarning;
+use errors::{
+    BudgetExceededError, ConvergenceError, InsufficientDataError, InvalidInputError, StatError,
+};
+use budget::*;
+use null::*;
+use combinatorics::*;
+use sampling::*;
+use bandit::*;
+use sequential::*;
+use surrogate::*;
+use privacy::*;
+use panel::*;
+use geo::*;
+use validation::*;
+#[cfg(feature = "timeseries")]
+use timeseries::*;
+use descriptive::*;
+use drift::*;
 use perm::*;
 use bootstrapping::*;
+#[cfg(feature = "ml-metrics")]
+use ml_metrics::*;
+#[cfg(feature = "analytic-tests")]
+use analytic::*;
+#[cfg(feature = "survival")]
+use survival::*;
+use gof::*;
+use correlation::*;
+use reference::*;
+use categorical::*;
+use robust::*;
+use curves::*;
+#[cfg(feature = "io")]
+use npy::*;
+#[cfg(feature = "io")]
+use arrow_stream::*;
+use result::*;
+use aggregation::*;
+#[cfg(feature = "parquet")]
+use parquet_reader::*;
+use rng::*;
+use pylars_core::{special, tools};
 use pyo3::prelude::*;
 
-#[pymodule]
+// Module state (the null cache, a shared `RandomState`) is behind locks and the pyclasses are frozen, so
+// every function can be called from several Python threads at once and the module runs without the GIL
+// on free-threaded CPython builds.
+#[pymodule(gil_used = false)]
 fn pylars(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<RandomState>()?;
+    m.add_class::<TestResult>()?;
+    m.add_class::<BootstrapResult>()?;
+    m.add_class::<PermutationResult>()?;
+    m.add_class::<NullDistribution>()?;
+    m.add("PylarsWarning", m.py().get_type::<PylarsWarning>())?;
+    m.add("StatError", m.py().get_type::<StatError>())?;
+    m.add("InvalidInputError", m.py().get_type::<InvalidInputError>())?;
+    m.add("InsufficientDataError", m.py().get_type::<InsufficientDataError>())?;
+    m.add("ConvergenceError", m.py().get_type::<ConvergenceError>())?;
+    m.add("BudgetExceededError", m.py().get_type::<BudgetExceededError>())?;
+    // Detects the instruction set of the summation kernels at import rather than on the first call.
+    m.add("SIMD_KERNEL", tools::sum_kernel().1)?;
     m.add_function(wrap_pyfunction!(permutation_test, m)?)?;
+    m.add_function(wrap_pyfunction!(cluster_permutation_test, m)?)?;
+    m.add_function(wrap_pyfunction!(paired_permutation_test, m)?)?;
+    m.add_function(wrap_pyfunction!(trend_test_ordered, m)?)?;
     m.add_function(wrap_pyfunction!(binom, m)?)?;
+    m.add_function(wrap_pyfunction!(binom_array, m)?)?;
+    m.add_function(wrap_pyfunction!(factorial, m)?)?;
+    m.add_function(wrap_pyfunction!(lfactorial, m)?)?;
+    m.add_function(wrap_pyfunction!(perm_exact, m)?)?;
+    m.add_function(wrap_pyfunction!(lperm, m)?)?;
+    m.add_function(wrap_pyfunction!(comb_exact, m)?)?;
+    m.add_function(wrap_pyfunction!(lcomb, m)?)?;
+    m.add_function(wrap_pyfunction!(multinomial, m)?)?;
+    m.add_function(wrap_pyfunction!(lmultinomial, m)?)?;
+    m.add_function(wrap_pyfunction!(stirling2, m)?)?;
+    m.add_function(wrap_pyfunction!(bell, m)?)?;
+    m.add_function(wrap_pyfunction!(sample_without_replacement, m)?)?;
+    m.add_function(wrap_pyfunction!(random_permutation, m)?)?;
+    m.add_function(wrap_pyfunction!(random_combinations, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_assignment, m)?)?;
+    m.add_function(wrap_pyfunction!(rerandomize, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_map_rng, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate_bandit, m)?)?;
+    m.add_function(wrap_pyfunction!(off_policy_value, m)?)?;
+    m.add_function(wrap_pyfunction!(group_sequential_bounds, m)?)?;
+    m.add_function(wrap_pyfunction!(group_sequential_test, m)?)?;
+    m.add_function(wrap_pyfunction!(conditional_power, m)?)?;
+    m.add_function(wrap_pyfunction!(reestimate_n, m)?)?;
+    m.add_function(wrap_pyfunction!(surrogate_extrapolation, m)?)?;
     m.add_function(wrap_pyfunction!(bootstrap_vec, m)?)?;
     m.add_function(wrap_pyfunction!(bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(timed_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(timed_permutation_test, m)?)?;
+    m.add_function(wrap_pyfunction!(bootstrap_ci, m)?)?;
+    m.add_function(wrap_pyfunction!(windowed_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(two_stage_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(private_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(panel_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(synthetic_control, m)?)?;
+    m.add_function(wrap_pyfunction!(geo_pair_test, m)?)?;
+    m.add_function(wrap_pyfunction!(pre_period_validation, m)?)?;
+    m.add_function(wrap_pyfunction!(variance_report, m)?)?;
+    m.add_function(wrap_pyfunction!(noise_robustness, m)?)?;
+    #[cfg(feature = "timeseries")]
+    {
+        m.add_function(wrap_pyfunction!(timeseries_mean_ci, m)?)?;
+        m.add_function(wrap_pyfunction!(changepoint_test, m)?)?;
+        m.add_function(wrap_pyfunction!(anomaly_bands, m)?)?;
+        m.add_function(wrap_pyfunction!(deseasonalize, m)?)?;
+        m.add_function(wrap_pyfunction!(forecast_comparison, m)?)?;
+        m.add_function(wrap_pyfunction!(diebold_mariano, m)?)?;
+    }
+    m.add_function(wrap_pyfunction!(mean, m)?)?;
+    m.add_function(wrap_pyfunction!(var, m)?)?;
+    m.add_function(wrap_pyfunction!(std_dev, m)?)?;
+    m.add_function(wrap_pyfunction!(skewness, m)?)?;
+    m.add_function(wrap_pyfunction!(kurtosis, m)?)?;
+    m.add_function(wrap_pyfunction!(median, m)?)?;
+    m.add_function(wrap_pyfunction!(mad, m)?)?;
+    m.add_function(wrap_pyfunction!(iqr, m)?)?;
+    m.add_function(wrap_pyfunction!(weighted_mean, m)?)?;
+    m.add_function(wrap_pyfunction!(weighted_var, m)?)?;
+    m.add_function(wrap_pyfunction!(weighted_quantile, m)?)?;
+    m.add_function(wrap_pyfunction!(histogram, m)?)?;
+    m.add_function(wrap_pyfunction!(kde, m)?)?;
+    m.add_function(wrap_pyfunction!(psi, m)?)?;
+    m.add_function(wrap_pyfunction!(wasserstein_test, m)?)?;
     m.add_function(wrap_pyfunction!(stratified_bootstrap, m)?)?;
+    #[cfg(feature = "ml-metrics")]
+    {
+        m.add_function(wrap_pyfunction!(calibration_bootstrap, m)?)?;
+        m.add_function(wrap_pyfunction!(classification_metric_test, m)?)?;
+        m.add_function(wrap_pyfunction!(ranking_metric_test, m)?)?;
+    }
+    #[cfg(feature = "analytic-tests")]
+    {
+        m.add_function(wrap_pyfunction!(wilcoxon, m)?)?;
+        m.add_function(wrap_pyfunction!(kruskal, m)?)?;
+        m.add_function(wrap_pyfunction!(friedmanchisquare, m)?)?;
+        m.add_function(wrap_pyfunction!(dagostino_k2, m)?)?;
+        m.add_function(wrap_pyfunction!(jarque_bera, m)?)?;
+        m.add_function(wrap_pyfunction!(levene, m)?)?;
+    }
+    #[cfg(feature = "survival")]
+    {
+        m.add_function(wrap_pyfunction!(logrank_test, m)?)?;
+        m.add_function(wrap_pyfunction!(kaplan_meier, m)?)?;
+    }
+    m.add_function(wrap_pyfunction!(shape_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(joint_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(shared_control_test, m)?)?;
+    m.add_function(wrap_pyfunction!(interaction_test, m)?)?;
+    m.add_function(wrap_pyfunction!(gof_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(correlation_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(categorical_test, m)?)?;
+    m.add_function(wrap_pyfunction!(ordinal_test, m)?)?;
+    m.add_function(wrap_pyfunction!(clustered_proportion_test, m)?)?;
+    m.add_function(wrap_pyfunction!(hodges_lehmann, m)?)?;
+    m.add_function(wrap_pyfunction!(suggest_cap, m)?)?;
+    m.add_function(wrap_pyfunction!(lorenz_test, m)?)?;
+    m.add_function(wrap_pyfunction!(quantile_test, m)?)?;
+    #[cfg(feature = "io")]
+    {
+        m.add_function(wrap_pyfunction!(bootstrap_from_npy, m)?)?;
+        m.add_function(wrap_pyfunction!(permutation_test_from_npy, m)?)?;
+        m.add_function(wrap_pyfunction!(bootstrap_from_arrow, m)?)?;
+        m.add_function(wrap_pyfunction!(permutation_test_from_arrow, m)?)?;
+    }
+    m.add_function(wrap_pyfunction!(load_result, m)?)?;
+    m.add_function(wrap_pyfunction!(aggregate_by_key, m)?)?;
+    m.add_function(wrap_pyfunction!(enable_null_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(disable_null_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(null_cache_info, m)?)?;
+    m.add_function(wrap_pyfunction!(init, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate_null, m)?)?;
+    m.add_function(wrap_pyfunction!(summarize, m)?)?;
+    #[cfg(feature = "parquet")]
+    {
+        m.add_function(wrap_pyfunction!(bootstrap_from_parquet, m)?)?;
+        m.add_function(wrap_pyfunction!(permutation_test_from_parquet, m)?)?;
+    }
+    m.add_function(wrap_pyfunction!(reference_bootstrap, m)?)?;
+    m.add_function(wrap_pyfunction!(reference_permutation_test, m)?)?;
     Ok(())
 }
 
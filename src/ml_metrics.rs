@@ -0,0 +1,574 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pylars_core::bootstrap::BootstrapOutput;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+struct CalibrationStats {
+    bin_pred: Vec<f64>,
+    bin_true: Vec<f64>,
+    ece: f64,
+    brier: f64,
+}
+
+fn calibration_stats(
+    sums_pred: &[f64],
+    sums_true: &[f64],
+    counts: &[usize],
+    brier_sum: f64,
+    n: usize,
+) -> CalibrationStats {
+    let mut bin_pred = Vec::with_capacity(counts.len());
+    let mut bin_true = Vec::with_capacity(counts.len());
+    let mut ece = 0.0;
+    for ((&sum_pred, &sum_true), &count) in sums_pred.iter().zip(sums_true).zip(counts) {
+        if count == 0 {
+            bin_pred.push(f64::NAN);
+            bin_true.push(f64::NAN);
+            continue;
+        }
+        let (mean_pred, mean_true) = (sum_pred / count as f64, sum_true / count as f64);
+        ece += count as f64 / n as f64 * (mean_true - mean_pred).abs();
+        bin_pred.push(mean_pred);
+        bin_true.push(mean_true);
+    }
+    CalibrationStats {
+        bin_pred,
+        bin_true,
+        ece,
+        brier: brier_sum / n as f64,
+    }
+}
+
+type CalibrationOutput = (
+    Vec<f64>,
+    Vec<f64>,
+    Vec<(f64, f64)>,
+    f64,
+    (f64, f64),
+    f64,
+    (f64, f64),
+);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (y_true, probs, bins = 10, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(y_true, probs, bins=10, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Evaluates the calibration of a probabilistic binary classifier and bootstraps confidence intervals
+/// for the reliability curve, the expected calibration error (ECE) and the Brier score.
+///
+/// Predictions are grouped into `bins` equal-width bins over [0, 1]. Examples are resampled with
+/// replacement, and every statistic is recomputed for each resample.
+///
+/// Args:
+///     y_true (List[float]): Observed binary outcomes (0 or 1).
+///     probs (List[float]): Predicted probabilities of the positive class, in [0, 1].
+///     bins (int, optional): The number of equal-width probability bins. Default is 10.
+///     confidence_level (float, optional): The confidence level for the intervals. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], List[(float, float)], float, (float, float), float, (float, float)]:
+///         A tuple containing:
+///         - mean_pred (List[float]): The mean predicted probability in each bin (NaN for empty bins).
+///         - frac_pos (List[float]): The observed fraction of positives in each bin (NaN for empty bins).
+///         - frac_pos_ci (List[(float, float)]): The confidence interval of the observed fraction in each bin.
+///         - ece (float): The expected calibration error.
+///         - (float, float): The confidence interval bounds for the ECE.
+///         - brier (float): The Brier score.
+///         - (float, float): The confidence interval bounds for the Brier score.
+/// """
+pub fn calibration_bootstrap(
+    py: Python<'_>,
+    y_true: Vec<f64>,
+    probs: Vec<f64>,
+    bins: usize,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<CalibrationOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let n = y_true.len();
+    if n != probs.len() {
+        raise!(InvalidInputError, "y_true and probs must have same size")
+    }
+    if n == 0 || bins == 0 {
+        raise!(
+            InvalidInputError,
+            "Inputs must be non-empty and bins must be positive"
+        )
+    }
+    if probs.iter().any(|p| !(0.0..=1.0).contains(p)) {
+        raise!(InvalidInputError, "All probabilities must lie in [0, 1]")
+    }
+    let bin_ids: Vec<usize> = probs
+        .iter()
+        .map(|&p| ((p * bins as f64) as usize).min(bins - 1))
+        .collect();
+
+    let accumulate = |ids: &mut dyn Iterator<Item = usize>| {
+        let mut sums_pred = vec![0.0; bins];
+        let mut sums_true = vec![0.0; bins];
+        let mut counts = vec![0usize; bins];
+        let mut brier_sum = 0.0;
+        for idx in ids {
+            let bin = bin_ids[idx];
+            sums_pred[bin] += probs[idx];
+            sums_true[bin] += y_true[idx];
+            counts[bin] += 1;
+            brier_sum += (probs[idx] - y_true[idx]).powi(2);
+        }
+        calibration_stats(&sums_pred, &sums_true, &counts, brier_sum, n)
+    };
+
+    let observed = accumulate(&mut (0..n));
+    let dist = rand::distributions::Uniform::new(0, n);
+    let resamples: Vec<CalibrationStats> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            accumulate(&mut (0..n).map(|_| dist.sample(rng)))
+        })
+    });
+
+    let frac_pos_ci: Vec<(f64, f64)> = (0..bins)
+        .map(|bin| {
+            let values: Vec<f64> = resamples
+                .iter()
+                .map(|stats| stats.bin_true[bin])
+                .filter(|v| !v.is_nan())
+                .collect();
+            if values.is_empty() {
+                (f64::NAN, f64::NAN)
+            } else {
+                let q = values.quantile(&[left_q, right_q]);
+                (q[0], q[1])
+            }
+        })
+        .collect();
+    let ece_q = resamples
+        .iter()
+        .map(|stats| stats.ece)
+        .collect::<Vec<f64>>()
+        .quantile(&[left_q, right_q]);
+    let brier_q = resamples
+        .iter()
+        .map(|stats| stats.brier)
+        .collect::<Vec<f64>>()
+        .quantile(&[left_q, right_q]);
+    Ok((
+        observed.bin_pred,
+        observed.bin_true,
+        frac_pos_ci,
+        observed.ece,
+        (ece_q[0], ece_q[1]),
+        observed.brier,
+        (brier_q[0], brier_q[1]),
+    ))
+}
+
+#[derive(Clone, Copy)]
+enum ClassMetric {
+    Precision,
+    Recall,
+    F1,
+}
+
+#[derive(Clone, Copy)]
+enum Average {
+    Binary(usize),
+    Micro,
+    Macro,
+}
+
+/// Per-class `(tp, fp, fn)` counts of one model.
+fn add_prediction(counts: &mut [[u64; 3]], true_class: usize, pred_class: usize) {
+    if true_class == pred_class {
+        counts[true_class][0] += 1;
+    } else {
+        counts[pred_class][1] += 1;
+        counts[true_class][2] += 1;
+    }
+}
+
+fn class_score(tp: u64, fp: u64, fn_: u64, metric: ClassMetric) -> f64 {
+    let (num, den) = match metric {
+        ClassMetric::Precision => (tp, tp + fp),
+        ClassMetric::Recall => (tp, tp + fn_),
+        ClassMetric::F1 => (2 * tp, 2 * tp + fp + fn_),
+    };
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+fn classification_score(counts: &[[u64; 3]], metric: ClassMetric, average: Average) -> f64 {
+    match average {
+        Average::Binary(positive) => {
+            let [tp, fp, fn_] = counts[positive];
+            class_score(tp, fp, fn_, metric)
+        }
+        Average::Micro => {
+            let [tp, fp, fn_] = counts.iter().fold([0, 0, 0], |acc, c| {
+                [acc[0] + c[0], acc[1] + c[1], acc[2] + c[2]]
+            });
+            class_score(tp, fp, fn_, metric)
+        }
+        Average::Macro => {
+            counts
+                .iter()
+                .map(|&[tp, fp, fn_]| class_score(tp, fp, fn_, metric))
+                .sum::<f64>()
+                / counts.len() as f64
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (y_true, preds_a, preds_b, metric = "f1", average = "binary", method = "bootstrap", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(y_true, preds_a, preds_b, metric='f1', average='binary', method='bootstrap', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares the precision, recall or F1 score of two classifiers evaluated on the same examples.
+///
+/// The examples are paired: `method="bootstrap"` resamples examples with replacement and keeps both
+/// predictions of every drawn example, `method="permutation"` randomly swaps the two predictions of
+/// every example to build the null distribution of the difference.
+///
+/// Args:
+///     y_true (List[int]): The true class labels.
+///     preds_a (List[int]): The labels predicted by the first (control) classifier.
+///     preds_b (List[int]): The labels predicted by the second (test) classifier.
+///     metric (str, optional): One of "precision", "recall" or "f1". Default is "f1".
+///     average (str, optional): "binary" scores the positive label 1 only (labels must be 0/1),
+///         "micro" pools counts over all classes, "macro" averages per-class scores. Default is "binary".
+///     method (str, optional): Either "bootstrap" or "permutation". Default is "bootstrap".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]:
+///         A tuple containing:
+///         - p_value (float): The p-value for the test (two-sided or one-sided depending on `two_sided`).
+///         - score_a (float): The metric of the first classifier.
+///         - score_b (float): The metric of the second classifier.
+///         - diff (float): The observed difference of the metric (score_b - score_a).
+///         - (float, float): The bootstrap confidence interval for the difference, or the interval
+///           of the permutation null distribution when `method="permutation"`.
+/// """
+pub fn classification_metric_test(
+    py: Python<'_>,
+    y_true: Vec<i64>,
+    preds_a: Vec<i64>,
+    preds_b: Vec<i64>,
+    metric: &str,
+    average: &str,
+    method: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let n = y_true.len();
+    if n != preds_a.len() || n != preds_b.len() {
+        raise!(InvalidInputError, "All arrays must have equal size")
+    }
+    if n == 0 {
+        raise!(InsufficientDataError, "Inputs must be non-empty")
+    }
+    let metric = match metric {
+        "precision" => ClassMetric::Precision,
+        "recall" => ClassMetric::Recall,
+        "f1" => ClassMetric::F1,
+        _ => raise!(
+            InvalidInputError,
+            "metric must be one of 'precision', 'recall' or 'f1'"
+        ),
+    };
+
+    let mut labels: Vec<i64> = y_true
+        .iter()
+        .chain(&preds_a)
+        .chain(&preds_b)
+        .copied()
+        .collect();
+    labels.sort_unstable();
+    labels.dedup();
+    let class_of = |label: &i64| labels.binary_search(label).unwrap();
+    let y_true: Vec<usize> = y_true.iter().map(class_of).collect();
+    let preds_a: Vec<usize> = preds_a.iter().map(class_of).collect();
+    let preds_b: Vec<usize> = preds_b.iter().map(class_of).collect();
+    let n_classes = labels.len();
+
+    let average = match average {
+        "binary" => {
+            if labels.iter().any(|&label| label != 0 && label != 1) {
+                raise!(
+                    InvalidInputError,
+                    "average='binary' requires labels to be 0 or 1"
+                )
+            }
+            match labels.binary_search(&1) {
+                Ok(positive) => Average::Binary(positive),
+                Err(_) => raise!(
+                    InvalidInputError,
+                    "average='binary' requires the positive label 1 to be present"
+                ),
+            }
+        }
+        "micro" => Average::Micro,
+        "macro" => Average::Macro,
+        _ => raise!(
+            InvalidInputError,
+            "average must be one of 'binary', 'micro' or 'macro'"
+        ),
+    };
+
+    let mut counts_a = vec![[0u64; 3]; n_classes];
+    let mut counts_b = vec![[0u64; 3]; n_classes];
+    for idx in 0..n {
+        add_prediction(&mut counts_a, y_true[idx], preds_a[idx]);
+        add_prediction(&mut counts_b, y_true[idx], preds_b[idx]);
+    }
+    let score_a = classification_score(&counts_a, metric, average);
+    let score_b = classification_score(&counts_b, metric, average);
+    let observed_diff = score_b - score_a;
+
+    let bootstrap = match method {
+        "bootstrap" => true,
+        "permutation" => false,
+        _ => raise!(
+            InvalidInputError,
+            "method must be either 'bootstrap' or 'permutation'"
+        ),
+    };
+    let dist = rand::distributions::Uniform::new(0, n);
+    let vec_diffs: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut counts_a = vec![[0u64; 3]; n_classes];
+            let mut counts_b = vec![[0u64; 3]; n_classes];
+            for j in 0..n {
+                if bootstrap {
+                    let idx = dist.sample(rng);
+                    add_prediction(&mut counts_a, y_true[idx], preds_a[idx]);
+                    add_prediction(&mut counts_b, y_true[idx], preds_b[idx]);
+                } else if rng.gen::<bool>() {
+                    add_prediction(&mut counts_a, y_true[j], preds_b[j]);
+                    add_prediction(&mut counts_b, y_true[j], preds_a[j]);
+                } else {
+                    add_prediction(&mut counts_a, y_true[j], preds_a[j]);
+                    add_prediction(&mut counts_b, y_true[j], preds_b[j]);
+                }
+            }
+            classification_score(&counts_b, metric, average)
+                - classification_score(&counts_a, metric, average)
+        })
+    });
+
+    let p = if bootstrap {
+        (vec_diffs.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64
+    } else {
+        (vec_diffs.iter().filter(|&&i| observed_diff > i).count() + 1) as f64
+            / (n_resamples + 1) as f64
+    };
+    let p_value = (2.0 - 2.0 * p).min(p * 2.0);
+    let q = vec_diffs.quantile(&[left_q, right_q]);
+    Ok((
+        if two_sided { p_value } else { p },
+        score_a,
+        score_b,
+        observed_diff,
+        (q[0], q[1]),
+    ))
+}
+
+#[derive(Clone, Copy)]
+enum RankingMetric {
+    Ndcg(usize),
+    Mrr(usize),
+}
+
+impl RankingMetric {
+    fn parse(metric: &str) -> PyResult<RankingMetric> {
+        let (name, k) = match metric.split_once('@') {
+            Some((name, k)) => match k.parse::<usize>() {
+                Ok(k) if k > 0 => (name, k),
+                _ => raise!(
+                    InvalidInputError,
+                    "The cutoff in '{}' must be a positive integer",
+                    metric
+                ),
+            },
+            None => (metric, usize::MAX),
+        };
+        Ok(match name {
+            "ndcg" => RankingMetric::Ndcg(k),
+            "mrr" => RankingMetric::Mrr(k),
+            _ => raise!(
+                InvalidInputError,
+                "metric must be 'ndcg', 'ndcg@k', 'mrr' or 'mrr@k'"
+            ),
+        })
+    }
+
+    /// Scores a single query from the relevances of its results in ranked order.
+    fn score(&self, relevances: &[f64]) -> f64 {
+        match *self {
+            RankingMetric::Ndcg(k) => {
+                let dcg = |rels: &[f64]| -> f64 {
+                    rels.iter()
+                        .take(k)
+                        .enumerate()
+                        .map(|(rank, rel)| (2f64.powf(*rel) - 1.0) / (rank as f64 + 2.0).log2())
+                        .sum()
+                };
+                let mut ideal = relevances.to_vec();
+                ideal.sort_unstable_by(|a, b| b.total_cmp(a));
+                let idcg = dcg(&ideal);
+                if idcg == 0.0 {
+                    0.0
+                } else {
+                    dcg(relevances) / idcg
+                }
+            }
+            RankingMetric::Mrr(k) => relevances
+                .iter()
+                .take(k)
+                .position(|&rel| rel > 0.0)
+                .map_or(0.0, |rank| 1.0 / (rank as f64 + 1.0)),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (relevances_a, relevances_b, query_ids, metric = "ndcg@10", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(relevances_a, relevances_b, query_ids, metric='ndcg@10', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares a ranking metric of two rankers evaluated on the same queries, resampling whole queries
+/// (query-level cluster bootstrap) so that results of the same query are never treated as independent.
+///
+/// Rows belonging to the same query form its ranked result list, in the order in which they appear.
+/// NDCG uses the exponential gain `2^rel - 1`, with the ideal ordering built from the ranker's own list.
+/// MRR is the reciprocal rank of the first result with a positive relevance.
+///
+/// Args:
+///     relevances_a (List[float]): Relevance grades of the results returned by the first (control) ranker.
+///     relevances_b (List[float]): Relevance grades of the results returned by the second (test) ranker.
+///     query_ids (List[int]): The query of every row; both rankers share the same row layout.
+///     metric (str, optional): "ndcg", "ndcg@k", "mrr" or "mrr@k". Default is "ndcg@10".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]:
+///         A tuple containing:
+///         - p_value (float): The p-value for the test (two-sided or one-sided depending on `two_sided`).
+///         - metric_a (float): The metric of the first ranker averaged over queries.
+///         - metric_b (float): The metric of the second ranker averaged over queries.
+///         - diff (float): The observed difference of the metric (metric_b - metric_a).
+///         - (float, float): The confidence interval bounds for the difference.
+/// """
+pub fn ranking_metric_test(
+    py: Python<'_>,
+    relevances_a: Vec<f64>,
+    relevances_b: Vec<f64>,
+    query_ids: Vec<i64>,
+    metric: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    if relevances_a.len() != query_ids.len() || relevances_b.len() != query_ids.len() {
+        raise!(InvalidInputError, "All arrays must have equal size")
+    }
+    check_no_nan("relevances_a", &relevances_a)?;
+    check_no_nan("relevances_b", &relevances_b)?;
+    if query_ids.is_empty() {
+        raise!(InsufficientDataError, "Inputs must be non-empty")
+    }
+    let metric = RankingMetric::parse(metric)?;
+
+    let mut query_index: HashMap<i64, usize> = HashMap::new();
+    let mut queries: Vec<(Vec<f64>, Vec<f64>)> = Vec::new();
+    for ((query, rel_a), rel_b) in query_ids.iter().zip(&relevances_a).zip(&relevances_b) {
+        let idx = *query_index.entry(*query).or_insert_with(|| {
+            queries.push((Vec::new(), Vec::new()));
+            queries.len() - 1
+        });
+        queries[idx].0.push(*rel_a);
+        queries[idx].1.push(*rel_b);
+    }
+    let (scores_a, scores_b): (Vec<f64>, Vec<f64>) = queries
+        .par_iter()
+        .map(|(rels_a, rels_b)| (metric.score(rels_a), metric.score(rels_b)))
+        .unzip();
+    let n_queries = queries.len();
+    let metric_a = scores_a.iter().sum::<f64>() / n_queries as f64;
+    let metric_b = scores_b.iter().sum::<f64>() / n_queries as f64;
+    let observed_diff = metric_b - metric_a;
+
+    let dist = rand::distributions::Uniform::new(0, n_queries);
+    let vec_diffs: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut sum_diff = 0.0;
+            for _ in 0..n_queries {
+                let idx = dist.sample(rng);
+                sum_diff += scores_b[idx] - scores_a[idx];
+            }
+            sum_diff / n_queries as f64
+        })
+    });
+
+    let p: f64 =
+        (vec_diffs.iter().filter(|&&i| i > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
+    let p_value = (2.0 - 2.0 * p).min(p * 2.0);
+    let q = vec_diffs.quantile(&[left_q, right_q]);
+    Ok((
+        if two_sided { p_value } else { p },
+        metric_a,
+        metric_b,
+        observed_diff,
+        (q[0], q[1]),
+    ))
+}
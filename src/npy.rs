@@ -0,0 +1,118 @@
+use crate::bootstrapping::*;
+use crate::errors::*;
+use crate::levels::*;
+use crate::perm::*;
+use crate::rng::*;
+use pylars_core::NpyArray;
+use pylars_core::tools::Accumulator;
+use pylars_core::{BootstrapCiMethod, Statistic, UpliftCiMethod};
+use pyo3::prelude::*;
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (path_a, path_b, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, ind = true, two_sided = true, bias_corrected = false, n_inner_resamples = 0, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(path_a, path_b, confidence_level=0.95, n_resamples=10000, ind=True, two_sided=True, bias_corrected=False, n_inner_resamples=0, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `bootstrap` on two samples stored as one-dimensional `.npy` files.
+///
+/// The files are memory-mapped in Rust, so the data never passes through Python. float64 arrays are
+/// resampled in place, other supported dtypes (float32, int64, int32, uint8, bool) are converted first.
+///
+/// Args:
+///     path_a (str): The path of the first sample.
+///     path_b (str): The path of the second sample.
+///     The remaining arguments are those of `bootstrap`.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]: The same tuple as `bootstrap`.
+/// """
+pub fn bootstrap_from_npy(
+    py: Python<'_>,
+    path_a: &str,
+    path_b: &str,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    bias_corrected: bool,
+    n_inner_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let (array_a, array_b) = (
+        NpyArray::open(path_a).or_raise()?,
+        NpyArray::open(path_b).or_raise()?,
+    );
+    let (column_a, column_b) = (array_a.column(), array_b.column());
+    bootstrap_levels(
+        py,
+        &[&column_a, &column_b],
+        &confidence_level,
+        n_resamples,
+        ind,
+        two_sided,
+        bias_corrected,
+        n_inner_resamples,
+        UpliftCiMethod::Ratio,
+        BootstrapCiMethod::Percentile,
+        Statistic::Mean,
+        Accumulator::F64,
+        &resampler,
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (path_a, path_b, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(path_a, path_b, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `permutation_test` on two samples stored as one-dimensional `.npy` files.
+///
+/// The files are memory-mapped in Rust and pooled once for the permutations, without a transfer
+/// through Python.
+///
+/// Args:
+///     path_a (str): The path of the first sample.
+///     path_b (str): The path of the second sample.
+///     The remaining arguments are those of `permutation_test`.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float)]: The same tuple as `permutation_test`.
+/// """
+pub fn permutation_test_from_npy(
+    py: Python<'_>,
+    path_a: &str,
+    path_b: &str,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PermutationLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let (column_a, column_b) = (
+        NpyArray::open(path_a).or_raise()?.column().into_owned(),
+        NpyArray::open(path_b).or_raise()?.column().into_owned(),
+    );
+    permutation_levels(
+        py,
+        &[&column_a, &column_b],
+        &confidence_level,
+        n_resamples,
+        two_sided,
+        false,
+        false,
+        Statistic::Mean,
+        Accumulator::F64,
+        &resampler,
+        None,
+    )
+}
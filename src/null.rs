@@ -0,0 +1,221 @@
+use crate::cache::*;
+use crate::errors::*;
+use crate::levels::*;
+use crate::rng::*;
+use crate::tools::*;
+use pylars_core::{BootstrapNull, PermutationNull};
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// The resampling stage behind a `NullDistribution`.
+enum Stage {
+    Bootstrap(Arc<BootstrapNull>),
+    Permutation(Arc<PermutationNull>),
+}
+
+#[pyclass(module = "pylars", frozen)]
+/// """
+/// A simulated distribution returned by `simulate_null`, to be summarized by `summarize`.
+///
+/// For "permutation" it holds the differences in means (or mean ratios) under random relabelling, for
+/// "bootstrap" the resampled uplifts.
+/// """
+pub struct NullDistribution {
+    stage: Stage,
+}
+
+impl NullDistribution {
+    fn values(&self) -> &[f64] {
+        match &self.stage {
+            Stage::Bootstrap(null) => &null.uplift_diffs,
+            Stage::Permutation(null) => &null.diffs,
+        }
+    }
+}
+
+#[pymethods]
+impl NullDistribution {
+    /// """
+    /// The test that produced the distribution, "bootstrap" or "permutation".
+    /// """
+    #[getter]
+    fn test(&self) -> &str {
+        match self.stage {
+            Stage::Bootstrap(_) => "bootstrap",
+            Stage::Permutation(_) => "permutation",
+        }
+    }
+
+    /// """
+    /// The observed statistic: the uplift for "bootstrap", the difference for "permutation".
+    /// """
+    #[getter]
+    fn statistic(&self) -> f64 {
+        match &self.stage {
+            Stage::Bootstrap(null) => null.uplift,
+            Stage::Permutation(null) => null.observed_diff,
+        }
+    }
+
+    /// """
+    /// The simulated statistics, in resample order.
+    /// """
+    #[getter(values)]
+    fn values_list(&self) -> Vec<f64> {
+        self.values().to_vec()
+    }
+
+    fn __len__(&self) -> usize {
+        self.values().len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "NullDistribution(test='{}', statistic={}, n_resamples={})",
+            self.test(),
+            self.statistic(),
+            self.values().len()
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, test = "permutation", n_resamples = 10_000, ind = true, accumulator = "f64", random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(args, test='permutation', n_resamples=10000, ind=True, accumulator='f64', random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs the resampling stage of `permutation_test` or `bootstrap` once, to summarize it any number of
+/// times with `summarize`.
+///
+/// The simulation is the one of the full test with the same arguments, so summarizing it with the
+/// defaults gives the same p-value and interval. When the null cache is enabled (see
+/// `enable_null_cache`), it is shared with the full tests.
+///
+/// Args:
+///     args (List[List[float]]): Two samples, or two (numerator, denominator) pairs, as for the tests.
+///     test (str, optional): "permutation" or "bootstrap". Default is "permutation".
+///     n_resamples (int, optional): The number of resamples. Default is 10000.
+///     ind (bool, optional): For "bootstrap", whether the samples are independent rather than paired.
+///         Default is True.
+///     accumulator (str, optional): The summation of the resampled sums, see `bootstrap`. Default is "f64".
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     NullDistribution: The simulated distribution.
+/// """
+pub fn simulate_null(
+    py: Python<'_>,
+    args: Vec<Vec<f64>>,
+    test: &str,
+    n_resamples: u64,
+    ind: bool,
+    accumulator: &str,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<NullDistribution> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let accumulator = Accumulator::parse(accumulator).or_raise()?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let stage = match test {
+        "permutation" => Stage::Permutation(
+            py.allow_threads(|| {
+                cached_permutation_null(&columns, n_resamples, false, accumulator, &resampler)
+            })
+            .or_raise()?,
+        ),
+        "bootstrap" => Stage::Bootstrap(
+            py.allow_threads(|| {
+                cached_bootstrap_null(&columns, n_resamples, ind, accumulator, &resampler)
+            })
+            .or_raise()?,
+        ),
+        _ => raise!(InvalidInputError, "test must be 'permutation' or 'bootstrap', got '{test}'"),
+    };
+    Ok(NullDistribution { stage })
+}
+
+#[pyfunction(signature = (null, observed = None, confidence_level = ConfidenceLevel::Single(0.95), alternative = "two-sided", null_value = 0.0))]
+#[pyo3(
+    text_signature = "(null, observed=None, confidence_level=0.95, alternative='two-sided', null_value=0.0)"
+)]
+/// """
+/// Summarizes a `NullDistribution` into a p-value and an interval.
+///
+/// For "permutation", the p-value compares `observed - null_value` with the permuted differences and the
+/// interval holds the central `confidence_level` share of the permuted differences, as in
+/// `permutation_test`. For "bootstrap", the resampled uplifts are shifted by `observed - statistic`, the
+/// p-value is the share of them on the opposite side of `null_value` and the interval is their
+/// percentile interval, as in `bootstrap`. With the defaults, the two-sided p-value matches the full
+/// test up to the tie convention.
+///
+/// Args:
+///     null (NullDistribution): The distribution returned by `simulate_null`.
+///     observed (float, optional): The observed statistic. Default is None, which uses the one of the
+///         simulated data.
+///     confidence_level (float or List[float], optional): The confidence level for the interval, or a list of
+///         levels to get one interval per level. Default is 0.95.
+///     alternative (str, optional): "two-sided", "less" or "greater", the alternative to the statistic
+///         being `null_value`. Default is "two-sided".
+///     null_value (float, optional): The value of the statistic under the null hypothesis. Default is 0.0.
+///
+/// Returns:
+///     Tuple[float, float, (float, float)]:
+///         A tuple containing:
+///         - p_value (float): The p-value for the chosen alternative.
+///         - statistic (float): The observed statistic.
+///         - (float, float): The interval bounds, or a list of them when `confidence_level` is a list.
+/// """
+pub fn summarize(
+    py: Python<'_>,
+    null: &NullDistribution,
+    observed: Option<f64>,
+    confidence_level: ConfidenceLevel,
+    alternative: &str,
+    null_value: f64,
+) -> PyResult<(f64, f64, PyObject)> {
+    let alternative = Alternative::parse(alternative).or_raise()?;
+    let levels = confidence_level.levels()?;
+    let bounds = |shift: f64| {
+        let q: Vec<f64> = levels
+            .iter()
+            .flat_map(|level| [(1.0 - level) / 2.0, (1.0 + level) / 2.0])
+            .collect();
+        let q = null.values().quantile(&q);
+        let intervals = q
+            .chunks(2)
+            .map(|pair| (pair[0] + shift, pair[1] + shift))
+            .collect();
+        confidence_level.intervals(py, intervals)
+    };
+    let values = null.values();
+    let observed = observed.unwrap_or_else(|| null.statistic());
+    let n = values.len() as f64;
+    let tail = |count: usize| (count as f64 + 1.0) / (n + 1.0);
+    match &null.stage {
+        Stage::Permutation(_) => {
+            let centered = observed - null_value;
+            let p_less = tail(values.iter().filter(|&&d| d <= centered).count());
+            let p_greater = tail(values.iter().filter(|&&d| d >= centered).count());
+            Ok((
+                alternative.p_value(p_less, p_greater),
+                observed,
+                bounds(0.0)?,
+            ))
+        }
+        Stage::Bootstrap(stage) => {
+            let shift = observed - stage.uplift;
+            let p_less = tail(values.iter().filter(|&&d| d + shift >= null_value).count());
+            let p_greater = tail(values.iter().filter(|&&d| d + shift <= null_value).count());
+            Ok((
+                alternative.p_value(p_less, p_greater),
+                observed,
+                bounds(shift)?,
+            ))
+        }
+    }
+}
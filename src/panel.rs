@@ -0,0 +1,206 @@
+use crate::diagnostics::*;
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+use std::collections::HashMap;
+
+/// Largest number of alternating demeaning passes of an unbalanced panel.
+const MAX_DEMEANING_PASSES: usize = 1_000;
+
+/// Change of the demeaned treatment, and of the demeaned outcome relative to its largest value, below
+/// which the alternating projections have converged.
+const DEMEANING_TOLERANCE: f64 = 1e-10;
+
+/// An observation of a panel: its unit, period, outcome and treatment.
+#[derive(Clone, Copy)]
+struct Observation {
+    unit: usize,
+    period: usize,
+    value: f64,
+    treated: f64,
+}
+
+/// Dense ids of `keys` in order of first appearance, and their number.
+fn dense_keys(keys: &[String]) -> (Vec<usize>, usize) {
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let dense = keys
+        .iter()
+        .map(|key| {
+            let next = ids.len();
+            *ids.entry(key.as_str()).or_insert(next)
+        })
+        .collect();
+    (dense, ids.len())
+}
+
+/// Two-way within estimate of the treatment effect: the outcome and the treatment are demeaned by unit
+/// and by period, by alternating projections until convergence (a single pass for a balanced panel),
+/// and the effect is the slope of the demeaned outcome on the demeaned treatment. NaN when the treatment
+/// has no variation left within units and periods, None when the projections have not converged after
+/// `MAX_DEMEANING_PASSES` passes.
+fn within_estimate(observations: &[Observation], n_units: usize, n_periods: usize) -> Option<f64> {
+    let mut y: Vec<f64> = observations.iter().map(|o| o.value).collect();
+    let mut d: Vec<f64> = observations.iter().map(|o| o.treated).collect();
+    let demean = |y: &mut [f64], d: &mut [f64], key: &dyn Fn(&Observation) -> usize, n: usize| {
+        let mut sums = vec![(0.0, 0.0, 0.0); n];
+        for (k, o) in observations.iter().enumerate() {
+            let sum = &mut sums[key(o)];
+            sum.0 += y[k];
+            sum.1 += d[k];
+            sum.2 += 1.0;
+        }
+        let (mut change_y, mut change_d): (f64, f64) = (0.0, 0.0);
+        for (k, o) in observations.iter().enumerate() {
+            let (sum_y, sum_d, count) = sums[key(o)];
+            y[k] -= sum_y / count;
+            d[k] -= sum_d / count;
+            change_y = change_y.max((sum_y / count).abs());
+            change_d = change_d.max((sum_d / count).abs());
+        }
+        (change_y, change_d)
+    };
+    // The outcome is compared with its own magnitude, the treatment indicator with 1.
+    let scale_y = y.iter().fold(f64::MIN_POSITIVE, |m, y| m.max(y.abs()));
+    let converged = (0..MAX_DEMEANING_PASSES).any(|pass| {
+        let unit_change = demean(&mut y, &mut d, &|o| o.unit, n_units);
+        let period_change = demean(&mut y, &mut d, &|o| o.period, n_periods);
+        pass > 0
+            && unit_change.0.max(period_change.0) < DEMEANING_TOLERANCE * scale_y
+            && unit_change.1.max(period_change.1) < DEMEANING_TOLERANCE
+    });
+    if !converged {
+        return None;
+    }
+    let sxx = d.iter().map(|d| d * d).sum::<f64>();
+    let sxy = d.iter().zip(&y).map(|(d, y)| d * y).sum::<f64>();
+    Some(if sxx <= DEMEANING_TOLERANCE {
+        f64::NAN
+    } else {
+        sxy / sxx
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (values, unit_ids, period_ids, treatment, confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(values, unit_ids, period_ids, treatment, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Estimates a treatment effect on a panel metric with unit and period fixed effects, and bootstraps it
+/// by resampling whole units.
+///
+/// The effect is the two-way within (difference-in-differences) estimate of the model
+/// `value = unit effect + period effect + effect * treatment`, which suits staggered rollouts where units
+/// switch to treatment at different periods. Unbalanced panels are handled by alternating projections.
+/// Every resample draws units with replacement and keeps all their periods, so the interval accounts
+/// for the correlation of the observations of a unit over time. Resamples without within variation of
+/// the treatment are dropped, and the p-value follows the conventions of `bootstrap`.
+///
+/// Args:
+///     values (List[float]): The metric of every observation.
+///     unit_ids (List[str]): The unit of every observation.
+///     period_ids (List[str]): The period of every observation.
+///     treatment (List[bool]): Whether the unit is treated in that period.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, (float, float), float]:
+///         A tuple containing:
+///         - effect (float): The two-way fixed-effects estimate of the treatment effect.
+///         - (float, float): The unit-level bootstrap interval of the effect.
+///         - p_value (float): The p-value of a zero effect.
+/// """
+pub fn panel_bootstrap(
+    py: Python<'_>,
+    values: Vec<f64>,
+    unit_ids: Vec<String>,
+    period_ids: Vec<String>,
+    treatment: Vec<bool>,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, (f64, f64), f64)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    let n = values.len();
+    if unit_ids.len() != n || period_ids.len() != n || treatment.len() != n {
+        raise!(
+            InvalidInputError,
+            "values, unit_ids, period_ids and treatment must have equal size"
+        )
+    }
+    if n == 0 {
+        raise!(InsufficientDataError, "The panel must contain observations")
+    }
+    let (units, n_units) = dense_keys(&unit_ids);
+    let (periods, n_periods) = dense_keys(&period_ids);
+    let mut by_unit: Vec<Vec<Observation>> = vec![Vec::new(); n_units];
+    for k in 0..n {
+        by_unit[units[k]].push(Observation {
+            unit: units[k],
+            period: periods[k],
+            value: values[k],
+            treated: if treatment[k] { 1.0 } else { 0.0 },
+        });
+    }
+    let observations: Vec<Observation> = by_unit.iter().flatten().copied().collect();
+    let not_converged = || {
+        ConvergenceError::new_err(format!(
+            "The demeaning has not converged after {MAX_DEMEANING_PASSES} passes"
+        ))
+    };
+    let effect = within_estimate(&observations, n_units, n_periods).ok_or_else(not_converged)?;
+    if effect.is_nan() {
+        raise!(
+            InvalidInputError,
+            "The treatment has no variation within units and periods, the effect is not identified"
+        )
+    }
+
+    let dist = Uniform::new(0, n_units);
+    let effects: Vec<Option<f64>> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut resample = Vec::with_capacity(n);
+            for unit in 0..n_units {
+                let drawn = dist.sample(rng);
+                resample.extend(by_unit[drawn].iter().map(|o| Observation { unit, ..*o }));
+            }
+            within_estimate(&resample, n_units, n_periods)
+        })
+    });
+    let effects: Vec<f64> = effects
+        .into_iter()
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(not_converged)?
+        .into_iter()
+        .filter(|e| !e.is_nan())
+        .collect();
+    let n_valid = effects.len() as u64;
+    check_dropped(py, (n_resamples - n_valid) as usize, n_resamples as usize)?;
+    let q = effects.quantile(&[left_q, right_q]);
+    check_interval(py, &effects, (q[0], q[1]))?;
+    let p = (effects.iter().filter(|&&e| e > 0.0).count() as f64 + 1.0) / (n_valid + 1) as f64;
+    Ok((
+        effect,
+        (q[0], q[1]),
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+    ))
+}
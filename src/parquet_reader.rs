@@ -0,0 +1,123 @@
+use crate::bootstrapping::*;
+use crate::errors::*;
+use crate::levels::*;
+use crate::perm::*;
+use crate::rng::*;
+use pylars_core::read_parquet_column;
+use pylars_core::tools::Accumulator;
+use pylars_core::{BootstrapCiMethod, Statistic, UpliftCiMethod};
+use pyo3::prelude::*;
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (path_a, path_b, column, filter = None, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, ind = true, two_sided = true, bias_corrected = false, n_inner_resamples = 0, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(path_a, path_b, column, filter=None, confidence_level=0.95, n_resamples=10000, ind=True, two_sided=True, bias_corrected=False, n_inner_resamples=0, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `bootstrap` on a numeric column read from two parquet files.
+///
+/// The column is streamed row group by row group in Rust, decoding only the value and filter columns.
+/// Null values are skipped. Requires the `parquet` build feature.
+///
+/// Args:
+///     path_a (str): The path of the parquet file of the first sample.
+///     path_b (str): The path of the parquet file of the second sample.
+///     column (str): The name of the numeric column to analyse.
+///     filter (str, optional): A row filter "column op value", with op one of ==, !=, <, <=, >, >=
+///         and value a number or a quoted string, e.g. "country == 'US'". Default is None.
+///     The remaining arguments are those of `bootstrap`.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]: The same tuple as `bootstrap`.
+/// """
+pub fn bootstrap_from_parquet(
+    py: Python<'_>,
+    path_a: &str,
+    path_b: &str,
+    column: &str,
+    filter: Option<&str>,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    bias_corrected: bool,
+    n_inner_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let column_a = read_parquet_column(path_a, column, filter).or_raise()?;
+    let column_b = read_parquet_column(path_b, column, filter).or_raise()?;
+    bootstrap_levels(
+        py,
+        &[&column_a, &column_b],
+        &confidence_level,
+        n_resamples,
+        ind,
+        two_sided,
+        bias_corrected,
+        n_inner_resamples,
+        UpliftCiMethod::Ratio,
+        BootstrapCiMethod::Percentile,
+        Statistic::Mean,
+        Accumulator::F64,
+        &resampler,
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (path_a, path_b, column, filter = None, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(path_a, path_b, column, filter=None, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Runs `permutation_test` on a numeric column read from two parquet files.
+///
+/// Reads the column like `bootstrap_from_parquet`. Requires the `parquet` build feature.
+///
+/// Args:
+///     path_a (str): The path of the parquet file of the first sample.
+///     path_b (str): The path of the parquet file of the second sample.
+///     column (str): The name of the numeric column to analyse.
+///     filter (str, optional): A row filter "column op value", see `bootstrap_from_parquet`. Default is None.
+///     The remaining arguments are those of `permutation_test`.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float)]: The same tuple as `permutation_test`.
+/// """
+pub fn permutation_test_from_parquet(
+    py: Python<'_>,
+    path_a: &str,
+    path_b: &str,
+    column: &str,
+    filter: Option<&str>,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PermutationLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let (column_a, column_b) = (
+        read_parquet_column(path_a, column, filter).or_raise()?,
+        read_parquet_column(path_b, column, filter).or_raise()?,
+    );
+    permutation_levels(
+        py,
+        &[&column_a, &column_b],
+        &confidence_level,
+        n_resamples,
+        two_sided,
+        false,
+        false,
+        Statistic::Mean,
+        Accumulator::F64,
+        &resampler,
+        None,
+    )
+}
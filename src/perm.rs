@@ -1,34 +1,119 @@
+use crate::cache::*;
+use crate::combinatorics::binom;
+use crate::diagnostics::*;
+use crate::errors::*;
+use crate::levels::*;
+use crate::result::*;
+use crate::rng::*;
 use crate::tools::*;
-use rand::prelude::*;
-use rand_xoshiro::Xoshiro256PlusPlus;
-use rayon::prelude::*;
+use pylars_core::{exact_permutation_null, statistic_permutation_null, PermutationNull, Statistic};
 use pyo3::prelude::*;
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
+#[allow(clippy::too_many_arguments)]
 #[pyfunction(
     signature = (
         args,
-        confidence_level = 0.95, 
+        confidence_level = ConfidenceLevel::Single(0.95), 
         n_resamples = 10_000, 
         two_sided = true,
+        standardize = false,
+        winsorize = None,
+        estimand = "ratio_of_sums",
+        weights = None,
+        statistic = "mean",
+        delta = None,
+        n_blocks = None,
+        accumulator = "f64",
+        max_seconds = None,
+        timings = false,
+        return_object = false,
+        exact = false,
+        random_state = None,
+        seed = None,
+        deterministic = None,
     )
 )]
-#[pyo3(text_signature = "(args, confidence_level=0.95, n_resamples=10000, two_sided=True)")]
+#[pyo3(text_signature = "(args, confidence_level=0.95, n_resamples=10000, two_sided=True, standardize=False, winsorize=None, estimand='ratio_of_sums', weights=None, statistic='mean', delta=None, n_blocks=None, accumulator='f64', max_seconds=None, timings=False, return_object=False, exact=False, random_state=None, seed=None, deterministic=None)")]
 /// """
 /// Performs a permutation test to evaluate the statistical significance of the difference in means
 /// (or mean ratios) between two or four sets of samples.
 ///
+/// Constant inputs: when all values are equal, every permutation ties with the observed difference and
+/// the p-value is 1.0 (0.5 one-sided). When the first group's mean (or ratio) is 0, the uplift is
+/// undefined and returned as NaN, while the p-value and interval of the difference are still valid.
+/// NaN values raise InvalidInputError, since no permutation can be compared with a NaN difference, and
+/// an empty group raises InsufficientDataError.
+///
 /// Args:
 ///     args (List[List[float]]): A list containing either two or four lists of floats.
 ///         - If two lists are provided: They represent two samples for comparison.
 ///           The function will test the difference in their means.
 ///         - If four lists are provided: They represent two pairs of (numerator, denominator) data sets.
 ///           The function will test the difference in their mean ratios (sum(num)/sum(den) for each pair).
-///     confidence_level (float, optional): The confidence level for constructing the confidence interval.
-///         Default is 0.95.
+///     confidence_level (float or List[float], optional): The confidence level for constructing the confidence
+///         interval, or a list of levels to get one interval per level from the same permutations. Default is 0.95.
 ///     n_resamples (int, optional): The number of permutation resamples to generate for building the null distribution.
 ///         Default is 10000.
 ///     two_sided (bool, optional): If True, returns a two-sided p-value. If False, returns a one-sided p-value.
 ///         Default is True.
+///     standardize (bool, optional): If True, the permuted statistic is the Welch t-statistic (the
+///         difference divided by its standard error, from delta-method variances for mean ratios) rather
+///         than the raw difference, which keeps the test valid when the group variances or sizes differ.
+///         The interval is then the permuted t-statistics scaled by the observed standard error.
+///         Default is False.
+///     winsorize (float, optional): A quantile level, e.g. 0.99, above which the pooled units of both
+///         groups are capped before the test. With four lists the per-unit ratios num / den are capped
+///         by lowering the numerator, so every unit's pair stays consistent, which cannot be done after
+///         aggregating the pairs. Default is None, which keeps the data as is.
+///     estimand (str, optional): With four lists, the target quantity of every group: "ratio_of_sums"
+///         for sum(num) / sum(den), resampling the (num, den) pairs, or "mean_of_ratios" for the mean of
+///         the per-unit ratios num / den, resampling the ratios (units with a zero denominator are left
+///         out, with a warning). Default is "ratio_of_sums".
+///     weights (List[List[float]], optional): A non-negative weight per observation (per unit with four
+///         lists) of each group, e.g. survey or importance weights. The test then compares the weighted
+///         means sum(w * x) / sum(w) (or weighted ratios sum(w * num) / sum(w * den)), and every weight
+///         is permuted along with its observation. Requires the "ratio_of_sums" estimand and the mean
+///         statistic. Default is None, which weighs all observations equally.
+///     statistic (str, optional): The statistic compared between two samples: "mean", or an
+///         outlier-robust location for adversarially heavy-tailed metrics such as payments with fraud
+///         spikes: "huber_mean" (Huber M-estimate) or "median_of_means" (median of the means of blocks of
+///         consecutive observations, so shuffle ordered data first); "median", "std", "var",
+///         "trimmed_mean" (cutting 10% from each tail) and "quantile" are also accepted. A statistic
+///         other than the mean resamples and recomputes it on every permutation, without
+///         standardize or accumulators, and the difference and uplift are those of the statistic.
+///         Default is "mean".
+///     delta (float, optional): The threshold of "huber_mean" in robust standard deviations
+///         (1.4826 MAD); smaller values downweight more observations. Default is None, which is 1.345.
+///     n_blocks (int, optional): The number of blocks of "median_of_means". Default is None, which is 10.
+///     accumulator (str, optional): The summation of the permuted sums: "f64" for plain addition,
+///         "kahan" for compensated summation or "f128-emulated" for double-double summation (about 32
+///         significant digits). Plain addition loses about log10(n) digits over n values, which shows on
+///         large values such as revenue in cents over millions of rows; "kahan" makes the error
+///         independent of n at a small cost, "f128-emulated" is slower still. Default is "f64".
+///     max_seconds (float, optional): A soft limit on the running time. The resamples run in batches and
+///         stop after the first batch that ends past the limit, with a RuntimeWarning reporting the number
+///         of resamples actually used. Default is None, which runs all `n_resamples`.
+///     timings (bool, optional): If True, also returns a dictionary of the seconds spent converting
+///         `args` and `weights` from Python ("conversion"), permuting ("resampling") and sorting the
+///         permuted statistics into the p-value and intervals ("quantiles"), and of the number of
+///         threads ("threads"). Default is False.
+///     return_object (bool, optional): If True, returns a `PermutationResult` with the fields of the tuple as
+///         named attributes instead of the tuple. Default is False.
+///     exact (bool, optional): If True, enumerates all binom(n, len_a) assignments of the n pooled units
+///         to the first group instead of drawing `n_resamples` random ones, which suits small samples
+///         (up to about 20 units) where Monte Carlo noise is large relative to the few distinct
+///         assignments. The one-sided p-value is then the share of assignments with a difference at
+///         most the observed one, the observed assignment included, and the two-sided p-value doubles
+///         the smaller tail, capped at 1. Requires the mean statistic without `standardize`,
+///         `accumulator` or `max_seconds`, and raises BudgetExceededError beyond 1e6 assignments.
+///         Default is False.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
 ///
 /// Returns:
 ///     Tuple[float, float, float, (float, float)]:
@@ -38,119 +123,563 @@ use pyo3::prelude::*;
 ///         - uplift (float): The relative difference (observed_diff / baseline_mean), where baseline_mean is the mean
 ///           (or ratio) of the first sample/pair.
 ///         - observed_diff (float): The observed absolute difference in means or mean ratios (e.g., mean_2 - mean_1).
-///         - (float, float): The confidence interval bounds for the observed difference based on the specified confidence level.
+///         - (float, float): The confidence interval bounds for the observed difference based on the specified confidence level,
+///           or a list of them when `confidence_level` is a list.
+///         With `return_object`, a `PermutationResult` in place of the tuple.
+///         With `timings`, a tuple of that result and the dictionary of timings.
 /// """
 pub fn permutation_test(
-    args: Vec<Vec<f64>>,
-    confidence_level: f64,
+    py: Python<'_>,
+    args: &Bound<'_, PyAny>,
+    confidence_level: ConfidenceLevel,
     n_resamples: u64,
     two_sided: bool,
-) -> (f64, f64, f64, (f64, f64)) {
-    let left_q = (1.0 - confidence_level) / 2.0;
-    let right_q = 1.0 - left_q;
-
-    let (vec_diffs, uplift, observed_diff): (Vec<f64>, f64, f64) = match args.len() {
-        2 => {
-            let (len_a, len_b) = (args[0].len(), args[1].len());
-            let mut combined: Vec<f64> = Vec::with_capacity(len_a + len_b);
-            combined.extend_from_slice(&args[0]);
-            combined.extend_from_slice(&args[1]);
-            let len_comb = combined.len();
-            let (a_mean, b_mean) = (
-                args[0].iter().sum::<f64>() / len_a as f64,
-                args[1].iter().sum::<f64>() / len_b as f64,
-            );
-
-            let observed_diff = b_mean - a_mean;
-            let uplift = observed_diff / a_mean;
-
-            let vec_diffs: Vec<f64> = (0..n_resamples)
-                .into_par_iter()
-                .map(|i| {
-                    let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
-                    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
-                    let mut ids: Vec<usize> = (0..len_comb).collect();
-                    ids.shuffle(&mut rng);
-
-                    let sum_a: f64 = ids[..len_a]
-                        .iter()
-                        .map(|id| unsafe { combined.get_unchecked(*id) })
-                        .sum();
-                    let sum_b: f64 = ids[len_a..]
-                        .iter()
-                        .map(|id| unsafe { combined.get_unchecked(*id) })
-                        .sum();
-                    (sum_b / len_b as f64) - (sum_a / len_a as f64)
-                })
-                .collect();
-
-            (vec_diffs, uplift, observed_diff)
-        }
-        4 => {
-            let (len_a, len_b) = (args[0].len(), args[2].len());
-
-            if len_a != args[1].len() || len_b != args[3].len() {
-                panic!("Each pair of arrays must be of equal length.");
+    standardize: bool,
+    winsorize: Option<f64>,
+    estimand: &str,
+    weights: Option<&Bound<'_, PyAny>>,
+    statistic: &str,
+    delta: Option<f64>,
+    n_blocks: Option<usize>,
+    accumulator: &str,
+    max_seconds: Option<f64>,
+    timings: bool,
+    return_object: bool,
+    exact: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PyObject> {
+    check_resamples(n_resamples)?;
+    let resampler =
+        new_resampler(random_state, seed, deterministic)?.with_deadline(deadline(max_seconds)?);
+    let mut breakdown = Timings::default();
+    let (args, weights) = Timings::time(&mut breakdown.conversion, || {
+        let args: Vec<Vec<f64>> = args.extract()?;
+        let weights: Option<Vec<Vec<f64>>> = weights.map(|weights| weights.extract()).transpose()?;
+        PyResult::Ok((args, weights))
+    })?;
+    let accumulator = Accumulator::parse(accumulator).or_raise()?;
+    let statistic = Statistic::parse(statistic, None)
+        .and_then(|statistic| statistic.with_tuning(delta, n_blocks, None))
+        .or_raise()?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let winsorized = winsorize
+        .map(|level| winsorize_columns(&columns, level).or_raise())
+        .transpose()?;
+    let columns: Vec<&[f64]> = match &winsorized {
+        Some(capped) => capped.iter().map(Vec::as_slice).collect(),
+        None => columns,
+    };
+    let estimand = Estimand::parse(estimand).or_raise()?;
+    let weighted = weights
+        .map(|weights| {
+            if estimand != Estimand::RatioOfSums || statistic != Statistic::Mean {
+                raise!(
+                    InvalidInputError,
+                    "weights require the 'ratio_of_sums' estimand and the mean statistic"
+                )
             }
+            weighted_columns(&columns, &weights).or_raise()
+        })
+        .transpose()?;
+    let columns: Vec<&[f64]> = match &weighted {
+        Some(weighted) => weighted.iter().map(Vec::as_slice).collect(),
+        None => columns,
+    };
+    let ratios = match estimand {
+        Estimand::RatioOfSums => None,
+        Estimand::MeanOfRatios => {
+            let (ratios, dropped) = unit_ratio_columns(&columns).or_raise()?;
+            check_zero_denominators(py, dropped, columns[0].len() + columns[2].len())?;
+            Some(ratios)
+        }
+    };
+    let columns: Vec<&[f64]> = match &ratios {
+        Some(ratios) => ratios.iter().map(Vec::as_slice).collect(),
+        None => columns,
+    };
+    let result = permutation_levels(
+        py,
+        &columns,
+        &confidence_level,
+        n_resamples,
+        two_sided,
+        standardize,
+        exact,
+        statistic,
+        accumulator,
+        &resampler,
+        timings.then_some(&mut breakdown),
+    )?;
+    let result = if return_object {
+        Bound::new(py, PermutationResult::new(py, result, &confidence_level)?)?.into_any()
+    } else {
+        result.into_pyobject(py)?.into_any()
+    };
+    if !timings {
+        return Ok(result.unbind());
+    }
+    Ok((result, breakdown.into_dict(py)?)
+        .into_pyobject(py)?
+        .into_any()
+        .unbind())
+}
 
-            let (ratio_a, ratio_b) = (
-                args[0].iter().sum::<f64>() / args[1].iter().sum::<f64>(),
-                args[2].iter().sum::<f64>() / args[3].iter().sum::<f64>(),
-            );
-
-            let observed_diff = ratio_b - ratio_a;
-            let uplift = observed_diff / ratio_a;
-
-            let mut numerators = Vec::with_capacity(len_a + len_b);
-            let mut denominators = Vec::with_capacity(len_a + len_b);
-
-            numerators.extend_from_slice(&args[0]);
-            denominators.extend_from_slice(&args[1]);
-            numerators.extend_from_slice(&args[2]);
-            denominators.extend_from_slice(&args[3]);
-
-            let len_comb = numerators.len();
-
-            let vec_diffs: Vec<f64> = (0..n_resamples)
-                .into_par_iter()
-                .map(|i| {
-                    let seed: u64 = i ^ i.wrapping_mul(0x9e3779b97f4a7c15);
-                    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
-                    let mut ids: Vec<usize> = (0..len_comb).collect();
-                    ids.shuffle(&mut rng);
+/// Largest number of label assignments enumerated by `permutation_test` with `exact`.
+const MAX_EXACT_ASSIGNMENTS: f64 = 1e6;
 
-                    let (sum_a_num, sum_a_den): (f64, f64) = ids[..len_a]
-                        .iter()
-                        .map(|&id| unsafe {
-                            (numerators.get_unchecked(id), denominators.get_unchecked(id))
-                        })
-                        .fold((0.0, 0.0), |(num, den), (a, b)| (num + a, den + b));
+/// Number of label assignments of the pooled units of two or four columns, `binom(n, len_a)`; other
+/// inputs are left to `exact_permutation_null` to reject.
+fn exact_assignments(columns: &[&[f64]]) -> f64 {
+    let (len_a, len_b) = match columns {
+        [a, b] | [a, _, b, _] => (a.len(), b.len()),
+        _ => return 0.0,
+    };
+    match u16::try_from(len_a + len_b) {
+        Ok(_) if len_a == 0 || len_b == 0 => 0.0,
+        Ok(n) => binom(n, len_a as u16),
+        Err(_) => f64::INFINITY,
+    }
+}
 
-                    let (sum_b_num, sum_b_den): (f64, f64) = ids[len_a..]
-                        .iter()
-                        .map(|&id| unsafe {
-                            (numerators.get_unchecked(id), denominators.get_unchecked(id))
-                        })
-                        .fold((0.0, 0.0), |(num, den), (a, b)| (num + a, den + b));
+/// `permutation_test` on borrowed columns, summarizing one permutation run at every level. With
+/// `timings`, the durations of the run and of the summaries are added to it.
+#[allow(clippy::too_many_arguments)]
+pub fn permutation_levels(
+    py: Python<'_>,
+    columns: &[&[f64]],
+    confidence_level: &ConfidenceLevel,
+    n_resamples: u64,
+    two_sided: bool,
+    standardize: bool,
+    exact: bool,
+    statistic: Statistic,
+    accumulator: Accumulator,
+    resampler: &Resampler,
+    timings: Option<&mut Timings>,
+) -> PyResult<PermutationLevels> {
+    if columns.iter().any(|column| column.is_empty()) {
+        raise!(InsufficientDataError, "Both samples must be non-empty")
+    }
+    for column in columns {
+        check_no_nan("args", column)?;
+    }
+    let started = Instant::now();
+    let null = if exact {
+        if statistic != Statistic::Mean
+            || standardize
+            || accumulator != Accumulator::F64
+            || resampler.deadline().is_some()
+        {
+            raise!(
+                InvalidInputError,
+                "exact requires the mean statistic without standardize, accumulator or max_seconds"
+            )
+        }
+        let assignments = exact_assignments(columns);
+        if assignments > MAX_EXACT_ASSIGNMENTS {
+            raise!(
+                BudgetExceededError,
+                "exact would enumerate {assignments:.3e} assignments, more than the limit of {MAX_EXACT_ASSIGNMENTS:.0e}; use the Monte Carlo test instead"
+            )
+        }
+        Arc::new(py.allow_threads(|| exact_permutation_null(columns)).or_raise()?)
+    } else if statistic == Statistic::Mean {
+        py.allow_threads(|| {
+            cached_permutation_null(columns, n_resamples, standardize, accumulator, resampler)
+        })
+        .or_raise()?
+    } else {
+        if standardize || accumulator != Accumulator::F64 {
+            raise!(
+                InvalidInputError,
+                "standardize and accumulator only apply to the mean statistic"
+            )
+        }
+        Arc::new(
+            py.allow_threads(|| statistic_permutation_null(columns, n_resamples, statistic, resampler))
+                .or_raise()?,
+        )
+    };
+    let resampled = Instant::now();
+    // An enumeration has no resampling error to warn about.
+    if !exact {
+        warn_truncated(py, null.diffs.len(), n_resamples)?;
+        check_groups(py, columns)?;
+    }
+    let summaries: Vec<(f64, f64, f64, (f64, f64))> = confidence_level
+        .levels()?
+        .into_iter()
+        .map(|level| null.summarize(level, two_sided))
+        .collect();
+    let (p_value, uplift, observed_diff, _) = summaries[0];
+    let intervals: Vec<(f64, f64)> = summaries.into_iter().map(|summary| summary.3).collect();
+    let (low, high) = widest(&intervals);
+    if !exact {
+        check_interval(py, &null.diffs, (low / null.scale, high / null.scale))?;
+    }
+    if let Some(timings) = timings {
+        timings.resampling += (resampled - started).as_secs_f64();
+        timings.quantiles += resampled.elapsed().as_secs_f64();
+    }
+    Ok((
+        p_value,
+        uplift,
+        observed_diff,
+        confidence_level.intervals(py, intervals)?,
+    ))
+}
 
-                    (sum_b_num / sum_b_den) - (sum_a_num / sum_a_den)
-                })
-                .collect();
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (values, cluster_ids, treatment_by_cluster, confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(values, cluster_ids, treatment_by_cluster, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Performs a permutation test for a cluster-randomized design, permuting treatment across clusters.
+///
+/// Every permutation shuffles the treatment labels among the clusters, keeping the number of treated
+/// clusters and the data of every cluster intact, so the test stays valid when observations within a
+/// cluster are correlated. The statistic is the difference in means of the observations of treated and
+/// control clusters.
+///
+/// Args:
+///     values (List[float]): The observations.
+///     cluster_ids (List[str]): The cluster of every observation.
+///     treatment_by_cluster (Dict[str, bool]): Whether every cluster is treated.
+///     confidence_level (float, optional): The confidence level for the interval of the null differences.
+///         Default is 0.95.
+///     n_resamples (int, optional): The number of permutations. Default is 10000.
+///     two_sided (bool, optional): If True, returns a two-sided p-value. If False, returns a one-sided p-value.
+///         Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float)]: The same tuple as `permutation_test`, with the control
+///         clusters as the first sample.
+/// """
+pub fn cluster_permutation_test(
+    py: Python<'_>,
+    values: Vec<f64>,
+    cluster_ids: Vec<String>,
+    treatment_by_cluster: HashMap<String, bool>,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, f64, (f64, f64))> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    if values.len() != cluster_ids.len() {
+        raise!(InvalidInputError, "values and cluster_ids must have equal size")
+    }
+    let mut clusters: HashMap<&str, (f64, f64)> = HashMap::new();
+    for (value, cluster) in values.iter().zip(&cluster_ids) {
+        let total = clusters.entry(cluster.as_str()).or_default();
+        total.0 += value;
+        total.1 += 1.0;
+    }
+    let mut clusters: Vec<(&str, (f64, f64))> = clusters.into_iter().collect();
+    clusters.sort_unstable_by_key(|(cluster, _)| *cluster);
+    let treated: Vec<bool> = clusters
+        .iter()
+        .map(|(cluster, _)| {
+            treatment_by_cluster.get(*cluster).copied().ok_or_else(|| {
+                InvalidInputError::new_err(format!(
+                    "treatment_by_cluster has no entry for cluster {cluster}"
+                ))
+            })
+        })
+        .collect::<PyResult<_>>()?;
+    let totals: Vec<(f64, f64)> = clusters.into_iter().map(|(_, total)| total).collect();
+    if treated.iter().all(|&t| t) || treated.iter().all(|&t| !t) {
+        raise!(InsufficientDataError, "Both treated and control clusters are required")
+    }
 
-            (vec_diffs, uplift, observed_diff)
-        }
-        _ => {
-            panic!("Input must contain either 2 or 4 vectors.");
+    let diff = |treated: &[bool]| {
+        let (mut a, mut b) = ((0.0, 0.0), (0.0, 0.0));
+        for (&(sum, len), &t) in totals.iter().zip(treated) {
+            let arm = if t { &mut b } else { &mut a };
+            arm.0 += sum;
+            arm.1 += len;
         }
+        (b.0 / b.1 - a.0 / a.1, a.0 / a.1)
     };
+    let (observed_diff, a_mean) = diff(&treated);
+    let uplift = observed_diff / a_mean;
+    let vec_diffs: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut labels = treated.clone();
+            labels.shuffle(rng);
+            diff(&labels).0
+        })
+    });
     let p = (vec_diffs.iter().filter(|i| observed_diff > **i).count() + 1) as f64
         / (n_resamples + 1) as f64;
     let p_value = (2.0 - 2.0 * p).min(p * 2.0);
     let q = vec_diffs.quantile(&[left_q, right_q]);
-    (
+    Ok((
         if two_sided { p_value } else { p },
         uplift,
         observed_diff,
         (q[0], q[1]),
-    )
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, confidence_level = ConfidenceLevel::Single(0.95), n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(args, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Performs a permutation test of the difference in means (or mean ratios) between two conditions
+/// observed on the same units, flipping every unit's data between the conditions.
+///
+/// In a within-subject design every unit, e.g. a user, contributes to both conditions, so the units of
+/// the two groups are not exchangeable and `permutation_test` would ignore the pairing. Under the null
+/// of no effect the two observations of a unit are exchangeable instead, and every permutation swaps
+/// them for a random half of the units. With four lists, every unit's (numerator, denominator) pair
+/// is swapped as a whole, and the ratios sum(num) / sum(den) of both conditions are recomputed, which
+/// gives the paired null of a ratio metric that the differences of the per-unit ratios cannot.
+///
+/// Args:
+///     args (List[List[float]]): Two or four lists of equal size, the i-th value of every list
+///         belonging to the i-th unit.
+///         - If two lists are provided: The values of every unit in the first and second condition.
+///           The function will test the mean of their differences.
+///         - If four lists are provided: The (numerator, denominator) pairs of every unit in the first
+///           and second condition. The function will test the difference in their ratios of sums.
+///     confidence_level (float or List[float], optional): The confidence level for the interval of the
+///         null differences, or a list of levels to get one interval per level. Default is 0.95.
+///     n_resamples (int, optional): The number of sign-flip permutations. Default is 10000.
+///     two_sided (bool, optional): If True, returns a two-sided p-value. If False, returns a one-sided p-value.
+///         Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float)]: The same tuple as `permutation_test`, with the first
+///         condition as the baseline.
+/// """
+pub fn paired_permutation_test(
+    py: Python<'_>,
+    args: Vec<Vec<f64>>,
+    confidence_level: ConfidenceLevel,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PermutationLevels> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let ones;
+    let (num_a, den_a, num_b, den_b): (&[f64], &[f64], &[f64], &[f64]) = match args.as_slice() {
+        [a, b] => {
+            ones = vec![1.0; a.len()];
+            (a, &ones, b, &ones)
+        }
+        [num_a, den_a, num_b, den_b] => (num_a, den_a, num_b, den_b),
+        _ => raise!(InvalidInputError, "args must contain two or four lists, got {}", args.len()),
+    };
+    let n = num_a.len();
+    if [den_a, num_b, den_b].iter().any(|column| column.len() != n) {
+        raise!(InvalidInputError, "The lists must have equal size, one value per unit")
+    }
+    if n == 0 {
+        raise!(InsufficientDataError, "The lists must contain at least one unit")
+    }
+    let totals = |column: &[f64]| column.iter().sum::<f64>();
+    let (sum_num_a, sum_den_a) = (totals(num_a), totals(den_a));
+    let (sum_num_b, sum_den_b) = (totals(num_b), totals(den_b));
+    if sum_den_a == 0.0 || sum_den_b == 0.0 {
+        raise!(InvalidInputError, "The denominators of both conditions must not sum to zero")
+    }
+    // Swapping a unit moves its difference between the conditions from one sum to the other.
+    let shifts: Vec<(f64, f64)> = (0..n)
+        .map(|i| (num_b[i] - num_a[i], den_b[i] - den_a[i]))
+        .collect();
+    let baseline = sum_num_a / sum_den_a;
+    let observed_diff = sum_num_b / sum_den_b - baseline;
+    let diffs: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let (mut num, mut den) = (0.0, 0.0);
+            for &(shift_num, shift_den) in &shifts {
+                if rng.gen::<bool>() {
+                    num += shift_num;
+                    den += shift_den;
+                }
+            }
+            (sum_num_b - num) / (sum_den_b - den) - (sum_num_a + num) / (sum_den_a + den)
+        })
+    });
+    let null = PermutationNull {
+        diffs,
+        uplift: observed_diff / baseline,
+        observed_diff,
+        observed_statistic: observed_diff,
+        scale: 1.0,
+        exact: false,
+    };
+    let summaries: Vec<(f64, f64, f64, (f64, f64))> = confidence_level
+        .levels()?
+        .into_iter()
+        .map(|level| null.summarize(level, two_sided))
+        .collect();
+    let (p_value, uplift, observed_diff, _) = summaries[0];
+    let intervals: Vec<(f64, f64)> = summaries.into_iter().map(|summary| summary.3).collect();
+    Ok((
+        p_value,
+        uplift,
+        observed_diff,
+        confidence_level.intervals(py, intervals)?,
+    ))
+}
+
+/// Trend statistic of `trend_test_ordered`.
+#[derive(Clone, Copy)]
+enum TrendStatistic {
+    /// Jonckheere–Terpstra: the number of pairs of observations from two groups ordered like their
+    /// groups, ties counting one half.
+    Jonckheere,
+    /// Linear contrast: the sum of the group means weighted by the centered scores.
+    Linear,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (groups, scores = None, method = "jonckheere", n_resamples = 10_000, alternative = "two-sided", random_state = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(groups, scores=None, method='jonckheere', n_resamples=10000, alternative='two-sided', random_state=None, seed=None, deterministic=None)")]
+/// """
+/// Performs a permutation test of a monotonic trend across ordered groups, such as increasing price
+/// points or treatment intensities.
+///
+/// The groups are ordered by their scores. The Jonckheere–Terpstra statistic counts the pairs of
+/// observations from two different groups that are ordered like their groups, so it only uses the order
+/// of the scores and the ranks of the data; the linear contrast weights the group means by the centered
+/// scores, which suits a dose-response expected to be linear in the score. Both are compared with their
+/// distribution over random reassignments of the observations to the groups, keeping the group sizes.
+///
+/// Args:
+///     groups (List[List[float]]): The samples of the groups.
+///     scores (List[float], optional): The intensity of every group. Default is None, which uses 0, 1, 2, ...
+///         in the order of `groups`.
+///     method (str, optional): "jonckheere" for the Jonckheere–Terpstra statistic, or "linear" for the
+///         linear contrast of the group means. Default is "jonckheere".
+///     n_resamples (int, optional): The number of permutations. Default is 10000.
+///     alternative (str, optional): "two-sided", "less" or "greater"; "greater" means the outcome
+///         increases with the score. Default is "two-sided".
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The observed trend statistic.
+///         - p_value (float): The permutation p-value for the chosen alternative.
+/// """
+pub fn trend_test_ordered(
+    py: Python<'_>,
+    groups: Vec<Vec<f64>>,
+    scores: Option<Vec<f64>>,
+    method: &str,
+    n_resamples: u64,
+    alternative: &str,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64)> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let alternative = Alternative::parse(alternative).or_raise()?;
+    let method = match method {
+        "jonckheere" => TrendStatistic::Jonckheere,
+        "linear" => TrendStatistic::Linear,
+        _ => raise!(InvalidInputError, "method must be 'jonckheere' or 'linear', got '{method}'"),
+    };
+    let k = groups.len();
+    if k < 2 {
+        raise!(InvalidInputError, "At least two groups are required")
+    }
+    if groups.iter().any(Vec::is_empty) {
+        raise!(InsufficientDataError, "All groups must be non-empty")
+    }
+    let scores = scores.unwrap_or_else(|| (0..k).map(|g| g as f64).collect());
+    if scores.len() != k || scores.iter().any(|s| !s.is_finite()) {
+        raise!(InvalidInputError, "scores must contain one finite value per group")
+    }
+    if scores.iter().all(|&s| s == scores[0]) {
+        raise!(InvalidInputError, "scores must not all be equal")
+    }
+    // Groups are relabelled by the rank of their score, so that label order is score order.
+    let mut order: Vec<usize> = (0..k).collect();
+    order.sort_by(|&i, &j| scores[i].total_cmp(&scores[j]));
+    if let TrendStatistic::Jonckheere = method {
+        if order.windows(2).any(|w| scores[w[0]] == scores[w[1]]) {
+            raise!(InvalidInputError, "The Jonckheere–Terpstra statistic requires distinct scores")
+        }
+    }
+    let mean_score = scores.iter().sum::<f64>() / k as f64;
+    let weights: Vec<f64> = order.iter().map(|&g| scores[g] - mean_score).collect();
+    let sizes: Vec<f64> = order.iter().map(|&g| groups[g].len() as f64).collect();
+
+    let mut pooled: Vec<(f64, usize)> = order
+        .iter()
+        .enumerate()
+        .flat_map(|(label, &g)| groups[g].iter().map(move |&x| (x, label)))
+        .collect();
+    pooled.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let values: Vec<f64> = pooled.iter().map(|&(x, _)| x).collect();
+    let labels: Vec<usize> = pooled.iter().map(|&(_, label)| label).collect();
+    let mut blocks = vec![0];
+    blocks.extend((1..values.len()).filter(|&i| values[i] != values[i - 1]));
+    blocks.push(values.len());
+
+    let statistic = |labels: &[usize]| -> f64 {
+        match method {
+            TrendStatistic::Jonckheere => {
+                let mut below = vec![0.0; k];
+                let mut stat = 0.0;
+                for block in blocks.windows(2) {
+                    let mut counts = vec![0.0; k];
+                    labels[block[0]..block[1]]
+                        .iter()
+                        .for_each(|&label| counts[label] += 1.0);
+                    let (mut lower, mut tied) = (0.0, 0.0);
+                    for g in 0..k {
+                        stat += counts[g] * (lower + 0.5 * tied);
+                        lower += below[g];
+                        tied += counts[g];
+                        below[g] += counts[g];
+                    }
+                }
+                stat
+            }
+            TrendStatistic::Linear => {
+                let mut sums = vec![0.0; k];
+                values
+                    .iter()
+                    .zip(labels)
+                    .for_each(|(x, &label)| sums[label] += x);
+                (0..k).map(|g| weights[g] * sums[g] / sizes[g]).sum()
+            }
+        }
+    };
+    let observed = statistic(&labels);
+    let permuted: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut labels = labels.clone();
+            labels.shuffle(rng);
+            statistic(&labels)
+        })
+    });
+    let tail = |count: usize| (count + 1) as f64 / (n_resamples + 1) as f64;
+    let p_less = tail(permuted.iter().filter(|&&s| s <= observed).count());
+    let p_greater = tail(permuted.iter().filter(|&&s| s >= observed).count());
+    Ok((observed, alternative.p_value(p_less, p_greater)))
 }
@@ -0,0 +1,165 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::tools::*;
+use pylars_core::bootstrap::BootstrapOutput;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_distr::Normal;
+
+/// Noise distribution of a privacy mechanism, by scale.
+#[derive(Clone, Copy)]
+enum Mechanism {
+    /// Laplace noise of scale `b` (standard deviation `b * sqrt(2)`).
+    Laplace,
+    /// Gaussian noise of standard deviation `sigma`.
+    Gaussian,
+}
+
+impl Mechanism {
+    fn parse(noise: &str) -> PyResult<Mechanism> {
+        Ok(match noise {
+            "laplace" => Mechanism::Laplace,
+            "gaussian" => Mechanism::Gaussian,
+            _ => raise!(
+                InvalidInputError,
+                "noise must be 'laplace' or 'gaussian', got '{noise}'"
+            ),
+        })
+    }
+
+    fn sample<R: Rng>(&self, scale: f64, rng: &mut R) -> f64 {
+        if scale == 0.0 {
+            return 0.0;
+        }
+        match self {
+            Mechanism::Laplace => {
+                let u: f64 = rng.gen_range(-0.5..0.5);
+                -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+            }
+            Mechanism::Gaussian => Normal::new(0.0, scale).unwrap().sample(rng),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (args, noise_scale, noise = "laplace", confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(args, noise_scale, noise='laplace', confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Bootstraps the uplift between two groups whose totals are released with privacy noise.
+///
+/// Differentially private pipelines often add Laplace or Gaussian noise of a known scale to every
+/// released sum. A plain bootstrap of the unit-level data then understates the uncertainty of the
+/// released metric: here, every resampled sum (of the values, or of the numerators and denominators) also
+/// receives a fresh noise draw of its group's scale, so the interval covers both the sampling and the
+/// privacy noise, and the p-value is the share of noisy resampled uplifts on the other side of zero,
+/// with the conventions of `bootstrap`. The scales are those of the noise added to each sum (for
+/// Laplace, the sensitivity divided by epsilon).
+///
+/// Args:
+///     args (List[List[float]]): Two samples, or two (numerator, denominator) pairs, as for `bootstrap`.
+///     noise_scale (Tuple[float, float]): The scale of the noise added to every released sum of the
+///         first and of the second group.
+///     noise (str, optional): "laplace" or "gaussian" (the scale is then the standard deviation).
+///         Default is "laplace".
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, float, (float, float)]:
+///         A tuple containing:
+///         - p_value (float): The p-value.
+///         - mean_1 (float): The mean (or ratio) of the first group, without noise.
+///         - mean_2 (float): The mean (or ratio) of the second group, without noise.
+///         - uplift (float): The relative difference between the groups.
+///         - (float, float): The interval of the uplift, including the privacy noise.
+/// """
+pub fn private_bootstrap(
+    py: Python<'_>,
+    args: Vec<Vec<f64>>,
+    noise_scale: (f64, f64),
+    noise: &str,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<BootstrapOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let mechanism = Mechanism::parse(noise)?;
+    if !(noise_scale.0 >= 0.0 && noise_scale.1 >= 0.0) {
+        raise!(InvalidInputError, "noise_scale must be non-negative")
+    }
+    let groups: Vec<(&[f64], Option<&[f64]>)> = match args.len() {
+        2 => vec![(&args[0], None), (&args[1], None)],
+        4 => {
+            if args[0].len() != args[1].len() || args[2].len() != args[3].len() {
+                raise!(
+                    InvalidInputError,
+                    "Each pair of arrays must be of equal length."
+                )
+            }
+            vec![(&args[0], Some(&args[1])), (&args[2], Some(&args[3]))]
+        }
+        _ => raise!(InvalidInputError, "Input must contain either 2 or 4 arrays"),
+    };
+    let statistic = |(values, denominators): (&[f64], Option<&[f64]>)| {
+        let sum = values.iter().sum::<f64>();
+        match denominators {
+            None => sum / values.len() as f64,
+            Some(denominators) => sum / denominators.iter().sum::<f64>(),
+        }
+    };
+    let (stat_1, stat_2) = (statistic(groups[0]), statistic(groups[1]));
+    let uplift = calculate_uplift(stat_1, stat_2);
+
+    let samplers: Vec<IndexSampler<2>> = groups
+        .iter()
+        .map(|&(values, denominators)| {
+            IndexSampler::new([values, denominators.unwrap_or(values)]).or_raise()
+        })
+        .collect::<PyResult<_>>()?;
+    let scales = [noise_scale.0, noise_scale.1];
+    let uplifts = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut stats = [0.0; 2];
+            for (g, &(values, denominators)) in groups.iter().enumerate() {
+                let (mut num, mut den) = (0.0, 0.0);
+                for _ in 0..values.len() {
+                    let [x, y] = samplers[g].draw(rng);
+                    num += x;
+                    den += y;
+                }
+                num += mechanism.sample(scales[g], rng);
+                stats[g] = match denominators {
+                    None => num / values.len() as f64,
+                    Some(_) => num / (den + mechanism.sample(scales[g], rng)),
+                };
+            }
+            calculate_uplift(stats[0], stats[1])
+        })
+    });
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let q = uplifts.quantile(&[left_q, 1.0 - left_q]);
+    let p = (uplifts.iter().filter(|&&u| u > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
+    Ok((
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+        stat_1,
+        stat_2,
+        uplift,
+        (q[0], q[1]),
+    ))
+}
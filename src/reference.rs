@@ -0,0 +1,65 @@
+//! Naive single-threaded implementations of the resampling tests.
+//!
+//! They are exposed under `_reference_*` names and are not part of the public API. Every resample is
+//! written out with explicit index vectors and a single sequential generator, mirroring the textbook
+//! definition, so the optimized kernels can be checked against them: both use the same p-value and
+//! quantile conventions, so on any input their p-values and intervals agree up to Monte Carlo error.
+
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::check_resamples;
+use pylars_core::bootstrap::BootstrapOutput;
+use pylars_core::reference;
+use pyo3::prelude::*;
+
+#[pyfunction(signature = (args, confidence_level = 0.95, n_resamples = 10_000, ind = true, two_sided = true, seed = 0))]
+#[pyo3(
+    name = "_reference_bootstrap",
+    text_signature = "(args, confidence_level=0.95, n_resamples=10000, ind=True, two_sided=True, seed=0)"
+)]
+/// """
+/// Slow reference implementation of `bootstrap`, with the same arguments and return value.
+/// """
+pub fn reference_bootstrap(
+    args: Vec<Vec<f64>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    ind: bool,
+    two_sided: bool,
+    seed: u64,
+) -> PyResult<BootstrapOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    reference::reference_bootstrap(
+        &columns,
+        confidence_level,
+        n_resamples,
+        ind,
+        two_sided,
+        seed,
+    )
+    .or_raise()
+}
+
+#[pyfunction(signature = (args, confidence_level = 0.95, n_resamples = 10_000, two_sided = true, seed = 0))]
+#[pyo3(
+    name = "_reference_permutation_test",
+    text_signature = "(args, confidence_level=0.95, n_resamples=10000, two_sided=True, seed=0)"
+)]
+/// """
+/// Slow reference implementation of `permutation_test`, with the same arguments and return value.
+/// """
+pub fn reference_permutation_test(
+    args: Vec<Vec<f64>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    seed: u64,
+) -> PyResult<(f64, f64, f64, (f64, f64))> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    reference::reference_permutation(&columns, confidence_level, n_resamples, two_sided, seed)
+        .or_raise()
+}
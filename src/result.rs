@@ -0,0 +1,413 @@
+use crate::errors::*;
+use crate::levels::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Leading bytes of every saved result file.
+const MAGIC: &[u8; 4] = b"PLRS";
+/// Version written by `Result.save`; `load_result` reads this version and older ones.
+const FORMAT_VERSION: u16 = 1;
+
+#[pyclass(name = "Result", module = "pylars", frozen, get_all)]
+#[derive(Clone)]
+/// """
+/// A test result that can be saved and loaded again without re-running the test.
+///
+/// The resampled distribution is kept as a sketch of equally spaced quantiles, which is enough to
+/// re-render histograms and intervals at any level and keeps files small.
+///
+/// Args:
+///     test (str): The name of the test that produced the result, e.g. "bootstrap".
+///     statistic (float): The point estimate or test statistic.
+///     p_value (float): The p-value.
+///     ci (Tuple[float, float]): The confidence interval bounds.
+///     distribution (List[float], optional): The resampled statistics, summarized into the sketch.
+///         Default is None.
+///     params (Dict[str, float], optional): The numeric parameters of the run, such as
+///         confidence_level or n_resamples. Default is None.
+///     sketch_size (int, optional): The number of quantiles kept from the distribution, from the
+///         minimum to the maximum. Default is 101.
+/// """
+pub struct TestResult {
+    test: String,
+    statistic: f64,
+    p_value: f64,
+    ci: (f64, f64),
+    params: BTreeMap<String, f64>,
+    sketch: Vec<f64>,
+}
+
+/// Sequential little-endian reader of a saved result, failing with a clear message on truncation.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> PyResult<&[u8]> {
+        if self.pos + n > self.bytes.len() {
+            raise!(StatError, "The result file is truncated")
+        }
+        self.pos += n;
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+
+    fn u32(&mut self) -> PyResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> PyResult<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> PyResult<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| StatError::new_err("The result file contains invalid text"))
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+#[pymethods]
+impl TestResult {
+    #[new]
+    #[pyo3(signature = (test, statistic, p_value, ci, distribution = None, params = None, sketch_size = 101))]
+    fn new(
+        test: String,
+        statistic: f64,
+        p_value: f64,
+        ci: (f64, f64),
+        distribution: Option<Vec<f64>>,
+        params: Option<BTreeMap<String, f64>>,
+        sketch_size: usize,
+    ) -> PyResult<Self> {
+        let sketch = match distribution {
+            Some(distribution) if !distribution.is_empty() => {
+                if sketch_size < 2 {
+                    raise!(InvalidInputError, "sketch_size must be at least 2")
+                }
+                let levels: Vec<f64> = (0..sketch_size)
+                    .map(|k| k as f64 / (sketch_size - 1) as f64)
+                    .collect();
+                distribution.quantile(&levels)
+            }
+            _ => Vec::new(),
+        };
+        Ok(TestResult {
+            test,
+            statistic,
+            p_value,
+            ci,
+            params: params.unwrap_or_default(),
+            sketch,
+        })
+    }
+
+    /// """
+    /// Writes the result to `path` in the versioned pylars binary format.
+    ///
+    /// Args:
+    ///     path (str): The destination file.
+    /// """
+    fn save(&self, path: &str) -> PyResult<()> {
+        let mut out = Vec::with_capacity(64 + 8 * self.sketch.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        write_string(&mut out, &self.test);
+        for value in [self.statistic, self.p_value, self.ci.0, self.ci.1] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.params.len() as u32).to_le_bytes());
+        for (name, value) in &self.params {
+            write_string(&mut out, name);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.sketch.len() as u32).to_le_bytes());
+        for value in &self.sketch {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        fs::write(path, out).map_err(|e| StatError::new_err(format!("Cannot write {path}: {e}")))
+    }
+
+    /// """
+    /// Approximates quantiles of the resampled distribution from the sketch by linear interpolation.
+    ///
+    /// Args:
+    ///     q (List[float]): The quantile levels in [0, 1].
+    ///
+    /// Returns:
+    ///     List[float]: The approximate quantiles.
+    /// """
+    fn quantile(&self, q: Vec<f64>) -> PyResult<Vec<f64>> {
+        if self.sketch.is_empty() {
+            raise!(InvalidInputError, "The result has no distribution sketch")
+        }
+        let last = (self.sketch.len() - 1) as f64;
+        Ok(q.iter()
+            .map(|&level| {
+                let pos = level.clamp(0.0, 1.0) * last;
+                let j = pos.floor() as usize;
+                match self.sketch.get(j + 1) {
+                    Some(&next) => self.sketch[j] + (pos - j as f64) * (next - self.sketch[j]),
+                    None => self.sketch[j],
+                }
+            })
+            .collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Result(test='{}', statistic={}, p_value={}, ci=({}, {}))",
+            self.test, self.statistic, self.p_value, self.ci.0, self.ci.1
+        )
+    }
+}
+
+#[pyfunction(signature = (path))]
+#[pyo3(text_signature = "(path)")]
+/// """
+/// Loads a result written by `Result.save`.
+///
+/// Args:
+///     path (str): The result file.
+///
+/// Returns:
+///     Result: The saved result, including its parameters and distribution sketch.
+/// """
+pub fn load_result(path: &str) -> PyResult<TestResult> {
+    let bytes =
+        fs::read(path).map_err(|e| StatError::new_err(format!("Cannot read {path}: {e}")))?;
+    let mut reader = Reader {
+        bytes: &bytes,
+        pos: 0,
+    };
+    if reader.take(4)? != MAGIC {
+        raise!(StatError, "{path} is not a pylars result file")
+    }
+    let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+    if version > FORMAT_VERSION {
+        raise!(
+            StatError,
+            "{path} uses result format version {version}, this pylars reads up to {FORMAT_VERSION}"
+        )
+    }
+    let test = reader.string()?;
+    let (statistic, p_value) = (reader.f64()?, reader.f64()?);
+    let ci = (reader.f64()?, reader.f64()?);
+    let params = (0..reader.u32()?)
+        .map(|_| Ok((reader.string()?, reader.f64()?)))
+        .collect::<PyResult<_>>()?;
+    let sketch = (0..reader.u32()?)
+        .map(|_| reader.f64())
+        .collect::<PyResult<_>>()?;
+    Ok(TestResult {
+        test,
+        statistic,
+        p_value,
+        ci,
+        params,
+        sketch,
+    })
+}
+
+/// Interval bounds of a `ConfidenceLevel` output, one per level.
+fn split_intervals(
+    py: Python<'_>,
+    confidence_level: &ConfidenceLevel,
+    intervals: &PyObject,
+) -> PyResult<Vec<(f64, f64)>> {
+    Ok(match confidence_level {
+        ConfidenceLevel::Single(_) => vec![intervals.extract(py)?],
+        ConfidenceLevel::Many(_) => intervals.extract(py)?,
+    })
+}
+
+/// One bound of every interval, as a float for a single level or a list in the order of the levels.
+fn bounds(
+    py: Python<'_>,
+    intervals: &[(f64, f64)],
+    many: bool,
+    bound: fn(&(f64, f64)) -> f64,
+) -> PyResult<PyObject> {
+    let bounds: Vec<f64> = intervals.iter().map(bound).collect();
+    Ok(if many {
+        bounds.into_pyobject(py)?.into_any().unbind()
+    } else {
+        bounds[0].into_pyobject(py)?.into_any().unbind()
+    })
+}
+
+#[pyclass(name = "BootstrapResult", module = "pylars", frozen)]
+/// """
+/// The result of `bootstrap` with `return_object=True`, with the fields of its tuple as attributes.
+///
+/// Attributes:
+///     p_value (float): The p-value for the test.
+///     mean_control (float): The mean (or ratio) of the first dataset.
+///     mean_test (float): The mean (or ratio) of the second dataset.
+///     uplift (float): The uplift (mean_test - mean_control) / mean_control.
+///     ci_low (float or List[float]): The lower bound of the uplift interval, or of every interval
+///         when `confidence_level` is a list.
+///     ci_high (float or List[float]): The upper bound of the uplift interval, or of every interval.
+/// """
+pub struct BootstrapResult {
+    #[pyo3(get)]
+    p_value: f64,
+    #[pyo3(get)]
+    mean_control: f64,
+    #[pyo3(get)]
+    mean_test: f64,
+    #[pyo3(get)]
+    uplift: f64,
+    intervals: Vec<(f64, f64)>,
+    many: bool,
+}
+
+impl BootstrapResult {
+    pub fn new(
+        py: Python<'_>,
+        levels: BootstrapLevels,
+        confidence_level: &ConfidenceLevel,
+    ) -> PyResult<Self> {
+        let (p_value, mean_control, mean_test, uplift, intervals) = levels;
+        Ok(BootstrapResult {
+            p_value,
+            mean_control,
+            mean_test,
+            uplift,
+            intervals: split_intervals(py, confidence_level, &intervals)?,
+            many: matches!(confidence_level, ConfidenceLevel::Many(_)),
+        })
+    }
+}
+
+#[pymethods]
+impl BootstrapResult {
+    #[getter]
+    fn ci_low(&self, py: Python<'_>) -> PyResult<PyObject> {
+        bounds(py, &self.intervals, self.many, |interval| interval.0)
+    }
+
+    #[getter]
+    fn ci_high(&self, py: Python<'_>) -> PyResult<PyObject> {
+        bounds(py, &self.intervals, self.many, |interval| interval.1)
+    }
+
+    /// """
+    /// Returns the attributes as a dictionary, e.g. to build a DataFrame row.
+    ///
+    /// Returns:
+    ///     Dict[str, float]: The attributes by name.
+    /// """
+    fn as_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("p_value", self.p_value)?;
+        dict.set_item("mean_control", self.mean_control)?;
+        dict.set_item("mean_test", self.mean_test)?;
+        dict.set_item("uplift", self.uplift)?;
+        dict.set_item("ci_low", self.ci_low(py)?)?;
+        dict.set_item("ci_high", self.ci_high(py)?)?;
+        Ok(dict.into_any().unbind())
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "BootstrapResult(p_value={}, mean_control={}, mean_test={}, uplift={}, ci_low={}, ci_high={})",
+            self.p_value,
+            self.mean_control,
+            self.mean_test,
+            self.uplift,
+            self.ci_low(py)?.bind(py).repr()?,
+            self.ci_high(py)?.bind(py).repr()?
+        ))
+    }
+}
+
+#[pyclass(name = "PermutationResult", module = "pylars", frozen)]
+/// """
+/// The result of `permutation_test` with `return_object=True`, with the fields of its tuple as
+/// attributes.
+///
+/// Attributes:
+///     p_value (float): The permutation p-value.
+///     uplift (float): The relative difference observed_diff / baseline, the mean (or ratio) of the
+///         first sample.
+///     observed_diff (float): The observed difference in means or mean ratios.
+///     ci_low (float or List[float]): The lower bound of the interval of the difference, or of every
+///         interval when `confidence_level` is a list.
+///     ci_high (float or List[float]): The upper bound of the interval, or of every interval.
+/// """
+pub struct PermutationResult {
+    #[pyo3(get)]
+    p_value: f64,
+    #[pyo3(get)]
+    uplift: f64,
+    #[pyo3(get)]
+    observed_diff: f64,
+    intervals: Vec<(f64, f64)>,
+    many: bool,
+}
+
+impl PermutationResult {
+    pub fn new(
+        py: Python<'_>,
+        levels: PermutationLevels,
+        confidence_level: &ConfidenceLevel,
+    ) -> PyResult<Self> {
+        let (p_value, uplift, observed_diff, intervals) = levels;
+        Ok(PermutationResult {
+            p_value,
+            uplift,
+            observed_diff,
+            intervals: split_intervals(py, confidence_level, &intervals)?,
+            many: matches!(confidence_level, ConfidenceLevel::Many(_)),
+        })
+    }
+}
+
+#[pymethods]
+impl PermutationResult {
+    #[getter]
+    fn ci_low(&self, py: Python<'_>) -> PyResult<PyObject> {
+        bounds(py, &self.intervals, self.many, |interval| interval.0)
+    }
+
+    #[getter]
+    fn ci_high(&self, py: Python<'_>) -> PyResult<PyObject> {
+        bounds(py, &self.intervals, self.many, |interval| interval.1)
+    }
+
+    /// """
+    /// Returns the attributes as a dictionary, e.g. to build a DataFrame row.
+    ///
+    /// Returns:
+    ///     Dict[str, float]: The attributes by name.
+    /// """
+    fn as_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("p_value", self.p_value)?;
+        dict.set_item("uplift", self.uplift)?;
+        dict.set_item("observed_diff", self.observed_diff)?;
+        dict.set_item("ci_low", self.ci_low(py)?)?;
+        dict.set_item("ci_high", self.ci_high(py)?)?;
+        Ok(dict.into_any().unbind())
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "PermutationResult(p_value={}, uplift={}, observed_diff={}, ci_low={}, ci_high={})",
+            self.p_value,
+            self.uplift,
+            self.observed_diff,
+            self.ci_low(py)?.bind(py).repr()?,
+            self.ci_high(py)?.bind(py).repr()?
+        ))
+    }
+}
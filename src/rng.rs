@@ -0,0 +1,127 @@
+use crate::errors::*;
+pub use pylars_core::rng::*;
+use pyo3::exceptions::PyRuntimeWarning;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use std::ffi::CString;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[pyclass(module = "pylars", frozen)]
+/// """
+/// A reproducible random stream shared across calls.
+///
+/// Passing the same `RandomState` as `random_state=` to several functions advances its state on every
+/// call, so an analysis gets a single reproducible stream instead of identical per-call seeds. The
+/// stream is locked while a call draws its seed, so a state shared by several Python threads stays
+/// valid, each call getting the next seed in the order the calls reach it. A `seed=`, or an int
+/// `random_state=`, instead seeds a new stream for a single call, so a reported seed reproduces its
+/// result.
+///
+/// Args:
+///     seed (int, optional): The seed of the stream. If None, the stream is seeded from OS entropy.
+/// """
+pub struct RandomState {
+    rng: Mutex<Xoshiro256PlusPlus>,
+}
+
+#[pymethods]
+impl RandomState {
+    #[new]
+    #[pyo3(signature = (seed = None))]
+    fn new(seed: Option<u64>) -> Self {
+        RandomState {
+            rng: Mutex::new(match seed {
+                Some(seed) => Xoshiro256PlusPlus::seed_from_u64(seed),
+                None => Xoshiro256PlusPlus::from_entropy(),
+            }),
+        }
+    }
+
+    /// """
+    /// Draws the next 64-bit integer from the stream.
+    /// """
+    fn next_seed(&self) -> u64 {
+        self.rng
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .next_u64()
+    }
+}
+
+/// A `random_state` argument: a shared `RandomState`, or an int seeding a new one for the call.
+#[derive(FromPyObject)]
+pub enum RandomStateArg<'py> {
+    Shared(Bound<'py, RandomState>),
+    Seed(u64),
+}
+
+/// Seed mixed into every per-resample generator of a call.
+///
+/// Without a `random_state` it is 0, which keeps the historical fixed seeds. An int seed gives the
+/// seed of the first call with a new `RandomState` of that seed.
+pub fn base_seed(random_state: Option<RandomStateArg<'_>>) -> u64 {
+    match random_state {
+        None => 0,
+        Some(RandomStateArg::Shared(state)) => state.get().next_seed(),
+        Some(RandomStateArg::Seed(seed)) => Xoshiro256PlusPlus::seed_from_u64(seed).next_u64(),
+    }
+}
+
+/// Builds the resampler of a call from its `random_state`, `seed` and `deterministic` arguments. A
+/// `seed` is the same as an int `random_state`, and the two cannot be combined.
+pub fn new_resampler(
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Resampler> {
+    let random_state = match (random_state, seed) {
+        (Some(_), Some(_)) => raise!(
+            InvalidInputError,
+            "random_state and seed cannot both be given"
+        ),
+        (random_state, None) => random_state,
+        (None, Some(seed)) => Some(RandomStateArg::Seed(seed)),
+    };
+    Ok(Resampler::from_seed(base_seed(random_state), deterministic))
+}
+
+/// Rejects a run of no resamples, whose distribution would have no quantiles.
+pub fn check_resamples(n_resamples: u64) -> PyResult<()> {
+    if n_resamples == 0 {
+        raise!(InvalidInputError, "n_resamples must be positive")
+    }
+    Ok(())
+}
+
+/// Deadline of a `max_seconds` or `time_budget` argument, counted from now.
+pub fn deadline(max_seconds: Option<f64>) -> PyResult<Option<Instant>> {
+    max_seconds
+        .map(|seconds| {
+            if !(seconds > 0.0 && seconds.is_finite()) {
+                raise!(
+                    InvalidInputError,
+                    "the time limit must be a positive number of seconds, got {seconds}"
+                )
+            }
+            Ok(Instant::now() + Duration::from_secs_f64(seconds))
+        })
+        .transpose()
+}
+
+/// Emits a `RuntimeWarning` when a run stopped at its `max_seconds` before `n_resamples` resamples.
+pub fn warn_truncated(py: Python<'_>, used: usize, n_resamples: u64) -> PyResult<()> {
+    if used as u64 >= n_resamples {
+        return Ok(());
+    }
+    let message = format!(
+        "max_seconds reached: used {used} of the {n_resamples} requested resamples, the p-value and interval are based on {used} resamples"
+    );
+    PyErr::warn(
+        py,
+        &py.get_type::<PyRuntimeWarning>(),
+        &CString::new(message)?,
+        1,
+    )
+}
@@ -0,0 +1,333 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::special::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::*;
+
+/// The pairwise values whose median is the Hodges–Lehmann estimate, seen as an implicit matrix with
+/// sorted rows: differences `b_j - a_i` for two samples, Walsh averages `(x_i + x_j) / 2, j >= i` for one.
+///
+/// Both matrices are monotone along rows and columns, so the number of entries below a threshold is
+/// counted for all rows at once with a single moving pointer.
+pub enum PairwiseMatrix {
+    Shift { a: Vec<f64>, b: Vec<f64> },
+    Walsh { x: Vec<f64> },
+}
+
+impl PairwiseMatrix {
+    /// Builds the matrix of `b - a`, or of the Walsh averages of `a` when `b` is None.
+    pub fn new(mut a: Vec<f64>, b: Option<Vec<f64>>) -> PyResult<Self> {
+        if a.is_empty() {
+            raise!(
+                InsufficientDataError,
+                "Cannot compute the estimate of an empty sample"
+            )
+        }
+        check_no_nan("a", &a)?;
+        a.sort_unstable_by(|x, y| x.total_cmp(y));
+        Ok(match b {
+            Some(mut b) => {
+                if b.is_empty() {
+                    raise!(
+                        InsufficientDataError,
+                        "Cannot compute the estimate of an empty sample"
+                    )
+                }
+                check_no_nan("b", &b)?;
+                b.sort_unstable_by(|x, y| x.total_cmp(y));
+                PairwiseMatrix::Shift { a, b }
+            }
+            None => PairwiseMatrix::Walsh { x: a },
+        })
+    }
+
+    fn n_rows(&self) -> usize {
+        match self {
+            PairwiseMatrix::Shift { a, .. } => a.len(),
+            PairwiseMatrix::Walsh { x } => x.len(),
+        }
+    }
+
+    /// Column range `[start, end)` of row `i`.
+    fn row(&self, i: usize) -> (usize, usize) {
+        match self {
+            PairwiseMatrix::Shift { b, .. } => (0, b.len()),
+            PairwiseMatrix::Walsh { x } => (i, x.len()),
+        }
+    }
+
+    #[inline(always)]
+    fn value(&self, i: usize, j: usize) -> f64 {
+        match self {
+            PairwiseMatrix::Shift { a, b } => b[j] - a[i],
+            PairwiseMatrix::Walsh { x } => (x[i] + x[j]) / 2.0,
+        }
+    }
+
+    /// Number of pairwise values.
+    pub fn len(&self) -> u64 {
+        match self {
+            PairwiseMatrix::Shift { a, b } => a.len() as u64 * b.len() as u64,
+            PairwiseMatrix::Walsh { x } => x.len() as u64 * (x.len() as u64 + 1) / 2,
+        }
+    }
+
+    /// For every row, the first column whose value is not below `t` (`>= t`, or `> t` if `inclusive`).
+    fn partition(&self, t: f64, inclusive: bool) -> Vec<usize> {
+        let below = |v: f64| if inclusive { v <= t } else { v < t };
+        let n = self.n_rows();
+        let mut cuts = Vec::with_capacity(n);
+        match self {
+            // Row values decrease with i, so the cut moves right.
+            PairwiseMatrix::Shift { b, .. } => {
+                let mut p = 0;
+                for i in 0..n {
+                    while p < b.len() && below(self.value(i, p)) {
+                        p += 1;
+                    }
+                    cuts.push(p);
+                }
+            }
+            // Row values increase with i, so the unclamped cut moves left.
+            PairwiseMatrix::Walsh { x } => {
+                let mut p = x.len();
+                for i in 0..n {
+                    while p > 0 && !below(self.value(i, p - 1)) {
+                        p -= 1;
+                    }
+                    cuts.push(p.max(i));
+                }
+            }
+        }
+        cuts
+    }
+
+    /// The `k`-th smallest (0-based) pairwise value, by randomized selection over the candidate band of
+    /// every row in expected O(n log n) time.
+    pub fn select(&self, k: u64) -> f64 {
+        let n = self.n_rows();
+        let starts: Vec<usize> = (0..n).map(|i| self.row(i).0).collect();
+        let mut lo = starts.clone();
+        let mut hi: Vec<usize> = (0..n).map(|i| self.row(i).1).collect();
+        let count = |cuts: &[usize]| -> u64 {
+            cuts.iter()
+                .zip(&starts)
+                .map(|(&c, &s)| (c - s) as u64)
+                .sum()
+        };
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+        loop {
+            let remaining: u64 = lo.iter().zip(&hi).map(|(&l, &h)| (h - l) as u64).sum();
+            if remaining <= n as u64 {
+                let mut candidates: Vec<f64> = (0..n)
+                    .flat_map(|i| (lo[i]..hi[i]).map(move |j| (i, j)))
+                    .map(|(i, j)| self.value(i, j))
+                    .collect();
+                candidates.sort_unstable_by(|a, b| a.total_cmp(b));
+                return candidates[(k - count(&lo)) as usize];
+            }
+            let mut pick = rng.gen_range(0..remaining);
+            let mut row = 0;
+            while pick >= (hi[row] - lo[row]) as u64 {
+                pick -= (hi[row] - lo[row]) as u64;
+                row += 1;
+            }
+            let pivot = self.value(row, lo[row] + pick as usize);
+
+            let below: Vec<usize> = self
+                .partition(pivot, false)
+                .into_iter()
+                .enumerate()
+                .map(|(i, c)| c.clamp(lo[i], hi[i]))
+                .collect();
+            if k < count(&below) {
+                hi = below;
+                continue;
+            }
+            let at_most: Vec<usize> = self
+                .partition(pivot, true)
+                .into_iter()
+                .enumerate()
+                .map(|(i, c)| c.clamp(lo[i], hi[i]))
+                .collect();
+            if k < count(&at_most) {
+                return pivot;
+            }
+            lo = at_most;
+        }
+    }
+
+    /// Median of the pairwise values.
+    pub fn median(&self) -> f64 {
+        let total = self.len();
+        let lower = self.select((total - 1) / 2);
+        if total % 2 == 1 {
+            lower
+        } else {
+            (lower + self.select(total / 2)) / 2.0
+        }
+    }
+}
+
+#[pyfunction(signature = (a, b = None, confidence_level = 0.95))]
+#[pyo3(text_signature = "(a, b=None, confidence_level=0.95)")]
+/// """
+/// Computes the Hodges–Lehmann estimate with its distribution-free confidence interval.
+///
+/// With two samples the estimate is the median of the pairwise differences `b_j - a_i`, the shift
+/// associated with the Mann–Whitney test. With one sample it is the median of the Walsh averages
+/// `(a_i + a_j) / 2`, the location associated with the Wilcoxon signed-rank test. The median and the
+/// interval bounds are found by randomized selection in O(n log n) expected time, without materializing
+/// the quadratic number of pairs. The interval inverts the rank test with its normal approximation.
+///
+/// Args:
+///     a (List[float]): The first sample, or the only sample for the one-sample estimate.
+///     b (List[float], optional): The second sample. Default is None.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///
+/// Returns:
+///     Tuple[float, (float, float)]:
+///         A tuple containing:
+///         - estimate (float): The Hodges–Lehmann shift (two samples) or location (one sample).
+///         - (float, float): The confidence interval bounds for the estimate.
+/// """
+pub fn hodges_lehmann(
+    a: Vec<f64>,
+    b: Option<Vec<f64>>,
+    confidence_level: f64,
+) -> PyResult<(f64, (f64, f64))> {
+    check_level(confidence_level)?;
+    let (n_a, n_b) = (a.len() as f64, b.as_ref().map(|b| b.len() as f64));
+    let matrix = PairwiseMatrix::new(a, b)?;
+    let total = matrix.len();
+    let sd = match n_b {
+        Some(n_b) => (n_a * n_b * (n_a + n_b + 1.0) / 12.0).sqrt(),
+        None => (n_a * (n_a + 1.0) * (2.0 * n_a + 1.0) / 24.0).sqrt(),
+    };
+    let z = norm_ppf(1.0 - (1.0 - confidence_level) / 2.0);
+    // Number of pairwise values excluded from each end of the interval.
+    let excluded = ((total as f64 / 2.0 - z * sd).floor().max(0.0) as u64).min((total - 1) / 2);
+    Ok((
+        matrix.median(),
+        (matrix.select(excluded), matrix.select(total - 1 - excluded)),
+    ))
+}
+
+/// Capping levels searched by `suggest_cap` by default.
+const DEFAULT_CAP_LEVELS: [f64; 9] = [0.9, 0.95, 0.975, 0.99, 0.995, 0.999, 0.9995, 0.9999, 1.0];
+
+/// `suggest_cap` output: the recommended level and cap, and the levels, caps, variance reductions and
+/// relative biases of the tradeoff curve.
+type CapSuggestion = (
+    Option<f64>,
+    Option<f64>,
+    Vec<f64>,
+    Vec<f64>,
+    Vec<f64>,
+    Vec<f64>,
+);
+
+#[pyfunction(signature = (values, target_variance_reduction = 0.5, levels = None))]
+#[pyo3(text_signature = "(values, target_variance_reduction=0.5, levels=None)")]
+/// """
+/// Recommends a capping threshold for a long-tailed metric from its variance-reduction vs. bias tradeoff.
+///
+/// Capping the values above a high quantile removes the rare huge values that dominate the variance of
+/// the mean, at the price of a bias towards lower means. Every candidate quantile level is evaluated in
+/// parallel: the cap is the pooled quantile, the variance reduction is `1 - var(capped) / var(values)`,
+/// which is also the relative reduction of the variance of the mean and so of the sample size needed
+/// for the same power, and the bias is the relative change of the mean. The recommendation is the
+/// highest level, so the mildest capping, that reaches `target_variance_reduction`. The level feeds the
+/// `winsorize` parameter of `bootstrap` and `permutation_test`, which caps at the same quantile of the
+/// pooled groups.
+///
+/// Args:
+///     values (List[float]): The pooled values of the metric, e.g. from a pre-period.
+///     target_variance_reduction (float, optional): The variance reduction to reach, between 0 and 1.
+///         Default is 0.5.
+///     levels (List[float], optional): The candidate quantile levels. Default is None, which uses
+///         0.9, 0.95, 0.975, 0.99, 0.995, 0.999, 0.9995, 0.9999 and 1 (no capping).
+///
+/// Returns:
+///     Tuple[Optional[float], Optional[float], List[float], List[float], List[float], List[float]]:
+///         A tuple containing:
+///         - level (float): The recommended quantile level, or None if no level reaches the target.
+///         - cap (float): The recommended cap, or None.
+///         - levels (List[float]): The candidate levels, in increasing order.
+///         - caps (List[float]): The cap of every level.
+///         - variance_reductions (List[float]): The variance reduction of every level.
+///         - biases (List[float]): The relative bias of the mean of every level.
+/// """
+pub fn suggest_cap(
+    py: Python<'_>,
+    values: Vec<f64>,
+    target_variance_reduction: f64,
+    levels: Option<Vec<f64>>,
+) -> PyResult<CapSuggestion> {
+    if values.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "values must contain at least two observations"
+        )
+    }
+    if !(0.0..=1.0).contains(&target_variance_reduction) {
+        raise!(
+            InvalidInputError,
+            "target_variance_reduction must be in [0, 1]"
+        )
+    }
+    let mut levels = levels.unwrap_or_else(|| DEFAULT_CAP_LEVELS.to_vec());
+    if levels.is_empty() || levels.iter().any(|level| !(0.0..=1.0).contains(level)) {
+        raise!(
+            InvalidInputError,
+            "levels must be quantile levels in [0, 1]"
+        )
+    }
+    levels.sort_by(f64::total_cmp);
+    check_no_nan("values", &values)?;
+    // Two passes, centring on the mean first: the one-pass `sum of squares - n * mean^2` form loses
+    // every digit of the variance when the mean is large.
+    let moments = |cap: f64| {
+        let n = values.len() as f64;
+        let mean = values.iter().map(|&x| x.min(cap)).sum::<f64>() / n;
+        let squares: f64 = values.iter().map(|&x| (x.min(cap) - mean).powi(2)).sum();
+        (mean, squares / (n - 1.0))
+    };
+    let (mean, variance) = moments(f64::INFINITY);
+    if variance == 0.0 {
+        raise!(
+            InvalidInputError,
+            "values are constant, there is no variance to reduce"
+        )
+    }
+    if mean == 0.0 {
+        raise!(
+            InvalidInputError,
+            "values have a zero mean, the relative bias is undefined"
+        )
+    }
+    let caps = values.quantile(&levels);
+    let curve: Vec<(f64, f64)> = py.allow_threads(|| {
+        caps.par_iter()
+            .map(|&cap| {
+                let (capped_mean, capped_variance) = moments(cap);
+                (1.0 - capped_variance / variance, capped_mean / mean - 1.0)
+            })
+            .collect()
+    });
+    let (reductions, biases): (Vec<f64>, Vec<f64>) = curve.into_iter().unzip();
+    let recommended = (0..levels.len())
+        .rev()
+        .find(|&k| reductions[k] >= target_variance_reduction);
+    Ok((
+        recommended.map(|k| levels[k]),
+        recommended.map(|k| caps[k]),
+        levels,
+        caps,
+        reductions,
+        biases,
+    ))
+}
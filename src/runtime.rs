@@ -0,0 +1,53 @@
+use crate::errors::*;
+use pyo3::prelude::*;
+use std::hint::black_box;
+
+/// Sizes in bytes of the buffers every worker allocates and frees during the warm-up, spanning the
+/// small-object and the large-allocation paths of the allocator.
+const WARMUP_BUFFERS: [usize; 3] = [1 << 10, 1 << 16, 1 << 22];
+
+#[pyfunction(signature = (n_threads = None, warmup = true))]
+#[pyo3(text_signature = "(n_threads=None, warmup=True)")]
+/// """
+/// Starts the thread pool of the resampling functions ahead of the first call.
+///
+/// The pool is otherwise started by the first parallel call, with one thread per core (or the
+/// RAYON_NUM_THREADS environment variable), which adds the start of every thread and their first
+/// allocations to the latency of that call. Called right after the import, this moves that cost to
+/// start-up, e.g. before a service accepts requests.
+///
+/// Args:
+///     n_threads (int, optional): The number of threads of the pool. It can only be chosen before the
+///         pool has started: once it runs, passing a different number raises an error. Default is None,
+///         which keeps the default size.
+///     warmup (bool, optional): If True, every thread also allocates and frees buffers of a few sizes,
+///         so that its allocator caches are set up before the first call. Default is True.
+///
+/// Returns:
+///     int: The number of threads of the pool.
+/// """
+pub fn init(py: Python<'_>, n_threads: Option<usize>, warmup: bool) -> PyResult<usize> {
+    if n_threads == Some(0) {
+        raise!(InvalidInputError, "n_threads must be positive")
+    }
+    py.allow_threads(|| {
+        let mut builder = rayon::ThreadPoolBuilder::new();
+        if let Some(n_threads) = n_threads {
+            builder = builder.num_threads(n_threads);
+        }
+        // Fails when the pool has already started, which is only an error for a different size.
+        let started = builder.build_global().is_ok();
+        let running = rayon::current_num_threads();
+        if let Some(n_threads) = n_threads.filter(|&n| !started && n != running) {
+            raise!(InvalidInputError, "the thread pool already runs {running} threads and cannot be resized to {n_threads}, call init before any other pylars function")
+        }
+        if warmup {
+            rayon::broadcast(|_| {
+                for size in WARMUP_BUFFERS {
+                    black_box(vec![0u8; size]);
+                }
+            });
+        }
+        Ok(running)
+    })
+}
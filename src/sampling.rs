@@ -0,0 +1,495 @@
+use crate::errors::*;
+use crate::rng::*;
+use ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand::seq::index;
+use rand_distr::{Distribution, Exp1, StandardNormal};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use std::collections::BTreeMap;
+
+type Assignment = (Vec<usize>, Vec<u64>, BTreeMap<String, Vec<u64>>);
+type Rerandomization = (Vec<usize>, f64, bool, u64, Vec<Vec<f64>>);
+
+/// Number of rerandomization tries drawn in parallel before checking for an accepted one.
+const TRY_BATCH: u64 = 1024;
+
+/// Resampler of a sampling utility; without a seed the draws come from OS entropy.
+fn seeded_resampler(seed: Option<u64>, deterministic: Option<bool>) -> Resampler {
+    Resampler::from_seed(seed.unwrap_or_else(rand::random), deterministic)
+}
+
+fn check_k(n: usize, k: usize) -> PyResult<()> {
+    if k > n {
+        raise!(
+            InvalidInputError,
+            "k must not be greater than n, got k={k} and n={n}"
+        )
+    }
+    Ok(())
+}
+
+#[pyfunction(signature = (n, k, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(n, k, seed=None, deterministic=None)")]
+/// """
+/// Draws k distinct indices from range(n), in random order.
+///
+/// Args:
+///     n (int): The size of the population.
+///     k (int): The number of indices to draw, at most n.
+///     seed (int, optional): The seed of the draw. If None, the draw is seeded from OS entropy.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     numpy.ndarray: The int64 indices.
+/// """
+pub fn sample_without_replacement<'py>(
+    py: Python<'py>,
+    n: usize,
+    k: usize,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Bound<'py, PyArray1<i64>>> {
+    check_k(n, k)?;
+    let resampler = seeded_resampler(seed, deterministic);
+    let sample = py.allow_threads(|| {
+        resampler
+            .map(1, |rng| {
+                index::sample(rng, n, k)
+                    .into_iter()
+                    .map(|i| i as i64)
+                    .collect::<Vec<i64>>()
+            })
+            .swap_remove(0)
+    });
+    Ok(sample.into_pyarray(py))
+}
+
+#[pyfunction(signature = (n, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(n, seed=None, deterministic=None)")]
+/// """
+/// Draws a uniformly random permutation of range(n).
+///
+/// Args:
+///     n (int): The number of items.
+///     seed (int, optional): The seed of the draw. If None, the draw is seeded from OS entropy.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     numpy.ndarray: The int64 permutation.
+/// """
+pub fn random_permutation<'py>(
+    py: Python<'py>,
+    n: usize,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> Bound<'py, PyArray1<i64>> {
+    let resampler = seeded_resampler(seed, deterministic);
+    let permutation = py.allow_threads(|| {
+        resampler
+            .map(1, |rng| {
+                let mut permutation: Vec<i64> = (0..n as i64).collect();
+                permutation.shuffle(rng);
+                permutation
+            })
+            .swap_remove(0)
+    });
+    permutation.into_pyarray(py)
+}
+
+#[pyfunction(signature = (n, k, count, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(n, k, count, seed=None, deterministic=None)")]
+/// """
+/// Draws `count` independent random k-subsets of range(n), in parallel.
+///
+/// Every row is drawn from its own generator, so the result does not depend on the number of threads.
+/// Rows are independent and may repeat.
+///
+/// Args:
+///     n (int): The size of the population.
+///     k (int): The size of every subset, at most n.
+///     count (int): The number of subsets to draw.
+///     seed (int, optional): The seed of the draws. If None, the draws are seeded from OS entropy.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     numpy.ndarray: A (count, k) int64 array with the indices of every subset in increasing order.
+/// """
+pub fn random_combinations<'py>(
+    py: Python<'py>,
+    n: usize,
+    k: usize,
+    count: u64,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Bound<'py, PyArray2<i64>>> {
+    check_k(n, k)?;
+    let resampler = seeded_resampler(seed, deterministic);
+    let rows = py.allow_threads(|| {
+        resampler.map(count, |rng| {
+            let mut row: Vec<i64> = index::sample(rng, n, k)
+                .into_iter()
+                .map(|i| i as i64)
+                .collect();
+            row.sort_unstable();
+            row
+        })
+    });
+    Ok(Array2::from_shape_vec((count as usize, k), rows.concat())
+        .unwrap()
+        .into_pyarray(py))
+}
+
+#[pyfunction(signature = (units, n_variants, strata = None, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(units, n_variants, strata=None, seed=None, deterministic=None)")]
+/// """
+/// Randomly assigns units to variants, balanced overall and within every stratum.
+///
+/// Within a stratum every variant receives the same number of units, up to one; the leftover units of
+/// a stratum go to the variants that are least filled overall, so the total counts also differ by at
+/// most one. The order of the units within every stratum is then shuffled.
+///
+/// Args:
+///     units (int): The number of units to assign.
+///     n_variants (int): The number of variants.
+///     strata (List[str], optional): The stratum of every unit. Default is None, a single stratum.
+///     seed (int, optional): The seed of the draw. If None, the draw is seeded from OS entropy.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[int], List[int], Dict[str, List[int]]]: The variant of every unit, the number of units
+///         per variant, and the number of units per variant in every stratum (empty without strata).
+/// """
+pub fn generate_assignment(
+    py: Python<'_>,
+    units: usize,
+    n_variants: usize,
+    strata: Option<Vec<String>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Assignment> {
+    if n_variants == 0 {
+        raise!(InvalidInputError, "n_variants must be positive")
+    }
+    if strata.as_ref().is_some_and(|strata| strata.len() != units) {
+        raise!(InvalidInputError, "strata must have one label per unit")
+    }
+    let mut members: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    match &strata {
+        Some(strata) => {
+            for (unit, stratum) in strata.iter().enumerate() {
+                members.entry(stratum).or_default().push(unit);
+            }
+        }
+        None => {
+            members.insert("", (0..units).collect());
+        }
+    }
+    let resampler = seeded_resampler(seed, deterministic);
+    let (assignment, counts, strata_counts) = py.allow_threads(|| {
+        resampler
+            .map(1, |rng| {
+                let mut assignment = vec![0; units];
+                let mut counts = vec![0u64; n_variants];
+                let mut strata_counts = BTreeMap::new();
+                for (stratum, units) in &members {
+                    let mut variants: Vec<usize> =
+                        (0..units.len()).map(|j| j % n_variants).collect();
+                    let leftover = units.len() % n_variants;
+                    if leftover > 0 {
+                        // Least filled variants first, ties in random order.
+                        let mut order: Vec<usize> = (0..n_variants).collect();
+                        order.shuffle(rng);
+                        order.sort_by_key(|&variant| counts[variant]);
+                        let full = units.len() - leftover;
+                        variants[full..].copy_from_slice(&order[..leftover]);
+                    }
+                    variants.shuffle(rng);
+                    let mut stratum_counts = vec![0u64; n_variants];
+                    for (&unit, &variant) in units.iter().zip(&variants) {
+                        assignment[unit] = variant;
+                        stratum_counts[variant] += 1;
+                    }
+                    for (count, stratum_count) in counts.iter_mut().zip(&stratum_counts) {
+                        *count += stratum_count;
+                    }
+                    strata_counts.insert(stratum.to_string(), stratum_counts);
+                }
+                (assignment, counts, strata_counts)
+            })
+            .swap_remove(0)
+    });
+    let strata_counts = if strata.is_some() {
+        strata_counts
+    } else {
+        BTreeMap::new()
+    };
+    Ok((assignment, counts, strata_counts))
+}
+
+/// Lower Cholesky factor of a symmetric matrix, or None if it is not positive definite.
+pub fn cholesky(a: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let p = a.len();
+    let mut l = vec![vec![0.0; p]; p];
+    for i in 0..p {
+        for j in 0..=i {
+            let s = a[i][j] - (0..j).map(|k| l[i][k] * l[j][k]).sum::<f64>();
+            if i == j {
+                if s <= 0.0 {
+                    return None;
+                }
+                l[i][i] = s.sqrt();
+            } else {
+                l[i][j] = s / l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+/// Largest Mahalanobis distance between the covariate means of a variant and of variant 0, scaled by
+/// n0 * nv / (n0 + nv) so that it is approximately chi-squared with one degree of freedom per covariate.
+fn mahalanobis_balance(
+    x: &[Vec<f64>],
+    assignment: &[usize],
+    n_variants: usize,
+    l: &[Vec<f64>],
+) -> f64 {
+    let p = l.len();
+    let mut sums = vec![vec![0.0; p]; n_variants];
+    let mut sizes = vec![0.0; n_variants];
+    for (row, &variant) in x.iter().zip(assignment) {
+        sizes[variant] += 1.0;
+        for (sum, value) in sums[variant].iter_mut().zip(row) {
+            *sum += value;
+        }
+    }
+    (1..n_variants)
+        .map(|variant| {
+            // Forward substitution L y = d, the distance being |y|^2.
+            let mut y = vec![0.0; p];
+            for i in 0..p {
+                let d = sums[variant][i] / sizes[variant] - sums[0][i] / sizes[0];
+                y[i] = (d - (0..i).map(|k| l[i][k] * y[k]).sum::<f64>()) / l[i][i];
+            }
+            sizes[0] * sizes[variant] / (sizes[0] + sizes[variant])
+                * y.iter().map(|v| v * v).sum::<f64>()
+        })
+        .fold(0.0, f64::max)
+}
+
+#[pyfunction(signature = (x_covariates, n_variants, balance_threshold, max_tries = 10_000, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(x_covariates, n_variants, balance_threshold, max_tries=10000, seed=None, deterministic=None)"
+)]
+/// """
+/// Rerandomization: draws balanced complete randomizations until the covariates are balanced.
+///
+/// Assignments are drawn in parallel batches and the first one, in draw order, whose Mahalanobis
+/// balance is below the threshold is accepted (Morgan and Rubin, 2012). The balance of an assignment
+/// is the largest distance between a variant and variant 0,
+/// n0 * nv / (n0 + nv) * (mean_v - mean_0)' S^-1 (mean_v - mean_0), with S the covariance matrix of the
+/// covariates; with two variants it is approximately chi-squared with one degree of freedom per
+/// covariate, so a quantile of that distribution is a natural threshold. If no assignment passes
+/// within max_tries, the best one drawn is returned.
+///
+/// Args:
+///     x_covariates (List[List[float]]): The covariates, one row per unit.
+///     n_variants (int): The number of variants, at least 2.
+///     balance_threshold (float): The largest accepted Mahalanobis balance.
+///     max_tries (int, optional): The largest number of assignments drawn. Default is 10000.
+///     seed (int, optional): The seed of the draws. If None, the draws are seeded from OS entropy.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[int], float, bool, int, List[List[float]]]: The variant of every unit, its Mahalanobis
+///         balance, whether it passed the threshold, the number of assignments drawn, and for every
+///         variant after the first the standardized mean differences of the covariates against
+///         variant 0.
+/// """
+pub fn rerandomize(
+    py: Python<'_>,
+    x_covariates: Vec<Vec<f64>>,
+    n_variants: usize,
+    balance_threshold: f64,
+    max_tries: u64,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Rerandomization> {
+    let n = x_covariates.len();
+    if n_variants < 2 {
+        raise!(InvalidInputError, "n_variants must be at least 2")
+    }
+    if n < 2 * n_variants {
+        raise!(
+            InsufficientDataError,
+            "Rerandomization needs at least two units per variant"
+        )
+    }
+    if max_tries == 0 {
+        raise!(InvalidInputError, "max_tries must be positive")
+    }
+    let p = x_covariates[0].len();
+    if p == 0 || x_covariates.iter().any(|row| row.len() != p) {
+        raise!(
+            InvalidInputError,
+            "x_covariates must have the same positive number of covariates in every row"
+        )
+    }
+    let means: Vec<f64> = (0..p)
+        .map(|j| x_covariates.iter().map(|row| row[j]).sum::<f64>() / n as f64)
+        .collect();
+    let covariance: Vec<Vec<f64>> = (0..p)
+        .map(|i| {
+            (0..p)
+                .map(|j| {
+                    x_covariates
+                        .iter()
+                        .map(|row| (row[i] - means[i]) * (row[j] - means[j]))
+                        .sum::<f64>()
+                        / (n - 1) as f64
+                })
+                .collect()
+        })
+        .collect();
+    let l = cholesky(&covariance).ok_or_else(|| {
+        InvalidInputError::new_err("The covariance matrix of x_covariates is singular, remove constant or collinear covariates")
+    })?;
+
+    let mut stream = Xoshiro256PlusPlus::seed_from_u64(seed.unwrap_or_else(rand::random));
+    let (best, tries) = py.allow_threads(|| {
+        let mut best: Option<(f64, Vec<usize>)> = None;
+        let mut tries = 0;
+        while tries < max_tries {
+            let batch = TRY_BATCH.min(max_tries - tries);
+            let resampler = Resampler::from_seed(stream.next_u64(), deterministic);
+            let draws = resampler.map(batch, |rng| {
+                let mut assignment: Vec<usize> = (0..n).map(|j| j % n_variants).collect();
+                assignment.shuffle(rng);
+                let balance = mahalanobis_balance(&x_covariates, &assignment, n_variants, &l);
+                (balance, assignment)
+            });
+            for (k, draw) in draws.into_iter().enumerate() {
+                if best.as_ref().is_none_or(|(balance, _)| draw.0 < *balance) {
+                    best = Some(draw);
+                }
+                if best.as_ref().unwrap().0 <= balance_threshold {
+                    return (best.unwrap(), tries + k as u64 + 1);
+                }
+            }
+            tries += batch;
+        }
+        (best.unwrap(), tries)
+    });
+    let (balance, assignment) = best;
+
+    let sds: Vec<f64> = (0..p).map(|j| covariance[j][j].sqrt()).collect();
+    let variant_means = |variant: usize| -> Vec<f64> {
+        let rows: Vec<&Vec<f64>> = x_covariates
+            .iter()
+            .zip(&assignment)
+            .filter(|(_, &v)| v == variant)
+            .map(|(row, _)| row)
+            .collect();
+        (0..p)
+            .map(|j| rows.iter().map(|row| row[j]).sum::<f64>() / rows.len() as f64)
+            .collect()
+    };
+    let control = variant_means(0);
+    let differences = (1..n_variants)
+        .map(|variant| {
+            variant_means(variant)
+                .iter()
+                .zip(&control)
+                .zip(&sds)
+                .map(|((mean, control), sd)| (mean - control) / sd)
+                .collect()
+        })
+        .collect();
+    Ok((
+        assignment,
+        balance,
+        balance <= balance_threshold,
+        tries,
+        differences,
+    ))
+}
+
+/// Built-in kernel of `parallel_map_rng`, drawing one value per iteration without the GIL.
+#[derive(Clone, Copy)]
+enum Kernel {
+    Uniform,
+    Normal,
+    Exponential,
+}
+
+impl Kernel {
+    fn parse(name: &str) -> PyResult<Kernel> {
+        Ok(match name {
+            "uniform" => Kernel::Uniform,
+            "normal" => Kernel::Normal,
+            "exponential" => Kernel::Exponential,
+            _ => raise!(InvalidInputError, "func must be a callable or one of 'uniform', 'normal' or 'exponential', got '{name}'"),
+        })
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self {
+            Kernel::Uniform => rng.gen(),
+            Kernel::Normal => StandardNormal.sample(rng),
+            Kernel::Exponential => Exp1.sample(rng),
+        }
+    }
+}
+
+#[pyfunction(signature = (func, n_iter, seed = None, deterministic = None))]
+#[pyo3(text_signature = "(func, n_iter, seed=None, deterministic=None)")]
+/// """
+/// Runs a Monte Carlo task over the per-iteration random streams used by the resampling engines.
+///
+/// Every iteration gets its own generator, derived from the seed and the index of the iteration as for
+/// the resamples of `bootstrap`, so the results are in iteration order and do not depend on the number
+/// of threads. A callable receives a 64-bit integer drawn from its iteration's stream, suitable as the
+/// seed of `numpy.random.default_rng`, and is called under the GIL; its results are returned as they
+/// are. A built-in kernel draws one value per iteration in parallel without the GIL: "uniform" on
+/// [0, 1), "normal" (standard) or "exponential" (rate 1).
+///
+/// Args:
+///     func (str or Callable[[int], Any]): A built-in kernel, or a function of the seed of an iteration.
+///     n_iter (int): The number of iterations.
+///     seed (int, optional): The seed of the streams. If None, the streams are seeded from OS entropy.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     List[Any] | numpy.ndarray: The result of the callable for every iteration, or the float64 draws
+///         of the built-in kernel.
+/// """
+pub fn parallel_map_rng(
+    py: Python<'_>,
+    func: &Bound<'_, PyAny>,
+    n_iter: u64,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<PyObject> {
+    let resampler = seeded_resampler(seed, deterministic);
+    if let Ok(name) = func.extract::<String>() {
+        let kernel = Kernel::parse(&name)?;
+        let draws = py.allow_threads(|| resampler.map(n_iter, |rng| kernel.sample(rng)));
+        return Ok(draws.into_pyarray(py).into_any().unbind());
+    }
+    if !func.is_callable() {
+        raise!(InvalidInputError, "func must be a string or a callable")
+    }
+    let function = func.clone().unbind();
+    let results = py
+        .allow_threads(|| {
+            resampler.map(n_iter, |rng| {
+                let seed = rng.next_u64();
+                Python::with_gil(|py| function.call1(py, (seed,)))
+            })
+        })
+        .into_iter()
+        .collect::<PyResult<Vec<PyObject>>>()?;
+    Ok(results.into_pyobject(py)?.into_any().unbind())
+}
@@ -0,0 +1,523 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::special::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+
+type SequentialTest = (Vec<f64>, Vec<f64>, Option<usize>, bool);
+
+/// Number of points of the grid carrying the density of the score between looks (odd, for Simpson's
+/// rule).
+const GRID_POINTS: usize = 801;
+
+/// Half-width of the grid in standard deviations of the score, the density beyond it being negligible.
+const GRID_SDS: f64 = 8.0;
+
+/// Lan-DeMets spending function: the one-sided type I error spent by information fraction `t`.
+#[derive(Clone, Copy)]
+enum Spending {
+    OBrienFleming,
+    Pocock,
+}
+
+impl Spending {
+    fn parse(spending: &str) -> PyResult<Spending> {
+        Ok(match spending {
+            "obrien_fleming" => Spending::OBrienFleming,
+            "pocock" => Spending::Pocock,
+            _ => raise!(
+                InvalidInputError,
+                "spending must be 'obrien_fleming' or 'pocock', got '{spending}'"
+            ),
+        })
+    }
+
+    fn spent(&self, alpha: f64, t: f64) -> f64 {
+        match self {
+            Spending::OBrienFleming => 2.0 * norm_sf(norm_ppf(1.0 - alpha / 2.0) / t.sqrt()),
+            Spending::Pocock => alpha * (1.0 + (std::f64::consts::E - 1.0) * t).ln(),
+        }
+    }
+}
+
+/// Information fractions of the looks: the given ones, checked, or `n_looks` equally spaced ones.
+fn fractions(n_looks: usize, information_fractions: Option<Vec<f64>>) -> PyResult<Vec<f64>> {
+    if n_looks == 0 {
+        raise!(InvalidInputError, "n_looks must be positive")
+    }
+    Ok(match information_fractions {
+        None => (1..=n_looks).map(|k| k as f64 / n_looks as f64).collect(),
+        Some(fractions) => {
+            if fractions.len() != n_looks {
+                raise!(
+                    InvalidInputError,
+                    "information_fractions must have n_looks values"
+                )
+            }
+            let increasing = fractions.windows(2).all(|pair| pair[0] < pair[1]);
+            if !increasing || fractions[0] <= 0.0 || fractions[n_looks - 1] != 1.0 {
+                raise!(
+                    InvalidInputError,
+                    "information_fractions must increase in (0, 1] and end at 1"
+                )
+            }
+            fractions
+        }
+    })
+}
+
+/// Z-scale boundaries of a group sequential design, by numerical integration of the score process.
+///
+/// The score `S_k = Z_k * sqrt(t_k)` has independent normal increments of variance `t_k - t_(k-1)`. Its
+/// density over the continuation region is carried on a grid from look to look, and every boundary is
+/// found by bisection so that the probability of first crossing it is the error spent at that look.
+fn boundaries(fractions: &[f64], alpha: f64, spending: Spending, two_sided: bool) -> Vec<f64> {
+    let exit_tail = |z: f64| {
+        if two_sided {
+            2.0 * norm_sf(z)
+        } else {
+            norm_sf(z)
+        }
+    };
+    let mut bounds = Vec::with_capacity(fractions.len());
+    // Grid points and density times Simpson weight of the score still in the continuation region.
+    let mut grid: Vec<(f64, f64)> = Vec::new();
+    // Symmetric two-sided boundaries spend alpha / 2 on each side.
+    let sides = if two_sided { 2.0 } else { 1.0 };
+    let mut spent = 0.0;
+    for (k, &t) in fractions.iter().enumerate() {
+        let target = sides * spending.spent(alpha / sides, t) - spent;
+        spent += target;
+        let exit = |b: f64| -> f64 {
+            if k == 0 {
+                return exit_tail(b);
+            }
+            let sd = (t - fractions[k - 1]).sqrt();
+            grid.iter()
+                .map(|&(s, mass)| {
+                    let upper = norm_sf((b * t.sqrt() - s) / sd);
+                    let lower = if two_sided {
+                        norm_cdf((-b * t.sqrt() - s) / sd)
+                    } else {
+                        0.0
+                    };
+                    mass * (upper + lower)
+                })
+                .sum()
+        };
+        let bound = if target <= 1e-15 {
+            f64::INFINITY
+        } else {
+            let (mut low, mut high) = (0.0, 40.0);
+            for _ in 0..100 {
+                let mid = 0.5 * (low + high);
+                if exit(mid) > target {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            0.5 * (low + high)
+        };
+        bounds.push(bound);
+
+        let upper = (bound * t.sqrt()).min(GRID_SDS * t.sqrt());
+        let lower = if two_sided {
+            -upper
+        } else {
+            -GRID_SDS * t.sqrt()
+        };
+        let h = (upper - lower) / (GRID_POINTS - 1) as f64;
+        let density = |s: f64| -> f64 {
+            if k == 0 {
+                return (-0.5 * s * s / t).exp() / (2.0 * std::f64::consts::PI * t).sqrt();
+            }
+            let var = t - fractions[k - 1];
+            grid.iter()
+                .map(|&(x, mass)| {
+                    mass * (-0.5 * (s - x).powi(2) / var).exp()
+                        / (2.0 * std::f64::consts::PI * var).sqrt()
+                })
+                .sum()
+        };
+        grid = (0..GRID_POINTS)
+            .map(|i| {
+                let s = lower + i as f64 * h;
+                let weight = match i {
+                    0 => 1.0,
+                    i if i == GRID_POINTS - 1 => 1.0,
+                    i if i % 2 == 1 => 4.0,
+                    _ => 2.0,
+                };
+                (s, density(s) * weight * h / 3.0)
+            })
+            .collect();
+    }
+    bounds
+}
+
+#[pyfunction(signature = (n_looks, alpha = 0.05, spending = "obrien_fleming", two_sided = true, information_fractions = None))]
+#[pyo3(
+    text_signature = "(n_looks, alpha=0.05, spending='obrien_fleming', two_sided=True, information_fractions=None)"
+)]
+/// """
+/// Computes the z-score boundaries of a group sequential design with Lan-DeMets alpha spending.
+///
+/// At look k the test stops and rejects when |z| (or z, one-sided) reaches the k-th boundary. The
+/// boundaries spend the one-sided error a = `alpha` (`alpha / 2` on each side when two-sided) over the
+/// looks, by information fraction t, with the O'Brien-Fleming-type function
+/// 2 - 2 * Phi(z_(1 - a/2) / sqrt(t)), which is very strict early and close to the fixed design at the
+/// end, or the Pocock-type function a * log(1 + (e - 1) * t), which is nearly the same at every look.
+/// They are computed by numerical integration, so the overall error is `alpha` up to about 1e-6.
+///
+/// Args:
+///     n_looks (int): The number of analyses, including the final one.
+///     alpha (float, optional): The overall type I error. Default is 0.05.
+///     spending (str, optional): "obrien_fleming" or "pocock". Default is "obrien_fleming".
+///     two_sided (bool, optional): Whether the boundaries are symmetric for a two-sided test.
+///         Default is True.
+///     information_fractions (List[float], optional): The share of the final sample size at every look,
+///         increasing and ending at 1. Default is None, which spaces the looks equally.
+///
+/// Returns:
+///     List[float]: The z-score boundary of every look (inf where no error is spent).
+/// """
+pub fn group_sequential_bounds(
+    n_looks: usize,
+    alpha: f64,
+    spending: &str,
+    two_sided: bool,
+    information_fractions: Option<Vec<f64>>,
+) -> PyResult<Vec<f64>> {
+    if !(alpha > 0.0 && alpha < 1.0) {
+        raise!(InvalidInputError, "alpha must be in (0, 1)")
+    }
+    let fractions = fractions(n_looks, information_fractions)?;
+    Ok(boundaries(
+        &fractions,
+        alpha,
+        Spending::parse(spending)?,
+        two_sided,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, n_looks, alpha = 0.05, spending = "obrien_fleming", two_sided = true, information_fractions = None, n_resamples = 2000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, n_looks, alpha=0.05, spending='obrien_fleming', two_sided=True, information_fractions=None, n_resamples=2000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Replays a group sequential test on two samples in arrival order.
+///
+/// Look k uses the first `t_k` share of each sample, where `t_k` is its information fraction. Its
+/// z-score is the difference in means (b - a) divided by its bootstrap standard error, and is
+/// compared with the boundaries of `group_sequential_bounds`.
+///
+/// Args:
+///     a (List[float]): The first sample, in arrival order.
+///     b (List[float]): The second sample, in arrival order.
+///     n_looks (int): The number of analyses, including the final one.
+///     alpha (float, optional): The overall type I error. Default is 0.05.
+///     spending (str, optional): "obrien_fleming" or "pocock". Default is "obrien_fleming".
+///     two_sided (bool, optional): Whether the test is two-sided. Default is True.
+///     information_fractions (List[float], optional): The share of each sample analysed at every look.
+///         Default is None, which spaces the looks equally.
+///     n_resamples (int, optional): The number of bootstrap resamples of every look. Default is 2000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], Optional[int], bool]:
+///         A tuple containing:
+///         - bounds (List[float]): The z-score boundary of every look.
+///         - z_scores (List[float]): The z-score of every look.
+///         - stop_look (int or None): The first look (from 1) whose z-score crosses its boundary.
+///         - rejected (bool): Whether the null hypothesis is rejected at some look.
+/// """
+pub fn group_sequential_test(
+    py: Python<'_>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+    n_looks: usize,
+    alpha: f64,
+    spending: &str,
+    two_sided: bool,
+    information_fractions: Option<Vec<f64>>,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<SequentialTest> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let fractions = fractions(n_looks, information_fractions.clone())?;
+    let bounds =
+        group_sequential_bounds(n_looks, alpha, spending, two_sided, information_fractions)?;
+    let z_scores: Vec<f64> = fractions
+        .iter()
+        .map(|&t| {
+            let a = &a[..((t * a.len() as f64).ceil() as usize).min(a.len())];
+            let b = &b[..((t * b.len() as f64).ceil() as usize).min(b.len())];
+            if a.len() < 2 || b.len() < 2 {
+                raise!(
+                    InsufficientDataError,
+                    "every look needs at least two observations per sample"
+                )
+            }
+            let sampler_a = IndexSampler::new([a]).or_raise()?;
+            let sampler_b = IndexSampler::new([b]).or_raise()?;
+            let diffs = py.allow_threads(|| {
+                resampler.map(n_resamples, |rng| {
+                    let mean_a =
+                        (0..a.len()).map(|_| sampler_a.draw(rng)[0]).sum::<f64>() / a.len() as f64;
+                    let mean_b =
+                        (0..b.len()).map(|_| sampler_b.draw(rng)[0]).sum::<f64>() / b.len() as f64;
+                    mean_b - mean_a
+                })
+            });
+            let mean = diffs.iter().sum::<f64>() / diffs.len() as f64;
+            let se = (diffs.iter().map(|d| (d - mean).powi(2)).sum::<f64>()
+                / (diffs.len() as f64 - 1.0))
+                .sqrt();
+            let diff =
+                b.iter().sum::<f64>() / b.len() as f64 - a.iter().sum::<f64>() / a.len() as f64;
+            Ok(diff / se)
+        })
+        .collect::<PyResult<_>>()?;
+    let stop_look = z_scores
+        .iter()
+        .zip(&bounds)
+        .position(|(&z, &bound)| {
+            if two_sided {
+                z.abs() >= bound
+            } else {
+                z >= bound
+            }
+        })
+        .map(|look| look + 1);
+    Ok((bounds, z_scores, stop_look, stop_look.is_some()))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (current_data_a, current_data_b, planned_n, mde = None, alpha = 0.05, two_sided = true, n_sims = 2000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(current_data_a, current_data_b, planned_n, mde=None, alpha=0.05, two_sided=True, n_sims=2000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Estimates by simulation the probability that a running experiment ends significant if continued to
+/// its planned size, for stopping early for futility.
+///
+/// Every simulation completes both groups to `planned_n` observations by resampling their current data,
+/// the future observations of the second group being shifted so that its mean exceeds the first one's by
+/// `mde`, and then runs the final two-sample z-test (Welch standard error) at level `alpha` on the
+/// completed data. A low conditional power under the minimum detectable effect means the experiment is
+/// unlikely to reach significance even if the effect is real.
+///
+/// Args:
+///     current_data_a (List[float]): The observations of the first group so far.
+///     current_data_b (List[float]): The observations of the second group so far.
+///     planned_n (int): The planned final number of observations of each group.
+///     mde (float, optional): The assumed difference in means (b - a) of the future observations.
+///         Default is None, which uses the current observed difference.
+///     alpha (float, optional): The significance level of the final test. Default is 0.05.
+///     two_sided (bool, optional): Whether the final test is two-sided (otherwise b > a).
+///         Default is True.
+///     n_sims (int, optional): The number of simulated continuations. Default is 2000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float]: The conditional power and the current difference in means (b - a).
+/// """
+pub fn conditional_power(
+    py: Python<'_>,
+    current_data_a: Vec<f64>,
+    current_data_b: Vec<f64>,
+    planned_n: usize,
+    mde: Option<f64>,
+    alpha: f64,
+    two_sided: bool,
+    n_sims: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64)> {
+    check_resamples(n_sims)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let (len_a, len_b) = (current_data_a.len(), current_data_b.len());
+    if len_a < 2 || len_b < 2 {
+        raise!(
+            InsufficientDataError,
+            "Each group needs at least two current observations"
+        )
+    }
+    if planned_n < len_a.max(len_b) {
+        raise!(
+            InvalidInputError,
+            "planned_n must be at least the current size of each group"
+        )
+    }
+    if !(alpha > 0.0 && alpha < 1.0) {
+        raise!(InvalidInputError, "alpha must be in (0, 1)")
+    }
+    let moments = |data: &[f64]| {
+        data.iter()
+            .fold((0.0, 0.0), |(sum, squares), &x| (sum + x, squares + x * x))
+    };
+    let (sum_a, squares_a) = moments(&current_data_a);
+    let (sum_b, squares_b) = moments(&current_data_b);
+    let (mean_a, mean_b) = (sum_a / len_a as f64, sum_b / len_b as f64);
+    let observed = mean_b - mean_a;
+    let shift = mean_a + mde.unwrap_or(observed) - mean_b;
+    let critical = norm_ppf(1.0 - if two_sided { alpha / 2.0 } else { alpha });
+    let sampler_a = IndexSampler::new([current_data_a.as_slice()]).or_raise()?;
+    let sampler_b = IndexSampler::new([current_data_b.as_slice()]).or_raise()?;
+    let n = planned_n as f64;
+    let significant = py.allow_threads(|| {
+        resampler.map(n_sims, |rng| {
+            let (mut sum_1, mut squares_1) = (sum_a, squares_a);
+            for _ in len_a..planned_n {
+                let [x] = sampler_a.draw(rng);
+                sum_1 += x;
+                squares_1 += x * x;
+            }
+            let (mut sum_2, mut squares_2) = (sum_b, squares_b);
+            for _ in len_b..planned_n {
+                let x = sampler_b.draw(rng)[0] + shift;
+                sum_2 += x;
+                squares_2 += x * x;
+            }
+            let var = |sum: f64, squares: f64| (squares - sum * sum / n) / (n - 1.0);
+            let se = ((var(sum_1, squares_1) + var(sum_2, squares_2)) / n).sqrt();
+            let z = (sum_2 - sum_1) / n / se;
+            if two_sided {
+                z.abs() >= critical
+            } else {
+                z >= critical
+            }
+        })
+    });
+    let power = significant.iter().filter(|&&s| s).count() as f64 / n_sims as f64;
+    Ok((power, observed))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (interim_a, interim_b, target_power, mde, alpha = 0.05, two_sided = true, blinded = true, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(interim_a, interim_b, target_power, mde, alpha=0.05, two_sided=True, blinded=True, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Re-estimates at an interim look the sample size an experiment needs to reach its target power, from
+/// the variance of the interim data.
+///
+/// The required size of each group is that of the two-sample z-test,
+/// `(z_alpha + z_power)^2 (var_a + var_b) / mde^2`, with the variances estimated on the interim data.
+/// With `blinded`, both groups are pooled and share the lumped one-sample variance, which ignores the
+/// group labels and so keeps the treatment effect hidden from the team re-estimating the size; it
+/// slightly overestimates the variance (by about `mde^2 / 4`), which errs on the side of power. The
+/// interval comes from bootstrapping the interim data (pooled when blinded), which shows how much the
+/// re-estimated size would move with the variance estimate.
+///
+/// Args:
+///     interim_a (List[float]): The observations of the first group at the interim look.
+///     interim_b (List[float]): The observations of the second group at the interim look.
+///     target_power (float): The desired power, between 0 and 1.
+///     mde (float): The minimum detectable difference in means.
+///     alpha (float, optional): The significance level of the final test. Default is 0.05.
+///     two_sided (bool, optional): Whether the final test is two-sided. Default is True.
+///     blinded (bool, optional): Whether to estimate the variance without the group labels. Default is True.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[int, (float, float)]: The required number of observations of each group, and the bootstrap
+///         interval of that size.
+/// """
+pub fn reestimate_n(
+    py: Python<'_>,
+    interim_a: Vec<f64>,
+    interim_b: Vec<f64>,
+    target_power: f64,
+    mde: f64,
+    alpha: f64,
+    two_sided: bool,
+    blinded: bool,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(u64, (f64, f64))> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    if interim_a.len() < 2 || interim_b.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "Each group needs at least two interim observations"
+        )
+    }
+    if !(target_power > 0.0 && target_power < 1.0) {
+        raise!(InvalidInputError, "target_power must be in (0, 1)")
+    }
+    if !(alpha > 0.0 && alpha < 1.0) {
+        raise!(InvalidInputError, "alpha must be in (0, 1)")
+    }
+    if mde == 0.0 || !mde.is_finite() {
+        raise!(
+            InvalidInputError,
+            "mde must be a non-zero finite difference"
+        )
+    }
+    let z = norm_ppf(1.0 - if two_sided { alpha / 2.0 } else { alpha }) + norm_ppf(target_power);
+    let required = |var_sum: f64| z * z * var_sum / (mde * mde);
+    let variance = |(n, sum, squares): (f64, f64, f64)| (squares - sum * sum / n) / (n - 1.0);
+    let moments = |data: &mut dyn Iterator<Item = f64>| {
+        data.fold((0.0, 0.0, 0.0), |(n, sum, squares), x| {
+            (n + 1.0, sum + x, squares + x * x)
+        })
+    };
+
+    let (n_required, sizes) = if blinded {
+        let pooled: Vec<f64> = interim_a.iter().chain(&interim_b).copied().collect();
+        let n_required = required(2.0 * variance(moments(&mut pooled.iter().copied())));
+        let sampler = IndexSampler::new([pooled.as_slice()]).or_raise()?;
+        let sizes = py.allow_threads(|| {
+            resampler.map(n_resamples, |rng| {
+                let var = variance(moments(
+                    &mut (0..pooled.len()).map(|_| sampler.draw(rng)[0]),
+                ));
+                required(2.0 * var)
+            })
+        });
+        (n_required, sizes)
+    } else {
+        let var_a = variance(moments(&mut interim_a.iter().copied()));
+        let var_b = variance(moments(&mut interim_b.iter().copied()));
+        let n_required = required(var_a + var_b);
+        let sampler_a = IndexSampler::new([interim_a.as_slice()]).or_raise()?;
+        let sampler_b = IndexSampler::new([interim_b.as_slice()]).or_raise()?;
+        let sizes = py.allow_threads(|| {
+            resampler.map(n_resamples, |rng| {
+                let var_a = variance(moments(
+                    &mut (0..interim_a.len()).map(|_| sampler_a.draw(rng)[0]),
+                ));
+                let var_b = variance(moments(
+                    &mut (0..interim_b.len()).map(|_| sampler_b.draw(rng)[0]),
+                ));
+                required(var_a + var_b)
+            })
+        });
+        (n_required, sizes)
+    };
+    let q = sizes.quantile(&[left_q, right_q]);
+    Ok((n_required.ceil() as u64, (q[0], q[1])))
+}
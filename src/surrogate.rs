@@ -0,0 +1,161 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::sampling::cholesky;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand_distr::{Distribution, StandardNormal};
+
+type SurrogateEstimate = (f64, f64, (f64, f64), f64);
+
+/// Surrogate index of the mean of the rows: the linear model `params` (intercept first) applied to the
+/// mean of every short-term metric.
+fn surrogate_index(means: &[f64], params: &[f64]) -> f64 {
+    params[0]
+        + means
+            .iter()
+            .zip(&params[1..])
+            .map(|(m, b)| m * b)
+            .sum::<f64>()
+}
+
+/// Column means of the rows `ids` of `rows`.
+fn column_means(rows: &[Vec<f64>], ids: impl Iterator<Item = usize>) -> Vec<f64> {
+    let mut sums = vec![0.0; rows[0].len()];
+    let mut n = 0.0;
+    for id in ids {
+        sums.iter_mut().zip(&rows[id]).for_each(|(s, x)| *s += x);
+        n += 1.0;
+    }
+    sums.into_iter().map(|s| s / n).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (short_term_a, short_term_b, surrogate_model_params, params_cov = None, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(short_term_a, short_term_b, surrogate_model_params, params_cov=None, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Estimates the long-term effect of a treatment from short-term metrics with a surrogate index, and
+/// bootstraps its uncertainty.
+///
+/// The surrogate index is the long-term outcome predicted by a linear model of the short-term metrics,
+/// fitted beforehand on a holdout where both were observed. The estimated long-term effect is the
+/// difference between the mean predictions of the two groups. Users are resampled within each group
+/// and, when `params_cov` is given, the model parameters are redrawn from a normal distribution in
+/// every resample, so the interval also covers the estimation error of the model. The estimate relies on
+/// the surrogacy assumption: the treatment affects the long-term outcome only through the short-term
+/// metrics.
+///
+/// Args:
+///     short_term_a (List[List[float]]): The short-term metrics of every user of the first group, one
+///         row per user.
+///     short_term_b (List[List[float]]): The short-term metrics of every user of the second group.
+///     surrogate_model_params (List[float]): The intercept followed by one coefficient per metric.
+///     params_cov (List[List[float]], optional): The covariance matrix of the parameters, e.g. from the
+///         holdout regression. Default is None, which treats the parameters as known.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, (float, float), float]:
+///         A tuple containing:
+///         - effect (float): The estimated long-term effect (b - a).
+///         - uplift (float): The effect relative to the predicted long-term mean of the first group.
+///         - (float, float): The bootstrap interval of the effect.
+///         - p_value (float): The two-sided bootstrap p-value of a zero effect.
+/// """
+pub fn surrogate_extrapolation(
+    py: Python<'_>,
+    short_term_a: Vec<Vec<f64>>,
+    short_term_b: Vec<Vec<f64>>,
+    surrogate_model_params: Vec<f64>,
+    params_cov: Option<Vec<Vec<f64>>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<SurrogateEstimate> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    if short_term_a.is_empty() || short_term_b.is_empty() {
+        raise!(InsufficientDataError, "Both groups must be non-empty")
+    }
+    if surrogate_model_params.is_empty() {
+        raise!(
+            InvalidInputError,
+            "surrogate_model_params must contain at least the intercept"
+        )
+    }
+    let p = surrogate_model_params.len() - 1;
+    if short_term_a
+        .iter()
+        .chain(&short_term_b)
+        .any(|row| row.len() != p)
+    {
+        raise!(
+            InvalidInputError,
+            "Every row must have one value per coefficient of surrogate_model_params"
+        )
+    }
+    let params_factor = params_cov
+        .map(|cov| {
+            if cov.len() != p + 1 || cov.iter().any(|row| row.len() != p + 1) {
+                raise!(
+                    InvalidInputError,
+                    "params_cov must be a square matrix of the size of surrogate_model_params"
+                )
+            }
+            cholesky(&cov)
+                .ok_or_else(|| InvalidInputError::new_err("params_cov must be positive definite"))
+        })
+        .transpose()?;
+    let (len_a, len_b) = (short_term_a.len(), short_term_b.len());
+    let index_a = surrogate_index(
+        &column_means(&short_term_a, 0..len_a),
+        &surrogate_model_params,
+    );
+    let index_b = surrogate_index(
+        &column_means(&short_term_b, 0..len_b),
+        &surrogate_model_params,
+    );
+    let effect = index_b - index_a;
+
+    let dist_a = rand::distributions::Uniform::new(0, len_a);
+    let dist_b = rand::distributions::Uniform::new(0, len_b);
+    let effects = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let means_a = column_means(&short_term_a, (0..len_a).map(|_| dist_a.sample(rng)));
+            let means_b = column_means(&short_term_b, (0..len_b).map(|_| dist_b.sample(rng)));
+            let params = match &params_factor {
+                None => surrogate_model_params.clone(),
+                Some(l) => {
+                    let z: Vec<f64> = (0..=p).map(|_| StandardNormal.sample(rng)).collect();
+                    surrogate_model_params
+                        .iter()
+                        .zip(l)
+                        .map(|(param, row)| {
+                            param + row.iter().zip(&z).map(|(l, z)| l * z).sum::<f64>()
+                        })
+                        .collect()
+                }
+            };
+            surrogate_index(&means_b, &params) - surrogate_index(&means_a, &params)
+        })
+    });
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let q = effects.quantile(&[left_q, 1.0 - left_q]);
+    let p_greater =
+        (effects.iter().filter(|&&e| e > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
+    Ok((
+        effect,
+        effect / index_a,
+        (q[0], q[1]),
+        (2.0 - 2.0 * p_greater).min(2.0 * p_greater),
+    ))
+}
@@ -0,0 +1,256 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::special::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Checks a right-censored sample and returns its (time, event) pairs sorted by time.
+fn sorted_sample(time: &[f64], event: &[bool]) -> PyResult<Vec<(f64, bool)>> {
+    if time.len() != event.len() {
+        raise!(InvalidInputError, "time and event must be of equal length")
+    }
+    if time.is_empty() {
+        raise!(InsufficientDataError, "The samples must be non-empty")
+    }
+    if time.iter().any(|&t| !(t >= 0.0 && t.is_finite())) {
+        raise!(
+            InvalidInputError,
+            "The times must be finite and non-negative"
+        )
+    }
+    let mut sample: Vec<(f64, bool)> = time.iter().copied().zip(event.iter().copied()).collect();
+    sample.sort_unstable_by(|x, y| x.0.total_cmp(&y.0));
+    Ok(sample)
+}
+
+/// Kaplan–Meier estimate at `times` of a sample sorted by time: the product of `1 - d / n` over the
+/// event times up to each point, with `d` events among the `n` subjects still at risk.
+fn km_curve(sorted: &[(f64, bool)], times: &[f64]) -> Vec<f64> {
+    let mut steps = Vec::new();
+    let mut survival = 1.0;
+    let mut k = 0;
+    while k < sorted.len() {
+        let t = sorted[k].0;
+        let at_risk = (sorted.len() - k) as f64;
+        let mut deaths = 0.0;
+        while k < sorted.len() && sorted[k].0 == t {
+            deaths += sorted[k].1 as u8 as f64;
+            k += 1;
+        }
+        if deaths > 0.0 {
+            survival *= 1.0 - deaths / at_risk;
+            steps.push((t, survival));
+        }
+    }
+    times
+        .iter()
+        .map(|&t| {
+            let passed = steps.partition_point(|&(s, _)| s <= t);
+            if passed == 0 {
+                1.0
+            } else {
+                steps[passed - 1].1
+            }
+        })
+        .collect()
+}
+
+/// Log-rank chi-square statistic of the pooled sample sorted by time, `in_a[k]` telling whether
+/// `pooled[k]` belongs to the first group of `len_a` subjects. NaN when no event tells the groups apart.
+fn logrank_statistic(pooled: &[(f64, bool)], in_a: &[bool], len_a: usize) -> f64 {
+    let (mut at_risk, mut at_risk_a) = (pooled.len() as f64, len_a as f64);
+    let (mut observed_minus_expected, mut variance) = (0.0, 0.0);
+    let mut k = 0;
+    while k < pooled.len() {
+        let t = pooled[k].0;
+        let (mut deaths, mut deaths_a, mut leaving, mut leaving_a) = (0.0, 0.0, 0.0, 0.0);
+        while k < pooled.len() && pooled[k].0 == t {
+            let death = pooled[k].1 as u8 as f64;
+            deaths += death;
+            leaving += 1.0;
+            if in_a[k] {
+                deaths_a += death;
+                leaving_a += 1.0;
+            }
+            k += 1;
+        }
+        if deaths > 0.0 {
+            let share = at_risk_a / at_risk;
+            observed_minus_expected += deaths_a - deaths * share;
+            if at_risk > 1.0 {
+                variance += deaths * share * (1.0 - share) * (at_risk - deaths) / (at_risk - 1.0);
+            }
+        }
+        at_risk -= leaving;
+        at_risk_a -= leaving_a;
+    }
+    observed_minus_expected * observed_minus_expected / variance
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (time_a, event_a, time_b, event_b, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(time_a, event_a, time_b, event_b, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares the survival curves of two right-censored samples, such as the time to churn of two
+/// cohorts, with the log-rank test.
+///
+/// The chi-square statistic is referred both to its asymptotic distribution with one degree of freedom
+/// and to its permutation distribution, which shuffles the group labels among the pooled subjects and
+/// stays valid for small samples or few events.
+///
+/// Args:
+///     time_a (List[float]): The follow-up times of the first group, non-negative.
+///     event_a (List[bool]): Whether each subject of the first group had the event (True) or was
+///         censored at its time (False).
+///     time_b (List[float]): The follow-up times of the second group, non-negative.
+///     event_b (List[bool]): The event indicators of the second group.
+///     n_resamples (int, optional): The number of permutations. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float]:
+///         A tuple containing:
+///         - statistic (float): The log-rank chi-square statistic.
+///         - p_value (float): The asymptotic p-value from the chi-square distribution with 1 degree of freedom.
+///         - permutation_p_value (float): The permutation p-value.
+/// """
+pub fn logrank_test(
+    py: Python<'_>,
+    time_a: Vec<f64>,
+    event_a: Vec<bool>,
+    time_b: Vec<f64>,
+    event_b: Vec<bool>,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, f64, f64)> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    sorted_sample(&time_a, &event_a)?;
+    sorted_sample(&time_b, &event_b)?;
+    if !event_a.iter().chain(&event_b).any(|&e| e) {
+        raise!(
+            InsufficientDataError,
+            "The samples must contain at least one event"
+        )
+    }
+    let len_a = time_a.len();
+    let mut pooled: Vec<(f64, bool, bool)> = time_a
+        .iter()
+        .zip(&event_a)
+        .map(|(&t, &e)| (t, e, true))
+        .chain(time_b.iter().zip(&event_b).map(|(&t, &e)| (t, e, false)))
+        .collect();
+    pooled.sort_unstable_by(|x, y| x.0.total_cmp(&y.0));
+    let labels: Vec<bool> = pooled.iter().map(|&(_, _, a)| a).collect();
+    let pooled: Vec<(f64, bool)> = pooled.into_iter().map(|(t, e, _)| (t, e)).collect();
+    let observed = logrank_statistic(&pooled, &labels, len_a);
+    if observed.is_nan() {
+        return Ok((f64::NAN, f64::NAN, f64::NAN));
+    }
+
+    // The pooled sample is sorted once: every permutation only shuffles the group labels.
+    let null: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut labels = labels.clone();
+            labels.shuffle(rng);
+            logrank_statistic(&pooled, &labels, len_a)
+        })
+    });
+    let exceed = null
+        .iter()
+        .filter(|&&s| s >= observed * (1.0 - 1e-12))
+        .count();
+    Ok((
+        observed,
+        chi2_sf(observed, 1.0),
+        (exceed + 1) as f64 / (n_resamples + 1) as f64,
+    ))
+}
+
+type KaplanMeierOutput = (Vec<f64>, Vec<f64>, Vec<(f64, f64)>);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (time, event, times = None, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(time, event, times=None, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Estimates the survival curve of a right-censored sample with the Kaplan–Meier estimator, with
+/// pointwise bootstrap confidence intervals.
+///
+/// Args:
+///     time (List[float]): The follow-up times, non-negative.
+///     event (List[bool]): Whether each subject had the event (True) or was censored at its time (False).
+///     times (List[float], optional): The times at which the curve is evaluated. Default is None, which
+///         uses the distinct event times.
+///     confidence_level (float, optional): The confidence level for the intervals. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples of the subjects. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], List[(float, float)]]:
+///         A tuple containing:
+///         - times (List[float]): The evaluation times.
+///         - survival (List[float]): The estimated probability of surviving past every time.
+///         - intervals (List[(float, float)]): The percentile bootstrap interval at every time.
+/// """
+pub fn kaplan_meier(
+    py: Python<'_>,
+    time: Vec<f64>,
+    event: Vec<bool>,
+    times: Option<Vec<f64>>,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<KaplanMeierOutput> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let sorted = sorted_sample(&time, &event)?;
+    let times = match times {
+        Some(times) => {
+            if times.iter().any(|t| t.is_nan()) {
+                raise!(InvalidInputError, "times must not contain NaN values")
+            }
+            times
+        }
+        None => {
+            let mut times: Vec<f64> = sorted.iter().filter(|s| s.1).map(|s| s.0).collect();
+            times.dedup();
+            times
+        }
+    };
+    let survival = km_curve(&sorted, &times);
+
+    let len = sorted.len();
+    let curves: Vec<Vec<f64>> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut resample: Vec<(f64, bool)> =
+                (0..len).map(|_| sorted[rng.gen_range(0..len)]).collect();
+            resample.sort_unstable_by(|x, y| x.0.total_cmp(&y.0));
+            km_curve(&resample, &times)
+        })
+    });
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let intervals = (0..times.len())
+        .map(|j| {
+            let column: Vec<f64> = curves.iter().map(|curve| curve[j]).collect();
+            let q = column.quantile(&[left_q, 1.0 - left_q]);
+            (q[0], q[1])
+        })
+        .collect();
+    Ok((times, survival, intervals))
+}
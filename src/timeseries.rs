@@ -0,0 +1,786 @@
+use crate::errors::*;
+use crate::levels::check_level;
+use crate::rng::*;
+use crate::special::*;
+use crate::tools::*;
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Default block length of a block bootstrap of `n` serially correlated observations, n^(1/3).
+fn default_block_length(n: usize) -> usize {
+    ((n as f64).cbrt().round() as usize).clamp(1, n.max(1))
+}
+
+/// Positions of a circular moving-block resample of `n` observations: blocks of `block_length`
+/// consecutive positions, with random starts and wrapping around the end, concatenated up to `n`.
+fn circular_block_indices<R: Rng>(n: usize, block_length: usize, rng: &mut R) -> Vec<usize> {
+    let starts = Uniform::new(0, n);
+    let mut indices = Vec::with_capacity(n);
+    while indices.len() < n {
+        let start = starts.sample(rng);
+        let take = block_length.min(n - indices.len());
+        indices.extend((start..start + take).map(|t| t % n));
+    }
+    indices
+}
+
+/// Circular moving-block resample of `series`, from the positions of `circular_block_indices`.
+fn circular_block_resample<R: Rng>(series: &[f64], block_length: usize, rng: &mut R) -> Vec<f64> {
+    circular_block_indices(series.len(), block_length, rng)
+        .into_iter()
+        .map(|t| series[t])
+        .collect()
+}
+
+/// Newey–West estimate of the long-run variance of `series`, with Bartlett weights up to `lag`.
+fn newey_west_variance(series: &[f64], lag: usize) -> f64 {
+    let n = series.len();
+    let mean = series.iter().sum::<f64>() / n as f64;
+    let autocovariance = |l: usize| {
+        (l..n)
+            .map(|t| (series[t] - mean) * (series[t - l] - mean))
+            .sum::<f64>()
+            / n as f64
+    };
+    (1..=lag.min(n - 1)).fold(autocovariance(0), |variance, l| {
+        variance + 2.0 * (1.0 - l as f64 / (lag + 1) as f64) * autocovariance(l)
+    })
+}
+
+/// Classical decomposition of `series` with a season of `period` observations: the trend is the
+/// centered moving average over one season (a 2 x period average for an even period), NaN within half a
+/// season of the ends, and the seasonal component is the mean detrended value of every phase, centered
+/// to sum to zero over a season.
+fn seasonal_decomposition(series: &[f64], period: usize) -> PyResult<(Vec<f64>, Vec<f64>)> {
+    let n = series.len();
+    if period < 2 || n < 2 * period {
+        raise!(
+            InsufficientDataError,
+            "period must be at least 2 and the series must cover at least two periods"
+        )
+    }
+    let half = period / 2;
+    let trend: Vec<f64> = (0..n)
+        .map(|t| {
+            if t < half || t + half >= n {
+                return f64::NAN;
+            }
+            let window = &series[t - half..=t + half];
+            if period % 2 == 1 {
+                window.iter().sum::<f64>() / period as f64
+            } else {
+                let inner = window[1..period].iter().sum::<f64>();
+                (inner + (window[0] + window[period]) / 2.0) / period as f64
+            }
+        })
+        .collect();
+    let mut phases = vec![(0.0, 0.0); period];
+    for (t, (x, level)) in series.iter().zip(&trend).enumerate() {
+        if !level.is_nan() {
+            phases[t % period].0 += x - level;
+            phases[t % period].1 += 1.0;
+        }
+    }
+    let indices: Vec<f64> = phases.iter().map(|(sum, count)| sum / count).collect();
+    let center = indices.iter().sum::<f64>() / period as f64;
+    let seasonal = (0..n).map(|t| indices[t % period] - center).collect();
+    Ok((seasonal, trend))
+}
+
+fn check_series(series: &[f64]) -> PyResult<()> {
+    if series.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "series must contain at least two observations"
+        )
+    }
+    if series.iter().any(|x| !x.is_finite()) {
+        raise!(
+            InvalidInputError,
+            "series must not contain NaN or infinite values"
+        )
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (series, method = "block_bootstrap", bandwidth = None, confidence_level = 0.95, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(series, method='block_bootstrap', bandwidth=None, confidence_level=0.95, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Computes a confidence interval for the mean of a serially correlated series, such as a daily
+/// aggregate metric.
+///
+/// Consecutive days of a metric are positively correlated, so the iid standard error of their mean is
+/// too small. "block_bootstrap" resamples blocks of consecutive observations (a circular moving-block
+/// bootstrap), which keeps the dependence within blocks, and returns the percentile interval.
+/// "newey_west" uses the heteroskedasticity and autocorrelation consistent (HAC) variance with Bartlett
+/// weights and the normal approximation, which needs no resampling.
+///
+/// Args:
+///     series (List[float]): The observations in time order.
+///     method (str, optional): "block_bootstrap" or "newey_west". Default is "block_bootstrap".
+///     bandwidth (int, optional): The block length of the bootstrap, or the number of lags of Newey–West.
+///         Default is None, which is n^(1/3) for the bootstrap and floor(4 (n / 100)^(2/9)) lags for
+///         Newey–West.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples, unused by "newey_west".
+///         Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, (float, float), float, int]:
+///         A tuple containing:
+///         - mean (float): The mean of the series.
+///         - (float, float): The confidence interval of the mean.
+///         - standard_error (float): The bootstrap or HAC standard error of the mean.
+///         - bandwidth (int): The block length or number of lags used.
+/// """
+pub fn timeseries_mean_ci(
+    py: Python<'_>,
+    series: Vec<f64>,
+    method: &str,
+    bandwidth: Option<usize>,
+    confidence_level: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<(f64, (f64, f64), f64, usize)> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    check_series(&series)?;
+    let n = series.len();
+    let mean = series.iter().sum::<f64>() / n as f64;
+    Ok(match method {
+        "block_bootstrap" => {
+            let block_length = bandwidth.unwrap_or_else(|| default_block_length(n));
+            if block_length == 0 || block_length > n {
+                raise!(
+                    InvalidInputError,
+                    "bandwidth must be between 1 and the length of the series"
+                )
+            }
+            let means: Vec<f64> = py.allow_threads(|| {
+                resampler.map(n_resamples, |rng| {
+                    circular_block_resample(&series, block_length, rng)
+                        .iter()
+                        .sum::<f64>()
+                        / n as f64
+                })
+            });
+            let q = means.quantile(&[left_q, right_q]);
+            let (_, variance, _, _) = central_moments(&means);
+            (mean, (q[0], q[1]), variance.sqrt(), block_length)
+        }
+        "newey_west" => {
+            let lag = bandwidth
+                .unwrap_or_else(|| (4.0 * (n as f64 / 100.0).powf(2.0 / 9.0)).floor() as usize);
+            let se = (newey_west_variance(&series, lag) / n as f64)
+                .max(0.0)
+                .sqrt();
+            let z = norm_ppf(right_q);
+            (mean, (mean - z * se, mean + z * se), se, lag)
+        }
+        _ => raise!(
+            InvalidInputError,
+            "method must be either 'block_bootstrap' or 'newey_west', got '{method}'"
+        ),
+    })
+}
+
+type Changepoints = (Vec<usize>, Vec<f64>, Vec<f64>);
+
+/// Position and size of the largest CUSUM deviation `|S_k - k / n * S_n| / sqrt(n)` of `segment`, over the
+/// splits leaving at least `min_size` observations on each side.
+fn cusum_scan(segment: &[f64], min_size: usize) -> (usize, f64) {
+    let n = segment.len();
+    let total = segment.iter().sum::<f64>();
+    let mut partial = 0.0;
+    let mut best = (min_size, f64::NEG_INFINITY);
+    for (k, x) in segment[..n - min_size].iter().enumerate() {
+        partial += x;
+        let split = k + 1;
+        if split < min_size {
+            continue;
+        }
+        let deviation = (partial - split as f64 / n as f64 * total).abs() / (n as f64).sqrt();
+        if deviation > best.1 {
+            best = (split, deviation);
+        }
+    }
+    best
+}
+
+/// `segment` with the order of its blocks of `block_length` consecutive observations shuffled, which
+/// breaks any change in level while keeping the short-range dependence within blocks.
+fn block_permute<R: Rng>(segment: &[f64], block_length: usize, rng: &mut R) -> Vec<f64> {
+    let mut blocks: Vec<&[f64]> = segment.chunks(block_length).collect();
+    blocks.shuffle(rng);
+    blocks.concat()
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (series, method = "cusum", n_resamples = 2_000, alpha = 0.05, block_length = None, min_segment_length = None, period = None, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(series, method='cusum', n_resamples=2000, alpha=0.05, block_length=None, min_segment_length=None, period=None, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Detects changes in the level of a metric series and assesses them by block permutation.
+///
+/// The candidate changepoint of a segment is the split with the largest CUSUM statistic
+/// `max_k |S_k - k / n * S_n| / sqrt(n)`. Its p-value is the share of block permutations of the segment
+/// (the order of blocks of consecutive observations shuffled) with a statistic at least as large, so
+/// serial correlation within blocks does not pass for a change. "cusum" reports the single most likely
+/// changepoint of the whole series whatever its p-value. "binary_segmentation" splits the series at
+/// its changepoint when the p-value is below alpha and tests both sides again, until no segment has a
+/// significant change.
+///
+/// Args:
+///     series (List[float]): The observations in time order.
+///     method (str, optional): "cusum" or "binary_segmentation". Default is "cusum".
+///     n_resamples (int, optional): The number of block permutations of every tested segment.
+///         Default is 2000.
+///     alpha (float, optional): The significance level of a split of binary segmentation. Default is 0.05.
+///     block_length (int, optional): The length of the permuted blocks; 1 permutes single observations,
+///         which assumes no serial correlation. Default is None, which is n^(1/3).
+///     min_segment_length (int, optional): The fewest observations on each side of a changepoint.
+///         Default is None, which is the block length, at least 2.
+///     period (int, optional): The length of a season; the series is then deseasonalized as by
+///         `deseasonalize` first, so that a recurring pattern is not taken for changes and the segment
+///         means are those of the adjusted series. Default is None, which tests the series as is.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[int], List[float], List[float]]:
+///         A tuple containing:
+///         - changepoints (List[int]): The index of the first observation after every change, in
+///           increasing order.
+///         - p_values (List[float]): The block permutation p-value of every changepoint, within the
+///           segment where it was found.
+///         - segment_means (List[float]): The mean of every segment between the changepoints.
+/// """
+pub fn changepoint_test(
+    py: Python<'_>,
+    series: Vec<f64>,
+    method: &str,
+    n_resamples: u64,
+    alpha: f64,
+    block_length: Option<usize>,
+    min_segment_length: Option<usize>,
+    period: Option<usize>,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<Changepoints> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    check_series(&series)?;
+    let series: Vec<f64> = match period {
+        Some(period) => {
+            let (seasonal, _) = seasonal_decomposition(&series, period)?;
+            series.iter().zip(&seasonal).map(|(x, s)| x - s).collect()
+        }
+        None => series,
+    };
+    let n = series.len();
+    let recursive = match method {
+        "cusum" => false,
+        "binary_segmentation" => true,
+        _ => raise!(
+            InvalidInputError,
+            "method must be either 'cusum' or 'binary_segmentation', got '{method}'"
+        ),
+    };
+    let block_length = block_length.unwrap_or_else(|| default_block_length(n));
+    if block_length == 0 {
+        raise!(InvalidInputError, "block_length must be positive")
+    }
+    let min_size = min_segment_length.unwrap_or(block_length.max(2));
+    if min_size == 0 || 2 * min_size > n {
+        raise!(
+            InvalidInputError,
+            "min_segment_length must be positive and at most half the length of the series"
+        )
+    }
+
+    let test = |segment: &[f64]| -> (usize, f64) {
+        let (split, observed) = cusum_scan(segment, min_size);
+        let null = resampler.map(n_resamples, |rng| {
+            cusum_scan(&block_permute(segment, block_length, rng), min_size).1
+        });
+        let exceed = null
+            .iter()
+            .filter(|&&s| s >= observed * (1.0 - 1e-12))
+            .count();
+        (split, (exceed + 1) as f64 / (n_resamples + 1) as f64)
+    };
+    let mut found: Vec<(usize, f64)> = py.allow_threads(|| {
+        if !recursive {
+            return vec![test(&series)];
+        }
+        let mut found = Vec::new();
+        let mut segments = vec![(0, n)];
+        while let Some((start, end)) = segments.pop() {
+            if end - start < 2 * min_size {
+                continue;
+            }
+            let (split, p) = test(&series[start..end]);
+            if p < alpha {
+                found.push((start + split, p));
+                segments.push((start, start + split));
+                segments.push((start + split, end));
+            }
+        }
+        found
+    });
+    found.sort_unstable_by_key(|&(changepoint, _)| changepoint);
+
+    let bounds: Vec<usize> = std::iter::once(0)
+        .chain(found.iter().map(|&(changepoint, _)| changepoint))
+        .chain(std::iter::once(n))
+        .collect();
+    let segment_means = bounds
+        .windows(2)
+        .map(|w| series[w[0]..w[1]].iter().sum::<f64>() / (w[1] - w[0]) as f64)
+        .collect();
+    let (changepoints, p_values) = found.into_iter().unzip();
+    Ok((changepoints, p_values, segment_means))
+}
+
+type AnomalyBands = (Vec<f64>, Vec<f64>, Vec<bool>, Vec<f64>);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (history, horizon, confidence_level = 0.95, season_length = None, n_resamples = 10_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(history, horizon, confidence_level=0.95, season_length=None, n_resamples=10000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Builds bootstrap prediction bands for the most recent points of a metric series and flags those
+/// outside them.
+///
+/// The last `horizon` points of `history` are scored against the points before them. The baseline is
+/// the mean of the earlier points, or the mean of every phase of the season with `season_length`
+/// (e.g. 7 for daily data with a weekly pattern), and the residuals are the deviations from it. Every
+/// resample adds to the baseline the error of its level, estimated by resampling blocks of whole
+/// seasons of residuals (a seasonal block bootstrap), and a path of `horizon` consecutive residuals
+/// from a random start, so the bands account for the uncertainty of the baseline and the serial
+/// correlation of the noise. The bands are the percentiles of the resampled paths at every step.
+///
+/// Args:
+///     history (List[float]): The observations in time order, ending with the points to score.
+///     horizon (int): The number of recent points to score.
+///     confidence_level (float, optional): The coverage of the prediction bands. Default is 0.95.
+///     season_length (int, optional): The period of the seasonal pattern. Default is None, no season.
+///     n_resamples (int, optional): The number of bootstrap paths. Default is 10000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[List[float], List[float], List[bool], List[float]]:
+///         A tuple containing:
+///         - lower (List[float]): The lower band of every recent point.
+///         - upper (List[float]): The upper band of every recent point.
+///         - anomalies (List[bool]): Whether every recent point lies outside its band.
+///         - tail_probabilities (List[float]): The two-sided share of the resampled paths at least as
+///           extreme as every recent point, an anomaly score that is small for surprising points.
+/// """
+pub fn anomaly_bands(
+    py: Python<'_>,
+    history: Vec<f64>,
+    horizon: usize,
+    confidence_level: f64,
+    season_length: Option<usize>,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<AnomalyBands> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    check_series(&history)?;
+    let season = season_length.unwrap_or(1);
+    if season == 0 {
+        raise!(InvalidInputError, "season_length must be positive")
+    }
+    if horizon == 0 || horizon >= history.len() {
+        raise!(
+            InvalidInputError,
+            "horizon must be positive and shorter than history"
+        )
+    }
+    let (fit, recent) = history.split_at(history.len() - horizon);
+    let n_seasons = fit.len() / season;
+    if n_seasons < 2 {
+        raise!(
+            InsufficientDataError,
+            "The points before the horizon must cover at least two seasons"
+        )
+    }
+
+    let mut phase_sums = vec![(0.0, 0.0); season];
+    for (t, &x) in fit.iter().enumerate() {
+        phase_sums[t % season].0 += x;
+        phase_sums[t % season].1 += 1.0;
+    }
+    let baseline: Vec<f64> = phase_sums.iter().map(|(sum, count)| sum / count).collect();
+    // Residuals of the complete seasons, so that every residual path stays aligned on the phases.
+    let residuals: Vec<f64> = fit[..n_seasons * season]
+        .iter()
+        .enumerate()
+        .map(|(t, x)| x - baseline[t % season])
+        .collect();
+    // Blocks of whole seasons, so that every phase gets the residuals of n_seasons resampled seasons.
+    let level_block = season * default_block_length(n_seasons);
+    let seasons = Uniform::new(0, n_seasons);
+
+    let paths: Vec<Vec<f64>> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mut level_error = vec![0.0; season];
+            let mut drawn = 0;
+            while drawn < residuals.len() {
+                let start = seasons.sample(rng) * season;
+                let take = level_block.min(residuals.len() - drawn);
+                for i in 0..take {
+                    level_error[i % season] +=
+                        residuals[(start + i) % residuals.len()] / n_seasons as f64;
+                }
+                drawn += take;
+            }
+            // The recent residuals start on the phase of the first scored point, so that every one of
+            // them is drawn among the residuals of its own phase.
+            let start = seasons.sample(rng) * season + fit.len() % season;
+            (0..horizon)
+                .map(|j| {
+                    let phase = (fit.len() + j) % season;
+                    baseline[phase] + level_error[phase] + residuals[(start + j) % residuals.len()]
+                })
+                .collect()
+        })
+    });
+
+    let mut lower = Vec::with_capacity(horizon);
+    let mut upper = Vec::with_capacity(horizon);
+    let mut anomalies = Vec::with_capacity(horizon);
+    let mut tail_probabilities = Vec::with_capacity(horizon);
+    for (j, &x) in recent.iter().enumerate() {
+        let step: Vec<f64> = paths.iter().map(|path| path[j]).collect();
+        let q = step.quantile(&[left_q, right_q]);
+        let below = step.iter().filter(|&&y| y <= x).count();
+        let above = step.iter().filter(|&&y| y >= x).count();
+        let tail = 2.0 * (below.min(above) + 1) as f64 / (n_resamples + 1) as f64;
+        lower.push(q[0]);
+        upper.push(q[1]);
+        anomalies.push(x < q[0] || x > q[1]);
+        tail_probabilities.push(tail.min(1.0));
+    }
+    Ok((lower, upper, anomalies, tail_probabilities))
+}
+
+#[pyfunction(signature = (series, period))]
+#[pyo3(text_signature = "(series, period)")]
+/// """
+/// Removes the seasonal pattern of a metric series by classical decomposition.
+///
+/// The trend is the centered moving average over one period (a 2 x period average for an even period),
+/// and the seasonal component is the mean deviation from the trend at every phase of the period,
+/// centered to sum to zero over a period. The adjusted series keeps the trend and the noise; it is what
+/// `changepoint_test` tests with `period`.
+///
+/// Args:
+///     series (List[float]): The observations in time order, covering at least two periods.
+///     period (int): The number of observations of a season, e.g. 7 for daily data with a weekly pattern.
+///
+/// Returns:
+///     Tuple[List[float], List[float], List[float]]:
+///         A tuple containing:
+///         - adjusted (List[float]): The series minus its seasonal component.
+///         - seasonal (List[float]): The seasonal component of every observation.
+///         - trend (List[float]): The moving-average trend, NaN within half a period of the ends.
+/// """
+pub fn deseasonalize(series: Vec<f64>, period: usize) -> PyResult<(Vec<f64>, Vec<f64>, Vec<f64>)> {
+    check_series(&series)?;
+    let (seasonal, trend) = seasonal_decomposition(&series, period)?;
+    let adjusted = series.iter().zip(&seasonal).map(|(x, s)| x - s).collect();
+    Ok((adjusted, seasonal, trend))
+}
+
+/// Accuracy metric of a forecast, from its errors (actual minus forecast) at the given positions.
+#[derive(Clone, Copy)]
+enum ForecastMetric {
+    Rmse,
+    Mae,
+    /// Mean absolute error divided by the mean absolute seasonal naive error of the actuals.
+    Mase(f64),
+    Mape,
+}
+
+impl ForecastMetric {
+    fn compute(&self, errors: &[f64], actuals: Option<&[f64]>, positions: &[usize]) -> f64 {
+        let n = positions.len() as f64;
+        match self {
+            ForecastMetric::Rmse => {
+                (positions.iter().map(|&t| errors[t].powi(2)).sum::<f64>() / n).sqrt()
+            }
+            ForecastMetric::Mae => positions.iter().map(|&t| errors[t].abs()).sum::<f64>() / n,
+            ForecastMetric::Mase(scale) => {
+                positions.iter().map(|&t| errors[t].abs()).sum::<f64>() / n / scale
+            }
+            ForecastMetric::Mape => {
+                let actuals = actuals.unwrap();
+                100.0
+                    * positions
+                        .iter()
+                        .map(|&t| (errors[t] / actuals[t]).abs())
+                        .sum::<f64>()
+                    / n
+            }
+        }
+    }
+}
+
+/// `forecast_comparison` output: the metric of both models, their difference with its interval, and
+/// the p-value.
+type ForecastComparison = (f64, f64, f64, (f64, f64), f64);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (errors_model_a, errors_model_b, metric = "rmse", block_size = None, actuals = None, seasonality = 1, confidence_level = 0.95, n_resamples = 10_000, two_sided = true, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(errors_model_a, errors_model_b, metric='rmse', block_size=None, actuals=None, seasonality=1, confidence_level=0.95, n_resamples=10000, two_sided=True, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares the accuracy of two forecasting models on the same periods with a block bootstrap.
+///
+/// The errors of a forecast are serially correlated, especially over multi-step horizons, so the
+/// periods are resampled in blocks of consecutive positions (a circular moving-block bootstrap), the
+/// same positions for both models, which keeps both the serial correlation and the correlation between
+/// the models. The interval and the p-value of the difference in the metric follow the conventions of
+/// `bootstrap`.
+///
+/// Args:
+///     errors_model_a (List[float]): The forecast errors (actual minus forecast) of the first model, in
+///         time order.
+///     errors_model_b (List[float]): The forecast errors of the second model over the same periods.
+///     metric (str, optional): "rmse", "mae", "mase" (the mean absolute error scaled by the mean
+///         absolute seasonal naive error of the actuals) or "mape" (mean absolute percentage error, in
+///         percent). Default is "rmse".
+///     block_size (int, optional): The length of the resampled blocks, at least the forecast horizon.
+///         Default is None, which is n^(1/3).
+///     actuals (List[float], optional): The actual values of the periods, required by "mase" and
+///         "mape". Default is None.
+///     seasonality (int, optional): The lag of the naive forecast scaling "mase". Default is 1.
+///     confidence_level (float, optional): The confidence level for the interval. Default is 0.95.
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 10000.
+///     two_sided (bool, optional): If True, computes a two-sided p-value. Otherwise, one-sided. Default is True.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, (float, float), float]:
+///         A tuple containing:
+///         - metric_a (float): The metric of the first model.
+///         - metric_b (float): The metric of the second model.
+///         - difference (float): metric_b - metric_a, negative when the second model is more accurate.
+///         - (float, float): The block bootstrap interval of the difference.
+///         - p_value (float): The p-value of equal accuracy.
+/// """
+pub fn forecast_comparison(
+    py: Python<'_>,
+    errors_model_a: Vec<f64>,
+    errors_model_b: Vec<f64>,
+    metric: &str,
+    block_size: Option<usize>,
+    actuals: Option<Vec<f64>>,
+    seasonality: usize,
+    confidence_level: f64,
+    n_resamples: u64,
+    two_sided: bool,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<ForecastComparison> {
+    check_resamples(n_resamples)?;
+    check_level(confidence_level)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let left_q = (1.0 - confidence_level) / 2.0;
+    let right_q = 1.0 - left_q;
+    check_series(&errors_model_a)?;
+    check_series(&errors_model_b)?;
+    let n = errors_model_a.len();
+    if errors_model_b.len() != n || actuals.as_ref().is_some_and(|actuals| actuals.len() != n) {
+        raise!(
+            InvalidInputError,
+            "errors_model_a, errors_model_b and actuals must have equal size"
+        )
+    }
+    let metric = match (metric, &actuals) {
+        ("rmse", _) => ForecastMetric::Rmse,
+        ("mae", _) => ForecastMetric::Mae,
+        ("mase" | "mape", None) => raise!(InvalidInputError, "metric '{metric}' requires actuals"),
+        ("mase", Some(actuals)) => {
+            if seasonality == 0 || seasonality >= n {
+                raise!(
+                    InvalidInputError,
+                    "seasonality must be positive and shorter than the series"
+                )
+            }
+            let scale = (seasonality..n)
+                .map(|t| (actuals[t] - actuals[t - seasonality]).abs())
+                .sum::<f64>()
+                / (n - seasonality) as f64;
+            if scale == 0.0 {
+                raise!(
+                    InvalidInputError,
+                    "The seasonal naive forecast of the actuals has no error, mase is undefined"
+                )
+            }
+            ForecastMetric::Mase(scale)
+        }
+        ("mape", Some(actuals)) => {
+            if actuals.contains(&0.0) {
+                raise!(InvalidInputError, "mape is undefined for zero actuals")
+            }
+            ForecastMetric::Mape
+        }
+        _ => raise!(
+            InvalidInputError,
+            "metric must be one of 'rmse', 'mae', 'mase' or 'mape', got '{metric}'"
+        ),
+    };
+    let block_size = block_size.unwrap_or_else(|| default_block_length(n));
+    if block_size == 0 || block_size > n {
+        raise!(
+            InvalidInputError,
+            "block_size must be between 1 and the length of the series"
+        )
+    }
+    let actuals = actuals.as_deref();
+    let all: Vec<usize> = (0..n).collect();
+    let metric_a = metric.compute(&errors_model_a, actuals, &all);
+    let metric_b = metric.compute(&errors_model_b, actuals, &all);
+
+    let differences: Vec<f64> = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let positions = circular_block_indices(n, block_size, rng);
+            metric.compute(&errors_model_b, actuals, &positions)
+                - metric.compute(&errors_model_a, actuals, &positions)
+        })
+    });
+    let q = differences.quantile(&[left_q, right_q]);
+    let p =
+        (differences.iter().filter(|&&d| d > 0.0).count() as f64 + 1.0) / (n_resamples + 1) as f64;
+    Ok((
+        metric_a,
+        metric_b,
+        metric_b - metric_a,
+        (q[0], q[1]),
+        if two_sided {
+            (2.0 - 2.0 * p).min(2.0 * p)
+        } else {
+            p
+        },
+    ))
+}
+
+#[pyfunction(signature = (errors_model_a, errors_model_b, loss = "squared", horizon = 1, alternative = "two-sided"))]
+#[pyo3(
+    text_signature = "(errors_model_a, errors_model_b, loss='squared', horizon=1, alternative='two-sided')"
+)]
+/// """
+/// Performs the Diebold–Mariano test of equal accuracy of two forecasts, with the small-sample
+/// correction of Harvey, Leybourne and Newbold (1997).
+///
+/// The loss differential `d_t = L(e_a,t) - L(e_b,t)` of h-step forecasts is autocorrelated up to lag
+/// h - 1, so its mean is divided by the standard error from its autocovariances up to that lag. The
+/// statistic is then scaled by `sqrt((n + 1 - 2h + h(h - 1) / n) / n)` and compared with Student's t
+/// distribution with n - 1 degrees of freedom, which keeps the size close to nominal for short
+/// evaluation windows. It is an analytic companion to `forecast_comparison`.
+///
+/// Args:
+///     errors_model_a (List[float]): The forecast errors of the first model, in time order.
+///     errors_model_b (List[float]): The forecast errors of the second model over the same periods.
+///     loss (str, optional): "squared" or "absolute". Default is "squared".
+///     horizon (int, optional): The forecast horizon h of the errors. Default is 1.
+///     alternative (str, optional): "two-sided", "less" or "greater"; "greater" means the first model
+///         has the larger loss, i.e. the second model is more accurate. Default is "two-sided".
+///
+/// Returns:
+///     Tuple[float, float]:
+///         A tuple containing:
+///         - statistic (float): The corrected Diebold–Mariano statistic.
+///         - p_value (float): The p-value for the chosen alternative.
+/// """
+pub fn diebold_mariano(
+    errors_model_a: Vec<f64>,
+    errors_model_b: Vec<f64>,
+    loss: &str,
+    horizon: usize,
+    alternative: &str,
+) -> PyResult<(f64, f64)> {
+    let alternative = Alternative::parse(alternative).or_raise()?;
+    check_series(&errors_model_a)?;
+    check_series(&errors_model_b)?;
+    let n = errors_model_a.len();
+    if errors_model_b.len() != n {
+        raise!(
+            InvalidInputError,
+            "errors_model_a and errors_model_b must have equal size"
+        )
+    }
+    if horizon == 0 || horizon >= n {
+        raise!(
+            InvalidInputError,
+            "horizon must be positive and shorter than the series"
+        )
+    }
+    let loss = match loss {
+        "squared" => |e: f64| e * e,
+        "absolute" => |e: f64| e.abs(),
+        _ => raise!(
+            InvalidInputError,
+            "loss must be either 'squared' or 'absolute', got '{loss}'"
+        ),
+    };
+    let differential: Vec<f64> = errors_model_a
+        .iter()
+        .zip(&errors_model_b)
+        .map(|(&a, &b)| loss(a) - loss(b))
+        .collect();
+    let mean = differential.iter().sum::<f64>() / n as f64;
+    let autocovariance = |l: usize| {
+        (l..n)
+            .map(|t| (differential[t] - mean) * (differential[t - l] - mean))
+            .sum::<f64>()
+            / n as f64
+    };
+    let long_run_variance = (1..horizon).fold(autocovariance(0), |variance, l| {
+        variance + 2.0 * autocovariance(l)
+    });
+    if long_run_variance <= 0.0 {
+        raise!(
+            InvalidInputError,
+            "The loss differential has no positive long-run variance, the test is undefined"
+        )
+    }
+    let (n_f, h) = (n as f64, horizon as f64);
+    let correction = ((n_f + 1.0 - 2.0 * h + h * (h - 1.0) / n_f) / n_f).sqrt();
+    let statistic = correction * mean / (long_run_variance / n_f).sqrt();
+    let df = n_f - 1.0;
+    let p_value = alternative.p_value(1.0 - t_sf(statistic, df), t_sf(statistic, df));
+    Ok((statistic, p_value))
+}
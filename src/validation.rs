@@ -0,0 +1,531 @@
+use crate::errors::*;
+use crate::rng::*;
+use crate::special::*;
+use crate::tools::*;
+use pylars_core::{bootstrap_null, permutation_null};
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+use rand_distr::StandardNormal;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Test run on every pseudo-period of `pre_period_validation`.
+#[derive(Clone, Copy)]
+enum ValidatedTest {
+    Bootstrap,
+    Permutation,
+}
+
+/// `pre_period_validation` output: the false-positive rate, the interval coverage and the p-value of
+/// every pseudo-period.
+type ValidationOutput = (f64, f64, Vec<f64>);
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (historic_values, historic_groups, n_periods, test = "bootstrap", alpha = 0.05, n_resamples = 2_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(historic_values, historic_groups, n_periods, test='bootstrap', alpha=0.05, n_resamples=2000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Validates a test on historical data where no treatment took place (an A/A test), reporting its
+/// empirical false-positive rate and interval coverage for that specific metric.
+///
+/// The history is cut into `n_periods` consecutive pseudo-periods of equal size, and the test compares
+/// the two groups of every pseudo-period, in parallel. Since the groups received the same experience, a
+/// well-calibrated test rejects in about `alpha` of the periods and its `1 - alpha` interval covers no
+/// effect in about `1 - alpha` of them. A much higher false-positive rate usually means the observations
+/// are not independent (repeated units, clustering, seasonality) or the metric is too heavy-tailed for
+/// the test. With `n_periods` periods, the rates have a standard error of about
+/// `sqrt(alpha (1 - alpha) / n_periods)`.
+///
+/// Args:
+///     historic_values (List[float]): The metric of every historical observation, in chronological order.
+///     historic_groups (List[int]): The group (0 or 1) every observation would have been assigned to.
+///     n_periods (int): The number of pseudo-periods.
+///     test (str, optional): "bootstrap" or "permutation", with the defaults of `bootstrap` and
+///         `permutation_test`. Default is "bootstrap".
+///     alpha (float, optional): The significance level of every test. Default is 0.05.
+///     n_resamples (int, optional): The number of resamples of every test. Default is 2000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, List[float]]:
+///         A tuple containing:
+///         - false_positive_rate (float): The share of pseudo-periods with a p-value below `alpha`.
+///         - coverage (float): The share of pseudo-periods whose `1 - alpha` interval contains no effect:
+///           a zero uplift for "bootstrap", the observed difference within the null interval for
+///           "permutation".
+///         - p_values (List[float]): The p-value of every pseudo-period.
+/// """
+pub fn pre_period_validation(
+    py: Python<'_>,
+    historic_values: Vec<f64>,
+    historic_groups: Vec<u8>,
+    n_periods: usize,
+    test: &str,
+    alpha: f64,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<ValidationOutput> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let test = match test {
+        "bootstrap" => ValidatedTest::Bootstrap,
+        "permutation" => ValidatedTest::Permutation,
+        _ => raise!(
+            InvalidInputError,
+            "test must be 'bootstrap' or 'permutation', got '{test}'"
+        ),
+    };
+    if historic_values.len() != historic_groups.len() {
+        raise!(
+            InvalidInputError,
+            "historic_values and historic_groups must have equal size"
+        )
+    }
+    if historic_groups.iter().any(|&g| g > 1) {
+        raise!(InvalidInputError, "historic_groups must contain 0 or 1")
+    }
+    if n_periods == 0 || n_periods > historic_values.len() {
+        raise!(
+            InvalidInputError,
+            "n_periods must be between 1 and the number of observations"
+        )
+    }
+    if !(alpha > 0.0 && alpha < 1.0) {
+        raise!(InvalidInputError, "alpha must be in (0, 1)")
+    }
+    let n = historic_values.len();
+    let periods: Vec<[Vec<f64>; 2]> = (0..n_periods)
+        .map(|period| {
+            let (start, end) = (period * n / n_periods, (period + 1) * n / n_periods);
+            let mut groups = [Vec::new(), Vec::new()];
+            for k in start..end {
+                groups[historic_groups[k] as usize].push(historic_values[k]);
+            }
+            if groups.iter().any(Vec::is_empty) {
+                raise!(
+                    InsufficientDataError,
+                    "Pseudo-period {period} lacks observations of one group, use fewer periods"
+                )
+            }
+            Ok(groups)
+        })
+        .collect::<PyResult<_>>()?;
+
+    // Every pseudo-period draws from its own seed: with the call's resampler, the periods of equal size
+    // would share their resample indices and their outcomes would be correlated.
+    let (seed, deterministic) = resampler.key();
+    let outcomes: Vec<(f64, bool)> = py.allow_threads(|| {
+        periods
+            .par_iter()
+            .enumerate()
+            .map(|(period, [a, b])| {
+                let resampler = Resampler::from_seed(
+                    resample_rng(seed, period as u64).gen(),
+                    Some(deterministic),
+                );
+                let columns = [a.as_slice(), b.as_slice()];
+                match test {
+                    ValidatedTest::Bootstrap => {
+                        let null = bootstrap_null(
+                            &columns,
+                            n_resamples,
+                            true,
+                            Accumulator::F64,
+                            &resampler,
+                        )
+                        .or_raise()?;
+                        let (p, _, _, _, ci) = null.summarize(1.0 - alpha, true, false, None);
+                        Ok((p, ci.0 <= 0.0 && 0.0 <= ci.1))
+                    }
+                    ValidatedTest::Permutation => {
+                        let null =
+                            permutation_null(&columns, n_resamples, Accumulator::F64, &resampler)
+                                .or_raise()?;
+                        let (p, _, diff, ci) = null.summarize(1.0 - alpha, true);
+                        Ok((p, ci.0 <= diff && diff <= ci.1))
+                    }
+                }
+            })
+            .collect::<PyResult<_>>()
+    })?;
+    let share = |count: usize| count as f64 / n_periods as f64;
+    let false_positive_rate = share(outcomes.iter().filter(|(p, _)| *p < alpha).count());
+    let coverage = share(outcomes.iter().filter(|(_, covered)| *covered).count());
+    let p_values = outcomes.into_iter().map(|(p, _)| p).collect();
+    Ok((false_positive_rate, coverage, p_values))
+}
+
+/// Ratio between two standard errors above which `variance_report` flags a discrepancy.
+const SE_DISCREPANCY: f64 = 1.25;
+
+/// `variance_report` output: the estimate, the naive, cluster-robust, delta-method and bootstrap standard
+/// errors, and the flagged discrepancies.
+type VarianceReport = (f64, f64, f64, f64, f64, Vec<String>);
+
+/// Per-cluster sums and counts of a sample, a cluster per observation without ids.
+fn cluster_totals(values: &[f64], cluster_ids: Option<&[String]>) -> Vec<(f64, f64)> {
+    match cluster_ids {
+        None => values.iter().map(|&x| (x, 1.0)).collect(),
+        Some(ids) => {
+            let mut totals: HashMap<&str, (f64, f64)> = HashMap::new();
+            for (x, id) in values.iter().zip(ids) {
+                let total = totals.entry(id.as_str()).or_default();
+                total.0 += x;
+                total.1 += 1.0;
+            }
+            let mut totals: Vec<(&str, (f64, f64))> = totals.into_iter().collect();
+            totals.sort_unstable_by_key(|(id, _)| *id);
+            totals.into_iter().map(|(_, total)| total).collect()
+        }
+    }
+}
+
+/// Mean of a sample with its variance assuming independent observations, and its cluster-robust (CR1)
+/// variance.
+fn mean_variances(values: &[f64], totals: &[(f64, f64)]) -> (f64, f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let iid = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0) / n;
+    let g = totals.len() as f64;
+    let clustered = g / (g - 1.0)
+        * totals
+            .iter()
+            .map(|(sum, count)| (sum - mean * count).powi(2))
+            .sum::<f64>()
+        / (n * n);
+    (mean, iid, clustered)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, cluster_ids_a = None, cluster_ids_b = None, estimand = "uplift", n_resamples = 2_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, cluster_ids_a=None, cluster_ids_b=None, estimand='uplift', n_resamples=2000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Compares several standard errors of the same estimate in one call, flagging the discrepancies that
+/// usually reveal a clustering or ratio-metric mistake.
+///
+/// The estimate is the uplift `mean_b / mean_a - 1` or the difference `mean_b - mean_a` of the
+/// observation means, and its standard error is computed four ways:
+/// - naive: independent observations and, for the uplift, a control mean treated as known;
+/// - cluster-robust: the CR1 variance of every mean over the clusters of `cluster_ids_a` and
+///   `cluster_ids_b`, propagated with the delta method;
+/// - delta-method: independent observations, propagated with the delta method, so that the uncertainty
+///   of the control mean enters the uplift (for the difference it equals the naive error);
+/// - bootstrap: the standard deviation of the estimate over resamples of whole clusters.
+///
+/// A cluster-robust (or bootstrap) error well above the naive one means the observations of a cluster,
+/// such as the sessions of a user, are correlated and must not be analysed as independent. A
+/// delta-method error well above the naive one means the uplift is a ratio metric whose denominator
+/// (the control mean) is itself noisy. A bootstrap error far from the analytic ones points to a small
+/// or heavy-tailed sample, where the normal approximations are unreliable. Discrepancies above a factor
+/// of 1.25 are flagged.
+///
+/// Args:
+///     a (List[float]): The observations of the first (control) group.
+///     b (List[float]): The observations of the second group.
+///     cluster_ids_a (List[str], optional): The cluster, e.g. the user, of every observation of `a`.
+///         Default is None, which makes every observation its own cluster.
+///     cluster_ids_b (List[str], optional): The cluster of every observation of `b`. Default is None.
+///     estimand (str, optional): "uplift" or "difference". Default is "uplift".
+///     n_resamples (int, optional): The number of bootstrap resamples. Default is 2000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, float, float, float, float, List[str]]:
+///         A tuple containing:
+///         - estimate (float): The uplift or difference.
+///         - naive_se (float): The naive standard error.
+///         - cluster_se (float): The cluster-robust standard error.
+///         - delta_se (float): The delta-method standard error.
+///         - bootstrap_se (float): The cluster bootstrap standard error.
+///         - flags (List[str]): A description of every flagged discrepancy, empty when the errors agree.
+/// """
+pub fn variance_report(
+    py: Python<'_>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+    cluster_ids_a: Option<Vec<String>>,
+    cluster_ids_b: Option<Vec<String>>,
+    estimand: &str,
+    n_resamples: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<VarianceReport> {
+    check_resamples(n_resamples)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let uplift = match estimand {
+        "uplift" => true,
+        "difference" => false,
+        _ => raise!(
+            InvalidInputError,
+            "estimand must be 'uplift' or 'difference', got '{estimand}'"
+        ),
+    };
+    if a.len() < 2 || b.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "Both groups must contain at least two observations"
+        )
+    }
+    for (values, ids) in [(&a, &cluster_ids_a), (&b, &cluster_ids_b)] {
+        if ids.as_ref().is_some_and(|ids| ids.len() != values.len()) {
+            raise!(
+                InvalidInputError,
+                "Every observation must have a cluster id"
+            )
+        }
+    }
+    let totals_a = cluster_totals(&a, cluster_ids_a.as_deref());
+    let totals_b = cluster_totals(&b, cluster_ids_b.as_deref());
+    if totals_a.len() < 2 || totals_b.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "Both groups must contain at least two clusters"
+        )
+    }
+    let (mean_a, iid_a, clustered_a) = mean_variances(&a, &totals_a);
+    let (mean_b, iid_b, clustered_b) = mean_variances(&b, &totals_b);
+    let estimate = |mean_a: f64, mean_b: f64| {
+        if uplift {
+            mean_b / mean_a - 1.0
+        } else {
+            mean_b - mean_a
+        }
+    };
+    // Delta-method standard error of the estimate from the variances of both means.
+    let propagated = |var_a: f64, var_b: f64| {
+        if uplift {
+            (var_b / mean_a.powi(2) + mean_b.powi(2) * var_a / mean_a.powi(4)).sqrt()
+        } else {
+            (var_a + var_b).sqrt()
+        }
+    };
+    let naive_se = if uplift {
+        iid_b.sqrt() / mean_a.abs()
+    } else {
+        (iid_a + iid_b).sqrt()
+    };
+    let cluster_se = propagated(clustered_a, clustered_b);
+    let delta_se = propagated(iid_a, iid_b);
+
+    let dist_a = Uniform::new(0, totals_a.len());
+    let dist_b = Uniform::new(0, totals_b.len());
+    let resampled_mean = |totals: &[(f64, f64)], dist: &Uniform<usize>, rng: &mut _| {
+        let (sum, count) = (0..totals.len()).fold((0.0, 0.0), |(sum, count), _| {
+            let (s, c) = totals[dist.sample(rng)];
+            (sum + s, count + c)
+        });
+        sum / count
+    };
+    let estimates = py.allow_threads(|| {
+        resampler.map(n_resamples, |rng| {
+            let mean_a = resampled_mean(&totals_a, &dist_a, rng);
+            let mean_b = resampled_mean(&totals_b, &dist_b, rng);
+            estimate(mean_a, mean_b)
+        })
+    });
+    let centre = estimates.iter().sum::<f64>() / estimates.len() as f64;
+    let bootstrap_se = (estimates.iter().map(|e| (e - centre).powi(2)).sum::<f64>()
+        / (estimates.len() as f64 - 1.0))
+        .sqrt();
+
+    let mut flags = Vec::new();
+    let ratio = |x: f64, y: f64| x.max(y) / x.min(y);
+    if cluster_se > SE_DISCREPANCY * delta_se {
+        flags.push(format!(
+            "the cluster-robust standard error is {:.2} times the one assuming independent observations: observations within a cluster are correlated, analyse the clusters",
+            cluster_se / delta_se
+        ));
+    }
+    if uplift && delta_se > SE_DISCREPANCY * naive_se {
+        flags.push(format!(
+            "the delta-method standard error is {:.2} times the naive one: the uplift is a ratio metric and the uncertainty of its denominator must be accounted for",
+            delta_se / naive_se
+        ));
+    }
+    if ratio(bootstrap_se, cluster_se) > SE_DISCREPANCY {
+        flags.push(format!(
+            "the bootstrap standard error is {:.2} times the cluster-robust one: the sample may be too small or heavy-tailed for the normal approximation",
+            bootstrap_se / cluster_se
+        ));
+    }
+    Ok((
+        estimate(mean_a, mean_b),
+        naive_se,
+        cluster_se,
+        delta_se,
+        bootstrap_se,
+        flags,
+    ))
+}
+
+/// Measurement noise injected by `noise_robustness`, of a given level.
+#[derive(Clone, Copy)]
+enum NoiseModel {
+    /// Additive Gaussian noise of standard deviation `level` times the pooled standard deviation.
+    Gaussian,
+    /// Mean-preserving log-normal factor of log-scale standard deviation `level`.
+    Multiplicative,
+    /// Swap of the group of every observation with probability `level`.
+    LabelFlip,
+}
+
+/// `noise_robustness` output: the p-value on the clean data, and the agreement rate and median p-value
+/// at every noise level.
+type NoiseRobustness = (f64, Vec<f64>, Vec<f64>);
+
+/// Two-sided p-value of the two-sample z-test (Welch standard error) from the count, sum and sum of
+/// squares of both groups.
+fn welch_z_p_value(a: (f64, f64, f64), b: (f64, f64, f64)) -> f64 {
+    let var = |(n, sum, squares): (f64, f64, f64)| (squares - sum * sum / n) / (n - 1.0) / n;
+    let z = (b.1 / b.0 - a.1 / a.0) / (var(a) + var(b)).sqrt();
+    2.0 * norm_sf(z.abs())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (a, b, noise_model = "gaussian", levels = vec![0.1, 0.25, 0.5, 1.0], alpha = 0.05, n_sims = 1_000, random_state = None, seed = None, deterministic = None))]
+#[pyo3(
+    text_signature = "(a, b, noise_model='gaussian', levels=[0.1, 0.25, 0.5, 1.0], alpha=0.05, n_sims=1000, random_state=None, seed=None, deterministic=None)"
+)]
+/// """
+/// Simulates how fragile the significance call of an experiment is to measurement or label noise.
+///
+/// At every noise level, `n_sims` copies of the data receive random noise and are re-tested with a
+/// two-sided two-sample z-test (Welch standard error). The agreement rate is the share of noisy copies
+/// whose call at level `alpha` (significant or not) matches the call on the clean data: a significant
+/// result whose agreement quickly drops with small noise levels rests on a fragile margin. The same
+/// simulated noise is scaled across levels, so the curves are smooth in the level.
+///
+/// Args:
+///     a (List[float]): The observations of the first group.
+///     b (List[float]): The observations of the second group.
+///     noise_model (str, optional): The injected noise:
+///         - "gaussian": additive normal noise whose standard deviation is `level` times the pooled
+///           standard deviation of the data,
+///         - "multiplicative": a mean-preserving log-normal factor of log-scale standard deviation `level`,
+///         - "label_flip": every observation moves to the other group with probability `level`, as
+///           with a misattributed assignment.
+///         Default is "gaussian".
+///     levels (List[float], optional): The noise levels to simulate. Default is [0.1, 0.25, 0.5, 1.0].
+///     alpha (float, optional): The significance level of the test. Default is 0.05.
+///     n_sims (int, optional): The number of noisy copies per level. Default is 1000.
+///     random_state (RandomState or int, optional): The random stream, see "Random state" in the README.
+///     seed (int, optional): The seed of this call, see "Random state" in the README.
+///     deterministic (bool, optional): The generator mode, see "Random state" in the README.
+///
+/// Returns:
+///     Tuple[float, List[float], List[float]]:
+///         A tuple containing:
+///         - p_value (float): The p-value on the clean data.
+///         - agreement (List[float]): The share of noisy copies with the same call, per level.
+///         - median_p_values (List[float]): The median p-value of the noisy copies, per level.
+/// """
+pub fn noise_robustness(
+    py: Python<'_>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+    noise_model: &str,
+    levels: Vec<f64>,
+    alpha: f64,
+    n_sims: u64,
+    random_state: Option<RandomStateArg<'_>>,
+    seed: Option<u64>,
+    deterministic: Option<bool>,
+) -> PyResult<NoiseRobustness> {
+    check_resamples(n_sims)?;
+    let resampler = new_resampler(random_state, seed, deterministic)?;
+    let model = match noise_model {
+        "gaussian" => NoiseModel::Gaussian,
+        "multiplicative" => NoiseModel::Multiplicative,
+        "label_flip" => NoiseModel::LabelFlip,
+        _ => raise!(
+            InvalidInputError,
+            "noise_model must be 'gaussian', 'multiplicative' or 'label_flip', got '{noise_model}'"
+        ),
+    };
+    if a.len() < 2 || b.len() < 2 {
+        raise!(
+            InsufficientDataError,
+            "Both groups must contain at least two observations"
+        )
+    }
+    if !(alpha > 0.0 && alpha < 1.0) {
+        raise!(InvalidInputError, "alpha must be in (0, 1)")
+    }
+    if levels.iter().any(|&level| level.is_nan() || level < 0.0) {
+        raise!(InvalidInputError, "levels must be non-negative")
+    }
+    if matches!(model, NoiseModel::LabelFlip) && levels.iter().any(|&level| level > 1.0) {
+        raise!(
+            InvalidInputError,
+            "label_flip levels are probabilities and must not exceed 1"
+        )
+    }
+    let moments = |data: &[f64]| {
+        data.iter()
+            .fold((data.len() as f64, 0.0, 0.0), |(n, sum, squares), &x| {
+                (n, sum + x, squares + x * x)
+            })
+    };
+    let p_value = welch_z_p_value(moments(&a), moments(&b));
+    let significant = p_value < alpha;
+    let pooled: Vec<f64> = a.iter().chain(&b).copied().collect();
+    let (n, sum, squares) = moments(&pooled);
+    let pooled_sd = ((squares - sum * sum / n) / (n - 1.0)).sqrt();
+    let len_a = a.len();
+
+    let mut agreement = Vec::with_capacity(levels.len());
+    let mut median_p_values = Vec::with_capacity(levels.len());
+    for &level in &levels {
+        let p_values = py.allow_threads(|| {
+            resampler.map(n_sims, |rng| {
+                let mut groups = [(0.0, 0.0, 0.0); 2];
+                for (k, &x) in pooled.iter().enumerate() {
+                    let mut group = usize::from(k >= len_a);
+                    let x = match model {
+                        NoiseModel::Gaussian => {
+                            let z: f64 = StandardNormal.sample(rng);
+                            x + level * pooled_sd * z
+                        }
+                        NoiseModel::Multiplicative => {
+                            let z: f64 = StandardNormal.sample(rng);
+                            x * (level * z - level * level / 2.0).exp()
+                        }
+                        NoiseModel::LabelFlip => {
+                            if rng.gen::<f64>() < level {
+                                group = 1 - group;
+                            }
+                            x
+                        }
+                    };
+                    let (count, sum, squares) = &mut groups[group];
+                    *count += 1.0;
+                    *sum += x;
+                    *squares += x * x;
+                }
+                if groups.iter().any(|&(count, _, _)| count < 2.0) {
+                    return f64::NAN;
+                }
+                welch_z_p_value(groups[0], groups[1])
+            })
+        });
+        let p_values: Vec<f64> = p_values.into_iter().filter(|p| !p.is_nan()).collect();
+        let agreeing = p_values
+            .iter()
+            .filter(|&&p| (p < alpha) == significant)
+            .count();
+        agreement.push(agreeing as f64 / p_values.len() as f64);
+        median_p_values.push(median(&p_values));
+    }
+    Ok((p_value, agreement, median_p_values))
+}